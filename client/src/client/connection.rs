@@ -2,51 +2,178 @@ use anyhow::{Context, Result};
 use base64::Engine;
 use chrono::Local;
 use futures_util::{SinkExt, StreamExt};
-use std::collections::HashMap;
+use lru::LruCache;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, RwLock};
-use tokio_tungstenite::{connect_async, tungstenite::Message};
-use tracing::{debug, error, info, warn};
+use tokio::sync::{mpsc, oneshot, watch, RwLock};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
+use tokio_tungstenite::{client_async_tls_with_config, tungstenite::Message};
+use tracing::{debug, error, info, info_span, trace, warn, Instrument};
 
+const DEFAULT_SERVER_PORT: u16 = 443;
+const DEFAULT_LOCAL_HOST: &str = "localhost";
 const MAX_RECONNECT_ATTEMPTS: u32 = 10;
 const INITIAL_BACKOFF_MS: u64 = 1000;
 const MAX_BACKOFF_MS: u64 = 60_000;
 const BACKOFF_MULTIPLIER: f64 = 1.5;
+const REGISTRATION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_REGISTRATION_ATTEMPTS: u8 = 3;
+/// Delay before retrying a registration whose requested subdomain was taken
+const SUBDOMAIN_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Number of request ids (and responses) remembered for deduplication
+const REQUEST_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(10_000).unwrap();
+/// How often to check whether in-flight requests have finished during shutdown
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long to wait for the close frame to be written during shutdown
+const CLOSE_FRAME_TIMEOUT: Duration = Duration::from_secs(1);
+/// Queued outgoing messages above which the server is falling behind
+const SEND_QUEUE_WARN_DEPTH: usize = 200;
+/// How long a local port gets to accept a health check connection
+const LOCAL_PORT_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+/// Consecutive failed requests after which a tunnel's local port is
+/// reported as unreachable
+const LOCAL_PORT_FAILURE_LIMIT: u32 = 3;
+/// Chunks of a streamed request body held for a local service that reads
+/// slower than they arrive; past this the upload is given up
+const STREAMING_BODY_BUFFER_CHUNKS: usize = 64;
+/// Requests held at once while the rest of their chunked body arrives
+const MAX_PENDING_REQUEST_BODIES: usize = 64;
+/// How long a request waits for the rest of its chunked body
+const PENDING_REQUEST_BODY_TIMEOUT: Duration = Duration::from_secs(60);
 
+use crate::config::{Config, ConnectionConfig, ProxyConfig, ServerScheme};
+use crate::error::BurrowError;
 use crate::protocol::{
-    decode_body, IncomingMessage, OutgoingMessage, TcpId, TcpTunnelId, TunnelId, WsId,
+    decode_body, IncomingMessage, ListedTunnel, OutgoingMessage, RequestId, TcpId, TcpTunnelId,
+    TunnelId, TunnelQuery, WsId, PROTOCOL_VERSION,
 };
 
-use super::http_proxy::forward_http_request;
+use super::connect_proxy;
+use super::happy_eyeballs;
+use super::http_proxy::{
+    self, forward_http_request, is_idempotent, streaming_body, BodyChunk, EventStream,
+    ForwardedResponse, ProxyClient, ProxyErrorKind,
+};
 use super::tui::{
-    ConnectionStatus, RequestEvent, ResponseEvent, TcpTunnelEvent, TuiCommand, TuiEvent,
-    TunnelEvent,
+    message_size, ChannelMonitor, ClientStateSnapshot, ConnectionStatus, ErrorSource,
+    NotificationLevel, RequestBodyKind, RequestEvent, ResponseEvent, TcpTunnelEvent,
+    TimingBreakdown, TuiCommand, TuiEvent, TunnelEvent,
 };
-use super::ws_proxy::WebSocketProxy;
+use super::ws_proxy::{WebSocketProxy, WsKeepalive};
 
 /// Configuration for a tunnel to restore on reconnect
-#[derive(Debug, Clone)]
-enum TunnelConfig {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TunnelConfig {
     Http {
         local_port: u16,
         subdomain: Option<String>,
+        extra_headers: Vec<(String, String)>,
+        tags: Vec<String>,
     },
     Tcp {
         local_port: u16,
     },
 }
 
+/// A tunnel queued with [`TunnelClient::register_tunnel`] or
+/// [`TunnelClient::register_tcp_tunnel`], whose id the server assigns once
+/// the client connects
+pub struct TunnelRegistration<T> {
+    id_rx: watch::Receiver<Option<T>>,
+}
+
+impl<T: Clone> TunnelRegistration<T> {
+    /// Wait until the server registered the tunnel and return its id. After
+    /// a reconnect the tunnel may get a new id; this returns the latest.
+    /// Fails if the client stops before the tunnel was registered.
+    pub async fn id(&mut self) -> Result<T> {
+        let id =
+            self.id_rx.wait_for(Option::is_some).await.map_err(|_| {
+                anyhow::anyhow!("The client stopped before the tunnel was registered")
+            })?;
+        Ok(id.clone().expect("waited for an id"))
+    }
+}
+
+/// Senders for the ids of tunnels registered through the public API, by
+/// local port
+#[derive(Clone, Default)]
+struct TunnelIdSenders {
+    http: HashMap<u16, watch::Sender<Option<TunnelId>>>,
+    tcp: HashMap<u16, watch::Sender<Option<TcpTunnelId>>>,
+}
+
+/// Server or token to connect with instead of the current ones, requested
+/// from the TUI
+struct ServerSwitch {
+    /// `None` reconnects to the same server, keeping the tunnels
+    host: Option<String>,
+    token: Option<String>,
+}
+
+/// What the command handler hands back when it stops
+type CommandTaskResult = (mpsc::Receiver<TuiCommand>, Option<ServerSwitch>);
+
+/// Latency pings shared between the ping task and the receiver task
+struct PingProbe {
+    started: Instant,
+    /// Sequence number of the latest ping
+    sequence: AtomicU64,
+    /// Whether the latest ping has been answered
+    answered: AtomicBool,
+}
+
+impl PingProbe {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            sequence: AtomicU64::new(0),
+            answered: AtomicBool::new(true),
+        }
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// Start a new ping, returning its payload: the sequence number and send time
+    fn next_ping(&self) -> Vec<u8> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        self.answered.store(false, Ordering::Relaxed);
+        let mut payload = sequence.to_be_bytes().to_vec();
+        payload.extend_from_slice(&self.elapsed_ms().to_be_bytes());
+        payload
+    }
+
+    /// Round-trip time in ms if `payload` answers the latest ping for the first time
+    fn pong(&self, payload: &[u8]) -> Option<u64> {
+        let sequence = u64::from_be_bytes(payload.get(..8)?.try_into().ok()?);
+        let sent_ms = u64::from_be_bytes(payload.get(8..16)?.try_into().ok()?);
+        if sequence != self.sequence.load(Ordering::Relaxed)
+            || self.answered.swap(true, Ordering::Relaxed)
+        {
+            return None;
+        }
+        Some(self.elapsed_ms().saturating_sub(sent_ms))
+    }
+}
+
 /// Information about a registered tunnel
 #[derive(Debug, Clone)]
 struct TunnelInfo {
-    #[allow(dead_code)]
     full_url: String,
     #[allow(dead_code)]
     local_host: String,
     local_port: u16,
+    /// Headers added to every request forwarded through this tunnel
+    extra_headers: Vec<(String, String)>,
 }
 
 /// Information about a registered TCP tunnel
@@ -61,11 +188,411 @@ struct TcpTunnelInfo {
 struct PendingTunnel {
     local_host: String,
     local_port: u16,
+    subdomain: Option<String>,
+    extra_headers: Vec<(String, String)>,
+    tags: Vec<String>,
+    /// Sent with every `RegisterTunnel` for this tunnel, retries included
+    registration_id: String,
+    /// When the most recent `RegisterTunnel` was sent
+    sent_at: Instant,
+    /// Number of `RegisterTunnel` messages sent so far
+    registration_attempt: u8,
+}
+
+impl PendingTunnel {
+    fn new(
+        local_host: &str,
+        local_port: u16,
+        subdomain: Option<String>,
+        extra_headers: Vec<(String, String)>,
+        tags: Vec<String>,
+    ) -> Self {
+        Self {
+            local_host: local_host.to_string(),
+            local_port,
+            subdomain,
+            extra_headers,
+            tags,
+            registration_id: next_registration_id(),
+            sent_at: Instant::now(),
+            registration_attempt: 1,
+        }
+    }
+
+    /// The `RegisterTunnel` message for this tunnel
+    fn register_message(
+        &self,
+        token: &str,
+        session_id: &str,
+        chunk_size_bytes: usize,
+    ) -> OutgoingMessage {
+        OutgoingMessage::RegisterTunnel {
+            token: token.to_string(),
+            session_id: session_id.to_string(),
+            registration_id: self.registration_id.clone(),
+            local_host: self.local_host.clone(),
+            local_port: self.local_port,
+            requested_subdomain: self.subdomain.clone(),
+            chunk_size_bytes,
+            protocol_version: PROTOCOL_VERSION,
+            tags: (!self.tags.is_empty()).then(|| self.tags.clone()),
+        }
+    }
+}
+
+/// A registration id unique within this process
+fn next_registration_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed).to_string()
 }
 
 /// Active TCP connection state
 struct TcpConnection {
     tx: mpsc::Sender<Vec<u8>>,
+    /// How long the local service took to accept the connection
+    connect_latency_ms: u64,
+}
+
+/// An HTTP request from the server, ready to be forwarded to the local service
+struct ForwardRequest {
+    request_id: RequestId,
+    tunnel_id: TunnelId,
+    local_host: String,
+    local_port: u16,
+    method: String,
+    path: String,
+    query_string: String,
+    headers: Vec<(String, String)>,
+    /// Tunnel headers added when forwarding, but not shown in the TUI
+    extra_headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    /// Body chunks still arriving, forwarded as they come in; replaces `body`
+    body_stream: Option<mpsc::Receiver<BodyChunk>>,
+    client_ip: Option<String>,
+    tunnel_url: Option<String>,
+    /// When the message carrying the request arrived, for
+    /// [`TimingBreakdown::queued_ms`]
+    received_at: Instant,
+}
+
+/// Tunnels the server confirmed on the current connection
+#[derive(Debug, Default)]
+struct RegisteredCounts {
+    http: usize,
+    tcp: usize,
+}
+
+/// Coalescing of queued outgoing messages into `batch` messages
+#[derive(Debug, Clone, Copy)]
+struct MessageBatching {
+    max_size: usize,
+    /// How long to wait for another message once a batch has started
+    timeout: Duration,
+}
+
+impl MessageBatching {
+    /// Gather the messages queued behind `first` into one batch message.
+    /// A message with nothing queued behind it is sent on its own. Waiting
+    /// for more stops as soon as a high-priority message arrives; it is
+    /// returned alongside the batch, to be sent first.
+    async fn collect(
+        &self,
+        first: String,
+        rx: &mut mpsc::Receiver<String>,
+        high_priority_rx: &mut mpsc::Receiver<Message>,
+    ) -> (Option<String>, Option<Message>) {
+        let Ok(second) = rx.try_recv() else {
+            return (Some(first), None);
+        };
+        let mut messages = vec![first, second];
+        let mut urgent = None;
+        while messages.len() < self.max_size {
+            let next = match rx.try_recv() {
+                Ok(text) => Some(text),
+                Err(_) => tokio::select! {
+                    biased;
+                    Some(msg) = high_priority_rx.recv() => {
+                        urgent = Some(msg);
+                        None
+                    }
+                    next = tokio::time::timeout(self.timeout, rx.recv()) => next.ok().flatten(),
+                },
+            };
+            match next {
+                Some(text) => messages.push(text),
+                None => break,
+            }
+        }
+
+        let count = messages.len();
+        match OutgoingMessage::batch(messages).and_then(|batch| batch.to_json()) {
+            Ok(json) => {
+                trace!("Sending {} messages as one batch", count);
+                (Some(json), urgent)
+            }
+            // Only messages serialized by us are queued, so this shouldn't happen
+            Err(e) => {
+                error!("Dropping {} messages, failed to build batch: {}", count, e);
+                (None, urgent)
+            }
+        }
+    }
+}
+
+/// Request already forwarded whose body chunks are passed on as they arrive
+struct StreamingRequestBody {
+    chunks_tx: mpsc::Sender<BodyChunk>,
+    /// Sequence number of the next chunk to pass on
+    next_sequence: u32,
+    /// Chunks that arrived ahead of `next_sequence`
+    out_of_order: BTreeMap<u32, Vec<u8>>,
+    /// Total number of chunks, known once the last chunk has arrived
+    total_chunks: Option<u32>,
+}
+
+impl StreamingRequestBody {
+    /// Take in a chunk and pass on every chunk that is now in sequence.
+    /// Fails if the body can't be completed any more.
+    fn accept(
+        &mut self,
+        sequence: u32,
+        data: &str,
+        data_encoding: Option<&str>,
+        is_last: bool,
+    ) -> Result<(), String> {
+        if sequence < self.next_sequence || self.out_of_order.contains_key(&sequence) {
+            debug!("Ignoring repeated body chunk {}", sequence);
+            return Ok(());
+        }
+        let chunk = decode_body(Some(data), data_encoding)
+            .ok_or_else(|| format!("chunk {} could not be decoded", sequence))?;
+        if self.out_of_order.len() >= STREAMING_BODY_BUFFER_CHUNKS {
+            return Err("too many chunks arrived out of order".to_string());
+        }
+        self.out_of_order.insert(sequence, chunk);
+        if is_last {
+            self.total_chunks = Some(sequence + 1);
+        }
+
+        while let Some(chunk) = self.out_of_order.remove(&self.next_sequence) {
+            match self.chunks_tx.try_send(BodyChunk::Data(chunk)) {
+                Ok(()) => self.next_sequence += 1,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    return Err("the local service is reading it too slowly".to_string())
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    return Err("the local service stopped reading it".to_string())
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Request whose body is still arriving in chunks
+struct PendingRequestBody {
+    request: ForwardRequest,
+    /// Received chunks by sequence, possibly out of order
+    chunks: BTreeMap<u32, Vec<u8>>,
+    /// Bytes received so far, including the body sent with the request
+    size: usize,
+    /// Total number of chunks, known once the last chunk has arrived
+    total_chunks: Option<u32>,
+    /// When the request is given up on if its body is still incomplete
+    expires_at: Instant,
+}
+
+impl PendingRequestBody {
+    fn new(request: ForwardRequest) -> Self {
+        Self {
+            size: request.body.as_ref().map_or(0, Vec::len),
+            request,
+            chunks: BTreeMap::new(),
+            total_chunks: None,
+            expires_at: Instant::now() + PENDING_REQUEST_BODY_TIMEOUT,
+        }
+    }
+
+    /// Take in a chunk, returning whether the body is complete. Fails with
+    /// the status to answer the request with if it can't be completed.
+    fn accept(
+        &mut self,
+        sequence: u32,
+        data: &str,
+        data_encoding: Option<&str>,
+        is_last: bool,
+        max_bytes: usize,
+    ) -> Result<bool, (u16, String)> {
+        if self.chunks.contains_key(&sequence) {
+            debug!("Ignoring repeated body chunk {}", sequence);
+        } else {
+            let chunk = decode_body(Some(data), data_encoding)
+                .ok_or_else(|| (400, format!("Body chunk {} could not be decoded", sequence)))?;
+            self.size += chunk.len();
+            if self.size > max_bytes {
+                return Err((413, "Request body larger than announced".to_string()));
+            }
+            self.chunks.insert(sequence, chunk);
+        }
+        if is_last {
+            self.total_chunks = Some(sequence + 1);
+        }
+        // Chunks may arrive out of order, so wait until every sequence is in.
+        // Sequences are distinct, so that is when the highest is the last.
+        let complete = self.total_chunks.is_some_and(|total| {
+            self.chunks.len() as u32 == total
+                && self.chunks.keys().next_back() == Some(&(total - 1))
+        });
+        Ok(complete)
+    }
+
+    /// The request with its assembled body
+    fn into_request(mut self) -> ForwardRequest {
+        let mut body = self.request.body.take().unwrap_or_default();
+        for chunk in self.chunks.into_values() {
+            body.extend_from_slice(&chunk);
+        }
+        self.request.body = Some(body);
+        self.request
+    }
+}
+
+/// Response sent for an idempotent request, kept to answer replays of it
+struct CachedResponse {
+    message: OutgoingMessage,
+    cached_at: Instant,
+    /// Bytes of body and headers, counted against the cache's size limit
+    size: usize,
+}
+
+/// An idempotent request being handled, and the replays of it that arrived
+/// meanwhile, answered on their own connection once it is done
+struct InFlightRequest {
+    started_at: Instant,
+    replays: Vec<mpsc::Sender<String>>,
+}
+
+/// What to do with an idempotent request, see [`RequestCaches::check`]
+enum Replay {
+    /// Not seen before, or its cached response expired: forward it
+    New,
+    /// Already answered: resend this response
+    Answered(OutgoingMessage),
+    /// Still being handled: it is answered when the original is
+    Parked,
+}
+
+/// Request deduplication, shared by every connection of a client so that a
+/// request replayed after a reconnect finds what the previous connection did
+struct RequestCaches {
+    in_flight: HashMap<RequestId, InFlightRequest>,
+    cached_responses: LruCache<RequestId, CachedResponse>,
+    /// Total size of `cached_responses`
+    cached_bytes: usize,
+    max_cached_bytes: usize,
+    /// How long a response may be resent, and a request counts as in flight
+    ttl: Duration,
+}
+
+impl RequestCaches {
+    fn new(max_cached_bytes: usize, ttl: Duration) -> Self {
+        Self {
+            in_flight: HashMap::new(),
+            cached_responses: LruCache::new(REQUEST_CACHE_CAPACITY),
+            cached_bytes: 0,
+            max_cached_bytes,
+            ttl,
+        }
+    }
+
+    /// Record an idempotent request, deciding whether it replays one already
+    /// seen. A replay of a request still in flight is answered via `reply_to`.
+    fn check(&mut self, request_id: &RequestId, reply_to: &mpsc::Sender<String>) -> Replay {
+        if let Some(in_flight) = self.in_flight.get_mut(request_id) {
+            if in_flight.started_at.elapsed() < self.ttl {
+                in_flight.replays.push(reply_to.clone());
+                return Replay::Parked;
+            }
+        }
+        if let Some(cached) = self.cached_responses.peek(request_id) {
+            if cached.cached_at.elapsed() < self.ttl {
+                return Replay::Answered(cached.message.clone());
+            }
+            self.remove_cached(request_id);
+        }
+
+        // Requests that never finish (e.g. dropped while their body was
+        // still arriving) don't count as in flight forever
+        if self.in_flight.len() >= REQUEST_CACHE_CAPACITY.get() {
+            let ttl = self.ttl;
+            self.in_flight.retain(|_, r| r.started_at.elapsed() < ttl);
+        }
+        self.in_flight.insert(
+            request_id.clone(),
+            InFlightRequest {
+                started_at: Instant::now(),
+                replays: Vec::new(),
+            },
+        );
+        Replay::New
+    }
+
+    /// Keep the response to a request from [`check`](Self::check) for
+    /// replays, within the size limit. Returns where replays that arrived
+    /// meanwhile are waiting for it.
+    fn store(
+        &mut self,
+        request_id: &RequestId,
+        message: &OutgoingMessage,
+    ) -> Vec<mpsc::Sender<String>> {
+        let replays = self.finish(request_id);
+        let size = match message {
+            OutgoingMessage::TunnelResponse { headers, body, .. } => {
+                body.as_ref().map_or(0, String::len)
+                    + headers
+                        .iter()
+                        .map(|[k, v]| k.len() + v.len())
+                        .sum::<usize>()
+            }
+            _ => 0,
+        };
+        if size > self.max_cached_bytes {
+            return replays;
+        }
+
+        self.remove_cached(request_id);
+        let cached = CachedResponse {
+            message: message.clone(),
+            cached_at: Instant::now(),
+            size,
+        };
+        if let Some((_, evicted)) = self.cached_responses.push(request_id.clone(), cached) {
+            self.cached_bytes -= evicted.size;
+        }
+        self.cached_bytes += size;
+        while self.cached_bytes > self.max_cached_bytes {
+            match self.cached_responses.pop_lru() {
+                Some((_, evicted)) => self.cached_bytes -= evicted.size,
+                None => break,
+            }
+        }
+        replays
+    }
+
+    /// A request from [`check`](Self::check) is done without a response to
+    /// keep; returns the replays that were waiting for it
+    fn finish(&mut self, request_id: &RequestId) -> Vec<mpsc::Sender<String>> {
+        self.in_flight
+            .remove(request_id)
+            .map(|r| r.replays)
+            .unwrap_or_default()
+    }
+
+    fn remove_cached(&mut self, request_id: &RequestId) {
+        if let Some(cached) = self.cached_responses.pop(request_id) {
+            self.cached_bytes -= cached.size;
+        }
+    }
 }
 
 /// Shared state for the tunnel client
@@ -74,6 +601,9 @@ struct ClientState {
     tunnels: HashMap<TunnelId, TunnelInfo>,
     /// Pending HTTP tunnel registrations (index -> pending info)
     pending_tunnels: Vec<PendingTunnel>,
+    /// Tunnels from before a reconnect, registered once the server's
+    /// `TunnelList` says which it still has
+    awaiting_tunnel_list: Vec<PendingTunnel>,
     /// Registered TCP tunnels (tcp_tunnel_id -> info)
     tcp_tunnels: HashMap<TcpTunnelId, TcpTunnelInfo>,
     /// Pending TCP tunnel registrations (local_port -> waiting)
@@ -82,21 +612,147 @@ struct ClientState {
     tcp_connections: HashMap<TcpId, TcpConnection>,
     /// Active WebSocket proxies (ws_id -> proxy)
     ws_proxies: HashMap<WsId, Arc<WebSocketProxy>>,
+    /// Event streams being relayed, stopped by sending on (or dropping) the
+    /// sender (request_id -> stop)
+    event_streams: HashMap<RequestId, oneshot::Sender<()>>,
+    /// Requests waiting for the rest of their streamed body (request_id -> pending)
+    pending_request_bodies: HashMap<RequestId, PendingRequestBody>,
+    /// Requests whose body is being streamed to the local service (request_id -> stream)
+    streaming_request_bodies: HashMap<RequestId, StreamingRequestBody>,
+    /// Streamed bodies at least this large are forwarded without buffering
+    streaming_threshold_bytes: usize,
     /// Local host for forwarding
     local_host: String,
+    /// API token, for re-sending registrations
+    token: String,
+    /// Sent with registrations so the server only hands over this client's
+    /// own tunnels
+    session_id: String,
+    /// Body chunk size advertised in registrations
+    chunk_size_bytes: usize,
+    /// Idempotent requests already seen, so replays aren't forwarded twice
+    request_caches: Arc<std::sync::Mutex<RequestCaches>>,
+    proxy: Arc<ProxyClient>,
+    /// Set once the client starts shutting down; new requests are refused
+    shutting_down: bool,
+    /// Requests forwarded to the local service that haven't been answered yet
+    in_flight_requests: HashSet<RequestId>,
+    /// Messages sent ahead of anything queued on the regular message channel
+    high_priority_tx: mpsc::Sender<Message>,
+    /// Position in a [`ConnectionPool`](super::ConnectionPool), reported with registered tunnels
+    pool_id: u8,
+    /// When the last heartbeat was sent, until the server echoes it
+    heartbeat_sent_at: Option<Instant>,
+    /// TCP connects to the local service slower than this are logged as warnings
+    slow_tcp_connect_threshold: Duration,
+    ws_keepalive: WsKeepalive,
+    /// Responses larger than this as one message are sent in chunks
+    max_outgoing_message_bytes: usize,
+    /// Consecutive requests per tunnel that couldn't reach the local service
+    local_port_failures: HashMap<TunnelId, u32>,
+    /// Where to report the ids of tunnels registered through the public API
+    tunnel_id_senders: TunnelIdSenders,
 }
 
 impl ClientState {
-    fn new(local_host: &str) -> Self {
+    fn new(
+        local_host: &str,
+        token: &str,
+        chunk_size_bytes: usize,
+        streaming_threshold_bytes: usize,
+        request_caches: Arc<std::sync::Mutex<RequestCaches>>,
+        proxy: Arc<ProxyClient>,
+        high_priority_tx: mpsc::Sender<Message>,
+    ) -> Self {
         Self {
             tunnels: HashMap::new(),
             pending_tunnels: Vec::new(),
+            awaiting_tunnel_list: Vec::new(),
             tcp_tunnels: HashMap::new(),
             pending_tcp_tunnels: Vec::new(),
             tcp_connections: HashMap::new(),
             ws_proxies: HashMap::new(),
+            event_streams: HashMap::new(),
+            pending_request_bodies: HashMap::new(),
+            streaming_request_bodies: HashMap::new(),
+            streaming_threshold_bytes,
             local_host: local_host.to_string(),
+            token: token.to_string(),
+            session_id: String::new(),
+            chunk_size_bytes,
+            request_caches,
+            proxy,
+            shutting_down: false,
+            in_flight_requests: HashSet::new(),
+            high_priority_tx,
+            pool_id: 0,
+            heartbeat_sent_at: None,
+            slow_tcp_connect_threshold: Duration::from_secs(1),
+            max_outgoing_message_bytes: usize::MAX,
+            ws_keepalive: WsKeepalive {
+                interval: Duration::from_secs(30),
+                pong_timeout: Duration::from_secs(10),
+            },
+            local_port_failures: HashMap::new(),
+            tunnel_id_senders: TunnelIdSenders::default(),
+        }
+    }
+
+    fn snapshot(&self) -> ClientStateSnapshot {
+        ClientStateSnapshot {
+            active_tunnel_count: self.tunnels.len(),
+            active_tcp_tunnel_count: self.tcp_tunnels.len(),
+            active_tcp_connection_count: self.tcp_connections.len(),
+            active_ws_proxy_count: self.ws_proxies.len(),
+            pending_tunnel_count: self.pending_tunnels.len() + self.awaiting_tunnel_list.len(),
+            pending_tcp_tunnel_count: self.pending_tcp_tunnels.len(),
+        }
+    }
+
+    /// Send a `RegisterTunnel` for a tunnel from before a reconnect
+    async fn register_pending_tunnel(
+        &mut self,
+        mut pending: PendingTunnel,
+        msg_tx: &mpsc::Sender<String>,
+    ) {
+        let msg = pending.register_message(&self.token, &self.session_id, self.chunk_size_bytes);
+        debug!("Re-registering HTTP tunnel for port {}", pending.local_port);
+        pending.sent_at = Instant::now();
+        self.pending_tunnels.push(pending);
+        if let Ok(json) = msg.to_json() {
+            let _ = msg_tx.send(json).await;
+        }
+    }
+
+    /// Record whether a request reached a tunnel's local service; true when
+    /// this failure makes the tunnel's port count as unreachable
+    fn record_local_port_result(&mut self, tunnel_id: &TunnelId, reached: bool) -> bool {
+        if reached {
+            self.local_port_failures.remove(tunnel_id);
+            return false;
+        }
+        let failures = self
+            .local_port_failures
+            .entry(tunnel_id.clone())
+            .or_default();
+        *failures += 1;
+        *failures == LOCAL_PORT_FAILURE_LIMIT
+    }
+
+    /// Give up on requests whose chunked body is taking too long, returning them
+    fn take_expired_request_bodies(&mut self) -> Vec<RequestId> {
+        let now = Instant::now();
+        let expired: Vec<RequestId> = self
+            .pending_request_bodies
+            .iter()
+            .filter(|(_, pending)| pending.expires_at <= now)
+            .map(|(request_id, _)| request_id.clone())
+            .collect();
+        for request_id in &expired {
+            warn!("Body of {} did not arrive in time", request_id);
+            self.pending_request_bodies.remove(request_id);
         }
+        expired
     }
 
     fn find_tunnel_port(&self, tunnel_id: &TunnelId) -> Option<u16> {
@@ -108,41 +764,360 @@ impl ClientState {
     }
 }
 
+/// How the client reconnects after losing the server connection
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectConfig {
+    /// Give up after this many consecutive failed attempts
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Factor the backoff grows by after each failed attempt
+    pub backoff_multiplier: f64,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: MAX_RECONNECT_ATTEMPTS,
+            initial_backoff_ms: INITIAL_BACKOFF_MS,
+            max_backoff_ms: MAX_BACKOFF_MS,
+            backoff_multiplier: BACKOFF_MULTIPLIER,
+        }
+    }
+}
+
 pub struct TunnelClient {
     server_host: String,
     server_port: u16,
     local_host: String,
     token: String,
-    tui_tx: Option<mpsc::Sender<TuiEvent>>,
+    /// Random per client, so after a reconnect the server hands over only
+    /// tunnels this client registered, not another client's on the same account
+    session_id: String,
+    tui_tx: Option<ChannelMonitor>,
     cmd_rx: Option<mpsc::Receiver<TuiCommand>>,
+    reconnect: ReconnectConfig,
+    /// Forwards requests to local services with the builder's proxy settings
+    proxy: Arc<ProxyClient>,
     registered_tunnels: Vec<TunnelConfig>,
+    /// Report server-assigned ids to the [`TunnelRegistration`]s handed out
+    tunnel_id_senders: TunnelIdSenders,
+    /// Local ports already checked for a running service, which aren't
+    /// checked again on every reconnect
+    probed_ports: HashSet<u16>,
     last_error: Option<String>,
+    chunk_size_bytes: usize,
+    streaming_threshold_bytes: usize,
+    max_incoming_message_bytes: usize,
+    max_outgoing_message_bytes: usize,
+    registration_timeout: Duration,
+    shutdown_timeout: Duration,
+    heartbeat_interval: Duration,
+    /// How often to ping the server to measure round-trip time; a ping
+    /// without a pong by the next one counts as lost
+    ws_ping_interval: Duration,
+    prefer_ipv6: bool,
+    happy_eyeballs_delay: Duration,
+    /// Connect through the proxy in `HTTPS_PROXY`/`ALL_PROXY` when one is set
+    use_env_proxy: bool,
+    server_scheme: ServerScheme,
+    message_batching: Option<MessageBatching>,
+    slow_tcp_connect_threshold: Duration,
+    ws_keepalive: WsKeepalive,
+    /// How often tunnels whose local port stopped answering are checked again
+    local_port_health_check_interval: Duration,
+    /// Messages waiting to be written to the server connection
+    queue_depth: Arc<AtomicU32>,
+    /// Deduplication caches, kept across reconnects
+    request_caches: Arc<std::sync::Mutex<RequestCaches>>,
+    /// Position in a [`ConnectionPool`](super::ConnectionPool), 0 when not pooled
+    pool_id: u8,
+}
+
+/// Builder for [`TunnelClient`]. Only the server host and token are required.
+pub struct TunnelClientBuilder {
+    server_host: Option<String>,
+    server_port: u16,
+    local_host: String,
+    token: Option<String>,
+    tui_tx: Option<ChannelMonitor>,
+    cmd_rx: Option<mpsc::Receiver<TuiCommand>>,
+    reconnect: ReconnectConfig,
+    proxy: ProxyConfig,
+    connection: ConnectionConfig,
+    use_env_proxy: bool,
+    server_scheme: ServerScheme,
+}
+
+impl Default for TunnelClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TunnelClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            server_host: None,
+            server_port: DEFAULT_SERVER_PORT,
+            local_host: DEFAULT_LOCAL_HOST.to_string(),
+            token: None,
+            tui_tx: None,
+            cmd_rx: None,
+            reconnect: ReconnectConfig::default(),
+            proxy: ProxyConfig::default(),
+            connection: ConnectionConfig::default(),
+            use_env_proxy: true,
+            server_scheme: ServerScheme::default(),
+        }
+    }
+
+    pub fn server_host(mut self, host: impl Into<String>) -> Self {
+        self.server_host = Some(host.into());
+        self
+    }
+
+    pub fn server_port(mut self, port: u16) -> Self {
+        self.server_port = port;
+        self
+    }
+
+    /// Host that tunnelled requests are forwarded to
+    pub fn local_host(mut self, host: impl Into<String>) -> Self {
+        self.local_host = host.into();
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn tui_sender(mut self, tx: ChannelMonitor) -> Self {
+        self.tui_tx = Some(tx);
+        self
+    }
+
+    /// Receiver for commands from the TUI, such as adding tunnels
+    pub fn command_receiver(mut self, rx: mpsc::Receiver<TuiCommand>) -> Self {
+        self.cmd_rx = Some(rx);
+        self
+    }
+
+    /// How to back off between reconnects. Replaces the `reconnect_*`
+    /// settings of [`connection_config`](Self::connection_config) if called
+    /// after it.
+    pub fn reconnect_config(mut self, cfg: ReconnectConfig) -> Self {
+        self.reconnect = cfg;
+        self
+    }
+
+    pub fn proxy_config(mut self, cfg: ProxyConfig) -> Self {
+        self.proxy = cfg;
+        self
+    }
+
+    pub fn connection_config(mut self, cfg: ConnectionConfig) -> Self {
+        self.reconnect = ReconnectConfig {
+            max_attempts: cfg.max_reconnect_attempts,
+            initial_backoff_ms: cfg.reconnect_initial_backoff_ms,
+            max_backoff_ms: cfg.reconnect_max_backoff_ms,
+            backoff_multiplier: cfg.reconnect_backoff_multiplier,
+        };
+        self.connection = cfg;
+        self
+    }
+
+    /// Connect to the server over `wss://` (the default) or plain `ws://`
+    pub fn server_scheme(mut self, scheme: ServerScheme) -> Self {
+        self.server_scheme = scheme;
+        self
+    }
+
+    /// Whether to honour `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` when
+    /// connecting to the server (the default)
+    pub fn use_env_proxy(mut self, enabled: bool) -> Self {
+        self.use_env_proxy = enabled;
+        self
+    }
+
+    pub fn build(self) -> Result<TunnelClient> {
+        let server_host = self.server_host.context("Server host is required")?;
+        let token = self.token.context("API token is required")?;
+
+        Ok(TunnelClient {
+            server_host,
+            server_port: self.server_port,
+            local_host: self.local_host,
+            token,
+            session_id: new_session_id(),
+            tui_tx: self.tui_tx,
+            cmd_rx: self.cmd_rx,
+            reconnect: self.reconnect,
+            proxy: Arc::new(ProxyClient::new(&self.proxy)),
+            registered_tunnels: Vec::new(),
+            tunnel_id_senders: TunnelIdSenders::default(),
+            probed_ports: HashSet::new(),
+            last_error: None,
+            chunk_size_bytes: self.connection.chunk_size_bytes,
+            streaming_threshold_bytes: self.proxy.streaming_threshold_bytes,
+            max_incoming_message_bytes: self.proxy.max_incoming_message_bytes,
+            max_outgoing_message_bytes: self.proxy.max_outgoing_message_bytes,
+            registration_timeout: Duration::from_secs(self.connection.registration_timeout_secs),
+            shutdown_timeout: Duration::from_secs(self.connection.graceful_shutdown_timeout_secs),
+            heartbeat_interval: Duration::from_secs(self.connection.heartbeat_interval_secs.max(1)),
+            ws_ping_interval: Duration::from_secs(self.connection.ws_ping_interval_secs.max(1)),
+            prefer_ipv6: self.connection.prefer_ipv6,
+            happy_eyeballs_delay: Duration::from_millis(self.connection.happy_eyeballs_timeout_ms),
+            use_env_proxy: self.use_env_proxy,
+            server_scheme: self.server_scheme,
+            message_batching: self.connection.message_batching.then(|| MessageBatching {
+                max_size: self.connection.max_batch_size.max(1),
+                timeout: Duration::from_millis(self.connection.batch_timeout_ms),
+            }),
+            slow_tcp_connect_threshold: Duration::from_millis(
+                self.connection.slow_tcp_connect_threshold_ms,
+            ),
+            ws_keepalive: WsKeepalive {
+                interval: Duration::from_secs(self.proxy.ws_keepalive_interval_secs),
+                pong_timeout: Duration::from_secs(self.proxy.ws_pong_timeout_secs),
+            },
+            local_port_health_check_interval: Duration::from_secs(
+                self.connection.local_port_health_check_interval_secs.max(1),
+            ),
+            queue_depth: Arc::new(AtomicU32::new(0)),
+            request_caches: Arc::new(std::sync::Mutex::new(RequestCaches::new(
+                self.proxy.cache_max_size_mb as usize * 1024 * 1024,
+                Duration::from_secs(self.connection.response_cache_ttl_secs),
+            ))),
+            pool_id: 0,
+        })
+    }
 }
 
 impl TunnelClient {
+    pub fn builder() -> TunnelClientBuilder {
+        TunnelClientBuilder::new()
+    }
+
+    #[deprecated(note = "use TunnelClient::builder() instead")]
     pub fn new(
         server_host: &str,
         server_port: u16,
         local_host: &str,
         token: String,
-        tui_tx: Option<mpsc::Sender<TuiEvent>>,
+        tui_tx: Option<ChannelMonitor>,
         cmd_rx: mpsc::Receiver<TuiCommand>,
+        config: &Config,
     ) -> Result<Self> {
-        Ok(Self {
-            server_host: server_host.to_string(),
-            server_port,
-            local_host: local_host.to_string(),
-            token,
-            tui_tx,
-            cmd_rx: Some(cmd_rx),
-            registered_tunnels: Vec::new(),
-            last_error: None,
-        })
+        let mut builder = Self::builder()
+            .server_host(server_host)
+            .server_port(server_port)
+            .local_host(local_host)
+            .token(token)
+            .command_receiver(cmd_rx)
+            .proxy_config(config.proxy.clone())
+            .connection_config(config.connection.clone());
+        if let Some(tx) = tui_tx {
+            builder = builder.tui_sender(tx);
+        }
+        builder.build()
+    }
+
+    /// Tunnels registered with [`register_tunnel`](Self::register_tunnel) and
+    /// [`register_tcp_tunnel`](Self::register_tcp_tunnel)
+    pub fn registered_tunnels(&self) -> &[TunnelConfig] {
+        &self.registered_tunnels
+    }
+
+    /// Number of messages waiting to be written to the server, for display
+    pub fn queue_depth(&self) -> Arc<AtomicU32> {
+        self.queue_depth.clone()
+    }
+
+    /// Set by [`ConnectionPool`](super::ConnectionPool) to tell its clients apart
+    pub(super) fn set_pool_id(&mut self, id: u8) {
+        self.pool_id = id;
+        if let Some(monitor) = &mut self.tui_tx {
+            monitor.set_pool_id(id);
+        }
+    }
+
+    /// Register an HTTP tunnel once connected, and again after every reconnect.
+    ///
+    /// Call before [`run`](Self::run); while running, tunnels are added
+    /// through the command channel instead. The tunnel id is assigned by the
+    /// server, so the returned registration resolves to it once connected.
+    pub fn register_tunnel(
+        &mut self,
+        local_port: u16,
+        subdomain: Option<String>,
+        tags: Vec<String>,
+    ) -> Result<TunnelRegistration<TunnelId>> {
+        self.check_local_port(local_port)?;
+        if let Some(subdomain) = &subdomain {
+            check_subdomain(subdomain).map_err(|e| anyhow::anyhow!(e))?;
+        }
+        for tag in &tags {
+            check_tunnel_tag(tag).map_err(|e| anyhow::anyhow!(e))?;
+        }
+        self.track_tunnel(TunnelConfig::Http {
+            local_port,
+            subdomain,
+            extra_headers: Vec::new(),
+            tags,
+        });
+        let (id_tx, id_rx) = watch::channel(None);
+        self.tunnel_id_senders.http.insert(local_port, id_tx);
+        Ok(TunnelRegistration { id_rx })
+    }
+
+    /// Register a TCP tunnel once connected, and again after every reconnect.
+    ///
+    /// Like [`register_tunnel`](Self::register_tunnel), this is for use before
+    /// [`run`](Self::run).
+    pub fn register_tcp_tunnel(
+        &mut self,
+        local_port: u16,
+    ) -> Result<TunnelRegistration<TcpTunnelId>> {
+        self.check_local_port(local_port)?;
+        self.track_tunnel(TunnelConfig::Tcp { local_port });
+        let (id_tx, id_rx) = watch::channel(None);
+        self.tunnel_id_senders.tcp.insert(local_port, id_tx);
+        Ok(TunnelRegistration { id_rx })
+    }
+
+    fn check_local_port(&self, local_port: u16) -> Result<()> {
+        if local_port == 0 {
+            anyhow::bail!("Invalid local port 0");
+        }
+        let taken = self.registered_tunnels.iter().any(|config| match config {
+            TunnelConfig::Http { local_port: p, .. } | TunnelConfig::Tcp { local_port: p } => {
+                *p == local_port
+            }
+        });
+        if taken {
+            anyhow::bail!(
+                "A tunnel for local port {} is already registered",
+                local_port
+            );
+        }
+        Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all, fields(server = %self.server_host))]
     pub async fn run(mut self) -> Result<()> {
         let mut attempt = 0u32;
-        let mut backoff_ms = INITIAL_BACKOFF_MS;
+        let mut backoff_ms = self.reconnect.initial_backoff_ms;
+        if self.server_scheme == ServerScheme::Ws {
+            warn!("Connecting without TLS encryption");
+        }
+
+        // Report TUI channel health for the lifetime of the client
+        let stats_handle = self
+            .tui_tx
+            .clone()
+            .map(|monitor| tokio::spawn(monitor.report_stats()));
 
         loop {
             attempt += 1;
@@ -152,6 +1127,7 @@ impl TunnelClient {
             } else {
                 ConnectionStatus::Reconnecting {
                     attempt,
+                    max_attempts: self.reconnect.max_attempts,
                     reason: self.last_error.clone().unwrap_or_default(),
                     next_retry_secs: 0,
                 }
@@ -160,11 +1136,45 @@ impl TunnelClient {
                 .await;
 
             match self.connect_and_run_once().await {
+                Err(e) if matches!(e.downcast_ref(), Some(BurrowError::SwitchingServer)) => {
+                    // A fresh server gets a fresh set of attempts
+                    attempt = 0;
+                    backoff_ms = self.reconnect.initial_backoff_ms;
+                    self.last_error = None;
+                }
+                Err(e) if matches!(e.downcast_ref(), Some(BurrowError::AuthFailed)) => {
+                    // Retrying with the same token can't succeed
+                    error!("Authentication failed, not reconnecting");
+                    self.send_tui_event(TuiEvent::ConnectionStatus(
+                        ConnectionStatus::Disconnected {
+                            reason: "Authentication failed".into(),
+                        },
+                    ))
+                    .await;
+                    if let Some(handle) = stats_handle {
+                        handle.abort();
+                    }
+                    return Err(e);
+                }
+                Err(e) if matches!(e.downcast_ref(), Some(BurrowError::ProtocolVersionTooOld)) => {
+                    // Reconnecting gets the same answer until the client is updated
+                    error!("{}", e);
+                    self.send_tui_event(TuiEvent::ConnectionStatus(
+                        ConnectionStatus::Disconnected {
+                            reason: e.to_string(),
+                        },
+                    ))
+                    .await;
+                    if let Some(handle) = stats_handle {
+                        handle.abort();
+                    }
+                    return Err(e);
+                }
                 Ok(()) => {
-                    info!("Connection closed normally");
+                    info!("Shut down gracefully");
                     self.send_tui_event(TuiEvent::ConnectionStatus(
                         ConnectionStatus::Disconnected {
-                            reason: "Connection closed".into(),
+                            reason: "Graceful shutdown".into(),
                         },
                     ))
                     .await;
@@ -175,13 +1185,22 @@ impl TunnelClient {
                     self.last_error = Some(reason.clone());
                     error!("Connection error: {}", reason);
 
-                    if attempt >= MAX_RECONNECT_ATTEMPTS {
+                    if attempt >= self.reconnect.max_attempts {
                         self.send_tui_event(TuiEvent::ConnectionStatus(
                             ConnectionStatus::Disconnected {
                                 reason: format!("Failed after {} attempts: {}", attempt, reason),
                             },
                         ))
                         .await;
+                        if self.wait_for_retry().await {
+                            info!("Retrying the connection");
+                            attempt = 0;
+                            backoff_ms = self.reconnect.initial_backoff_ms;
+                            continue;
+                        }
+                        if let Some(handle) = stats_handle {
+                            handle.abort();
+                        }
                         return Err(e);
                     }
 
@@ -189,6 +1208,7 @@ impl TunnelClient {
                     self.send_tui_event(TuiEvent::ConnectionStatus(
                         ConnectionStatus::Reconnecting {
                             attempt,
+                            max_attempts: self.reconnect.max_attempts,
                             reason: reason.clone(),
                             next_retry_secs: retry_secs,
                         },
@@ -197,110 +1217,328 @@ impl TunnelClient {
 
                     info!(
                         "Reconnecting in {}s (attempt {}/{})",
-                        retry_secs, attempt, MAX_RECONNECT_ATTEMPTS
+                        retry_secs, attempt, self.reconnect.max_attempts
                     );
-                    tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+                    if self
+                        .wait_for_backoff(Duration::from_millis(backoff_ms))
+                        .await
+                    {
+                        // A new server or token gets a fresh set of attempts
+                        attempt = 0;
+                        backoff_ms = self.reconnect.initial_backoff_ms;
+                        self.last_error = None;
+                        continue;
+                    }
 
-                    backoff_ms = ((backoff_ms as f64) * BACKOFF_MULTIPLIER) as u64;
-                    backoff_ms = backoff_ms.min(MAX_BACKOFF_MS);
+                    backoff_ms = ((backoff_ms as f64) * self.reconnect.backoff_multiplier) as u64;
+                    backoff_ms = backoff_ms.min(self.reconnect.max_backoff_ms);
                 }
             }
         }
 
+        if let Some(handle) = stats_handle {
+            handle.abort();
+        }
         Ok(())
     }
 
     async fn send_tui_event(&self, event: TuiEvent) {
-        if let Some(tx) = &self.tui_tx {
-            let _ = tx.send(event).await;
+        if let Some(monitor) = &self.tui_tx {
+            monitor.send(event).await;
         }
     }
 
-    fn track_tunnel(&mut self, config: TunnelConfig) {
-        self.registered_tunnels.push(config);
-    }
-
-    async fn connect_and_run_once(&mut self) -> Result<()> {
-        // Take the command receiver on first call
-        let cmd_rx = self.cmd_rx.take();
+    /// After giving up, wait for the TUI to ask for another round of
+    /// attempts; false without a TUI or once it has gone away
+    async fn wait_for_retry(&mut self) -> bool {
+        loop {
+            let Some(cmd_rx) = self.cmd_rx.as_mut() else {
+                return false;
+            };
+            let Some(command) = cmd_rx.recv().await else {
+                return false;
+            };
+            if self.handle_idle_command(command) {
+                return true;
+            }
+        }
+    }
+
+    /// Sleep between reconnect attempts, cut short when the TUI switches
+    /// server or token (or asks to retry); returns whether it was
+    async fn wait_for_backoff(&mut self, backoff: Duration) -> bool {
+        let sleep = tokio::time::sleep(backoff);
+        tokio::pin!(sleep);
+        loop {
+            let Some(cmd_rx) = self.cmd_rx.as_mut() else {
+                sleep.await;
+                return false;
+            };
+            tokio::select! {
+                _ = &mut sleep => return false,
+                command = cmd_rx.recv() => match command {
+                    Some(command) => {
+                        if self.handle_idle_command(command) {
+                            return true;
+                        }
+                    }
+                    None => {
+                        self.cmd_rx = None;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Handle a TUI command while not connected. Switching server or token
+    /// applies the switch, and tunnels added meanwhile are registered once
+    /// connected. Returns whether to connect again right away.
+    fn handle_idle_command(&mut self, command: TuiCommand) -> bool {
+        match command {
+            TuiCommand::Retry => true,
+            TuiCommand::SetServerHost {
+                new_host,
+                new_token,
+            } => {
+                self.apply_server_switch(ServerSwitch {
+                    host: Some(new_host),
+                    token: new_token,
+                });
+                true
+            }
+            TuiCommand::UpdateToken { token } => {
+                self.apply_server_switch(ServerSwitch {
+                    host: None,
+                    token: Some(token),
+                });
+                true
+            }
+            TuiCommand::AddHttpTunnel {
+                local_port,
+                subdomain,
+                extra_headers,
+                tags,
+            } => {
+                self.track_tunnel(TunnelConfig::Http {
+                    local_port,
+                    subdomain,
+                    extra_headers,
+                    tags,
+                });
+                false
+            }
+            TuiCommand::AddTcpTunnel { local_port } => {
+                self.track_tunnel(TunnelConfig::Tcp { local_port });
+                false
+            }
+            _ => false,
+        }
+    }
 
+    fn apply_server_switch(&mut self, switch: ServerSwitch) {
+        if let Some(host) = switch.host {
+            self.server_host = host;
+            // Tunnels are re-added by the user for the new server
+            self.registered_tunnels.clear();
+            self.probed_ports.clear();
+        }
+        if let Some(token) = switch.token {
+            self.token = token;
+        }
+    }
+
+    fn track_tunnel(&mut self, config: TunnelConfig) {
+        self.registered_tunnels.push(config);
+    }
+
+    /// Track a tunnel added from the TUI, whose port was checked when it was added
+    fn track_added_tunnel(&mut self, config: TunnelConfig) {
+        if let TunnelConfig::Http { local_port, .. } = &config {
+            self.probed_ports.insert(*local_port);
+        }
+        self.track_tunnel(config);
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn connect_and_run_once(&mut self) -> Result<()> {
         // Connect to server
-        let ws_url = format!("wss://{}:{}/tunnel/ws", self.server_host, self.server_port);
+        let ws_url = format!(
+            "{}://{}:{}/tunnel/ws",
+            self.server_scheme.as_str(),
+            self.server_host,
+            self.server_port
+        );
         info!("Connecting to {}...", ws_url);
 
-        let (ws_stream, _) = connect_async(&ws_url)
+        let proxy = if self.use_env_proxy {
+            connect_proxy::resolve_proxy(&ws_url)
+        } else {
+            None
+        };
+        let stream = match proxy {
+            Some(proxy) => connect_proxy::connect(
+                &proxy,
+                &self.server_host,
+                self.server_port,
+                self.prefer_ipv6,
+                self.happy_eyeballs_delay,
+            )
+            .await
+            .context("Failed to connect to server")?,
+            None => happy_eyeballs::connect(
+                &self.server_host,
+                self.server_port,
+                self.prefer_ipv6,
+                self.happy_eyeballs_delay,
+            )
             .await
-            .context("Failed to connect to server")?;
+            .context("Failed to connect to server")?,
+        };
+        let server_addr = stream.peer_addr().context("Failed to connect to server")?;
+        // Messages up to twice the limit are read so that an oversized request
+        // can still be answered; anything larger fails the connection before
+        // it is buffered
+        let hard_limit = self.max_incoming_message_bytes.saturating_mul(2);
+        let ws_config = WebSocketConfig {
+            max_message_size: Some(hard_limit),
+            max_frame_size: Some(hard_limit),
+            ..Default::default()
+        };
+        let (ws_stream, _) =
+            client_async_tls_with_config(ws_url.as_str(), stream, Some(ws_config), None)
+                .await
+                .context("Failed to connect to server")?;
 
-        info!("Connected to server");
+        info!("Connected to server at {}", server_addr);
         self.send_tui_event(TuiEvent::ConnectionStatus(ConnectionStatus::Connected))
             .await;
+        self.send_tui_event(TuiEvent::ServerAddress(server_addr))
+            .await;
 
         // Split the stream
         let (write, read) = ws_stream.split();
 
-        // Create message channel - text messages go through this
+        // Create message channel - responses and tunnel data go through this
         let (msg_tx, mut msg_rx) = mpsc::channel::<String>(256);
 
-        // Channel for raw WebSocket messages (including pong frames)
-        let (ws_tx, mut ws_rx) = mpsc::channel::<Message>(256);
+        // Channel for messages that shouldn't wait behind responses when the
+        // connection is congested: heartbeats, pongs, close and error responses
+        let (high_priority_tx, mut high_priority_rx) = mpsc::channel::<Message>(256);
 
         // Channel for tracking newly registered tunnels
         let (tunnel_config_tx, mut tunnel_config_rx) = mpsc::channel::<TunnelConfig>(16);
 
         // Spawn message sender task - owns the write half exclusively
-        let sender_handle = tokio::spawn(async move {
+        let queue_depth = self.queue_depth.clone();
+        let message_batching = self.message_batching;
+        let mut sender_handle = tokio::spawn(async move {
             let mut write = write;
+            let mut backed_up = false;
             loop {
                 tokio::select! {
-                    Some(msg) = ws_rx.recv() => {
-                        if let Err(e) = write.send(msg).await {
-                            if !e.to_string().contains("closing") {
-                                debug!("Send error (connection closing): {}", e);
-                            }
+                    biased;
+                    Some(msg) = high_priority_rx.recv() => {
+                        let closing = matches!(msg, Message::Close(_));
+                        if !send_frame(&mut write, msg).await || closing {
                             break;
                         }
                     }
                     Some(text) = msg_rx.recv() => {
-                        if let Err(e) = write.send(Message::Text(text)).await {
-                            if !e.to_string().contains("closing") {
-                                debug!("Send error (connection closing): {}", e);
+                        let (text, urgent) = match message_batching {
+                            Some(batching) => {
+                                batching.collect(text, &mut msg_rx, &mut high_priority_rx).await
+                            }
+                            None => (Some(text), None),
+                        };
+                        if let Some(msg) = urgent {
+                            let closing = matches!(msg, Message::Close(_));
+                            if !send_frame(&mut write, msg).await || closing {
+                                break;
+                            }
+                        }
+                        if let Some(text) = text {
+                            if !send_frame(&mut write, Message::Text(text)).await {
+                                break;
                             }
-                            break;
                         }
                     }
                     else => break,
                 }
+                let depth = high_priority_rx.len() + msg_rx.len();
+                queue_depth.store(depth as u32, Ordering::Relaxed);
+                if depth > SEND_QUEUE_WARN_DEPTH && !backed_up {
+                    warn!(
+                        "{} messages waiting to be sent; the connection to the server can't keep up",
+                        depth
+                    );
+                }
+                backed_up = depth > SEND_QUEUE_WARN_DEPTH;
             }
+            queue_depth.store(0, Ordering::Relaxed);
         });
 
         // Initialize state
-        let state = Arc::new(RwLock::new(ClientState::new(&self.local_host)));
+        // The server may replay requests after a reconnect, so keep the
+        // dedup caches from the previous connection
+        let mut state = ClientState::new(
+            &self.local_host,
+            &self.token,
+            self.chunk_size_bytes,
+            self.streaming_threshold_bytes,
+            self.request_caches.clone(),
+            self.proxy.clone(),
+            high_priority_tx.clone(),
+        );
+        state.pool_id = self.pool_id;
+        state.session_id = self.session_id.clone();
+        state.slow_tcp_connect_threshold = self.slow_tcp_connect_threshold;
+        state.ws_keepalive = self.ws_keepalive;
+        state.max_outgoing_message_bytes = self.max_outgoing_message_bytes;
+        state.tunnel_id_senders = self.tunnel_id_senders.clone();
+        let state = Arc::new(RwLock::new(state));
+
+        // A local service that isn't running yet doesn't stop its tunnel from
+        // being registered, but the user is told, once rather than on every
+        // reconnect
+        for config in &self.registered_tunnels {
+            if let TunnelConfig::Http { local_port, .. } = config {
+                if self.probed_ports.insert(*local_port) {
+                    tokio::spawn(warn_if_port_unreachable(
+                        self.local_host.clone(),
+                        *local_port,
+                        self.tui_tx.clone(),
+                    ));
+                }
+            }
+        }
 
-        // Re-register existing tunnels on reconnect
+        // Re-register existing tunnels on reconnect. The server may not have
+        // noticed the old connection drop yet, so HTTP tunnels it still has
+        // are taken over rather than registered a second time.
+        let mut queries = Vec::new();
         for config in &self.registered_tunnels {
             match config {
                 TunnelConfig::Http {
                     local_port,
                     subdomain,
+                    extra_headers,
+                    tags,
                 } => {
-                    let mut s = state.write().await;
-                    s.pending_tunnels.push(PendingTunnel {
-                        local_host: self.local_host.clone(),
+                    state
+                        .write()
+                        .await
+                        .awaiting_tunnel_list
+                        .push(PendingTunnel::new(
+                            &self.local_host,
+                            *local_port,
+                            subdomain.clone(),
+                            extra_headers.clone(),
+                            tags.clone(),
+                        ));
+                    queries.push(TunnelQuery {
                         local_port: *local_port,
+                        requested_subdomain: subdomain.clone(),
                     });
-                    drop(s);
-
-                    let msg = OutgoingMessage::register_tunnel(
-                        &self.token,
-                        &self.local_host,
-                        *local_port,
-                        subdomain.clone(),
-                    );
-                    if let Ok(json) = msg.to_json() {
-                        let _ = msg_tx.send(json).await;
-                        debug!("Re-registering HTTP tunnel for port {}", local_port);
-                    }
                 }
                 TunnelConfig::Tcp { local_port } => {
                     let mut s = state.write().await;
@@ -315,45 +1553,77 @@ impl TunnelClient {
                 }
             }
         }
+        if !queries.is_empty() {
+            let msg = OutgoingMessage::query_tunnels(&self.token, &self.session_id, queries);
+            if let Ok(json) = msg.to_json() {
+                let _ = msg_tx.send(json).await;
+                debug!("Asking the server which tunnels it still has");
+            }
+        }
 
-        // Spawn command handler task if we have a receiver
-        let command_handle = if let Some(mut cmd_rx) = cmd_rx {
+        // Spawn command handler task if we have a receiver. It hands the
+        // receiver back when stopped, so commands sent while reconnecting
+        // wait in the channel for the next connection. It is only taken now
+        // that the connection is up, so a failed attempt can't lose it.
+        let cmd_rx = self.cmd_rx.take();
+        let (stop_commands_tx, mut stop_commands_rx) = oneshot::channel::<()>();
+        let mut command_handle = if let Some(mut cmd_rx) = cmd_rx {
             let msg_tx_cmd = msg_tx.clone();
             let token_clone = self.token.clone();
+            let session_id = self.session_id.clone();
             let local_host_clone = self.local_host.clone();
             let state_cmd = state.clone();
             let tunnel_config_tx = tunnel_config_tx.clone();
+            let chunk_size_bytes = self.chunk_size_bytes;
+            let tui_tx_cmd = self.tui_tx.clone();
 
             Some(tokio::spawn(async move {
-                while let Some(cmd) = cmd_rx.recv().await {
+                loop {
+                    let cmd = tokio::select! {
+                        cmd = cmd_rx.recv() => match cmd {
+                            Some(cmd) => cmd,
+                            None => break,
+                        },
+                        _ = &mut stop_commands_rx => break,
+                    };
                     match cmd {
                         TuiCommand::AddHttpTunnel {
                             local_port,
                             subdomain,
+                            extra_headers,
+                            tags,
                         } => {
                             // Track for reconnect
                             let _ = tunnel_config_tx
                                 .send(TunnelConfig::Http {
                                     local_port,
                                     subdomain: subdomain.clone(),
+                                    extra_headers: extra_headers.clone(),
+                                    tags: tags.clone(),
                                 })
                                 .await;
 
                             // Add to pending tunnels
-                            {
-                                let mut s = state_cmd.write().await;
-                                s.pending_tunnels.push(PendingTunnel {
-                                    local_host: local_host_clone.clone(),
-                                    local_port,
-                                });
-                            }
-                            // Send registration message
-                            let msg = OutgoingMessage::register_tunnel(
-                                &token_clone,
+                            let pending = PendingTunnel::new(
                                 &local_host_clone,
                                 local_port,
                                 subdomain,
+                                extra_headers,
+                                tags,
+                            );
+                            let msg = pending.register_message(
+                                &token_clone,
+                                &session_id,
+                                chunk_size_bytes,
                             );
+                            state_cmd.write().await.pending_tunnels.push(pending);
+                            tokio::spawn(warn_if_port_unreachable(
+                                local_host_clone.clone(),
+                                local_port,
+                                tui_tx_cmd.clone(),
+                            ));
+
+                            // Send registration message
                             if let Ok(json) = msg.to_json() {
                                 if msg_tx_cmd.send(json).await.is_err() {
                                     break;
@@ -381,22 +1651,74 @@ impl TunnelClient {
                                 debug!("Sent register_tcp_tunnel for port {}", local_port);
                             }
                         }
+                        TuiCommand::SetServerHost {
+                            new_host,
+                            new_token,
+                        } => {
+                            let switch = ServerSwitch {
+                                host: Some(new_host),
+                                token: new_token,
+                            };
+                            return (cmd_rx, Some(switch));
+                        }
+                        TuiCommand::UpdateToken { token } => {
+                            let switch = ServerSwitch {
+                                host: None,
+                                token: Some(token),
+                            };
+                            return (cmd_rx, Some(switch));
+                        }
+                        // Only meaningful once the connection has given up
+                        TuiCommand::Retry => {}
+                        TuiCommand::RequestSnapshot => {
+                            let snapshot = state_cmd.read().await.snapshot();
+                            if let Some(tx) = &tui_tx_cmd {
+                                tx.send(TuiEvent::StateSnapshot(snapshot)).await;
+                            }
+                        }
                     }
                 }
+                (cmd_rx, None)
             }))
         } else {
             None
         };
 
-        // Spawn heartbeat sender task - sends heartbeat every 25 seconds
-        let msg_tx_heartbeat = msg_tx.clone();
+        // Spawn watchdog that retries tunnel registrations the server never confirmed
+        let watchdog_handle = tokio::spawn(timeout_watchdog(
+            state.clone(),
+            msg_tx.clone(),
+            self.tui_tx.clone(),
+            self.token.clone(),
+            self.session_id.clone(),
+            self.chunk_size_bytes,
+            self.registration_timeout,
+        ));
+
+        // Spawn task that checks whether unreachable local ports are back
+        tokio::spawn(local_port_health_check(
+            state.clone(),
+            msg_tx.clone(),
+            self.tui_tx.clone(),
+            self.local_port_health_check_interval,
+        ));
+
+        // Spawn heartbeat sender task
+        let high_priority_tx_heartbeat = high_priority_tx.clone();
+        let heartbeat_interval = self.heartbeat_interval;
+        let state_heartbeat = state.clone();
         let heartbeat_handle = tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(25));
+            let mut interval = tokio::time::interval(heartbeat_interval);
             loop {
                 interval.tick().await;
                 let msg = OutgoingMessage::Heartbeat {};
                 if let Ok(json) = msg.to_json() {
-                    if msg_tx_heartbeat.send(json).await.is_err() {
+                    state_heartbeat.write().await.heartbeat_sent_at = Some(Instant::now());
+                    if high_priority_tx_heartbeat
+                        .send(Message::Text(json))
+                        .await
+                        .is_err()
+                    {
                         break;
                     }
                     debug!("Sent heartbeat");
@@ -404,49 +1726,119 @@ impl TunnelClient {
             }
         });
 
+        // Spawn ping task measuring round-trip time and lost pings for the TUI
+        let probe = Arc::new(PingProbe::new());
+        let probe_ping = probe.clone();
+        let high_priority_tx_ping = high_priority_tx.clone();
+        let tui_tx_ping = self.tui_tx.clone();
+        let ws_ping_interval = self.ws_ping_interval;
+        let ping_handle = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ws_ping_interval);
+            loop {
+                interval.tick().await;
+                if !probe_ping.answered.load(Ordering::Relaxed) {
+                    debug!("Ping went unanswered");
+                    if let Some(tx) = &tui_tx_ping {
+                        tx.send(TuiEvent::Heartbeat { rtt_ms: None }).await;
+                    }
+                }
+                let payload = probe_ping.next_ping();
+                if high_priority_tx_ping
+                    .send(Message::Ping(payload))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
         // Spawn message receiver task
         let state_clone = state.clone();
         let msg_tx_clone = msg_tx.clone();
         let server_host = self.server_host.clone();
-        let ws_tx_for_pong = ws_tx.clone();
+        let high_priority_tx_pong = high_priority_tx.clone();
         let tui_tx_clone = self.tui_tx.clone();
+        let max_incoming_message_bytes = self.max_incoming_message_bytes;
 
         let receiver_handle = tokio::spawn(async move {
             let mut read = read;
-            let mut tunnels_registered = 0;
-            let mut tcp_tunnels_registered = 0;
+            let mut registered = RegisteredCounts::default();
 
             while let Some(result) = read.next().await {
                 match result {
+                    Ok(Message::Text(text)) if text.len() > max_incoming_message_bytes => {
+                        warn!("Dropping oversized message: {} bytes", text.len());
+                        answer_oversized_request(&text, &msg_tx_clone).await;
+                    }
                     Ok(Message::Text(text)) => {
                         if let Err(e) = handle_message(
                             &text,
+                            Instant::now(),
                             &state_clone,
                             &msg_tx_clone,
                             &server_host,
-                            &mut tunnels_registered,
-                            &mut tcp_tunnels_registered,
+                            &mut registered,
                             &tui_tx_clone,
                         )
                         .await
                         {
+                            if matches!(
+                                e.downcast_ref(),
+                                Some(BurrowError::AuthFailed | BurrowError::ProtocolVersionTooOld)
+                            ) {
+                                return Err(e);
+                            }
                             error!("Error handling message: {}", e);
+                            report_error(&tui_tx_clone, ErrorSource::Protocol, format!("{:#}", e))
+                                .await;
                         }
                     }
                     Ok(Message::Ping(data)) => {
                         debug!("Received ping, sending pong");
-                        let _ = ws_tx_for_pong.send(Message::Pong(data)).await;
+                        let _ = high_priority_tx_pong.send(Message::Pong(data)).await;
                     }
-                    Ok(Message::Pong(_)) => {
+                    Ok(Message::Pong(data)) => {
                         debug!("Received pong");
+                        if let (Some(rtt_ms), Some(tx)) = (probe.pong(&data), &tui_tx_clone) {
+                            tx.send(TuiEvent::Heartbeat {
+                                rtt_ms: Some(rtt_ms),
+                            })
+                            .await;
+                        }
                     }
                     Ok(Message::Close(frame)) => {
+                        let (code, reason) = frame
+                            .as_ref()
+                            .map(|f| (u16::from(f.code), f.reason.to_string()))
+                            .unwrap_or((1005, String::new()));
                         info!(
-                            "Server closed connection: {:?}",
-                            frame.map(|f| f.reason.to_string())
+                            "Server closed connection: {} ({}) {:?}",
+                            code,
+                            close_code_description(code),
+                            reason
                         );
+
+                        // RFC 6455: answer with a close frame before dropping the connection
+                        let _ = high_priority_tx_pong.send(Message::Close(frame)).await;
+
+                        if (4000..=4999).contains(&code) {
+                            let reason = format!("Server: {}", reason);
+                            if let Some(tx) = &tui_tx_clone {
+                                tx.send(TuiEvent::ConnectionStatus(
+                                    ConnectionStatus::Disconnected {
+                                        reason: reason.clone(),
+                                    },
+                                ))
+                                .await;
+                            }
+                            return Err(anyhow::anyhow!(reason));
+                        }
                         break;
                     }
+                    Ok(Message::Binary(data)) if data.len() > max_incoming_message_bytes => {
+                        warn!("Dropping oversized message: {} bytes", data.len());
+                    }
                     Ok(Message::Binary(_)) => {
                         debug!("Received binary message (ignoring)");
                     }
@@ -457,21 +1849,28 @@ impl TunnelClient {
                     _ => {}
                 }
             }
+            Ok(())
         });
 
+        // Kept for the close frame sent on shutdown, and to see when
+        // queued responses have been flushed before sending it
+        let msg_tx_close = msg_tx.clone();
+        let high_priority_tx_close = high_priority_tx.clone();
+
         // Drop the senders to signal tasks to stop when we're done
         drop(msg_tx);
-        drop(ws_tx);
+        drop(high_priority_tx);
         drop(tunnel_config_tx);
 
         // Collect any tunnel configs that were registered
         while let Ok(config) = tunnel_config_rx.try_recv() {
-            self.track_tunnel(config);
+            self.track_added_tunnel(config);
         }
 
         // Wait for shutdown or disconnect
+        let mut server_switch = None;
         let result = tokio::select! {
-            _ = sender_handle => {
+            _ = &mut sender_handle => {
                 debug!("Sender task ended");
                 Err(anyhow::anyhow!("Connection lost"))
             }
@@ -479,19 +1878,48 @@ impl TunnelClient {
                 debug!("Heartbeat task ended");
                 Err(anyhow::anyhow!("Connection lost"))
             }
-            _ = receiver_handle => {
-                debug!("Receiver task ended");
+            _ = ping_handle => {
+                debug!("Ping task ended");
                 Err(anyhow::anyhow!("Connection lost"))
             }
-            _ = async {
-                if let Some(handle) = command_handle {
+            _ = watchdog_handle => {
+                debug!("Registration watchdog task ended");
+                Err(anyhow::anyhow!("Connection lost"))
+            }
+            result = receiver_handle => {
+                debug!("Receiver task ended");
+                match result {
+                    Ok(Err(e)) => Err(e),
+                    _ => Err(anyhow::anyhow!("Connection lost")),
+                }
+            }
+            result = async {
+                if let Some(handle) = command_handle.as_mut() {
                     handle.await
                 } else {
-                    std::future::pending::<Result<(), tokio::task::JoinError>>().await
+                    std::future::pending::<Result<CommandTaskResult, tokio::task::JoinError>>().await
                 }
             } => {
                 debug!("Command handler task ended");
-                Err(anyhow::anyhow!("Connection lost"))
+                command_handle = None;
+                match result {
+                    Ok((cmd_rx, switch)) => {
+                        // Keep taking commands on the next connection
+                        self.cmd_rx = Some(cmd_rx);
+                        match switch {
+                            Some(switch) => {
+                                match &switch.host {
+                                    Some(host) => info!("Switching server to {}", host),
+                                    None => info!("Reconnecting with a new token"),
+                                }
+                                server_switch = Some(switch);
+                                Err(BurrowError::SwitchingServer.into())
+                            }
+                            None => Err(anyhow::anyhow!("Connection lost")),
+                        }
+                    }
+                    Err(_) => Err(anyhow::anyhow!("Connection lost")),
+                }
             }
             _ = tokio::signal::ctrl_c() => {
                 info!("\nShutting down...");
@@ -499,39 +1927,305 @@ impl TunnelClient {
             }
         };
 
+        // The connection ended some other way; take the command receiver back
+        if let Some(mut handle) = command_handle {
+            let _ = stop_commands_tx.send(());
+            match tokio::time::timeout(Duration::from_secs(1), &mut handle).await {
+                Ok(Ok((cmd_rx, _))) => self.cmd_rx = Some(cmd_rx),
+                _ => {
+                    handle.abort();
+                    warn!("Command handler didn't stop; new tunnels can't be added");
+                }
+            }
+        }
+
+        if result.is_ok() || server_switch.is_some() {
+            self.shutdown(&state, msg_tx_close, high_priority_tx_close, sender_handle)
+                .await;
+        }
+
         // Collect any remaining tunnel configs
         while let Ok(config) = tunnel_config_rx.try_recv() {
-            self.track_tunnel(config);
+            self.track_added_tunnel(config);
+        }
+
+        if let Some(switch) = server_switch {
+            self.apply_server_switch(switch);
         }
 
         result
     }
+
+    /// Let in-flight requests finish and their responses go out, then close
+    /// the connection cleanly
+    async fn shutdown(
+        &self,
+        state: &Arc<RwLock<ClientState>>,
+        msg_tx: mpsc::Sender<String>,
+        high_priority_tx: mpsc::Sender<Message>,
+        sender_handle: JoinHandle<()>,
+    ) {
+        state.write().await.shutting_down = true;
+
+        let deadline = Instant::now() + self.shutdown_timeout;
+        loop {
+            let in_flight = state.read().await.in_flight_requests.len();
+            // The close frame jumps the queue, so wait for responses to be written
+            let queued = msg_tx.max_capacity() - msg_tx.capacity();
+            if in_flight == 0 && queued == 0 {
+                break;
+            }
+            if Instant::now() >= deadline {
+                warn!(
+                    "Shutting down with {} request(s) still in flight",
+                    in_flight
+                );
+                break;
+            }
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+
+        let frame = CloseFrame {
+            code: CloseCode::Normal,
+            reason: "client_shutdown".into(),
+        };
+        if high_priority_tx
+            .send(Message::Close(Some(frame)))
+            .await
+            .is_ok()
+        {
+            let _ = tokio::time::timeout(CLOSE_FRAME_TIMEOUT, sender_handle).await;
+        }
+    }
+}
+
+/// Periodically retry tunnel registrations that the server hasn't confirmed,
+/// giving up after [`MAX_REGISTRATION_ATTEMPTS`].
+async fn timeout_watchdog(
+    state: Arc<RwLock<ClientState>>,
+    msg_tx: mpsc::Sender<String>,
+    tui_tx: Option<ChannelMonitor>,
+    token: String,
+    session_id: String,
+    chunk_size_bytes: usize,
+    registration_timeout: Duration,
+) {
+    let mut interval = tokio::time::interval(REGISTRATION_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let mut retries = Vec::new();
+        let mut failures = Vec::new();
+        {
+            let mut s = state.write().await;
+            s.pending_tunnels.retain_mut(|pending| {
+                if pending.sent_at.elapsed() < registration_timeout {
+                    return true;
+                }
+                if pending.registration_attempt >= MAX_REGISTRATION_ATTEMPTS {
+                    failures.push(pending.local_port);
+                    return false;
+                }
+                pending.registration_attempt += 1;
+                pending.sent_at = Instant::now();
+                retries.push((
+                    pending.local_port,
+                    pending.registration_attempt,
+                    pending.register_message(&token, &session_id, chunk_size_bytes),
+                ));
+                true
+            });
+        }
+
+        for local_port in failures {
+            warn!(
+                "Tunnel registration for port {} failed after {} attempts",
+                local_port, MAX_REGISTRATION_ATTEMPTS
+            );
+            if let Some(tx) = &tui_tx {
+                tx.send(TuiEvent::Notification {
+                    message: format!(
+                        "Tunnel registration for port {} failed after {} attempts",
+                        local_port, MAX_REGISTRATION_ATTEMPTS
+                    ),
+                    level: NotificationLevel::Error,
+                })
+                .await;
+            }
+        }
+
+        for (local_port, attempt, msg) in retries {
+            warn!(
+                "Tunnel registration for port {} timed out, retrying ({}/{})",
+                local_port, attempt, MAX_REGISTRATION_ATTEMPTS
+            );
+            if let Some(tx) = &tui_tx {
+                tx.send(TuiEvent::Notification {
+                    message: format!(
+                        "Tunnel registration for port {} timed out, retrying ({}/{})",
+                        local_port, attempt, MAX_REGISTRATION_ATTEMPTS
+                    ),
+                    level: NotificationLevel::Warn,
+                })
+                .await;
+            }
+
+            // The retry reuses the registration id, so a server that did get
+            // the original answers with the same tunnel
+            if let Ok(json) = msg.to_json() {
+                if msg_tx.send(json).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// A random id for a client's connections to the server
+fn new_session_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    // Each RandomState is seeded randomly, which is all this needs
+    let mut id = String::new();
+    for _ in 0..2 {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        id.push_str(&format!("{:016x}", hasher.finish()));
+    }
+    id
+}
+
+/// Whether something accepts connections on the local port
+async fn local_port_reachable(local_host: &str, local_port: u16) -> bool {
+    matches!(
+        tokio::time::timeout(
+            LOCAL_PORT_CHECK_TIMEOUT,
+            TcpStream::connect((local_host, local_port)),
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
+/// Tell the user when nothing is listening on a tunnel's local port yet
+async fn warn_if_port_unreachable(
+    local_host: String,
+    local_port: u16,
+    tui_tx: Option<ChannelMonitor>,
+) {
+    if local_port_reachable(&local_host, local_port).await {
+        return;
+    }
+    warn!(
+        "Port {} not reachable on {}, starting tunnel anyway",
+        local_port, local_host
+    );
+    if let Some(tx) = &tui_tx {
+        tx.send(TuiEvent::Notification {
+            message: format!("Port {} not reachable – starting tunnel anyway", local_port),
+            level: NotificationLevel::Warn,
+        })
+        .await;
+    }
+}
+
+/// Periodically check the local ports of tunnels whose requests kept
+/// failing, and clear them once they accept connections again. Ends when
+/// the connection does.
+async fn local_port_health_check(
+    state: Arc<RwLock<ClientState>>,
+    msg_tx: mpsc::Sender<String>,
+    tui_tx: Option<ChannelMonitor>,
+    check_interval: Duration,
+) {
+    let mut interval = tokio::time::interval(check_interval);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if msg_tx.is_closed() {
+            return;
+        }
+
+        let unreachable: Vec<(TunnelId, u16)> = {
+            let s = state.read().await;
+            s.local_port_failures
+                .iter()
+                .filter(|(_, failures)| **failures >= LOCAL_PORT_FAILURE_LIMIT)
+                .filter_map(|(tunnel_id, _)| {
+                    Some((tunnel_id.clone(), s.tunnels.get(tunnel_id)?.local_port))
+                })
+                .collect()
+        };
+        let local_host = state.read().await.local_host.clone();
+        for (tunnel_id, local_port) in unreachable {
+            if !local_port_reachable(&local_host, local_port).await {
+                continue;
+            }
+            info!("Port {} is reachable again", local_port);
+            state.write().await.local_port_failures.remove(&tunnel_id);
+            if let Some(tx) = &tui_tx {
+                tx.send(TuiEvent::Notification {
+                    message: format!("Port {} is reachable again", local_port),
+                    level: NotificationLevel::Info,
+                })
+                .await;
+            }
+        }
+    }
+}
+
+/// Whether an error code means the server doesn't accept the token. The
+/// server sends `invalid_token`; `auth_failed` is accepted as well.
+fn is_auth_failure(code: &str) -> bool {
+    matches!(code, "invalid_token" | "auth_failed")
 }
 
+#[tracing::instrument(level = "trace", skip_all, fields(len = text.len()))]
 async fn handle_message(
     text: &str,
+    received_at: Instant,
     state: &Arc<RwLock<ClientState>>,
     msg_tx: &mpsc::Sender<String>,
     server_host: &str,
-    tunnels_registered: &mut usize,
-    tcp_tunnels_registered: &mut usize,
-    tui_tx: &Option<mpsc::Sender<TuiEvent>>,
+    registered: &mut RegisteredCounts,
+    tui_tx: &Option<ChannelMonitor>,
 ) -> Result<()> {
     let msg = IncomingMessage::from_json(text).context("Failed to parse message")?;
 
     match msg {
         IncomingMessage::TunnelRegistered {
+            registration_id,
             tunnel_id,
             subdomain: _,
             full_url,
         } => {
             let mut s = state.write().await;
 
-            // Find the pending tunnel for this registration
-            let pending = s.pending_tunnels.get(*tunnels_registered);
-            let (local_host, local_port) = pending
-                .map(|p| (p.local_host.clone(), p.local_port))
-                .unwrap_or_else(|| (s.local_host.clone(), 0));
+            let pending = match registration_id {
+                Some(id) => match s
+                    .pending_tunnels
+                    .iter()
+                    .position(|p| p.registration_id == id)
+                {
+                    Some(i) => Some(s.pending_tunnels.remove(i)),
+                    None => {
+                        // The answer to a retry of a registration already confirmed
+                        debug!("Ignoring repeated confirmation of registration {}", id);
+                        return Ok(());
+                    }
+                },
+                // Older servers confirm registrations in the order they were sent
+                None => (!s.pending_tunnels.is_empty()).then(|| s.pending_tunnels.remove(0)),
+            };
+            let (local_host, local_port, extra_headers, tags) = pending
+                .map(|p| (p.local_host, p.local_port, p.extra_headers, p.tags))
+                .unwrap_or_else(|| (s.local_host.clone(), 0, Vec::new(), Vec::new()));
 
             info!(
                 "Tunnel registered: {} -> {}:{}",
@@ -540,24 +2234,72 @@ async fn handle_message(
 
             // Send TUI event
             if let Some(tx) = tui_tx {
-                let _ = tx
-                    .send(TuiEvent::TunnelRegistered(TunnelEvent {
-                        full_url: full_url.clone(),
-                        local_port,
-                    }))
-                    .await;
+                tx.send(TuiEvent::TunnelRegistered(TunnelEvent {
+                    tunnel_id: tunnel_id.clone(),
+                    full_url: full_url.clone(),
+                    local_port,
+                    pool_id: s.pool_id,
+                    extra_header_count: extra_headers.len(),
+                    tags,
+                }))
+                .await;
             }
 
+            if let Some(id_tx) = s.tunnel_id_senders.http.get(&local_port) {
+                id_tx.send_replace(Some(tunnel_id.clone()));
+            }
             s.tunnels.insert(
                 tunnel_id,
                 TunnelInfo {
                     full_url,
                     local_host,
                     local_port,
+                    extra_headers,
                 },
             );
 
-            *tunnels_registered += 1;
+            registered.http += 1;
+        }
+
+        IncomingMessage::TunnelList { tunnels } => {
+            let mut s = state.write().await;
+            let awaiting = std::mem::take(&mut s.awaiting_tunnel_list);
+            let (kept, register) = match_listed_tunnels(awaiting, tunnels);
+
+            for (pending, listed) in kept {
+                info!(
+                    "Tunnel kept across reconnect: {} -> {}:{}",
+                    listed.full_url, pending.local_host, pending.local_port
+                );
+                if let Some(tx) = tui_tx {
+                    tx.send(TuiEvent::TunnelRegistered(TunnelEvent {
+                        tunnel_id: listed.tunnel_id.clone(),
+                        full_url: listed.full_url.clone(),
+                        local_port: pending.local_port,
+                        pool_id: s.pool_id,
+                        extra_header_count: pending.extra_headers.len(),
+                        tags: pending.tags,
+                    }))
+                    .await;
+                }
+                if let Some(id_tx) = s.tunnel_id_senders.http.get(&pending.local_port) {
+                    id_tx.send_replace(Some(listed.tunnel_id.clone()));
+                }
+                s.tunnels.insert(
+                    listed.tunnel_id,
+                    TunnelInfo {
+                        full_url: listed.full_url,
+                        local_host: pending.local_host,
+                        local_port: pending.local_port,
+                        extra_headers: pending.extra_headers,
+                    },
+                );
+                registered.http += 1;
+            }
+
+            for pending in register {
+                s.register_pending_tunnel(pending, msg_tx).await;
+            }
         }
 
         IncomingMessage::TcpTunnelRegistered {
@@ -574,14 +2316,19 @@ async fn handle_message(
 
             // Send TUI event
             if let Some(tx) = tui_tx {
-                let _ = tx
-                    .send(TuiEvent::TcpTunnelRegistered(TcpTunnelEvent {
-                        server_port,
-                        local_port,
-                    }))
-                    .await;
+                tx.send(TuiEvent::TcpTunnelRegistered(TcpTunnelEvent {
+                    tcp_tunnel_id: tcp_tunnel_id.clone(),
+                    server_host: server_host.to_string(),
+                    server_port,
+                    local_port,
+                    pool_id: s.pool_id,
+                }))
+                .await;
             }
 
+            if let Some(id_tx) = s.tunnel_id_senders.tcp.get(&local_port) {
+                id_tx.send_replace(Some(tcp_tunnel_id.clone()));
+            }
             s.tcp_tunnels.insert(
                 tcp_tunnel_id,
                 TcpTunnelInfo {
@@ -590,7 +2337,7 @@ async fn handle_message(
                 },
             );
 
-            *tcp_tunnels_registered += 1;
+            registered.tcp += 1;
         }
 
         IncomingMessage::TunnelRequest {
@@ -603,16 +2350,94 @@ async fn handle_message(
             body,
             body_encoding,
             client_ip,
+            body_chunked,
         } => {
             let s = state.read().await;
-            let local_port = s.find_tunnel_port(&tunnel_id).unwrap_or(3000);
+            if s.shutting_down {
+                let high_priority_tx = s.high_priority_tx.clone();
+                drop(s);
+                debug!(
+                    method = %method,
+                    path = %path,
+                    request_id = %request_id,
+                    tunnel_id = %tunnel_id,
+                    "Refusing request while shutting down"
+                );
+                let msg = OutgoingMessage::tunnel_response(
+                    &request_id,
+                    503,
+                    vec![("content-type".to_string(), "text/plain".to_string())],
+                    Some(b"Service Unavailable: client shutting down".to_vec()),
+                );
+                if let Ok(json) = msg.to_json() {
+                    let _ = high_priority_tx.send(Message::Text(json)).await;
+                }
+                return Ok(());
+            }
+            let tunnel_port = s.find_tunnel_port(&tunnel_id);
             let local_host = s.local_host.clone();
+            let tunnel_url = s.tunnels.get(&tunnel_id).map(|t| t.full_url.clone());
+            let extra_headers = s
+                .tunnels
+                .get(&tunnel_id)
+                .map(|t| t.extra_headers.clone())
+                .unwrap_or_default();
+            let request_caches = s.request_caches.clone();
+            let (max_outgoing_message_bytes, chunk_size_bytes) =
+                (s.max_outgoing_message_bytes, s.chunk_size_bytes);
             drop(s);
 
-            debug!("{} {} -> localhost:{}", method, path, local_port);
+            if is_idempotent(&method) {
+                let replay = request_caches.lock().unwrap().check(&request_id, msg_tx);
+                match replay {
+                    Replay::New => {}
+                    Replay::Answered(message) => {
+                        debug!("Resending cached response for replayed {}", request_id);
+                        send_response(
+                            &message,
+                            max_outgoing_message_bytes,
+                            chunk_size_bytes,
+                            msg_tx,
+                        )
+                        .await;
+                        return Ok(());
+                    }
+                    Replay::Parked => {
+                        debug!(
+                            "Replayed {} is still in flight, answering it once done",
+                            request_id
+                        );
+                        return Ok(());
+                    }
+                }
+            }
 
-            let msg_tx = msg_tx.clone();
-            let body_data = decode_body(body.as_deref(), body_encoding.as_deref());
+            let local_port = match tunnel_port {
+                Some(port) => port,
+                None => {
+                    report_error(
+                        tui_tx,
+                        ErrorSource::Tunnel {
+                            tunnel_id: tunnel_id.clone(),
+                        },
+                        format!(
+                            "{} {} for unknown tunnel, forwarding to port 3000",
+                            method, path
+                        ),
+                    )
+                    .await;
+                    3000
+                }
+            };
+
+            info!(
+                method = %method,
+                path = %path,
+                local_port = local_port,
+                request_id = %request_id,
+                tunnel_id = %tunnel_id,
+                "Forwarding request"
+            );
 
             // Convert headers
             let headers: Vec<(String, String)> = headers
@@ -626,99 +2451,138 @@ async fn handle_message(
                 })
                 .collect();
 
-            // Send TUI request event
-            if let Some(tx) = tui_tx {
-                let _ = tx
-                    .send(TuiEvent::RequestReceived(RequestEvent {
-                        request_id: request_id.clone(),
-                        method: method.clone(),
-                        path: path.clone(),
-                        query_string: query_string.clone(),
-                        headers: headers.clone(),
-                        body: body_data.clone(),
-                        timestamp: Local::now(),
-                        client_ip,
-                    }))
-                    .await;
-            }
-
-            let tui_tx_clone = tui_tx.clone();
-            let request_id_clone = request_id.clone();
-            let method_clone = method.clone();
-            let path_clone = path.clone();
-
-            tokio::spawn(async move {
-                let start = Instant::now();
-                let response = forward_http_request(
-                    &local_host,
-                    local_port,
-                    &method_clone,
-                    &path_clone,
-                    &query_string,
-                    headers,
-                    body_data,
-                )
-                .await;
+            let request = ForwardRequest {
+                request_id,
+                tunnel_id,
+                local_host,
+                local_port,
+                method,
+                path,
+                query_string,
+                headers,
+                extra_headers,
+                body: decode_body(body.as_deref(), body_encoding.as_deref()),
+                body_stream: None,
+                client_ip,
+                tunnel_url,
+                received_at,
+            };
 
-                let duration_ms = start.elapsed().as_millis() as u64;
+            let threshold = state.read().await.streaming_threshold_bytes;
+            if body_chunked && content_length(&request.headers).is_none_or(|len| len >= threshold) {
+                // Too large (or of unknown size) to buffer; forward chunks as they arrive.
+                // Chunks are never waited on, so a slow local service doesn't
+                // stall the whole connection.
+                let (chunks_tx, chunks_rx) = mpsc::channel(STREAMING_BODY_BUFFER_CHUNKS);
+                let mut request = request;
+                if let Some(initial) = request.body.take().filter(|b| !b.is_empty()) {
+                    let _ = chunks_tx.try_send(BodyChunk::Data(initial));
+                }
+                request.body_stream = Some(chunks_rx);
+                state.write().await.streaming_request_bodies.insert(
+                    request.request_id.clone(),
+                    StreamingRequestBody {
+                        chunks_tx,
+                        next_sequence: 0,
+                        out_of_order: BTreeMap::new(),
+                        total_chunks: None,
+                    },
+                );
+                forward_request(request, state, msg_tx, tui_tx).await;
+            } else if body_chunked {
+                // Hold the request until the whole body has been streamed
+                let mut s = state.write().await;
+                let expired = s.take_expired_request_bodies();
+                let full = s.pending_request_bodies.len() >= MAX_PENDING_REQUEST_BODIES;
+                let request_id = request.request_id.clone();
+                if !full {
+                    s.pending_request_bodies
+                        .insert(request_id.clone(), PendingRequestBody::new(request));
+                }
+                drop(s);
 
-                let msg = match response {
-                    Ok((status, headers, body)) => {
-                        debug!(
-                            "{} {} -> {} {}",
-                            method_clone,
-                            path_clone,
-                            status,
-                            body.as_ref().map(|b| b.len()).unwrap_or(0)
-                        );
+                refuse_expired_request_bodies(expired, state, msg_tx).await;
+                if full {
+                    warn!(
+                        "Too many requests waiting for their body, refusing {}",
+                        request_id
+                    );
+                    let reason = "Too many uploads in progress";
+                    refuse_pending_request(state, &request_id, 503, reason, msg_tx).await;
+                }
+            } else {
+                forward_request(request, state, msg_tx, tui_tx).await;
+            }
+        }
 
-                        // Send TUI response event
-                        if let Some(tx) = &tui_tx_clone {
-                            let _ = tx
-                                .send(TuiEvent::ResponseSent(ResponseEvent {
-                                    request_id: request_id_clone.clone(),
-                                    status,
-                                    headers: headers.clone(),
-                                    body: body.clone(),
-                                    duration_ms,
-                                }))
-                                .await;
-                        }
+        IncomingMessage::TunnelRequestChunk {
+            request_id,
+            sequence,
+            data,
+            data_encoding,
+            is_last,
+        } => {
+            let expired = state.write().await.take_expired_request_bodies();
+            refuse_expired_request_bodies(expired, state, msg_tx).await;
 
-                        OutgoingMessage::tunnel_response(&request_id_clone, status, headers, body)
+            let mut s = state.write().await;
+            if let Some(stream) = s.streaming_request_bodies.get_mut(&request_id) {
+                if let Err(reason) =
+                    stream.accept(sequence, &data, data_encoding.as_deref(), is_last)
+                {
+                    warn!("Giving up streamed body of {}: {}", request_id, reason);
+                    if let Some(stream) = s.streaming_request_bodies.remove(&request_id) {
+                        let _ = stream.chunks_tx.try_send(BodyChunk::Failed(reason));
                     }
-                    Err(e) => {
-                        warn!("{} {} -> error: {}", method_clone, path_clone, e);
-
-                        // Send TUI error response event
-                        if let Some(tx) = &tui_tx_clone {
-                            let _ = tx
-                                .send(TuiEvent::ResponseSent(ResponseEvent {
-                                    request_id: request_id_clone.clone(),
-                                    status: 502,
-                                    headers: vec![(
-                                        "content-type".to_string(),
-                                        "text/plain".to_string(),
-                                    )],
-                                    body: Some(format!("Bad Gateway: {}", e).into_bytes()),
-                                    duration_ms,
-                                }))
-                                .await;
-                        }
-
-                        OutgoingMessage::tunnel_response(
-                            &request_id_clone,
-                            502,
-                            vec![("content-type".to_string(), "text/plain".to_string())],
-                            Some(format!("Bad Gateway: {}", e).into_bytes()),
-                        )
+                } else if stream.total_chunks == Some(stream.next_sequence) {
+                    if let Some(stream) = s.streaming_request_bodies.remove(&request_id) {
+                        // The last chunks may still fill the channel
+                        tokio::spawn(async move {
+                            let _ = stream.chunks_tx.send(BodyChunk::End).await;
+                        });
                     }
-                };
+                }
+                return Ok(());
+            }
 
-                if let Ok(json) = msg.to_json() {
-                    let _ = msg_tx.send(json).await;
+            let max_bytes = s.streaming_threshold_bytes;
+            let Some(pending) = s.pending_request_bodies.get_mut(&request_id) else {
+                // e.g. of a request whose forwarding already failed or that
+                // was given up on
+                debug!("Body chunk for unknown request: {}", request_id);
+                return Ok(());
+            };
+
+            let accepted = pending.accept(
+                sequence,
+                &data,
+                data_encoding.as_deref(),
+                is_last,
+                max_bytes,
+            );
+            if accepted == Ok(false) {
+                return Ok(());
+            }
+            let Some(pending) = s.pending_request_bodies.remove(&request_id) else {
+                return Ok(());
+            };
+            drop(s);
+
+            match accepted {
+                Ok(_) => {
+                    let request = pending.into_request();
+                    debug!(
+                        "Assembled {} byte streamed body for {}",
+                        request.body.as_ref().map_or(0, Vec::len),
+                        request_id
+                    );
+                    forward_request(request, state, msg_tx, tui_tx).await;
                 }
-            });
+                Err((status, reason)) => {
+                    warn!("Giving up body of {}: {}", request_id, reason);
+                    refuse_pending_request(state, &request_id, status, &reason, msg_tx).await;
+                }
+            }
         }
 
         IncomingMessage::WsUpgrade {
@@ -730,6 +2594,7 @@ async fn handle_message(
             let s = state.read().await;
             let local_port = s.find_tunnel_port(&tunnel_id).unwrap_or(3000);
             let local_host = s.local_host.clone();
+            let ws_keepalive = s.ws_keepalive;
             drop(s);
 
             info!(
@@ -749,25 +2614,27 @@ async fn handle_message(
                     &path,
                     headers,
                     msg_tx.clone(),
+                    ws_keepalive,
                 )
                 .await
                 {
                     Ok(proxy) => {
                         info!(
-                            "WebSocket connected: {} -> localhost:{}",
-                            ws_id_clone, local_port
+                            "WebSocket connected: {} -> localhost:{} (subprotocol: {})",
+                            ws_id_clone,
+                            local_port,
+                            proxy.subprotocol().unwrap_or("none")
                         );
                         // Send ws_upgraded
                         let msg = OutgoingMessage::WsUpgraded {
                             ws_id: ws_id_clone.clone(),
-                            headers: vec![], // Local WS libs don't typically expose response headers
+                            headers: proxy.upgrade_headers(),
                         };
                         if let Ok(json) = msg.to_json() {
                             let _ = msg_tx.send(json).await;
                         }
 
                         // Store proxy
-                        let proxy = Arc::new(proxy);
                         {
                             let mut s = state_clone.write().await;
                             s.ws_proxies.insert(ws_id_clone.clone(), proxy.clone());
@@ -776,6 +2643,20 @@ async fn handle_message(
                         // Start forwarding
                         proxy.run(&ws_id_clone).await;
 
+                        let metrics = proxy.metrics();
+                        debug!(
+                            "WebSocket {} closed: {} frames ({} bytes) to local, {} frames ({} bytes) from local, {:.0}ms between frames",
+                            ws_id_clone,
+                            metrics.frames_sent,
+                            metrics.bytes_sent,
+                            metrics.frames_received,
+                            metrics.bytes_received,
+                            metrics.avg_frame_interval_ms
+                        );
+                        for line in metrics.histogram_chart() {
+                            debug!("  {}", line);
+                        }
+
                         // Clean up
                         {
                             let mut s = state_clone.write().await;
@@ -835,6 +2716,7 @@ async fn handle_message(
         } => {
             let s = state.read().await;
             let local_port = s.find_tcp_tunnel(&tcp_tunnel_id).map(|t| t.local_port);
+            let slow_threshold = s.slow_tcp_connect_threshold;
             drop(s);
 
             if let Some(local_port) = local_port {
@@ -843,14 +2725,33 @@ async fn handle_message(
                 let msg_tx = msg_tx.clone();
                 let state_clone = state.clone();
                 let tcp_id_clone = tcp_id.clone();
+                let tui_tx = tui_tx.clone();
 
                 tokio::spawn(async move {
+                    let started = Instant::now();
                     match TcpStream::connect(format!("localhost:{}", local_port)).await {
                         Ok(stream) => {
+                            let connect_latency = started.elapsed();
+                            let connect_latency_ms = connect_latency.as_millis() as u64;
+                            if connect_latency > slow_threshold {
+                                warn!(
+                                    tcp_id = %tcp_id_clone,
+                                    local_port,
+                                    connect_latency_ms,
+                                    "Local service was slow to accept TCP connection"
+                                );
+                            }
                             info!(
-                                "TCP connected to localhost:{}, starting forwarding",
-                                local_port
+                                "TCP connected to localhost:{} in {}ms, starting forwarding",
+                                local_port, connect_latency_ms
                             );
+                            if let Some(tx) = &tui_tx {
+                                tx.send(TuiEvent::TcpConnectionEstablished {
+                                    tcp_tunnel_id,
+                                    connect_latency_ms,
+                                })
+                                .await;
+                            }
                             // Send tcp_connected
                             let msg = OutgoingMessage::tcp_connected(&tcp_id_clone);
                             if let Ok(json) = msg.to_json() {
@@ -858,7 +2759,14 @@ async fn handle_message(
                             }
 
                             // Start bidirectional forwarding
-                            handle_tcp_connection(stream, &tcp_id_clone, msg_tx, state_clone).await;
+                            handle_tcp_connection(
+                                stream,
+                                &tcp_id_clone,
+                                connect_latency_ms,
+                                msg_tx,
+                                state_clone,
+                            )
+                            .await;
                         }
                         Err(e) => {
                             error!("TCP connect failed for {}: {}", tcp_id_clone, e);
@@ -906,19 +2814,809 @@ async fn handle_message(
 
         IncomingMessage::Heartbeat { .. } => {
             debug!("Received heartbeat");
+            // The server echoes our heartbeats, which gives an application-level round trip
+            let sent_at = state.write().await.heartbeat_sent_at.take();
+            if let (Some(sent_at), Some(tx)) = (sent_at, tui_tx) {
+                let rtt_ms = sent_at.elapsed().as_millis() as u64;
+                tx.send(TuiEvent::AppHeartbeat { rtt_ms }).await;
+            }
+        }
+
+        IncomingMessage::Batch { messages } => {
+            for message in messages {
+                Box::pin(handle_message(
+                    &message.to_string(),
+                    received_at,
+                    state,
+                    msg_tx,
+                    server_host,
+                    registered,
+                    tui_tx,
+                ))
+                .await?;
+            }
+        }
+
+        IncomingMessage::TunnelResponseCancel { request_id } => {
+            // Dropping the sender stops the relay
+            if state
+                .write()
+                .await
+                .event_streams
+                .remove(&request_id)
+                .is_some()
+            {
+                debug!("Stopping event stream {}", request_id);
+            }
         }
 
-        IncomingMessage::Error { code, message } => {
+        IncomingMessage::Unknown => {
+            debug!("Ignoring message of unknown type: {}", text);
+        }
+
+        IncomingMessage::Error {
+            code,
+            message,
+            tunnel_id: Some(tunnel_id),
+            ..
+        } => {
+            warn!(
+                "Server error for tunnel {}: {} - {}",
+                tunnel_id, code, message
+            );
+            if let Some(tx) = tui_tx {
+                tx.send(TuiEvent::TunnelError {
+                    tunnel_id,
+                    code,
+                    message,
+                })
+                .await;
+            }
+        }
+
+        IncomingMessage::Error {
+            code,
+            message,
+            registration_id,
+            ..
+        } => {
             error!("Server error: {} - {}", code, message);
+
+            let source = if is_auth_failure(&code) {
+                ErrorSource::Auth
+            } else {
+                ErrorSource::Protocol
+            };
+            report_error(tui_tx, source, format!("{}: {}", code, message)).await;
+
+            match code.as_str() {
+                // A server without `QueryTunnels`; register as before
+                "unknown_message" if !state.read().await.awaiting_tunnel_list.is_empty() => {
+                    let mut s = state.write().await;
+                    for pending in std::mem::take(&mut s.awaiting_tunnel_list) {
+                        s.register_pending_tunnel(pending, msg_tx).await;
+                    }
+                }
+                "subdomain_taken" => {
+                    // Older servers answer registrations in the order they were
+                    // sent, so the rejected one is the oldest still pending
+                    let retry = {
+                        let mut guard = state.write().await;
+                        let s = &mut *guard;
+                        let rejected = match &registration_id {
+                            Some(id) => s
+                                .pending_tunnels
+                                .iter_mut()
+                                .find(|p| p.registration_id == *id),
+                            None => s.pending_tunnels.first_mut(),
+                        };
+                        match rejected {
+                            Some(pending) if pending.subdomain.is_some() => {
+                                let taken = pending.subdomain.take().unwrap_or_default();
+                                pending.sent_at = Instant::now() + SUBDOMAIN_RETRY_DELAY;
+                                let msg = pending.register_message(
+                                    &s.token,
+                                    &s.session_id,
+                                    s.chunk_size_bytes,
+                                );
+                                Some((taken, pending.local_port, msg))
+                            }
+                            _ => None,
+                        }
+                    };
+
+                    if let Some((taken, local_port, msg)) = retry {
+                        warn!(
+                            "Subdomain '{}' taken (port {}, tunnel #{}), retrying with random assignment",
+                            taken,
+                            local_port,
+                            registered.http + 1
+                        );
+                        if let Some(tx) = tui_tx {
+                            tx.send(TuiEvent::Notification {
+                                message: "Subdomain taken, retrying with random assignment"
+                                    .to_string(),
+                                level: NotificationLevel::Warn,
+                            })
+                            .await;
+                        }
+
+                        let msg_tx = msg_tx.clone();
+                        tokio::spawn(async move {
+                            tokio::time::sleep(SUBDOMAIN_RETRY_DELAY).await;
+                            if let Ok(json) = msg.to_json() {
+                                let _ = msg_tx.send(json).await;
+                            }
+                        });
+                    }
+                }
+                code if is_auth_failure(code) => return Err(BurrowError::AuthFailed.into()),
+                "token_expired" | "expired_token" => {
+                    // Without the TUI there is no way to ask for a new token
+                    let Some(tx) = tui_tx else {
+                        return Err(BurrowError::AuthFailed.into());
+                    };
+                    tx.send(TuiEvent::Notification {
+                        message: "Token expired".to_string(),
+                        level: NotificationLevel::Error,
+                    })
+                    .await;
+                    tx.send(TuiEvent::TokenRequired).await;
+                }
+                "protocol_version_too_old" => return Err(BurrowError::ProtocolVersionTooOld.into()),
+                _ => {}
+            }
         }
     }
 
     Ok(())
 }
 
+/// Write one frame to the server; false once the connection is gone
+async fn send_frame<S>(write: &mut S, msg: Message) -> bool
+where
+    S: futures_util::Sink<Message> + Unpin,
+    S::Error: std::fmt::Display,
+{
+    match write.send(msg).await {
+        Ok(()) => true,
+        Err(e) => {
+            if !e.to_string().contains("closing") {
+                debug!("Send error (connection closing): {}", e);
+            }
+            false
+        }
+    }
+}
+
+/// Answer a `TunnelRequest` that was too large to handle with a 413, so the
+/// visitor isn't left waiting for the server to time out. Only the message
+/// type and request id are read from `text`.
+async fn answer_oversized_request(text: &str, msg_tx: &mpsc::Sender<String>) {
+    #[derive(serde::Deserialize)]
+    struct Envelope {
+        #[serde(rename = "type")]
+        kind: String,
+        request_id: Option<RequestId>,
+    }
+
+    let Ok(Envelope {
+        kind,
+        request_id: Some(request_id),
+    }) = serde_json::from_str(text)
+    else {
+        return;
+    };
+    if kind != "tunnel_request" {
+        return;
+    }
+
+    refuse_request(
+        &request_id,
+        413,
+        "Request too large for the tunnel client",
+        msg_tx,
+    )
+    .await;
+}
+
+/// Answer a request held for its chunked body without forwarding it, along
+/// with replays of it that arrived meanwhile
+async fn refuse_pending_request(
+    state: &Arc<RwLock<ClientState>>,
+    request_id: &RequestId,
+    status: u16,
+    reason: &str,
+    msg_tx: &mpsc::Sender<String>,
+) {
+    let request_caches = state.read().await.request_caches.clone();
+    let replays = request_caches.lock().unwrap().finish(request_id);
+    refuse_request(request_id, status, reason, msg_tx).await;
+    for reply_to in replays {
+        refuse_request(request_id, status, reason, &reply_to).await;
+    }
+}
+
+async fn refuse_expired_request_bodies(
+    expired: Vec<RequestId>,
+    state: &Arc<RwLock<ClientState>>,
+    msg_tx: &mpsc::Sender<String>,
+) {
+    for request_id in expired {
+        let reason = "Request body did not arrive in time";
+        refuse_pending_request(state, &request_id, 408, reason, msg_tx).await;
+    }
+}
+
+/// Answer a request without forwarding it
+async fn refuse_request(
+    request_id: &RequestId,
+    status: u16,
+    reason: &str,
+    msg_tx: &mpsc::Sender<String>,
+) {
+    let msg = OutgoingMessage::tunnel_response(
+        request_id,
+        status,
+        vec![("content-type".to_string(), "text/plain".to_string())],
+        Some(reason.as_bytes().to_vec()),
+    );
+    if let Ok(json) = msg.to_json() {
+        let _ = msg_tx.send(json).await;
+    }
+}
+
+/// Pair tunnels awaiting re-registration with those the server kept, by
+/// local port and requested subdomain. Returns the kept pairs and the
+/// tunnels that still need a `RegisterTunnel`.
+fn match_listed_tunnels(
+    awaiting: Vec<PendingTunnel>,
+    mut listed: Vec<ListedTunnel>,
+) -> (Vec<(PendingTunnel, ListedTunnel)>, Vec<PendingTunnel>) {
+    let mut kept = Vec::new();
+    let mut register = Vec::new();
+    for pending in awaiting {
+        let found = listed.iter().position(|tunnel| {
+            tunnel.local_port == pending.local_port
+                && pending
+                    .subdomain
+                    .as_ref()
+                    .is_none_or(|subdomain| *subdomain == tunnel.subdomain)
+        });
+        match found {
+            Some(i) => kept.push((pending, listed.remove(i))),
+            None => register.push(pending),
+        }
+    }
+    (kept, register)
+}
+
+/// What a WebSocket close code means, for logging
+fn close_code_description(code: u16) -> &'static str {
+    match code {
+        1000 => "normal closure",
+        1001 => "going away",
+        1005 => "no code given",
+        4000..=4999 => "application-defined",
+        _ => "protocol close code",
+    }
+}
+
+/// Add a non-fatal error to the TUI error log
+async fn report_error(tui_tx: &Option<ChannelMonitor>, source: ErrorSource, message: String) {
+    if let Some(tx) = tui_tx {
+        tx.send(TuiEvent::Error { message, source }).await;
+    }
+}
+
+/// Subdomains the server refuses to hand out
+const RESERVED_SUBDOMAINS: [&str; 8] = [
+    "www",
+    "api",
+    "admin",
+    "app",
+    "dashboard",
+    "status",
+    "health",
+    "metrics",
+];
+
+/// The server's subdomain rules: 2 to 32 lowercase letters, digits or '-',
+/// starting and ending with a letter or digit, and not reserved
+fn check_subdomain(subdomain: &str) -> std::result::Result<(), String> {
+    let alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    let valid = (2..=32).contains(&subdomain.len())
+        && subdomain.chars().all(|c| alphanumeric(c) || c == '-')
+        && subdomain.starts_with(alphanumeric)
+        && subdomain.ends_with(alphanumeric);
+    if !valid {
+        return Err(format!(
+            "Invalid subdomain '{}': use 2 to 32 lowercase letters, digits or '-', \
+             starting and ending with a letter or digit",
+            subdomain
+        ));
+    }
+    if RESERVED_SUBDOMAINS.contains(&subdomain) {
+        return Err(format!("Subdomain '{}' is reserved", subdomain));
+    }
+    Ok(())
+}
+
+/// Tags follow the subdomain rules, plus ':' for names like `team:frontend`
+pub fn check_tunnel_tag(tag: &str) -> std::result::Result<(), String> {
+    if tag.is_empty()
+        || tag.len() > 32
+        || !tag
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == ':')
+    {
+        return Err(format!(
+            "Invalid tag '{}': use up to 32 lowercase letters, digits, '-' or ':'",
+            tag
+        ));
+    }
+    Ok(())
+}
+
+/// Add a tunnel's own headers, replacing any the client sent with the same name
+fn with_extra_headers(
+    mut headers: Vec<(String, String)>,
+    extra_headers: Vec<(String, String)>,
+) -> Vec<(String, String)> {
+    headers.retain(|(name, _)| {
+        !extra_headers
+            .iter()
+            .any(|(extra, _)| extra.eq_ignore_ascii_case(name))
+    });
+    headers.extend(extra_headers);
+    headers
+}
+
+fn content_length(headers: &[(String, String)]) -> Option<usize> {
+    headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+}
+
+/// Report a request to the TUI and forward it to the local service in the background
+async fn forward_request(
+    request: ForwardRequest,
+    state: &Arc<RwLock<ClientState>>,
+    msg_tx: &mpsc::Sender<String>,
+    tui_tx: &Option<ChannelMonitor>,
+) {
+    let ForwardRequest {
+        request_id,
+        tunnel_id,
+        local_host,
+        local_port,
+        method,
+        path,
+        query_string,
+        headers,
+        extra_headers,
+        body: body_data,
+        body_stream,
+        client_ip,
+        tunnel_url,
+        received_at,
+    } = request;
+    let msg_tx = msg_tx.clone();
+    let (high_priority_tx, max_outgoing_message_bytes, chunk_size_bytes, proxy) = {
+        let s = state.read().await;
+        (
+            s.high_priority_tx.clone(),
+            s.max_outgoing_message_bytes,
+            s.chunk_size_bytes,
+            s.proxy.clone(),
+        )
+    };
+
+    let streamed = body_stream.is_some();
+    let (tui_body, body) = match body_stream {
+        Some(chunks) => (
+            Some(RequestBodyKind::Streaming(
+                content_length(&headers).unwrap_or(0),
+            )),
+            Some(streaming_body(chunks)),
+        ),
+        None => (
+            body_data.clone().map(RequestBodyKind::Buffered),
+            body_data.map(Into::into),
+        ),
+    };
+
+    // Send TUI request event
+    if let Some(tx) = tui_tx {
+        let size_bytes = message_size(&headers, tui_body.as_ref().map_or(0, |b| b.size()));
+        tx.send(TuiEvent::RequestReceived(RequestEvent {
+            request_id: request_id.clone(),
+            method: method.clone(),
+            path: path.clone(),
+            query_string: query_string.clone(),
+            headers: headers.clone(),
+            body: tui_body,
+            timestamp: Local::now(),
+            client_ip,
+            tunnel_url,
+            size_bytes,
+        }))
+        .await;
+    }
+
+    state
+        .write()
+        .await
+        .in_flight_requests
+        .insert(request_id.clone());
+
+    let state = state.clone();
+    let tui_tx_clone = tui_tx.clone();
+    let request_id_clone = request_id.clone();
+    let method_clone = method.clone();
+    let path_clone = path.clone();
+
+    tokio::spawn(async move {
+        let start = Instant::now();
+        let queued_ms = start.duration_since(received_at).as_millis() as u64;
+        let response = forward_http_request(
+            &proxy,
+            &local_host,
+            local_port,
+            &method_clone,
+            &path_clone,
+            &query_string,
+            with_extra_headers(headers, extra_headers),
+            body,
+        )
+        .instrument(info_span!(
+            "forward_http_request",
+            request_id = %request_id_clone,
+            tunnel_id = %tunnel_id,
+        ))
+        .await;
+        if streamed {
+            // Body chunks still to come have nowhere to go once the local
+            // service answered or couldn't be reached
+            state
+                .write()
+                .await
+                .streaming_request_bodies
+                .remove(&request_id_clone);
+        }
+
+        let duration_ms = start.elapsed().as_millis() as u64;
+
+        // Only failing to connect says the service isn't there; timeouts
+        // and errors once connected mean it is, just misbehaving
+        let reached = match &response {
+            Ok(_) => true,
+            Err(e) => !http_proxy::is_connect_failure(e),
+        };
+        let port_unreachable = state
+            .write()
+            .await
+            .record_local_port_result(&tunnel_id, reached);
+
+        // Server-Sent Events are relayed as they arrive instead of buffered
+        let response = match response {
+            Ok(ForwardedResponse {
+                status,
+                headers,
+                upstream,
+                events: Some(events),
+                ..
+            }) => {
+                info!(
+                    method = %method_clone,
+                    path = %path_clone,
+                    status = status,
+                    request_id = %request_id_clone,
+                    tunnel_id = %tunnel_id,
+                    "Streaming event stream"
+                );
+                if let Some(tx) = &tui_tx_clone {
+                    tx.send(TuiEvent::ResponseSent(ResponseEvent {
+                        request_id: request_id_clone.clone(),
+                        status,
+                        size_bytes: message_size(&headers, 0),
+                        headers: headers.clone(),
+                        body: None,
+                        duration_ms,
+                        timing: upstream
+                            .map(|upstream| TimingBreakdown::new(queued_ms, upstream, duration_ms)),
+                        error_kind: None,
+                    }))
+                    .await;
+                }
+                // A stream can't be sent twice, so replays of it are turned away
+                let request_caches = state.read().await.request_caches.clone();
+                let replays = request_caches.lock().unwrap().finish(&request_id_clone);
+                if !replays.is_empty() {
+                    let refused = OutgoingMessage::tunnel_response(
+                        &request_id_clone,
+                        502,
+                        vec![("content-type".to_string(), "text/plain".to_string())],
+                        Some(b"Bad Gateway: event stream already being relayed".to_vec()),
+                    );
+                    for reply_to in replays {
+                        send_response(
+                            &refused,
+                            max_outgoing_message_bytes,
+                            chunk_size_bytes,
+                            &reply_to,
+                        )
+                        .await;
+                    }
+                }
+
+                let (stop_tx, stop_rx) = oneshot::channel();
+                state
+                    .write()
+                    .await
+                    .event_streams
+                    .insert(request_id_clone.clone(), stop_tx);
+                relay_event_stream(&request_id_clone, status, headers, events, stop_rx, &msg_tx)
+                    .await;
+                let mut s = state.write().await;
+                s.event_streams.remove(&request_id_clone);
+                s.in_flight_requests.remove(&request_id_clone);
+                return;
+            }
+            response => response,
+        };
+
+        // Error responses skip ahead of regular responses
+        let is_error = response.is_err();
+        let msg = match response {
+            Ok(ForwardedResponse {
+                status,
+                headers,
+                body,
+                upstream,
+                ..
+            }) => {
+                info!(
+                    method = %method_clone,
+                    path = %path_clone,
+                    status = status,
+                    duration_ms = duration_ms,
+                    response_bytes = body.as_ref().map_or(0, Vec::len),
+                    request_id = %request_id_clone,
+                    tunnel_id = %tunnel_id,
+                    "Request completed"
+                );
+
+                // Send TUI response event
+                if let Some(tx) = &tui_tx_clone {
+                    tx.send(TuiEvent::ResponseSent(ResponseEvent {
+                        request_id: request_id_clone.clone(),
+                        status,
+                        headers: headers.clone(),
+                        body: body.clone(),
+                        duration_ms,
+                        timing: upstream
+                            .map(|upstream| TimingBreakdown::new(queued_ms, upstream, duration_ms)),
+                        error_kind: None,
+                        size_bytes: message_size(&headers, body.as_ref().map_or(0, Vec::len)),
+                    }))
+                    .await;
+                }
+
+                OutgoingMessage::tunnel_response(&request_id_clone, status, headers, body)
+            }
+            Err(e) => {
+                warn!(
+                    method = %method_clone,
+                    path = %path_clone,
+                    duration_ms = duration_ms,
+                    request_id = %request_id_clone,
+                    tunnel_id = %tunnel_id,
+                    error = format!("{:#}", e),
+                    "Request failed"
+                );
+                report_error(
+                    &tui_tx_clone,
+                    ErrorSource::Proxy,
+                    format!("{} {}: {:#}", method_clone, path_clone, e),
+                )
+                .await;
+
+                let kind = ProxyErrorKind::of(&e);
+                let status = kind.status();
+                let error_body = kind.error_page(&proxy, &e, local_port);
+                if let Some(tx) = &tui_tx_clone {
+                    let (code, message) = if port_unreachable {
+                        (
+                            "local_port_unreachable",
+                            format!(
+                                "Port {} not reachable after {} failed requests",
+                                local_port, LOCAL_PORT_FAILURE_LIMIT
+                            ),
+                        )
+                    } else {
+                        (
+                            kind.code(),
+                            format!("{} {}: {:#}", method_clone, path_clone, e),
+                        )
+                    };
+                    tx.send(TuiEvent::TunnelError {
+                        tunnel_id: tunnel_id.clone(),
+                        code: code.to_string(),
+                        message,
+                    })
+                    .await;
+                }
+
+                let headers = vec![(
+                    "content-type".to_string(),
+                    "text/html; charset=utf-8".to_string(),
+                )];
+
+                // Send TUI error response event
+                if let Some(tx) = &tui_tx_clone {
+                    tx.send(TuiEvent::ResponseSent(ResponseEvent {
+                        request_id: request_id_clone.clone(),
+                        status,
+                        headers: headers.clone(),
+                        body: Some(error_body.clone().into_bytes()),
+                        duration_ms,
+                        timing: None,
+                        error_kind: Some(kind),
+                        size_bytes: message_size(&headers, error_body.len()),
+                    }))
+                    .await;
+                }
+
+                OutgoingMessage::tunnel_response(
+                    &request_id_clone,
+                    status,
+                    headers,
+                    Some(error_body.into_bytes()),
+                )
+            }
+        };
+
+        // Replays that arrived while this was in flight get the same answer
+        let request_caches = state.read().await.request_caches.clone();
+        let replays = if is_idempotent(&method_clone) {
+            request_caches
+                .lock()
+                .unwrap()
+                .store(&request_id_clone, &msg)
+        } else {
+            Vec::new()
+        };
+        for reply_to in replays {
+            send_response(
+                &msg,
+                max_outgoing_message_bytes,
+                chunk_size_bytes,
+                &reply_to,
+            )
+            .await;
+        }
+
+        if is_error {
+            if let Ok(json) = msg.to_json() {
+                let _ = high_priority_tx.send(Message::Text(json)).await;
+            }
+        } else {
+            send_response(&msg, max_outgoing_message_bytes, chunk_size_bytes, &msg_tx).await;
+        }
+        state
+            .write()
+            .await
+            .in_flight_requests
+            .remove(&request_id_clone);
+    });
+}
+
+/// Send a response, as a head followed by body chunks when it would be
+/// larger than `max_bytes` as one message
+async fn send_response(
+    msg: &OutgoingMessage,
+    max_bytes: usize,
+    chunk_size: usize,
+    msg_tx: &mpsc::Sender<String>,
+) {
+    let Ok(json) = msg.to_json() else { return };
+    if json.len() <= max_bytes {
+        let _ = msg_tx.send(json).await;
+        return;
+    }
+    let OutgoingMessage::TunnelResponse {
+        request_id,
+        status,
+        headers,
+        body,
+        body_encoding,
+        ..
+    } = msg
+    else {
+        warn!("Dropping oversized message: {} bytes", json.len());
+        return;
+    };
+    debug!(
+        "Response to {} is {} bytes, sending it in chunks",
+        request_id,
+        json.len()
+    );
+
+    let body = decode_body(body.as_deref(), body_encoding.as_deref()).unwrap_or_default();
+    let headers = headers
+        .iter()
+        .map(|[name, value]| (name.clone(), value.clone()))
+        .collect();
+    let head = OutgoingMessage::tunnel_response_head(request_id, *status, headers);
+    let Ok(json) = head.to_json() else { return };
+    if msg_tx.send(json).await.is_err() {
+        return;
+    }
+    let mut chunks: Vec<&[u8]> = body.chunks(chunk_size.max(1)).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    let last = chunks.len().saturating_sub(1);
+    for (sequence, chunk) in chunks.into_iter().enumerate() {
+        let msg = OutgoingMessage::tunnel_response_chunk(
+            request_id,
+            sequence as u32,
+            chunk.to_vec(),
+            sequence == last,
+        );
+        let Ok(json) = msg.to_json() else { return };
+        if msg_tx.send(json).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Send the status and headers of an event stream response, then each chunk
+/// as the local service writes it, until the stream ends or `stop` fires
+async fn relay_event_stream(
+    request_id: &RequestId,
+    status: u16,
+    headers: Vec<(String, String)>,
+    mut events: EventStream,
+    mut stop: oneshot::Receiver<()>,
+    msg_tx: &mpsc::Sender<String>,
+) {
+    let head = OutgoingMessage::tunnel_response_head(request_id, status, headers);
+    let Ok(json) = head.to_json() else { return };
+    if msg_tx.send(json).await.is_err() {
+        return;
+    }
+
+    let mut sequence = 0;
+    loop {
+        let chunk = tokio::select! {
+            chunk = events.next_chunk() => chunk,
+            _ = &mut stop => {
+                debug!("Event stream {} cancelled by the server", request_id);
+                return;
+            }
+        };
+        let is_last = chunk.is_none();
+        let msg = OutgoingMessage::tunnel_response_chunk(
+            request_id,
+            sequence,
+            chunk.unwrap_or_default(),
+            is_last,
+        );
+        sequence += 1;
+        let Ok(json) = msg.to_json() else { return };
+        if msg_tx.send(json).await.is_err() || is_last {
+            return;
+        }
+    }
+}
+
 async fn handle_tcp_connection(
     stream: TcpStream,
     tcp_id: &TcpId,
+    connect_latency_ms: u64,
     msg_tx: mpsc::Sender<String>,
     state: Arc<RwLock<ClientState>>,
 ) {
@@ -930,8 +3628,13 @@ async fn handle_tcp_connection(
     // Store connection
     {
         let mut s = state.write().await;
-        s.tcp_connections
-            .insert(tcp_id.clone(), TcpConnection { tx: local_tx });
+        s.tcp_connections.insert(
+            tcp_id.clone(),
+            TcpConnection {
+                tx: local_tx,
+                connect_latency_ms,
+            },
+        );
     }
 
     let tcp_id_owned = tcp_id.clone();
@@ -988,6 +3691,722 @@ async fn handle_tcp_connection(
     // Clean up
     {
         let mut s = state.write().await;
-        s.tcp_connections.remove(tcp_id);
+        if let Some(conn) = s.tcp_connections.remove(tcp_id) {
+            debug!(
+                "TCP connection {} closed (connected in {}ms)",
+                tcp_id, conn.connect_latency_ms
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_tunnels_before_run() {
+        let mut client = TunnelClient::builder()
+            .server_host("tunnel.example.com")
+            .token("brw_test")
+            .build()
+            .unwrap();
+
+        client
+            .register_tunnel(
+                3000,
+                Some("myapp".to_string()),
+                vec!["team:web".to_string()],
+            )
+            .unwrap();
+        client.register_tcp_tunnel(5432).unwrap();
+        assert!(matches!(
+            &client.registered_tunnels[..],
+            [
+                TunnelConfig::Http { local_port: 3000, subdomain: Some(s), .. },
+                TunnelConfig::Tcp { local_port: 5432 },
+            ] if s == "myapp"
+        ));
+
+        assert!(client.register_tunnel(3000, None, Vec::new()).is_err());
+        assert!(client.register_tcp_tunnel(0).is_err());
+        assert!(client
+            .register_tunnel(8080, Some("Not Valid".to_string()), Vec::new())
+            .is_err());
+        assert!(client
+            .register_tunnel(8080, None, vec!["Prod".to_string()])
+            .is_err());
+        assert_eq!(client.registered_tunnels.len(), 2);
+    }
+
+    #[test]
+    fn test_reconnect_backoff_from_connection_config() {
+        let connection = ConnectionConfig {
+            max_reconnect_attempts: 3,
+            reconnect_initial_backoff_ms: 200,
+            reconnect_max_backoff_ms: 5000,
+            reconnect_backoff_multiplier: 2.0,
+            ..ConnectionConfig::default()
+        };
+        let client = TunnelClient::builder()
+            .server_host("tunnel.example.com")
+            .token("brw_test")
+            .connection_config(connection)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.reconnect,
+            ReconnectConfig {
+                max_attempts: 3,
+                initial_backoff_ms: 200,
+                max_backoff_ms: 5000,
+                backoff_multiplier: 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_match_listed_tunnels_by_port_and_subdomain() {
+        let pending = |port, subdomain: Option<&str>| {
+            PendingTunnel::new(
+                "localhost",
+                port,
+                subdomain.map(str::to_string),
+                Vec::new(),
+                Vec::new(),
+            )
+        };
+        let listed = |port, subdomain: &str| ListedTunnel {
+            tunnel_id: TunnelId(format!("t-{}", subdomain)),
+            subdomain: subdomain.to_string(),
+            full_url: format!("https://{}.example.com", subdomain),
+            local_port: port,
+        };
+
+        let (kept, register) = match_listed_tunnels(
+            vec![
+                pending(3000, None),
+                pending(4000, Some("api")),
+                pending(5000, Some("web")),
+            ],
+            vec![listed(3000, "default"), listed(5000, "other")],
+        );
+        let kept: Vec<_> = kept
+            .iter()
+            .map(|(pending, listed)| (pending.local_port, listed.subdomain.as_str()))
+            .collect();
+        assert_eq!(kept, [(3000, "default")]);
+        let register: Vec<_> = register.iter().map(|p| p.local_port).collect();
+        assert_eq!(register, [4000, 5000]);
+    }
+
+    fn test_state(high_priority_tx: mpsc::Sender<Message>) -> Arc<RwLock<ClientState>> {
+        Arc::new(RwLock::new(ClientState::new(
+            "localhost",
+            "brw_test",
+            64 * 1024,
+            64 * 1024,
+            Arc::new(std::sync::Mutex::new(RequestCaches::new(
+                1024 * 1024,
+                Duration::from_secs(60),
+            ))),
+            Arc::new(ProxyClient::default()),
+            high_priority_tx,
+        )))
+    }
+
+    fn test_request(request_id: &str, local_port: u16) -> ForwardRequest {
+        ForwardRequest {
+            request_id: RequestId::from(request_id),
+            tunnel_id: TunnelId("t1".to_string()),
+            local_host: "127.0.0.1".to_string(),
+            local_port,
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            query_string: String::new(),
+            headers: Vec::new(),
+            extra_headers: Vec::new(),
+            body: None,
+            body_stream: None,
+            client_ip: None,
+            tunnel_url: None,
+            received_at: Instant::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_token_stops_the_client() {
+        let (high_priority_tx, _high_priority_rx) = mpsc::channel(16);
+        let (msg_tx, _msg_rx) = mpsc::channel(16);
+        let state = test_state(high_priority_tx);
+        let (tui_tx, mut tui_rx) = super::super::tui::create_event_channel(16);
+        // As sent by the server's tunnel socket for a bad token
+        let error = serde_json::json!({
+            "type": "error",
+            "code": "invalid_token",
+            "message": "Invalid or missing API token",
+        })
+        .to_string();
+
+        let result = handle_message(
+            &error,
+            Instant::now(),
+            &state,
+            &msg_tx,
+            "example.com",
+            &mut RegisteredCounts::default(),
+            &Some(tui_tx),
+        )
+        .await;
+        let err = result.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<BurrowError>(),
+            Some(BurrowError::AuthFailed)
+        ));
+        assert!(matches!(
+            tui_rx.recv().await,
+            Some(TuiEvent::Error {
+                source: ErrorSource::Auth,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_matched_by_registration_id() {
+        let (high_priority_tx, _high_priority_rx) = mpsc::channel(16);
+        let (msg_tx, _msg_rx) = mpsc::channel(16);
+        let state = test_state(high_priority_tx);
+        let (first, second) = {
+            let mut s = state.write().await;
+            for port in [3000, 4000] {
+                s.pending_tunnels.push(PendingTunnel::new(
+                    "localhost",
+                    port,
+                    None,
+                    Vec::new(),
+                    Vec::new(),
+                ));
+            }
+            (
+                s.pending_tunnels[0].registration_id.clone(),
+                s.pending_tunnels[1].registration_id.clone(),
+            )
+        };
+        assert_ne!(first, second);
+
+        let confirm = |registration_id: &str, tunnel_id: &str| {
+            serde_json::json!({
+                "type": "tunnel_registered",
+                "registration_id": registration_id,
+                "tunnel_id": tunnel_id,
+                "subdomain": tunnel_id,
+                "full_url": format!("https://{}.example.com", tunnel_id),
+            })
+            .to_string()
+        };
+        let mut registered = RegisteredCounts::default();
+        // Confirmations out of order, and a repeat for a retried registration
+        for (registration_id, tunnel_id) in [(&second, "b"), (&first, "a"), (&second, "c")] {
+            handle_message(
+                &confirm(registration_id, tunnel_id),
+                Instant::now(),
+                &state,
+                &msg_tx,
+                "example.com",
+                &mut registered,
+                &None,
+            )
+            .await
+            .unwrap();
+        }
+
+        let s = state.read().await;
+        assert_eq!(registered.http, 2);
+        assert!(s.pending_tunnels.is_empty());
+        assert_eq!(s.tunnels[&TunnelId("a".to_string())].local_port, 3000);
+        assert_eq!(s.tunnels[&TunnelId("b".to_string())].local_port, 4000);
+        assert!(!s.tunnels.contains_key(&TunnelId("c".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_queued_time_counts_from_arrival() {
+        let local = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_port = local.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = local.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .await;
+        });
+
+        let (high_priority_tx, _high_priority_rx) = mpsc::channel(16);
+        let (msg_tx, _msg_rx) = mpsc::channel(16);
+        let (tui_tx, mut tui_rx) = super::super::tui::create_event_channel(16);
+        let state = test_state(high_priority_tx);
+        // The message waited behind others before it was handled
+        let request = ForwardRequest {
+            received_at: Instant::now() - Duration::from_millis(200),
+            ..test_request("req-1", local_port)
+        };
+        forward_request(request, &state, &msg_tx, &Some(tui_tx)).await;
+
+        let timing = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(TuiEvent::ResponseSent(event)) = tui_rx.recv().await {
+                    return event.timing.unwrap();
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert!(timing.queued_ms >= 200, "{:?}", timing);
+        assert!(timing.total_ms >= timing.queued_ms + timing.first_byte_ms);
+        assert_eq!(
+            timing.queued_ms + timing.first_byte_ms + timing.read_ms(),
+            timing.total_ms
+        );
+    }
+
+    #[test]
+    fn test_pending_body_assembled_in_order() {
+        let mut pending = PendingRequestBody::new(ForwardRequest {
+            body: Some(b"<".to_vec()),
+            ..test_request("req-1", 3000)
+        });
+        assert_eq!(pending.accept(2, "c", None, true, 1024), Ok(false));
+        assert_eq!(pending.accept(0, "a", None, false, 1024), Ok(false));
+        // A repeated chunk doesn't stand in for a missing one
+        assert_eq!(pending.accept(0, "a", None, false, 1024), Ok(false));
+        assert_eq!(pending.accept(1, "b", None, false, 1024), Ok(true));
+        assert_eq!(pending.into_request().body.as_deref(), Some(&b"<abc"[..]));
+
+        let mut pending = PendingRequestBody::new(test_request("req-2", 3000));
+        assert_eq!(
+            pending
+                .accept(0, "%%", Some("base64"), false, 1024)
+                .unwrap_err()
+                .0,
+            400
+        );
+        let mut pending = PendingRequestBody::new(test_request("req-3", 3000));
+        assert_eq!(
+            pending.accept(0, "abcd", None, false, 3).unwrap_err().0,
+            413
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_bodies_capped_and_expired() {
+        let (high_priority_tx, _high_priority_rx) = mpsc::channel(16);
+        let (msg_tx, mut msg_rx) = mpsc::channel(16);
+        let state = test_state(high_priority_tx);
+        {
+            let mut s = state.write().await;
+            for i in 0..MAX_PENDING_REQUEST_BODIES {
+                let request_id = format!("held-{}", i);
+                s.pending_request_bodies.insert(
+                    RequestId::from(request_id.as_str()),
+                    PendingRequestBody::new(test_request(&request_id, 3000)),
+                );
+            }
+        }
+        let handle = |text: String| {
+            let state = state.clone();
+            let msg_tx = msg_tx.clone();
+            async move {
+                handle_message(
+                    &text,
+                    Instant::now(),
+                    &state,
+                    &msg_tx,
+                    "example.com",
+                    &mut RegisteredCounts::default(),
+                    &None,
+                )
+                .await
+                .unwrap();
+            }
+        };
+        let upload = |request_id: &str| {
+            serde_json::json!({
+                "type": "tunnel_request",
+                "request_id": request_id,
+                "tunnel_id": "t1",
+                "method": "POST",
+                "path": "/upload",
+                "query_string": "",
+                "headers": [["content-length", "4"]],
+                "body_chunked": true,
+            })
+            .to_string()
+        };
+
+        handle(upload("req-1")).await;
+        let refused = msg_rx.try_recv().unwrap();
+        assert!(refused.contains("\"request_id\":\"req-1\""));
+        assert!(refused.contains("\"status\":503"));
+
+        // A request whose body stopped arriving makes room
+        state
+            .write()
+            .await
+            .pending_request_bodies
+            .get_mut(&RequestId::from("held-0"))
+            .unwrap()
+            .expires_at = Instant::now();
+        handle(upload("req-2")).await;
+        let expired = msg_rx.try_recv().unwrap();
+        assert!(expired.contains("\"request_id\":\"held-0\""));
+        assert!(expired.contains("\"status\":408"));
+        assert!(msg_rx.try_recv().is_err());
+        assert!(state
+            .read()
+            .await
+            .pending_request_bodies
+            .contains_key(&RequestId::from("req-2")));
+    }
+
+    #[test]
+    fn test_streaming_body_chunks_passed_on_in_order() {
+        let (chunks_tx, mut chunks_rx) = mpsc::channel(STREAMING_BODY_BUFFER_CHUNKS);
+        let mut stream = StreamingRequestBody {
+            chunks_tx,
+            next_sequence: 0,
+            out_of_order: BTreeMap::new(),
+            total_chunks: None,
+        };
+        stream.accept(1, "b", None, true).unwrap();
+        assert!(chunks_rx.try_recv().is_err());
+        stream.accept(0, "a", None, false).unwrap();
+        // Repeats of a chunk are passed on once
+        stream.accept(0, "a", None, false).unwrap();
+        stream.accept(1, "x", None, true).unwrap();
+
+        let received: Vec<Vec<u8>> = std::iter::from_fn(|| match chunks_rx.try_recv() {
+            Ok(BodyChunk::Data(chunk)) => Some(chunk),
+            _ => None,
+        })
+        .collect();
+        assert_eq!(received, [b"a".to_vec(), b"b".to_vec()]);
+        assert_eq!(stream.total_chunks, Some(stream.next_sequence));
+
+        assert!(stream.accept(2, "%%", Some("base64"), false).is_err());
+    }
+
+    #[test]
+    fn test_streaming_body_bounded_for_slow_service() {
+        let (chunks_tx, _chunks_rx) = mpsc::channel(STREAMING_BODY_BUFFER_CHUNKS);
+        let mut stream = StreamingRequestBody {
+            chunks_tx,
+            next_sequence: 0,
+            out_of_order: BTreeMap::new(),
+            total_chunks: None,
+        };
+        for sequence in 0..STREAMING_BODY_BUFFER_CHUNKS as u32 {
+            stream.accept(sequence, "a", None, false).unwrap();
+        }
+        assert!(stream
+            .accept(STREAMING_BODY_BUFFER_CHUNKS as u32, "a", None, false)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streaming_body_dropped_when_forwarding_fails() {
+        // Nothing listens on the port any more
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let (high_priority_tx, mut high_priority_rx) = mpsc::channel(16);
+        let (msg_tx, _msg_rx) = mpsc::channel(16);
+        let state = test_state(high_priority_tx);
+        state.write().await.tunnels.insert(
+            TunnelId("t1".to_string()),
+            TunnelInfo {
+                full_url: "https://a.example.com".to_string(),
+                local_host: "127.0.0.1".to_string(),
+                local_port,
+                extra_headers: Vec::new(),
+            },
+        );
+        let request = serde_json::json!({
+            "type": "tunnel_request",
+            "request_id": "req-1",
+            "tunnel_id": "t1",
+            "method": "POST",
+            "path": "/upload",
+            "query_string": "",
+            "headers": [["transfer-encoding", "chunked"]],
+            "body_chunked": true,
+        });
+        handle_message(
+            &request.to_string(),
+            Instant::now(),
+            &state,
+            &msg_tx,
+            "example.com",
+            &mut RegisteredCounts::default(),
+            &None,
+        )
+        .await
+        .unwrap();
+        assert!(state
+            .read()
+            .await
+            .streaming_request_bodies
+            .contains_key(&RequestId::from("req-1")));
+
+        let answer = tokio::time::timeout(Duration::from_secs(5), high_priority_rx.recv())
+            .await
+            .unwrap();
+        assert!(matches!(answer, Some(Message::Text(text)) if text.contains("\"status\":502")));
+        assert!(state.read().await.streaming_request_bodies.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_request_is_answered() {
+        let (msg_tx, mut msg_rx) = mpsc::channel(16);
+        let request = serde_json::json!({
+            "type": "tunnel_request",
+            "body": "a".repeat(1024),
+            "request_id": "req-1",
+            "tunnel_id": "t1",
+        })
+        .to_string();
+        answer_oversized_request(&request, &msg_tx).await;
+        let response: serde_json::Value =
+            serde_json::from_str(&msg_rx.try_recv().unwrap()).unwrap();
+        assert_eq!(response["request_id"], "req-1");
+        assert_eq!(response["status"], 413);
+
+        let frame = serde_json::json!({"type": "ws_frame", "ws_id": "ws-1", "data": "a"});
+        answer_oversized_request(&frame.to_string(), &msg_tx).await;
+        answer_oversized_request("not json", &msg_tx).await;
+        assert!(msg_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_stops_when_cancelled() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let local_service = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\ndata: hi\n\n",
+                )
+                .await
+                .unwrap();
+            // Never ends the stream; returns once the client hangs up
+            while socket.read(&mut buf).await.unwrap_or(0) > 0 {}
+        });
+
+        let ForwardedResponse {
+            status,
+            headers,
+            events,
+            ..
+        } = forward_http_request(
+            &ProxyClient::default(),
+            "127.0.0.1",
+            port,
+            "GET",
+            "/events",
+            "",
+            Vec::new(),
+            None,
+        )
+        .await
+        .unwrap();
+        let (msg_tx, mut msg_rx) = mpsc::channel(16);
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let relay = tokio::spawn(async move {
+            let request_id = RequestId::from("req-1");
+            let events = events.expect("event stream");
+            relay_event_stream(&request_id, status, headers, events, stop_rx, &msg_tx).await;
+        });
+
+        let head = msg_rx.recv().await.unwrap();
+        assert!(head.contains("\"streaming\":true"));
+        let chunk = msg_rx.recv().await.unwrap();
+        assert!(chunk.contains("\"is_last\":false"));
+
+        drop(stop_tx);
+        let stopped = tokio::time::timeout(Duration::from_secs(5), async {
+            relay.await.unwrap();
+            local_service.await.unwrap();
+        });
+        assert!(stopped.await.is_ok());
+        assert!(msg_rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_batch_wait_gives_way_to_high_priority() {
+        let batching = MessageBatching {
+            max_size: 10,
+            timeout: Duration::from_secs(30),
+        };
+        let (msg_tx, mut msg_rx) = mpsc::channel(16);
+        let (high_priority_tx, mut high_priority_rx) = mpsc::channel(16);
+        msg_tx.send("{\"n\":2}".to_string()).await.unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            high_priority_tx
+                .send(Message::Pong(Vec::new()))
+                .await
+                .unwrap();
+        });
+
+        let collected = tokio::time::timeout(
+            Duration::from_secs(5),
+            batching.collect("{\"n\":1}".to_string(), &mut msg_rx, &mut high_priority_rx),
+        )
+        .await;
+        let (batch, urgent) = collected.expect("a high-priority message ends the wait");
+        assert!(matches!(urgent, Some(Message::Pong(_))));
+        let batch: serde_json::Value = serde_json::from_str(&batch.unwrap()).unwrap();
+        assert_eq!(batch["messages"].as_array().map(Vec::len), Some(2));
+        drop(msg_tx);
+    }
+
+    #[test]
+    fn test_replays_are_parked_and_cache_is_bounded_by_size() {
+        let mut caches = RequestCaches::new(100, Duration::from_secs(60));
+        let (reply_tx, _reply_rx) = mpsc::channel(1);
+        let response = |id: &RequestId, body_len: usize| OutgoingMessage::TunnelResponse {
+            request_id: id.clone(),
+            status: 200,
+            headers: Vec::new(),
+            body: Some("a".repeat(body_len)),
+            body_encoding: None,
+            streaming: false,
+        };
+        let first = RequestId::from("req-1");
+        let second = RequestId::from("req-2");
+
+        // A replay while the original is in flight waits for its response
+        assert!(matches!(caches.check(&first, &reply_tx), Replay::New));
+        assert!(matches!(caches.check(&first, &reply_tx), Replay::Parked));
+        assert_eq!(caches.store(&first, &response(&first, 60)).len(), 1);
+        assert!(matches!(
+            caches.check(&first, &reply_tx),
+            Replay::Answered(_)
+        ));
+
+        // Together both responses are over 100 bytes, so the older one goes
+        assert!(matches!(caches.check(&second, &reply_tx), Replay::New));
+        assert!(caches.store(&second, &response(&second, 60)).is_empty());
+        assert_eq!(caches.cached_bytes, 60);
+        assert!(matches!(caches.check(&first, &reply_tx), Replay::New));
+        assert!(matches!(
+            caches.check(&second, &reply_tx),
+            Replay::Answered(_)
+        ));
+
+        // Too large to keep at all
+        caches.store(&first, &response(&first, 200));
+        assert!(matches!(caches.check(&first, &reply_tx), Replay::New));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_response_is_chunked() {
+        let (msg_tx, mut msg_rx) = mpsc::channel(16);
+        let request_id = RequestId::from("req-1");
+        let msg =
+            OutgoingMessage::tunnel_response(&request_id, 200, Vec::new(), Some(vec![b'a'; 10]));
+
+        send_response(&msg, 1024, 4, &msg_tx).await;
+        assert_eq!(msg_rx.try_recv().unwrap(), msg.to_json().unwrap());
+
+        send_response(&msg, 16, 4, &msg_tx).await;
+        let sent: Vec<String> = std::iter::from_fn(|| msg_rx.try_recv().ok()).collect();
+        assert_eq!(sent.len(), 4);
+        assert!(sent[0].contains("\"streaming\":true"));
+        assert!(sent[3].contains("\"is_last\":true"));
+        assert!(!sent[2].contains("\"is_last\":true"));
+    }
+
+    #[test]
+    fn test_local_port_failures_counted_until_reached() {
+        let (high_priority_tx, _high_priority_rx) = mpsc::channel(16);
+        let state = test_state(high_priority_tx);
+        let mut s = state.try_write().unwrap();
+        let tunnel_id = TunnelId("t1".to_string());
+
+        // Only the failure that reaches the limit reports the port
+        assert!(!s.record_local_port_result(&tunnel_id, false));
+        assert!(!s.record_local_port_result(&tunnel_id, false));
+        assert!(s.record_local_port_result(&tunnel_id, false));
+        assert!(!s.record_local_port_result(&tunnel_id, false));
+
+        // Reaching the service starts the count over
+        assert!(!s.record_local_port_result(&tunnel_id, true));
+        assert!(!s.local_port_failures.contains_key(&tunnel_id));
+        assert!(!s.record_local_port_result(&tunnel_id, false));
+        assert!(!s.record_local_port_result(&tunnel_id, false));
+        assert!(s.record_local_port_result(&tunnel_id, false));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_clears_port_reachable_again() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local_port = listener.local_addr().unwrap().port();
+
+        let (high_priority_tx, _high_priority_rx) = mpsc::channel(16);
+        let (msg_tx, _msg_rx) = mpsc::channel(16);
+        let (tui_tx, mut tui_rx) = super::super::tui::create_event_channel(16);
+        let state = test_state(high_priority_tx);
+        {
+            let mut s = state.write().await;
+            s.local_host = "127.0.0.1".to_string();
+            for (id, failures) in [("t1", LOCAL_PORT_FAILURE_LIMIT), ("t2", 1)] {
+                s.tunnels.insert(
+                    TunnelId(id.to_string()),
+                    TunnelInfo {
+                        full_url: format!("https://{}.example.com", id),
+                        local_host: "127.0.0.1".to_string(),
+                        local_port,
+                        extra_headers: Vec::new(),
+                    },
+                );
+                s.local_port_failures
+                    .insert(TunnelId(id.to_string()), failures);
+            }
+        }
+
+        let check = tokio::spawn(local_port_health_check(
+            state.clone(),
+            msg_tx.clone(),
+            Some(tui_tx),
+            Duration::from_millis(10),
+        ));
+        let message = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(TuiEvent::Notification { message, .. }) = tui_rx.recv().await {
+                    return message;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        check.abort();
+
+        assert_eq!(message, format!("Port {} is reachable again", local_port));
+        let s = state.read().await;
+        assert!(!s
+            .local_port_failures
+            .contains_key(&TunnelId("t1".to_string())));
+        // Tunnels not yet counted as unreachable aren't checked
+        assert_eq!(
+            s.local_port_failures.get(&TunnelId("t2".to_string())),
+            Some(&1)
+        );
     }
 }
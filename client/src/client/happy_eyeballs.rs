@@ -0,0 +1,102 @@
+//! Dual-stack connection setup in the style of Happy Eyeballs (RFC 8305).
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{lookup_host, TcpStream};
+use tokio::task::JoinSet;
+use tracing::debug;
+
+/// Connect to `host:port`, trying the preferred address family first and
+/// starting the next attempt whenever the current one hasn't connected
+/// within `fallback_delay` or fails. The first connection to succeed wins.
+pub async fn connect(
+    host: &str,
+    port: u16,
+    prefer_ipv6: bool,
+    fallback_delay: Duration,
+) -> io::Result<TcpStream> {
+    // getaddrinfo resolves A and AAAA records in one go
+    let addrs: Vec<SocketAddr> = lookup_host((host, port)).await?.collect();
+    let mut pending = sort_addrs(addrs, prefer_ipv6).into_iter();
+
+    let mut attempts = JoinSet::new();
+    let mut last_error = None;
+    if let Some(addr) = pending.next() {
+        attempts.spawn(attempt(addr));
+    }
+
+    while !attempts.is_empty() {
+        let start_next = tokio::select! {
+            Some(joined) = attempts.join_next() => match joined {
+                Ok(Ok(stream)) => return Ok(stream),
+                Ok(Err(e)) => {
+                    last_error = Some(e);
+                    true
+                }
+                Err(e) => {
+                    last_error = Some(io::Error::other(e));
+                    true
+                }
+            },
+            _ = tokio::time::sleep(fallback_delay), if !pending.as_slice().is_empty() => true,
+        };
+        if start_next {
+            if let Some(addr) = pending.next() {
+                attempts.spawn(attempt(addr));
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No addresses found for {}", host),
+        )
+    }))
+}
+
+async fn attempt(addr: SocketAddr) -> io::Result<TcpStream> {
+    debug!("Trying {}", addr);
+    TcpStream::connect(addr).await.inspect_err(|e| {
+        debug!("Connection to {} failed: {}", addr, e);
+    })
+}
+
+/// Alternate between address families, starting with the preferred one
+fn sort_addrs(addrs: Vec<SocketAddr>, prefer_ipv6: bool) -> Vec<SocketAddr> {
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == prefer_ipv6);
+
+    let mut sorted = Vec::with_capacity(preferred.len() + other.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return sorted,
+            (a, b) => sorted.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_falls_back_to_ipv4() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // ::1 is tried first (if localhost has one) and refused
+        let stream = connect("localhost", port, true, Duration::from_millis(250))
+            .await
+            .unwrap();
+        assert!(stream.peer_addr().unwrap().is_ipv4());
+
+        let v4: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let v6: SocketAddr = "[::1]:1".parse().unwrap();
+        assert_eq!(sort_addrs(vec![v4, v4, v6], true), vec![v6, v4, v4]);
+    }
+}
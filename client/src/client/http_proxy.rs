@@ -1,34 +1,464 @@
 use anyhow::Result;
+use futures_util::future::BoxFuture;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use reqwest::Client;
+use reqwest::{Body, Client};
+use std::fmt;
 use std::str::FromStr;
-use std::sync::OnceLock;
-
-/// Shared HTTP client for connection pooling and reuse
-static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
-
-/// Get or create the shared HTTP client
-fn get_client() -> &'static Client {
-    HTTP_CLIENT.get_or_init(|| {
-        Client::builder()
-            .redirect(reqwest::redirect::Policy::none())
-            .pool_max_idle_per_host(10)
-            .build()
-            .expect("failed to create HTTP client")
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{debug, info_span, warn, Instrument};
+
+use super::response_cache::{self, ResponseCache};
+use crate::config::{InjectedHeader, ProxyConfig};
+use crate::error::BurrowError;
+
+/// HTTP client for forwarding requests to local services, along with the
+/// [`ProxyConfig`] settings it was built from. Shared by everything one
+/// [`TunnelClient`](super::TunnelClient) forwards, so connections are reused.
+pub struct ProxyClient {
+    client: Client,
+    total_timeout: Option<Duration>,
+    inject_headers: Vec<InjectedHeader>,
+    response_header_allowlist: Option<Vec<String>>,
+    request_header_denylist: Vec<String>,
+    response_cache: Option<Mutex<ResponseCache>>,
+    max_sse_duration: Option<Duration>,
+    error_template: Option<String>,
+}
+
+/// Page returned for failed requests unless `custom_error_template` is set
+const DEFAULT_ERROR_TEMPLATE: &str = include_str!("error_page.html");
+
+/// Why forwarding a request to the local service failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyErrorKind {
+    /// Could not establish a connection within `connect_timeout_ms`
+    ConnectTimeout,
+    /// Upstream stopped sending data for longer than `read_timeout_ms`
+    ReadTimeout,
+    /// The whole exchange took longer than `total_timeout_ms`
+    TotalTimeout,
+    /// Any other upstream failure (connection refused, protocol error, ...)
+    Upstream,
+}
+
+impl ProxyErrorKind {
+    /// Classify an error returned by [`forward_http_request`]
+    pub fn of(err: &anyhow::Error) -> Self {
+        err.downcast_ref::<ProxyErrorKind>()
+            .copied()
+            .unwrap_or(ProxyErrorKind::Upstream)
+    }
+
+    /// Status code reported back through the tunnel
+    pub fn status(self) -> u16 {
+        match self {
+            ProxyErrorKind::ConnectTimeout | ProxyErrorKind::Upstream => 502,
+            ProxyErrorKind::ReadTimeout | ProxyErrorKind::TotalTimeout => 504,
+        }
+    }
+
+    /// Short identifier shown next to the tunnel in the TUI
+    pub fn code(self) -> &'static str {
+        match self {
+            ProxyErrorKind::ConnectTimeout => "connect_timeout",
+            ProxyErrorKind::ReadTimeout => "read_timeout",
+            ProxyErrorKind::TotalTimeout => "total_timeout",
+            ProxyErrorKind::Upstream => "bad_gateway",
+        }
+    }
+
+    /// Reason phrase for [`status`](Self::status)
+    pub fn reason(self) -> &'static str {
+        match self.status() {
+            504 => "Gateway Timeout",
+            _ => "Bad Gateway",
+        }
+    }
+
+    /// HTML page reported back through the tunnel, rendered from the
+    /// proxy's `custom_error_template` or the built-in page
+    pub fn error_page(self, proxy: &ProxyClient, err: &anyhow::Error, local_port: u16) -> String {
+        let template = proxy
+            .error_template
+            .as_deref()
+            .unwrap_or(DEFAULT_ERROR_TEMPLATE);
+        let message = match self {
+            ProxyErrorKind::ConnectTimeout => "did not accept the connection in time",
+            ProxyErrorKind::ReadTimeout => "stopped sending its response",
+            ProxyErrorKind::TotalTimeout => "took too long to respond",
+            ProxyErrorKind::Upstream => "is not responding",
+        };
+        render_error_page(
+            template,
+            self.status(),
+            self.reason(),
+            local_port,
+            &format!("Your local service on port {} {}", local_port, message),
+            &format!("{:#}", err),
+        )
+    }
+}
+
+/// Fill in `{status}`, `{reason}`, `{local_port}`, `{message}` and `{details}`
+fn render_error_page(
+    template: &str,
+    status: u16,
+    reason: &str,
+    local_port: u16,
+    message: &str,
+    details: &str,
+) -> String {
+    template
+        .replace("{status}", &status.to_string())
+        .replace("{reason}", reason)
+        .replace("{local_port}", &local_port.to_string())
+        .replace("{message}", message)
+        .replace("{details}", &escape_html(details))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl fmt::Display for ProxyErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProxyErrorKind::ConnectTimeout => write!(f, "upstream connection timeout"),
+            ProxyErrorKind::ReadTimeout => write!(f, "upstream read timeout"),
+            ProxyErrorKind::TotalTimeout => write!(f, "upstream request timeout"),
+            ProxyErrorKind::Upstream => write!(f, "upstream error"),
+        }
+    }
+}
+
+impl ProxyClient {
+    pub fn new(config: &ProxyConfig) -> Self {
+        build_client(config)
+    }
+}
+
+impl Default for ProxyClient {
+    fn default() -> Self {
+        Self::new(&ProxyConfig::default())
+    }
+}
+
+fn build_client(config: &ProxyConfig) -> ProxyClient {
+    let mut builder = Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .pool_max_idle_per_host(10)
+        .connector_layer(ConnectTimingLayer);
+
+    if let Some(timeout) = timeout_from_ms(config.connect_timeout_ms) {
+        builder = builder.connect_timeout(timeout);
+    }
+    if let Some(timeout) = timeout_from_ms(config.read_timeout_ms) {
+        builder = builder.read_timeout(timeout);
+    }
+    let total_timeout = timeout_from_ms(config.total_timeout_ms);
+    if let Some(timeout) = total_timeout {
+        builder = builder.timeout(timeout);
+    }
+
+    ProxyClient {
+        client: builder.build().expect("failed to create HTTP client"),
+        total_timeout,
+        inject_headers: config.inject_headers.clone(),
+        response_header_allowlist: config.response_header_allowlist.clone(),
+        request_header_denylist: config.request_header_denylist.clone(),
+        response_cache: config
+            .cache_responses
+            .then(|| Mutex::new(ResponseCache::new(config.cache_max_size_mb))),
+        max_sse_duration: (config.max_sse_duration_secs > 0)
+            .then(|| Duration::from_secs(config.max_sse_duration_secs)),
+        error_template: config.custom_error_template.as_ref().and_then(|path| {
+            std::fs::read_to_string(path)
+                .inspect_err(|e| {
+                    warn!(
+                        "Failed to read custom_error_template {}: {}, using the default page",
+                        path.display(),
+                        e
+                    )
+                })
+                .ok()
+        }),
+    }
+}
+
+fn timeout_from_ms(ms: Option<u64>) -> Option<Duration> {
+    ms.filter(|&ms| ms > 0).map(Duration::from_millis)
+}
+
+/// Tag timeout errors with their [`ProxyErrorKind`] so callers can tell them apart
+fn classify_error(
+    err: reqwest::Error,
+    url: &str,
+    started: Instant,
+    proxy: &ProxyClient,
+) -> anyhow::Error {
+    let is_connect = err.is_connect();
+    if !err.is_timeout() {
+        return BurrowError::http(url, err).into();
+    }
+
+    let kind = if is_connect {
+        ProxyErrorKind::ConnectTimeout
+    } else if proxy
+        .total_timeout
+        .is_some_and(|total| started.elapsed() >= total)
+    {
+        ProxyErrorKind::TotalTimeout
+    } else {
+        ProxyErrorKind::ReadTimeout
+    };
+
+    anyhow::Error::new(BurrowError::http(url, err)).context(kind)
+}
+
+/// Whether forwarding failed because the local service couldn't be
+/// connected to, as opposed to failing once connected
+pub fn is_connect_failure(err: &anyhow::Error) -> bool {
+    ProxyErrorKind::of(err) == ProxyErrorKind::ConnectTimeout
+        || err
+            .chain()
+            .any(|cause| match cause.downcast_ref::<BurrowError>() {
+                Some(BurrowError::HttpRequest { source, .. }) => source.is_connect(),
+                _ => false,
+            })
+}
+
+/// Whether repeating a request with this method has the same effect as sending it once
+pub fn is_idempotent(method: &str) -> bool {
+    matches!(
+        method.to_ascii_uppercase().as_str(),
+        "GET" | "HEAD" | "OPTIONS" | "TRACE" | "PUT" | "DELETE"
+    )
+}
+
+/// Part of a streamed request body, as passed on by the connection
+#[derive(Debug)]
+pub enum BodyChunk {
+    Data(Vec<u8>),
+    /// Every chunk has been passed on
+    End,
+    /// The body can't be completed, e.g. a chunk couldn't be decoded
+    Failed(String),
+}
+
+/// Request body that forwards chunks as they are received. The body only
+/// ends cleanly on [`BodyChunk::End`]; if the sender goes away first the
+/// upload fails, so the local service never takes a cut-off body as whole.
+pub fn streaming_body(chunks: mpsc::Receiver<BodyChunk>) -> Body {
+    Body::wrap_stream(futures_util::stream::unfold(
+        Some(chunks),
+        |chunks| async move {
+            let mut chunks = chunks?;
+            let item = match chunks.recv().await {
+                Some(BodyChunk::Data(chunk)) => return Some((Ok(chunk), Some(chunks))),
+                Some(BodyChunk::End) => return None,
+                Some(BodyChunk::Failed(reason)) => std::io::Error::other(reason),
+                None => std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "request body ended early",
+                ),
+            };
+            Some((Err(item), None))
+        },
+    ))
+}
+
+/// Headers that only apply to a single connection and are never forwarded
+fn is_hop_by_hop(name: &str) -> bool {
+    matches!(
+        name,
+        "connection"
+            | "keep-alive"
+            | "proxy-authenticate"
+            | "proxy-authorization"
+            | "te"
+            | "trailers"
+            | "transfer-encoding"
+            | "upgrade"
+    )
+}
+
+/// Whether a lowercase header name matches any of `patterns`, where a
+/// trailing `*` matches any suffix
+fn header_matches(name: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.to_ascii_lowercase();
+        match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        }
     })
 }
 
+/// Point `Host` at the local service and keep the public host in `X-Forwarded-Host`
+fn inject_headers(
+    header_map: &mut HeaderMap,
+    enabled: &[InjectedHeader],
+    local_host: &str,
+    local_port: u16,
+    original_host: Option<&str>,
+) {
+    if enabled.contains(&InjectedHeader::Host) {
+        let host = if matches!(local_port, 80 | 443) {
+            local_host.to_string()
+        } else {
+            format!("{}:{}", local_host, local_port)
+        };
+        if let Ok(value) = HeaderValue::from_str(&host) {
+            header_map.insert(reqwest::header::HOST, value);
+        }
+    }
+
+    if enabled.contains(&InjectedHeader::XForwardedHost) {
+        if let Some(value) = original_host.and_then(|h| HeaderValue::from_str(h).ok()) {
+            header_map.insert(HeaderName::from_static("x-forwarded-host"), value);
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Set while a request is sent, so the connector can report how long
+    /// opening a new connection for it took
+    static CONNECT_MS: Arc<AtomicU64>;
+}
+
+/// Connector layer recording connect times in [`CONNECT_MS`]
+#[derive(Clone)]
+struct ConnectTimingLayer;
+
+impl<S> tower_layer::Layer<S> for ConnectTimingLayer {
+    type Service = ConnectTiming<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectTiming(inner)
+    }
+}
+
+#[derive(Clone)]
+struct ConnectTiming<S>(S);
+
+impl<S, R> tower_service::Service<R> for ConnectTiming<S>
+where
+    S: tower_service::Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, std::result::Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), S::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, dst: R) -> Self::Future {
+        // Connects are started from the task sending the request
+        let connect_ms = CONNECT_MS.try_with(Arc::clone).ok();
+        let started = Instant::now();
+        let connecting = self.0.call(dst);
+        Box::pin(async move {
+            let conn = connecting.await;
+            if let Some(connect_ms) = connect_ms {
+                connect_ms.store(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+            }
+            conn
+        })
+    }
+}
+
+/// Where the time went while the local service handled a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamTiming {
+    /// Opening a new connection, 0 when a pooled one was reused
+    pub connect_ms: u64,
+    /// Until the status line arrived, including `connect_ms`
+    pub first_byte_ms: u64,
+}
+
+/// What the local service answered a forwarded request with
+pub struct ForwardedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// `None` for an empty body, and for Server-Sent Events, which are read
+    /// from `events` instead
+    pub body: Option<Vec<u8>>,
+    /// How long the local service took to respond; `None` when answered
+    /// from the cache
+    pub upstream: Option<UpstreamTiming>,
+    pub events: Option<EventStream>,
+}
+
+/// Body of a `text/event-stream` response, read as the local service sends
+/// it rather than buffered, since the stream may never end
+pub struct EventStream {
+    response: reqwest::Response,
+    /// When `max_sse_duration_secs` runs out
+    deadline: Option<Instant>,
+}
+
+impl EventStream {
+    /// The next chunk of the stream, or `None` once it has ended, failed or
+    /// run for `max_sse_duration_secs`
+    pub async fn next_chunk(&mut self) -> Option<Vec<u8>> {
+        let chunk = match self.deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), self.response.chunk())
+                .await
+                .ok()?,
+            None => self.response.chunk().await,
+        };
+        match chunk {
+            Ok(chunk) => chunk.map(|bytes| bytes.to_vec()),
+            Err(e) => {
+                debug!("Event stream ended: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .trim_start()
+                .to_ascii_lowercase()
+                .starts_with("text/event-stream")
+        })
+}
+
 /// Forward an HTTP request to the local service
+#[allow(clippy::too_many_arguments)]
 pub async fn forward_http_request(
+    proxy: &ProxyClient,
     local_host: &str,
     local_port: u16,
     method: &str,
     path: &str,
     query_string: &str,
     headers: Vec<(String, String)>,
-    body: Option<Vec<u8>>,
-) -> Result<(u16, Vec<(String, String)>, Option<Vec<u8>>)> {
-    let client = get_client();
+    body: Option<Body>,
+) -> Result<ForwardedResponse> {
+    let started = Instant::now();
 
     // Build URL
     let url = if query_string.is_empty() {
@@ -40,28 +470,45 @@ pub async fn forward_http_request(
         )
     };
 
+    // Serve cacheable GET responses without asking the local service again
+    let cache_key = proxy
+        .response_cache
+        .as_ref()
+        .filter(|_| method.eq_ignore_ascii_case("GET"))
+        .map(|_| ResponseCache::key(method, &url, &headers));
+    if let (Some(cache), Some(key)) = (&proxy.response_cache, &cache_key) {
+        if let Some((status, mut headers, body)) = cache.lock().unwrap().get(key) {
+            headers.push(("x-burrow-cache".to_string(), "HIT".to_string()));
+            return Ok(ForwardedResponse {
+                status,
+                headers,
+                body,
+                upstream: None,
+                events: None,
+            });
+        }
+    }
+
+    // Time spent waiting for the local service, apart from the tunnel round trip
+    let upstream_span = info_span!("http_upstream", method = %method, url = %url);
+
     // Build request
     let method = reqwest::Method::from_str(method)?;
-    let mut request = client.request(method, &url);
+    let mut request = proxy.client.request(method, &url);
 
-    // Add headers (skip hop-by-hop headers)
-    let mut header_map = HeaderMap::with_capacity(headers.len());
+    // Add headers (skip hop-by-hop and denied headers)
+    let mut header_map = HeaderMap::with_capacity(headers.len() + 2);
+    let mut original_host = None;
     for (name, value) in headers {
         let name_lower = name.to_lowercase();
 
-        // Skip hop-by-hop headers
-        if matches!(
-            name_lower.as_str(),
-            "connection"
-                | "keep-alive"
-                | "proxy-authenticate"
-                | "proxy-authorization"
-                | "te"
-                | "trailers"
-                | "transfer-encoding"
-                | "upgrade"
-                | "host"
-        ) {
+        if name_lower == "host" {
+            original_host = Some(value);
+            continue;
+        }
+
+        if is_hop_by_hop(&name_lower) || header_matches(&name_lower, &proxy.request_header_denylist)
+        {
             continue;
         }
 
@@ -71,6 +518,13 @@ pub async fn forward_http_request(
             header_map.insert(header_name, header_value);
         }
     }
+    inject_headers(
+        &mut header_map,
+        &proxy.inject_headers,
+        local_host,
+        local_port,
+        original_host.as_deref(),
+    );
     request = request.headers(header_map);
 
     // Add body
@@ -79,10 +533,35 @@ pub async fn forward_http_request(
     }
 
     // Send request
-    let response = request.send().await?;
+    let upstream_started = Instant::now();
+    let connect_ms = Arc::new(AtomicU64::new(0));
+    let response = CONNECT_MS
+        .scope(connect_ms.clone(), request.send().instrument(upstream_span))
+        .await
+        .map_err(|e| classify_error(e, &url, started, proxy))?;
+    let timing = UpstreamTiming {
+        connect_ms: connect_ms.load(Ordering::Relaxed),
+        first_byte_ms: upstream_started.elapsed().as_millis() as u64,
+    };
+    debug!(
+        url = %url,
+        connect_ms = timing.connect_ms,
+        upstream_latency_ms = timing.first_byte_ms,
+        "Local service responded"
+    );
 
     // Extract response
     let status = response.status().as_u16();
+    let header_str = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let lifetime = response_cache::freshness(
+        header_str(reqwest::header::CACHE_CONTROL),
+        header_str(reqwest::header::EXPIRES),
+    );
 
     let response_headers: Vec<(String, String)> = response
         .headers()
@@ -90,20 +569,15 @@ pub async fn forward_http_request(
         .filter_map(|(name, value)| {
             let name_str = name.as_str().to_lowercase();
 
-            // Skip hop-by-hop headers
-            if matches!(
-                name_str.as_str(),
-                "connection"
-                    | "keep-alive"
-                    | "proxy-authenticate"
-                    | "proxy-authorization"
-                    | "te"
-                    | "trailers"
-                    | "transfer-encoding"
-                    | "upgrade"
-            ) {
+            // Hop-by-hop headers are dropped even if allowlisted
+            if is_hop_by_hop(&name_str) {
                 return None;
             }
+            if let Some(allowlist) = &proxy.response_header_allowlist {
+                if !header_matches(&name_str, allowlist) {
+                    return None;
+                }
+            }
 
             value
                 .to_str()
@@ -112,14 +586,49 @@ pub async fn forward_http_request(
         })
         .collect();
 
-    let body = response.bytes().await.ok().map(|b| b.to_vec());
+    if is_event_stream(response.headers()) {
+        let stream = EventStream {
+            response,
+            deadline: proxy.max_sse_duration.map(|max| Instant::now() + max),
+        };
+        return Ok(ForwardedResponse {
+            status,
+            headers: response_headers,
+            body: None,
+            upstream: Some(timing),
+            events: Some(stream),
+        });
+    }
+
+    let body = match response.bytes().await {
+        Ok(bytes) => Some(bytes.to_vec()),
+        Err(e) if e.is_timeout() => return Err(classify_error(e, &url, started, proxy)),
+        Err(_) => None,
+    };
     let body = if body.as_ref().map(|b| b.is_empty()).unwrap_or(true) {
         None
     } else {
         body
     };
 
-    Ok((status, response_headers, body))
+    let mut response = (status, response_headers, body);
+    if let (Some(cache), Some(key)) = (&proxy.response_cache, cache_key) {
+        if let Some(lifetime) = lifetime {
+            cache.lock().unwrap().insert(key, &response, lifetime);
+        }
+        response
+            .1
+            .push(("x-burrow-cache".to_string(), "MISS".to_string()));
+    }
+
+    let (status, headers, body) = response;
+    Ok(ForwardedResponse {
+        status,
+        headers,
+        body,
+        upstream: Some(timing),
+        events: None,
+    })
 }
 
 #[cfg(test)]
@@ -129,9 +638,233 @@ mod tests {
     #[tokio::test]
     async fn test_forward_request_not_running() {
         // This should fail since there's no server running
-        let result =
-            forward_http_request("localhost", 19999, "GET", "/test", "", vec![], None).await;
+        let result = forward_http_request(
+            &ProxyClient::default(),
+            "localhost",
+            19999,
+            "GET",
+            "/test",
+            "",
+            vec![],
+            None,
+        )
+        .await;
 
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_each_client_keeps_its_own_settings() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0; 4096];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 204 No Content\r\nX-Internal: 1\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+
+        let filtered = ProxyClient::new(&ProxyConfig {
+            response_header_allowlist: Some(vec!["content-type".to_string()]),
+            ..ProxyConfig::default()
+        });
+        let unfiltered = ProxyClient::default();
+        for (proxy, kept) in [(&filtered, false), (&unfiltered, true), (&filtered, false)] {
+            let response =
+                forward_http_request(proxy, "127.0.0.1", port, "GET", "/", "", vec![], None)
+                    .await
+                    .unwrap();
+            let has_internal = response
+                .headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("x-internal"));
+            assert_eq!(has_internal, kept);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streaming_body_only_ends_on_end() {
+        use http_body_util::BodyExt;
+
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(BodyChunk::Data(b"ab".to_vec())).await.unwrap();
+        tx.send(BodyChunk::Data(b"cd".to_vec())).await.unwrap();
+        tx.send(BodyChunk::End).await.unwrap();
+        let body = streaming_body(rx).collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"abcd");
+
+        // The connection went away halfway through
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(BodyChunk::Data(b"ab".to_vec())).await.unwrap();
+        drop(tx);
+        assert!(streaming_body(rx).collect().await.is_err());
+
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(BodyChunk::Failed("bad chunk".to_string()))
+            .await
+            .unwrap();
+        assert!(streaming_body(rx).collect().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_forward_request_sets_host_headers() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_lowercase()
+        });
+
+        let headers = vec![("Host".to_string(), "myapp.example.com".to_string())];
+        let response = forward_http_request(
+            &ProxyClient::default(),
+            "127.0.0.1",
+            port,
+            "GET",
+            "/",
+            "",
+            headers,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status, 204);
+
+        let request = server.await.unwrap();
+        assert!(request.contains(&format!("\r\nhost: 127.0.0.1:{}\r\n", port)));
+        assert!(request.contains("\r\nx-forwarded-host: myapp.example.com\r\n"));
+        assert!(!request.contains("\r\nhost: myapp.example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_forward_request_streams_event_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\ndata: hi\n\n",
+                )
+                .await
+                .unwrap();
+        });
+
+        let response = forward_http_request(
+            &ProxyClient::default(),
+            "127.0.0.1",
+            port,
+            "GET",
+            "/events",
+            "",
+            vec![],
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.body.is_none());
+
+        let mut events = response.events.expect("event stream");
+        assert_eq!(
+            events.next_chunk().await.as_deref(),
+            Some(&b"data: hi\n\n"[..])
+        );
+        assert_eq!(events.next_chunk().await, None);
+    }
+
+    #[test]
+    fn test_error_kind_classification() {
+        let err = anyhow::anyhow!("boom").context(ProxyErrorKind::ReadTimeout);
+        assert_eq!(ProxyErrorKind::of(&err), ProxyErrorKind::ReadTimeout);
+        assert_eq!(ProxyErrorKind::ReadTimeout.status(), 504);
+        assert_eq!(ProxyErrorKind::ReadTimeout.reason(), "Gateway Timeout");
+
+        let err = anyhow::anyhow!("connection refused");
+        assert_eq!(ProxyErrorKind::of(&err), ProxyErrorKind::Upstream);
+        assert_eq!(ProxyErrorKind::ConnectTimeout.status(), 502);
+    }
+
+    #[tokio::test]
+    async fn test_is_connect_failure() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let Err(refused) = forward_http_request(
+            &ProxyClient::default(),
+            "127.0.0.1",
+            port,
+            "GET",
+            "/",
+            "",
+            vec![],
+            None,
+        )
+        .await
+        else {
+            panic!("nothing listens on port {}", port);
+        };
+        assert!(is_connect_failure(&refused));
+
+        let timeout = anyhow::anyhow!("boom").context(ProxyErrorKind::ConnectTimeout);
+        assert!(is_connect_failure(&timeout));
+        // The service is there, just slow or misbehaving
+        let slow = anyhow::anyhow!("boom").context(ProxyErrorKind::ReadTimeout);
+        assert!(!is_connect_failure(&slow));
+        assert!(!is_connect_failure(&anyhow::anyhow!("invalid header")));
+    }
+
+    #[test]
+    fn test_render_error_page() {
+        let page = render_error_page(
+            "<h1>{status} {reason}</h1><p>{message} ({local_port})</p><pre>{details}</pre>",
+            502,
+            "Bad Gateway",
+            3000,
+            "Your local service on port 3000 is not responding",
+            "error sending request for <http://localhost:3000/>",
+        );
+        assert_eq!(
+            page,
+            "<h1>502 Bad Gateway</h1><p>Your local service on port 3000 is not responding (3000)</p>\
+             <pre>error sending request for &lt;http://localhost:3000/&gt;</pre>"
+        );
+        assert!(DEFAULT_ERROR_TEMPLATE.contains("{status} {reason}"));
+    }
+
+    #[test]
+    fn test_is_idempotent() {
+        assert!(is_idempotent("GET"));
+        assert!(is_idempotent("delete"));
+        assert!(!is_idempotent("POST"));
+        assert!(!is_idempotent("PATCH"));
+    }
+
+    #[test]
+    fn test_header_matches() {
+        let patterns = vec!["Content-Type".to_string(), "x-custom-*".to_string()];
+        assert!(header_matches("content-type", &patterns));
+        assert!(header_matches("x-custom-trace", &patterns));
+        assert!(!header_matches("content-length", &patterns));
+        assert!(!header_matches("x-customer", &patterns));
+    }
 }
@@ -2,13 +2,25 @@
 //!
 //! This module provides the core tunnel functionality:
 //! - [`TunnelClient`] - Main client that manages tunnel connections
+//! - [`ConnectionPool`] - Several clients connected to different servers
 //! - HTTP proxy for forwarding requests to local services
 //! - WebSocket proxy for bidirectional WebSocket tunneling
 //! - TUI for interactive request inspection
 
+mod connect_proxy;
 mod connection;
+mod happy_eyeballs;
 mod http_proxy;
+mod pool;
+mod response_cache;
 pub mod tui;
 mod ws_proxy;
 
-pub use connection::TunnelClient;
+pub use connection::{
+    check_tunnel_tag, ReconnectConfig, TunnelClient, TunnelClientBuilder, TunnelConfig,
+    TunnelRegistration,
+};
+pub use http_proxy::{
+    forward_http_request, ForwardedResponse, ProxyClient, ProxyErrorKind, UpstreamTiming,
+};
+pub use pool::ConnectionPool;
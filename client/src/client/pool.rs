@@ -0,0 +1,76 @@
+//! Running several [`TunnelClient`]s side by side, one per server.
+
+use anyhow::Result;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use tracing::error;
+
+use super::connection::TunnelClientBuilder;
+use super::tui::ChannelMonitor;
+use super::TunnelClient;
+
+/// Clients connected to different servers at the same time, all reporting to
+/// the same TUI. With more than one client, each gets a `pool_id` starting at
+/// 1, shown as a `[P1]`/`[P2]` prefix on its tunnels. The TUI's connection
+/// status follows the first client; the others report theirs separately.
+pub struct ConnectionPool {
+    clients: Vec<TunnelClient>,
+    tui_tx: Option<ChannelMonitor>,
+}
+
+impl ConnectionPool {
+    pub fn new(tui_tx: Option<ChannelMonitor>) -> Self {
+        Self {
+            clients: Vec::new(),
+            tui_tx,
+        }
+    }
+
+    /// Build a client into the pool, sharing the pool's TUI sender
+    pub fn add(&mut self, builder: TunnelClientBuilder) -> Result<&mut TunnelClient> {
+        if self.clients.len() == usize::from(u8::MAX) {
+            anyhow::bail!("Too many servers in the connection pool");
+        }
+        let mut builder = builder;
+        if let Some(tx) = &self.tui_tx {
+            builder = builder.tui_sender(tx.clone());
+        }
+        self.clients.push(builder.build()?);
+        Ok(self.clients.last_mut().expect("client was just added"))
+    }
+
+    /// Clients in the order they were added
+    pub fn clients_mut(&mut self) -> &mut [TunnelClient] {
+        &mut self.clients
+    }
+
+    /// Queue depth of the first client, which receives the TUI's commands
+    pub fn queue_depth(&self) -> Option<Arc<AtomicU32>> {
+        self.clients.first().map(TunnelClient::queue_depth)
+    }
+
+    /// Run all clients until each has finished, returning the first error
+    pub async fn run(self) -> Result<()> {
+        let pooled = self.clients.len() > 1;
+        let mut set = JoinSet::new();
+        for (pool_id, mut client) in (1..).zip(self.clients) {
+            if pooled {
+                client.set_pool_id(pool_id);
+            }
+            set.spawn(client.run());
+        }
+
+        let mut result = Ok(());
+        while let Some(joined) = set.join_next().await {
+            let outcome = joined.map_err(anyhow::Error::from).and_then(|r| r);
+            if let Err(e) = outcome {
+                error!("Pooled client stopped: {:#}", e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
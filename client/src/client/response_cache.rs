@@ -0,0 +1,159 @@
+//! Opt-in cache for GET responses the local service marks as cacheable.
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Method, URL and a hash of the request headers
+pub type CacheKey = (String, String, u64);
+
+/// Status, headers and body of a forwarded response
+pub type Response = (u16, Vec<(String, String)>, Option<Vec<u8>>);
+
+struct CachedResponse {
+    response: Response,
+    expires_at: Instant,
+    size: usize,
+}
+
+/// Responses kept until they go stale, bounded by their total size
+pub struct ResponseCache {
+    entries: LruCache<CacheKey, CachedResponse>,
+    max_bytes: usize,
+    used_bytes: usize,
+}
+
+impl ResponseCache {
+    pub fn new(max_size_mb: u32) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            max_bytes: max_size_mb as usize * 1024 * 1024,
+            used_bytes: 0,
+        }
+    }
+
+    pub fn key(method: &str, url: &str, headers: &[(String, String)]) -> CacheKey {
+        let mut headers: Vec<_> = headers
+            .iter()
+            .map(|(name, value)| (name.to_lowercase(), value))
+            .collect();
+        headers.sort();
+
+        let mut hasher = DefaultHasher::new();
+        headers.hash(&mut hasher);
+        (method.to_uppercase(), url.to_string(), hasher.finish())
+    }
+
+    /// A fresh cached response for the key, if any
+    pub fn get(&mut self, key: &CacheKey) -> Option<Response> {
+        let entry = self.entries.get(key)?;
+        if entry.expires_at > Instant::now() {
+            return Some(entry.response.clone());
+        }
+        if let Some(stale) = self.entries.pop(key) {
+            self.used_bytes -= stale.size;
+        }
+        None
+    }
+
+    /// Keep the response for as long as its headers allow, if it is cacheable
+    pub fn insert(&mut self, key: CacheKey, response: &Response, lifetime: Duration) {
+        let (status, headers, body) = response;
+        if key.0 != "GET" || !matches!(status, 200 | 304) || lifetime.is_zero() {
+            return;
+        }
+
+        let size = body.as_ref().map_or(0, Vec::len)
+            + headers
+                .iter()
+                .map(|(name, value)| name.len() + value.len())
+                .sum::<usize>();
+        if size > self.max_bytes {
+            return;
+        }
+
+        let entry = CachedResponse {
+            response: response.clone(),
+            expires_at: Instant::now() + lifetime,
+            size,
+        };
+        if let Some(replaced) = self.entries.put(key, entry) {
+            self.used_bytes -= replaced.size;
+        }
+        self.used_bytes += size;
+
+        while self.used_bytes > self.max_bytes {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.used_bytes -= evicted.size,
+                None => break,
+            }
+        }
+    }
+}
+
+/// How long a response may be cached, from its `Cache-Control` and `Expires`
+/// headers. Only `Cache-Control: public` (with `max-age` or `s-maxage`) or an
+/// `Expires` date in the future make a response cacheable.
+pub fn freshness(cache_control: Option<&str>, expires: Option<&str>) -> Option<Duration> {
+    let directives: Vec<String> = cache_control
+        .unwrap_or("")
+        .split(',')
+        .map(|d| d.trim().to_lowercase())
+        .filter(|d| !d.is_empty())
+        .collect();
+
+    let forbidden = ["private", "no-store", "no-cache"];
+    if directives.iter().any(|d| forbidden.contains(&d.as_str())) {
+        return None;
+    }
+
+    if directives.iter().any(|d| d == "public") {
+        let max_age = directives.iter().find_map(|d| {
+            d.strip_prefix("s-maxage=")
+                .or_else(|| d.strip_prefix("max-age="))
+                .and_then(|secs| secs.parse().ok())
+        });
+        if let Some(secs) = max_age {
+            return Some(Duration::from_secs(secs));
+        }
+    }
+
+    let expires = DateTime::parse_from_rfc2822(expires?.trim()).ok()?;
+    (expires.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freshness_and_eviction() {
+        assert_eq!(
+            freshness(Some("public, max-age=300"), None),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(freshness(Some("private, max-age=300"), None), None);
+        assert_eq!(freshness(Some("max-age=300"), None), None);
+        assert_eq!(freshness(None, Some("Thu, 01 Jan 1970 00:00:00 GMT")), None);
+        assert!(freshness(None, Some("Fri, 01 Jan 2100 00:00:00 GMT")).is_some());
+
+        let mut cache = ResponseCache::new(1);
+        let body = Some(vec![0u8; 600 * 1024]);
+        let ttl = Duration::from_secs(60);
+        let first = ResponseCache::key("GET", "/a", &[]);
+        let second = ResponseCache::key("GET", "/b", &[]);
+        cache.insert(first.clone(), &(200, vec![], body.clone()), ttl);
+        assert!(cache.get(&first).is_some());
+
+        // Over the size limit, the least recently used entry goes
+        cache.insert(second.clone(), &(200, vec![], body), ttl);
+        assert!(cache.get(&first).is_none());
+        assert!(cache.get(&second).is_some());
+
+        let post = ResponseCache::key("POST", "/a", &[]);
+        cache.insert(post.clone(), &(200, vec![], None), ttl);
+        assert!(cache.get(&post).is_none());
+    }
+}
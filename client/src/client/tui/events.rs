@@ -1,6 +1,9 @@
 use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::net::SocketAddr;
 
-use crate::protocol::RequestId;
+use crate::client::{ProxyErrorKind, UpstreamTiming};
+use crate::protocol::{RequestId, TcpTunnelId, TunnelId};
 
 /// Events that flow from the connection to the TUI
 #[derive(Debug, Clone)]
@@ -9,36 +12,183 @@ pub enum TuiEvent {
     TunnelRegistered(TunnelEvent),
     /// TCP tunnel registered
     TcpTunnelRegistered(TcpTunnelEvent),
+    /// The local service accepted a connection made for a TCP tunnel
+    TcpConnectionEstablished {
+        tcp_tunnel_id: TcpTunnelId,
+        connect_latency_ms: u64,
+    },
     /// Incoming HTTP request
     RequestReceived(RequestEvent),
     /// Response sent back
     ResponseSent(ResponseEvent),
     /// Connection status changed
     ConnectionStatus(ConnectionStatus),
+    /// Connection status of a pooled client other than the first, which
+    /// [`TuiEvent::ConnectionStatus`] is about
+    PoolConnectionStatus {
+        pool_id: u8,
+        status: ConnectionStatus,
+    },
+    /// Address the server connection was made to, once connected
+    ServerAddress(SocketAddr),
+    /// Periodic event channel statistics
+    ChannelStats { sent: u64, dropped: u64 },
+    /// Result of a ping to the server: the round-trip time, or `None` if no pong came back
+    Heartbeat { rtt_ms: Option<u64> },
+    /// Round-trip time of an application heartbeat message echoed by the server
+    AppHeartbeat { rtt_ms: u64 },
+    /// The server no longer accepts the API token; a new one is needed
+    TokenRequired,
+    /// Answer to `TuiCommand::RequestSnapshot`
+    StateSnapshot(ClientStateSnapshot),
+    /// Transient message for the user
+    Notification {
+        message: String,
+        level: NotificationLevel,
+    },
+    /// Non-fatal error, kept in the error log
+    Error {
+        message: String,
+        source: ErrorSource,
+    },
+    /// Something went wrong for one tunnel only, e.g. its local service is
+    /// down or the server is rate limiting it
+    TunnelError {
+        tunnel_id: TunnelId,
+        code: String,
+        message: String,
+    },
+}
+
+impl TuiEvent {
+    /// Variant name, recorded on the span that handles the event
+    pub fn name(&self) -> &'static str {
+        match self {
+            TuiEvent::TunnelRegistered(_) => "tunnel_registered",
+            TuiEvent::TcpTunnelRegistered(_) => "tcp_tunnel_registered",
+            TuiEvent::TcpConnectionEstablished { .. } => "tcp_connection_established",
+            TuiEvent::RequestReceived(_) => "request_received",
+            TuiEvent::ResponseSent(_) => "response_sent",
+            TuiEvent::ConnectionStatus(_) => "connection_status",
+            TuiEvent::PoolConnectionStatus { .. } => "pool_connection_status",
+            TuiEvent::ServerAddress(_) => "server_address",
+            TuiEvent::ChannelStats { .. } => "channel_stats",
+            TuiEvent::Heartbeat { .. } => "heartbeat",
+            TuiEvent::AppHeartbeat { .. } => "app_heartbeat",
+            TuiEvent::TokenRequired => "token_required",
+            TuiEvent::StateSnapshot(_) => "state_snapshot",
+            TuiEvent::Notification { .. } => "notification",
+            TuiEvent::Error { .. } => "error",
+            TuiEvent::TunnelError { .. } => "tunnel_error",
+        }
+    }
+}
+
+/// Where a [`TuiEvent::Error`] came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorSource {
+    /// A server message that couldn't be handled
+    Protocol,
+    /// Forwarding a request to the local service failed
+    Proxy,
+    /// A problem with a specific tunnel
+    Tunnel { tunnel_id: TunnelId },
+    /// The server rejected our credentials
+    Auth,
+}
+
+impl std::fmt::Display for ErrorSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorSource::Protocol => write!(f, "protocol"),
+            ErrorSource::Proxy => write!(f, "proxy"),
+            ErrorSource::Tunnel { tunnel_id } => write!(f, "tunnel {}", tunnel_id),
+            ErrorSource::Auth => write!(f, "auth"),
+        }
+    }
+}
+
+/// Severity of a [`TuiEvent::Notification`], used to pick its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotificationLevel {
+    #[default]
+    Info,
+    Warn,
+    Error,
 }
 
 /// Commands that flow from the TUI to the connection
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TuiCommand {
     /// Register a new HTTP tunnel
     AddHttpTunnel {
         local_port: u16,
         subdomain: Option<String>,
+        /// Added to every request forwarded through the tunnel
+        extra_headers: Vec<(String, String)>,
+        /// Sent to the server so dashboards can filter tunnels
+        tags: Vec<String>,
     },
     /// Register a new TCP tunnel
     AddTcpTunnel { local_port: u16 },
+    /// Disconnect and connect to a different server; tunnels must be re-added
+    SetServerHost {
+        new_host: String,
+        new_token: Option<String>,
+    },
+    /// Reconnect to the same server with a new API token, keeping the tunnels
+    UpdateToken { token: String },
+    /// Report what the connection is tracking with `TuiEvent::StateSnapshot`
+    RequestSnapshot,
+    /// Start reconnecting again after the connection gave up
+    Retry,
+}
+
+impl TuiCommand {
+    /// Tunnel changes wait for the connection while reconnecting; the
+    /// others, such as switching server, are what lets it reconnect
+    pub fn waits_for_connection(&self) -> bool {
+        matches!(
+            self,
+            TuiCommand::AddHttpTunnel { .. } | TuiCommand::AddTcpTunnel { .. }
+        )
+    }
+}
+
+/// What a connection is currently tracking, for debugging tunnels that
+/// don't show up as expected
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientStateSnapshot {
+    pub active_tunnel_count: usize,
+    pub active_tcp_tunnel_count: usize,
+    pub active_tcp_connection_count: usize,
+    pub active_ws_proxy_count: usize,
+    /// Registrations sent that the server hasn't confirmed yet
+    pub pending_tunnel_count: usize,
+    pub pending_tcp_tunnel_count: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct TunnelEvent {
+    pub tunnel_id: TunnelId,
     pub full_url: String,
     pub local_port: u16,
+    /// Client in the connection pool that owns the tunnel, 0 when not pooled
+    pub pool_id: u8,
+    /// Number of custom headers added to forwarded requests
+    pub extra_header_count: usize,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TcpTunnelEvent {
+    pub tcp_tunnel_id: TcpTunnelId,
+    /// Server the tunnel's port is opened on
+    pub server_host: String,
     pub server_port: u16,
     pub local_port: u16,
+    /// Client in the connection pool that owns the tunnel, 0 when not pooled
+    pub pool_id: u8,
 }
 
 #[derive(Debug, Clone)]
@@ -48,9 +198,43 @@ pub struct RequestEvent {
     pub path: String,
     pub query_string: String,
     pub headers: Vec<(String, String)>,
-    pub body: Option<Vec<u8>>,
+    pub body: Option<RequestBodyKind>,
     pub timestamp: DateTime<Local>,
     pub client_ip: Option<String>,
+    /// Public URL of the tunnel the request arrived through
+    pub tunnel_url: Option<String>,
+    /// Headers plus body, see [`message_size`]
+    pub size_bytes: usize,
+}
+
+/// A request body as seen by the TUI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestBodyKind {
+    /// Held in memory and shown in full
+    Buffered(Vec<u8>),
+    /// Streamed straight to the local service; the size is from
+    /// `Content-Length`, or 0 if unknown
+    Streaming(usize),
+}
+
+impl RequestBodyKind {
+    /// Body size in bytes; 0 for a streamed body of unknown size
+    pub fn size(&self) -> usize {
+        match self {
+            RequestBodyKind::Buffered(body) => body.len(),
+            RequestBodyKind::Streaming(len) => *len,
+        }
+    }
+}
+
+/// Size of a request or response for traffic accounting: the lengths of
+/// all header names and values plus the body
+pub fn message_size(headers: &[(String, String)], body_len: usize) -> usize {
+    headers
+        .iter()
+        .map(|(name, value)| name.len() + value.len())
+        .sum::<usize>()
+        + body_len
 }
 
 #[derive(Debug, Clone)]
@@ -60,6 +244,50 @@ pub struct ResponseEvent {
     pub headers: Vec<(String, String)>,
     pub body: Option<Vec<u8>>,
     pub duration_ms: u64,
+    /// Breakdown of `duration_ms`, for responses from the local service
+    pub timing: Option<TimingBreakdown>,
+    /// Set when the response was synthesized because forwarding failed
+    pub error_kind: Option<ProxyErrorKind>,
+    /// Headers plus body, see [`message_size`]
+    pub size_bytes: usize,
+}
+
+/// Where the time for a request went, from receiving it to having read the
+/// whole response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingBreakdown {
+    /// Waiting to be forwarded after it was received
+    pub queued_ms: u64,
+    /// Opening a connection to the local service, 0 when one was reused
+    pub connect_ms: u64,
+    /// From forwarding until the status line arrived, including `connect_ms`
+    pub first_byte_ms: u64,
+    pub total_ms: u64,
+}
+
+impl TimingBreakdown {
+    /// Combine the local service timings with the queueing around them.
+    /// `forward_ms` is the time from forwarding to having read the response.
+    pub fn new(queued_ms: u64, upstream: UpstreamTiming, forward_ms: u64) -> Self {
+        TimingBreakdown {
+            queued_ms,
+            connect_ms: upstream.connect_ms,
+            first_byte_ms: upstream.first_byte_ms,
+            total_ms: queued_ms + forward_ms,
+        }
+    }
+
+    /// Waiting for the local service to respond, once connected
+    pub fn wait_ms(&self) -> u64 {
+        self.first_byte_ms.saturating_sub(self.connect_ms)
+    }
+
+    /// Reading the response body
+    pub fn read_ms(&self) -> u64 {
+        self.total_ms
+            .saturating_sub(self.queued_ms)
+            .saturating_sub(self.first_byte_ms)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -68,12 +296,24 @@ pub enum ConnectionStatus {
     Connected,
     Reconnecting {
         attempt: u32,
+        /// Attempts allowed before giving up
+        max_attempts: u32,
         reason: String,
         next_retry_secs: u64,
     },
     Disconnected {
         reason: String,
     },
+    /// Inspecting requests loaded from a file; there is no server connection
+    Offline {
+        source: String,
+    },
+    /// Running `burrow tunnel proxy`; requests come from a local listener
+    LocalProxy {
+        listen_port: u16,
+        /// `host:port` requests are forwarded to
+        target: String,
+    },
 }
 
 impl std::fmt::Display for ConnectionStatus {
@@ -81,10 +321,47 @@ impl std::fmt::Display for ConnectionStatus {
         match self {
             ConnectionStatus::Connecting => write!(f, "Connecting"),
             ConnectionStatus::Connected => write!(f, "Connected"),
-            ConnectionStatus::Reconnecting { attempt, .. } => {
-                write!(f, "Reconnecting ({})", attempt)
+            ConnectionStatus::Reconnecting {
+                attempt,
+                max_attempts,
+                ..
+            } => {
+                write!(f, "Reconnecting (attempt {}/{})", attempt, max_attempts)
             }
             ConnectionStatus::Disconnected { .. } => write!(f, "Disconnected"),
+            ConnectionStatus::Offline { source } => {
+                write!(f, "Offline (imported from {})", source)
+            }
+            ConnectionStatus::LocalProxy {
+                listen_port,
+                target,
+            } => write!(f, "Local proxy (:{} → {})", listen_port, target),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_breakdown_parts_add_up() {
+        let upstream = UpstreamTiming {
+            connect_ms: 5,
+            first_byte_ms: 50,
+        };
+        let timing = TimingBreakdown::new(2, upstream, 62);
+
+        assert_eq!(timing.total_ms, 64);
+        assert_eq!(timing.wait_ms(), 45);
+        assert_eq!(timing.read_ms(), 12);
+        assert_eq!(
+            timing.queued_ms + timing.connect_ms + timing.wait_ms() + timing.read_ms(),
+            timing.total_ms
+        );
+
+        // Clock skew between the measurements never underflows
+        let skewed = TimingBreakdown::new(0, upstream, 10);
+        assert_eq!(skewed.read_ms(), 0);
+    }
+}
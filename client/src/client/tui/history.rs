@@ -0,0 +1,53 @@
+//! Persistence for request list search history.
+//!
+//! Queries are stored one per line, newest first, in `~/.burrow/search_history`.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+
+/// Maximum number of search queries remembered
+pub const MAX_SEARCH_HISTORY: usize = 20;
+
+fn history_path() -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("Could not determine home directory")?;
+    Ok(dirs.home_dir().join(".burrow").join("search_history"))
+}
+
+/// Load saved search history, or an empty history if there is none
+pub fn load_search_history() -> VecDeque<String> {
+    let Ok(path) = history_path() else {
+        return VecDeque::new();
+    };
+
+    fs::read_to_string(path)
+        .map(|contents| {
+            contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .take(MAX_SEARCH_HISTORY)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Save search history so it is available in the next session
+pub fn save_search_history(history: &VecDeque<String>) -> Result<()> {
+    let path = history_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let mut contents = String::new();
+    for query in history.iter().take(MAX_SEARCH_HISTORY) {
+        contents.push_str(query);
+        contents.push('\n');
+    }
+
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
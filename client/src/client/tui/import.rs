@@ -0,0 +1,211 @@
+//! Loading request logs from HAR files for offline inspection.
+//!
+//! Accepts either a full HAR document (`{"log": {"entries": [...]}}`) or a
+//! JSONL file with one HAR entry per line.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use super::{message_size, RequestBodyKind, RequestEvent, ResponseEvent, TuiEvent};
+use crate::protocol::RequestId;
+
+#[derive(Debug, Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarEntry {
+    started_date_time: String,
+    #[serde(default)]
+    time: f64,
+    request: HarRequest,
+    #[serde(default)]
+    response: Option<HarResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    #[serde(default)]
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarPostData {
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarResponse {
+    status: i64,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    #[serde(default)]
+    content: Option<HarContent>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarContent {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    encoding: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+/// Load a HAR or JSONL file and convert it into the TUI events that would
+/// have been produced had the requests gone through a live tunnel.
+pub fn load_har(path: &Path) -> Result<Vec<TuiEvent>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let entries = match serde_json::from_str::<Har>(&contents) {
+        Ok(har) => har.log.entries,
+        Err(_) => contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str::<HarEntry>(line)
+                    .with_context(|| format!("Invalid HAR entry on line {}", i + 1))
+            })
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Failed to parse {} as HAR or JSONL", path.display()))?,
+    };
+
+    let mut events = Vec::with_capacity(entries.len() * 2);
+    for (i, entry) in entries.into_iter().enumerate() {
+        let request_id = RequestId(format!("imported-{}", i));
+        let (path, query_string) = split_url(&entry.request.url);
+        let tunnel_url = url::Url::parse(&entry.request.url)
+            .ok()
+            .map(|u| u.origin().ascii_serialization());
+        let timestamp = DateTime::parse_from_rfc3339(&entry.started_date_time)
+            .map(|t| t.with_timezone(&Local))
+            .unwrap_or_else(|_| Local::now());
+
+        let headers = into_pairs(entry.request.headers);
+        let body = entry
+            .request
+            .post_data
+            .and_then(|p| p.text)
+            .map(|text| RequestBodyKind::Buffered(text.into_bytes()));
+        events.push(TuiEvent::RequestReceived(RequestEvent {
+            request_id: request_id.clone(),
+            method: entry.request.method,
+            path,
+            query_string,
+            size_bytes: message_size(&headers, body.as_ref().map_or(0, |b| b.size())),
+            headers,
+            body,
+            timestamp,
+            client_ip: None,
+            tunnel_url,
+        }));
+
+        // HAR uses status 0 for requests that never got a response
+        if let Some(response) = entry.response.filter(|r| r.status > 0) {
+            let body = response.content.and_then(|c| match c.encoding.as_deref() {
+                Some("base64") => c
+                    .text
+                    .and_then(|t| base64::engine::general_purpose::STANDARD.decode(t).ok()),
+                _ => c.text.map(String::into_bytes),
+            });
+
+            let headers = into_pairs(response.headers);
+            events.push(TuiEvent::ResponseSent(ResponseEvent {
+                request_id,
+                status: response.status as u16,
+                size_bytes: message_size(&headers, body.as_ref().map_or(0, Vec::len)),
+                headers,
+                body,
+                duration_ms: entry.time.max(0.0) as u64,
+                timing: None,
+                error_kind: None,
+            }));
+        }
+    }
+
+    Ok(events)
+}
+
+fn split_url(raw: &str) -> (String, String) {
+    match url::Url::parse(raw) {
+        Ok(url) => (
+            url.path().to_string(),
+            url.query().unwrap_or_default().to_string(),
+        ),
+        Err(_) => match raw.split_once('?') {
+            Some((path, query)) => (path.to_string(), query.to_string()),
+            None => (raw.to_string(), String::new()),
+        },
+    }
+}
+
+fn into_pairs(headers: Vec<HarHeader>) -> Vec<(String, String)> {
+    headers.into_iter().map(|h| (h.name, h.value)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_load_har_document() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"{{"log": {{"entries": [{{
+                "startedDateTime": "2024-01-01T12:00:00Z",
+                "time": 42.5,
+                "request": {{"method": "POST", "url": "http://localhost/api?x=1",
+                             "headers": [{{"name": "accept", "value": "*/*"}}],
+                             "postData": {{"text": "hello"}}}},
+                "response": {{"status": 201, "headers": [],
+                              "content": {{"text": "aGk=", "encoding": "base64"}}}}
+            }}]}}}}"#
+        )
+        .unwrap();
+
+        let events = load_har(file.path()).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let TuiEvent::RequestReceived(req) = &events[0] else {
+            panic!("expected request event");
+        };
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/api");
+        assert_eq!(req.query_string, "x=1");
+        assert_eq!(req.body, Some(RequestBodyKind::Buffered(b"hello".to_vec())));
+
+        let TuiEvent::ResponseSent(resp) = &events[1] else {
+            panic!("expected response event");
+        };
+        assert_eq!(resp.status, 201);
+        assert_eq!(resp.duration_ms, 42);
+        assert_eq!(resp.body.as_deref(), Some(b"hi".as_slice()));
+    }
+}
@@ -0,0 +1,104 @@
+//! Navigation keys for the default, Vim and Emacs keyboard modes.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::config::KeyboardMode;
+
+/// What a navigation key does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Next,
+    Previous,
+    PageDown,
+    PageUp,
+    /// Leave the current view or input
+    Back,
+    /// Delete from the cursor to the end of the input; inputs are edited at
+    /// the end, so this clears the line
+    DeleteToEnd,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keybindings {
+    bindings: Vec<(KeyCode, KeyModifiers, Action)>,
+}
+
+impl Keybindings {
+    pub fn for_mode(mode: KeyboardMode) -> Self {
+        match mode {
+            KeyboardMode::Default => Self::default(),
+            KeyboardMode::Vim => Self::vim(),
+            KeyboardMode::Emacs => Self::emacs(),
+        }
+    }
+
+    /// Arrow and page keys only
+    fn common() -> Vec<(KeyCode, KeyModifiers, Action)> {
+        vec![
+            (KeyCode::Down, KeyModifiers::NONE, Action::Next),
+            (KeyCode::Up, KeyModifiers::NONE, Action::Previous),
+            (KeyCode::PageDown, KeyModifiers::NONE, Action::PageDown),
+            (KeyCode::PageUp, KeyModifiers::NONE, Action::PageUp),
+        ]
+    }
+
+    /// `j`/`k` and `Ctrl-D`/`Ctrl-U` (also `Ctrl-F`/`Ctrl-B`) for pages
+    pub fn vim() -> Self {
+        let mut bindings = Self::default().bindings;
+        bindings.extend([
+            (KeyCode::Char('d'), KeyModifiers::CONTROL, Action::PageDown),
+            (KeyCode::Char('f'), KeyModifiers::CONTROL, Action::PageDown),
+            (KeyCode::Char('u'), KeyModifiers::CONTROL, Action::PageUp),
+            (KeyCode::Char('b'), KeyModifiers::CONTROL, Action::PageUp),
+        ]);
+        Self { bindings }
+    }
+
+    /// `Ctrl-N`/`Ctrl-P`, `Ctrl-F`/`Ctrl-B` for pages, `Ctrl-G` to go back
+    /// and `Ctrl-K` to delete to the end of an input
+    pub fn emacs() -> Self {
+        let mut bindings = Self::common();
+        bindings.extend([
+            (KeyCode::Char('n'), KeyModifiers::CONTROL, Action::Next),
+            (KeyCode::Char('p'), KeyModifiers::CONTROL, Action::Previous),
+            (KeyCode::Char('f'), KeyModifiers::CONTROL, Action::PageDown),
+            (KeyCode::Char('b'), KeyModifiers::CONTROL, Action::PageUp),
+            (KeyCode::Char('g'), KeyModifiers::CONTROL, Action::Back),
+            (
+                KeyCode::Char('k'),
+                KeyModifiers::CONTROL,
+                Action::DeleteToEnd,
+            ),
+        ]);
+        Self { bindings }
+    }
+
+    /// The action for a key in a list view
+    pub fn action(&self, key: KeyEvent) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|&&(code, modifiers, _)| code == key.code && modifiers == key.modifiers)
+            .map(|&(_, _, action)| action)
+    }
+
+    /// The action for a key while typing; keys without a modifier are text
+    pub fn input_action(&self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.action(key)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Keybindings {
+    /// Arrow keys plus `j`/`k`
+    fn default() -> Self {
+        let mut bindings = Self::common();
+        bindings.extend([
+            (KeyCode::Char('j'), KeyModifiers::NONE, Action::Next),
+            (KeyCode::Char('k'), KeyModifiers::NONE, Action::Previous),
+        ]);
+        Self { bindings }
+    }
+}
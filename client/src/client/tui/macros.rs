@@ -0,0 +1,172 @@
+//! Persistence for keyboard macros.
+//!
+//! Macros are stored as a JSON object of register to key list in
+//! `~/.burrow/macros.json`, with keys written like `j`, `ctrl-r`, `alt-shift-up`
+//! or `enter`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tracing::warn;
+
+use super::store;
+
+/// Load saved macros, or none if there are none. Keys that can't be read
+/// back are dropped.
+pub fn load_macros() -> HashMap<char, Vec<KeyEvent>> {
+    let saved: BTreeMap<char, Vec<String>> = store::load(store::MACROS_FILE);
+    saved
+        .into_iter()
+        .map(|(register, keys)| {
+            let keys = keys.iter().filter_map(|key| parse_key(key)).collect();
+            (register, keys)
+        })
+        .collect()
+}
+
+/// Save macros so they are available in the next session. Keys that can't
+/// be written down are left out with a warning.
+pub fn save_macros(macros: &HashMap<char, Vec<KeyEvent>>) -> Result<()> {
+    let sorted: BTreeMap<char, Vec<String>> = macros
+        .iter()
+        .map(|(register, keys)| {
+            let names: Vec<String> = keys.iter().filter_map(key_name).collect();
+            if names.len() < keys.len() {
+                warn!(
+                    "Left {} keys out of macro '{}' that can't be saved",
+                    keys.len() - names.len(),
+                    register
+                );
+            }
+            (*register, names)
+        })
+        .collect();
+    store::save(store::MACROS_FILE, &sorted)
+}
+
+/// Modifiers written as prefixes, in the order they are written
+const MODIFIER_PREFIXES: [(KeyModifiers, &str); 3] = [
+    (KeyModifiers::CONTROL, "ctrl-"),
+    (KeyModifiers::ALT, "alt-"),
+    (KeyModifiers::SHIFT, "shift-"),
+];
+
+/// Whether a key survives being saved and loaded again
+pub fn can_save(key: &KeyEvent) -> bool {
+    key_name(key).is_some()
+}
+
+/// How a key is written in the macros file, if it can be
+fn key_name(key: &KeyEvent) -> Option<String> {
+    let name = match key.code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::BackTab => "backtab".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        _ => return None,
+    };
+    let mut written = String::new();
+    let mut modifiers = key.modifiers;
+    for (modifier, prefix) in MODIFIER_PREFIXES {
+        if modifiers.contains(modifier) {
+            written.push_str(prefix);
+            modifiers.remove(modifier);
+        }
+    }
+    // Super, hyper and meta aren't written down
+    if !modifiers.is_empty() {
+        return None;
+    }
+    written.push_str(&name);
+    Some(written)
+}
+
+/// Read back a key written by [`key_name`]
+fn parse_key(mut name: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    for (modifier, prefix) in MODIFIER_PREFIXES {
+        match name.strip_prefix(prefix) {
+            Some(rest) if !rest.is_empty() => {
+                modifiers |= modifier;
+                name = rest;
+            }
+            _ => {}
+        }
+    }
+    let code = match name {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                (Some('f'), Some(_)) => KeyCode::F(name[1..].parse().ok()?),
+                _ => return None,
+            }
+        }
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names_round_trip() {
+        let keys = [
+            KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL),
+            KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('-'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('Q'), KeyModifiers::SHIFT),
+            KeyEvent::new(KeyCode::Char('-'), KeyModifiers::ALT),
+            KeyEvent::new(
+                KeyCode::Up,
+                KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT,
+            ),
+        ];
+        for key in keys {
+            let name = key_name(&key).unwrap();
+            assert_eq!(parse_key(&name), Some(key), "{}", name);
+        }
+        assert_eq!(parse_key("nonsense"), None);
+        assert!(!can_save(&KeyEvent::new(
+            KeyCode::Insert,
+            KeyModifiers::NONE
+        )));
+        assert!(!can_save(&KeyEvent::new(
+            KeyCode::Char('j'),
+            KeyModifiers::SUPER
+        )));
+    }
+}
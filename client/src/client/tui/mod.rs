@@ -1,21 +1,63 @@
 mod events;
+mod history;
+mod import;
+mod keybindings;
+mod macros;
+mod monitor;
+mod store;
+mod theme;
 mod ui;
 
 pub use events::*;
+pub use import::load_har;
+pub use monitor::ChannelMonitor;
+pub use ui::format_size;
 
-use crate::protocol::RequestId;
+use keybindings::{Action, Keybindings};
+use theme::Theme;
+
+use crate::client::{check_tunnel_tag, ProxyErrorKind};
+use crate::config::{
+    Config, DisplayDensity, RequestListColumn, RequestListColumnConfig, ServerScheme,
+    StatusBarSlot, TimeDisplay, TuiConfig,
+};
+use crate::protocol::{RequestId, TcpTunnelId, TunnelId};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::io;
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use chrono::Local;
+use chrono::{DateTime, Local};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    cursor::Show,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ratatui::{backend::CrosstermBackend, widgets::TableState, Terminal};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Position, Rect},
+    widgets::TableState,
+    Terminal,
+};
 use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// The most recent error reported for a tunnel
+#[derive(Debug, Clone)]
+pub struct TunnelErrorInfo {
+    pub code: String,
+    pub message: String,
+    pub at: DateTime<Local>,
+}
 
 /// A logged request with optional response
 #[derive(Debug, Clone)]
@@ -25,13 +67,131 @@ pub struct RequestLog {
     pub path: String,
     pub query_string: String,
     pub request_headers: Vec<(String, String)>,
-    pub request_body: Option<Vec<u8>>,
+    pub request_body: Option<RequestBodyKind>,
     pub status: Option<u16>,
     pub response_headers: Vec<(String, String)>,
     pub response_body: Option<Vec<u8>>,
+    /// Total time to forward the request and read the response
     pub duration_ms: Option<u64>,
+    /// Breakdown of `duration_ms`, for responses from the local service
+    pub timing_breakdown: Option<TimingBreakdown>,
     pub timestamp: chrono::DateTime<Local>,
     pub client_ip: Option<String>,
+    pub error_kind: Option<ProxyErrorKind>,
+    /// Public URL of the tunnel the request arrived through
+    pub tunnel_url: Option<String>,
+    /// Header and body bytes of the request and response
+    pub request_size_bytes: usize,
+    pub response_size_bytes: usize,
+    /// User-defined labels, kept in sync with `App::request_tags`
+    pub tags: Vec<String>,
+}
+
+impl RequestLog {
+    /// Whether the response is a Server-Sent Events stream, relayed as it
+    /// arrives rather than captured
+    pub fn is_event_stream(&self) -> bool {
+        self.response_headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("content-type")
+                && value
+                    .trim_start()
+                    .to_ascii_lowercase()
+                    .starts_with("text/event-stream")
+        })
+    }
+}
+
+/// An entry in the error log view
+#[derive(Debug, Clone)]
+pub struct ErrorLogEntry {
+    pub timestamp: chrono::DateTime<Local>,
+    pub source: ErrorSource,
+    pub message: String,
+}
+
+/// Maximum number of entries kept in the error log
+const MAX_ERROR_LOG: usize = 50;
+
+/// A connection status change, for the connection history view
+#[derive(Debug, Clone)]
+pub struct ConnectionHistoryEntry {
+    pub status: ConnectionStatus,
+    pub occurred_at: chrono::DateTime<Local>,
+}
+
+/// How long the local service took to accept connections for one TCP tunnel
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectLatency {
+    pub count: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+impl ConnectLatency {
+    fn record(&mut self, latency_ms: u64) {
+        self.count += 1;
+        self.total_ms += latency_ms;
+        self.max_ms = self.max_ms.max(latency_ms);
+    }
+
+    pub fn average_ms(&self) -> u64 {
+        self.total_ms.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+/// Maximum number of entries kept in the connection history
+const MAX_CONNECTION_HISTORY: usize = 100;
+
+/// Rows built above and below the visible part of the request list
+const VISIBLE_WINDOW_MARGIN: usize = 10;
+/// Lines of the request list taken by its borders and header, not counting
+/// the density's margin under the header
+const REQUEST_LIST_CHROME_HEIGHT: usize = 3;
+/// Commands held while reconnecting
+const MAX_PENDING_COMMANDS: usize = 20;
+/// Two clicks on the same request within this long open it
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+/// Characters a request list column grows or shrinks by per key press
+const COLUMN_RESIZE_STEP: i16 = 2;
+/// Quiet time after resizing a column before the layout is written to the
+/// config file, so holding Ctrl-Left doesn't rewrite it for every step
+const LAYOUT_SAVE_DELAY: Duration = Duration::from_secs(2);
+/// Narrowest a request list column can be made
+const MIN_COLUMN_WIDTH: u16 = 4;
+/// Least space for the column without a configured width
+const FILL_COLUMN_MIN_WIDTH: u16 = 20;
+/// Macros kept in registers at once
+const MAX_NAMED_MACROS: usize = 10;
+/// Keys one replay may run, so a macro that replays itself stops
+const MAX_MACRO_REPLAY_KEYS: usize = 1000;
+
+/// How often the connection quality indicator is recomputed
+const QUALITY_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+/// Round-trip times averaged for the connection quality
+const MAX_RTT_SAMPLES: usize = 6;
+
+/// Connection quality from ping round-trip time and lost pings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionQuality {
+    Excellent,
+    Good,
+    Fair,
+    Poor,
+}
+
+impl ConnectionQuality {
+    /// Rate an average round-trip time and fraction of lost pings
+    pub fn from_stats(rtt_ms: u64, loss: f64) -> Self {
+        if rtt_ms < 50 && loss == 0.0 {
+            Self::Excellent
+        } else if rtt_ms < 150 && loss < 0.01 {
+            Self::Good
+        } else if rtt_ms < 500 && loss < 0.05 {
+            Self::Fair
+        } else {
+            Self::Poor
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -40,6 +200,137 @@ pub enum ViewMode {
     AddTunnel,
     RequestList,
     RequestDetail,
+    ErrorLog,
+    ConnectionHistory,
+    SwitchServer,
+    Timeline,
+    /// Side-by-side comparison of two requests
+    RequestDiff,
+    /// Prompt for a new API token after the old one expired
+    UpdateToken,
+    /// Every tunnel with its traffic, for when the status bar can't fit them
+    TunnelStats,
+}
+
+/// Where a tunnel can be reached from outside
+enum TunnelAddress {
+    Url(String),
+    /// Shell command for a TCP tunnel
+    Command(String),
+}
+
+/// Put `text` on the system clipboard with an OSC 52 escape sequence, which
+/// the terminal handles (also over SSH)
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    use base64::Engine;
+    use std::io::Write;
+
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
+
+/// How far back the timeline view reaches
+pub const TIMELINE_WINDOW_SECS: i64 = 30;
+/// Most requests drawn in the timeline view
+const TIMELINE_MAX_REQUESTS: usize = 20;
+
+/// How long to show the fallback view before trying to render normally again
+const RENDER_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+thread_local! {
+    /// Set while `ui::draw` runs, so the panic hook leaves the terminal alone
+    /// for panics the render loop recovers from
+    static RENDERING: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Key that consecutive requests in the request list are grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Path,
+    PathAndMethod,
+    Host,
+}
+
+impl GroupBy {
+    /// The next grouping when cycling through them, `None` turning it off
+    fn next(current: Option<GroupBy>) -> Option<GroupBy> {
+        match current {
+            None => Some(GroupBy::Path),
+            Some(GroupBy::Path) => Some(GroupBy::PathAndMethod),
+            Some(GroupBy::PathAndMethod) => Some(GroupBy::Host),
+            Some(GroupBy::Host) => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupBy::Path => "path",
+            GroupBy::PathAndMethod => "method and path",
+            GroupBy::Host => "host",
+        }
+    }
+
+    pub fn key(self, req: &RequestLog) -> String {
+        match self {
+            GroupBy::Path => req.path.clone(),
+            GroupBy::PathAndMethod => format!("{} {}", req.method, req.path),
+            GroupBy::Host => req
+                .request_headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+                .map(|(_, host)| host.clone())
+                .unwrap_or_else(|| "-".to_string()),
+        }
+    }
+}
+
+/// The request list after filtering: positions in `App::requests` in the
+/// order shown, and the rows drawn for them
+#[derive(Debug)]
+pub struct FilteredList {
+    pub indices: Vec<usize>,
+    pub rows: Vec<ListRow>,
+}
+
+/// Everything a [`FilteredList`] is built from, to tell when a cached one
+/// is out of date
+#[derive(Debug, PartialEq)]
+struct ListKey {
+    len: usize,
+    newest: Option<RequestId>,
+    oldest: Option<RequestId>,
+    search_query: String,
+    bookmarks_only: bool,
+    bookmarked: usize,
+    group_by: Option<GroupBy>,
+    expanded_groups: usize,
+    tag_edits: u64,
+}
+
+/// A row of the request list, indexing into `filtered_requests()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListRow {
+    Request {
+        index: usize,
+        in_group: bool,
+    },
+    /// Two or more consecutive requests with the same `GroupBy` key
+    Group {
+        key: String,
+        requests: Range<usize>,
+        expanded: bool,
+    },
+}
+
+/// Tabs within the request detail view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DetailTab {
+    #[default]
+    Overview,
+    /// Request and response as they would appear on the wire
+    Raw,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -54,59 +345,626 @@ pub enum AddTunnelField {
     TunnelType,
     Port,
     Subdomain,
+    /// Comma-separated tags sent with the registration (HTTP only)
+    Tags,
+    /// `Header-Name: Value` lines added to forwarded requests (HTTP only)
+    Headers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchServerField {
+    Host,
+    Token,
 }
 
 /// TUI application state
 pub struct App {
     pub tunnels: Vec<TunnelEvent>,
     pub tcp_tunnels: Vec<TcpTunnelEvent>,
+    /// Connect latency to the local service, per TCP tunnel
+    pub tcp_connect_latency: HashMap<TcpTunnelId, ConnectLatency>,
+    /// Last error per HTTP tunnel
+    pub tunnel_errors: HashMap<TunnelId, TunnelErrorInfo>,
     pub requests: Vec<RequestLog>,
     pub table_state: TableState,
+    /// Positions in the list's rows that the request list builds rows for
+    pub visible_window: Range<usize>,
+    /// Rows the request list shows at once, for paging
+    page_rows: usize,
+    /// Screen row of each request list row from `table_state.offset()` on,
+    /// followed by the row below the last one; used to map mouse clicks
+    pub row_y_positions: Vec<u16>,
+    /// Where the status bar shows each tunnel URL, for opening it on click
+    pub status_bar_links: Vec<(Rect, String)>,
+    /// Time and list position of the last click, to detect double clicks
+    last_click: Option<(Instant, usize)>,
+    keybindings: Keybindings,
+    pub theme: Theme,
     pub tunnel_list_state: TableState,
     pub view_mode: ViewMode,
     pub connection_status: ConnectionStatus,
+    /// Status of the other clients in a connection pool, by pool id
+    pub pool_statuses: BTreeMap<u8, ConnectionStatus>,
+    /// When the next reconnect attempt is due, for the countdown
+    pub reconnect_deadline: Option<Instant>,
     pub should_quit: bool,
+    pub events_sent: u64,
+    pub events_dropped: u64,
+    /// Request and response bytes seen through the tunnels
+    pub total_bytes_in: u64,
+    pub total_bytes_out: u64,
+    /// Messages waiting to be written to the server connection
+    pub message_queue_depth: u32,
+    /// Pings sent and answered on the current connection
+    pub heartbeat_sent: u32,
+    pub heartbeat_received: u32,
+    /// Recent ping round-trip times in ms, newest last
+    rtt_samples: VecDeque<u64>,
+    /// Shown next to the connection status; `None` until measured
+    pub connection_quality: Option<ConnectionQuality>,
+    /// Latest application heartbeat round trip, which also includes the
+    /// time the server takes to handle a message
+    pub heartbeat_rtt_ms: Option<u64>,
+    /// Server address of the current connection, for showing IPv4/IPv6
+    pub server_address: Option<SocketAddr>,
+    /// Whether the server is reached over plain `ws://`
+    pub no_tls: bool,
+    quality_updated_at: Instant,
+    /// Current terminal size as (columns, rows)
+    pub terminal_size: (u16, u16),
+    /// Transient message shown in place of the help footer
+    pub notification: Option<String>,
+    pub notification_level: NotificationLevel,
+    pub time_display: TimeDisplay,
+    pub display_density: DisplayDensity,
+    pub detail_tab: DetailTab,
+    /// First request picked for a diff, while the second is being chosen
+    pub diff_base: Option<RequestId>,
+    /// Request compared against `diff_base` in the diff view
+    pub diff_target: Option<RequestId>,
+    pub diff_scroll: u16,
     max_requests: usize,
+    /// Selection before the last `clear`, restored when requests come in again
+    pre_clear_position: Option<usize>,
+    /// Requests removed by the last `clear`, for Ctrl-Z
+    undo_clear: Option<Vec<RequestLog>>,
+    undo_clear_limit: usize,
+    /// What the status bar shows, left to right
+    pub status_bar_slots: Vec<StatusBarSlot>,
+    /// Columns of the request list, left to right
+    pub request_list_columns: Vec<RequestListColumnConfig>,
+    /// Index into `request_list_columns` that Ctrl-Left/Right resizes
+    pub selected_column: usize,
+    /// Characters added to or taken from each column's configured width
+    pub column_width_adjustments: BTreeMap<RequestListColumn, i16>,
+    /// When the changed column widths are to be saved
+    layout_save_due: Option<Instant>,
+
+    /// Recent non-fatal errors, newest first
+    pub error_log: VecDeque<ErrorLogEntry>,
+    /// Errors logged since the error log was last opened
+    pub unseen_errors: usize,
+    /// View to return to when leaving the error log
+    view_before_error_log: ViewMode,
+    view_before_tunnel_stats: ViewMode,
+    /// Connection status changes, oldest first; the last entry is `connection_status`
+    pub connection_history: VecDeque<ConnectionHistoryEntry>,
+    /// Selections restored when toggling between the tunnel and request lists
+    pub last_tunnel_list_selection: Option<usize>,
+    pub last_request_list_selection: Option<usize>,
+
+    // Request list search state
+    /// Filter applied to the request list, matched against "METHOD path"
+    pub search_query: String,
+    /// Whether the search box has focus
+    pub searching: bool,
+    /// Recent search queries, newest first
+    pub search_history: VecDeque<String>,
+    /// Position in `search_history` while browsing it with Up/Down
+    pub history_cursor: Option<usize>,
+
+    /// Requests pinned to the top of the list; kept when the list is cleared
+    pub bookmarked: HashSet<RequestId>,
+    /// Whether the request list only shows bookmarked requests
+    pub bookmarks_only: bool,
+    /// Collapse runs of similar requests in the request list
+    pub group_by: Option<GroupBy>,
+    /// Groups opened with Space, by the oldest request in the group
+    expanded_groups: HashSet<RequestId>,
+    /// The last [`FilteredList`] built, reused while nothing it depends on
+    /// changed so a redraw doesn't filter and sort every request
+    list_cache: RefCell<Option<(ListKey, Arc<FilteredList>)>>,
+    /// Bumped when a request's tags change, as `tag:` searches match them
+    tag_edits: u64,
+
+    /// Keep the newest request selected as requests arrive; turned off by
+    /// moving the selection. Not followed while a request is open, so the
+    /// detail view keeps showing the same request
+    pub auto_scroll: bool,
+
+    /// Tags by request, including requests no longer in the list
+    pub request_tags: HashMap<RequestId, Vec<String>>,
+    /// Whether the tag editor has focus
+    pub tagging: bool,
+    /// Comma-separated tags being edited for the selected request
+    pub tag_input: String,
+
+    /// Free-text notes by request, including requests no longer in the list
+    pub request_notes: HashMap<RequestId, String>,
+    /// Request whose note is being edited in the detail view, and the text so far
+    pub editing_note: Option<(RequestId, String)>,
+    /// Path typed after `O`, opened under the selected tunnel's URL
+    pub tunnel_path_input: Option<String>,
+    /// Title and lines shown over the current view until a key is pressed
+    pub popup: Option<(String, Vec<String>)>,
+
+    // Keyboard macro state
+    /// Register being recorded into with `Q`
+    pub recording_macro: Option<char>,
+    /// Keys recorded so far
+    pub macro_buffer: Vec<KeyEvent>,
+    /// Recorded macros by register, replayed with `@`
+    pub named_macros: HashMap<char, Vec<KeyEvent>>,
+    /// Register recorded last, replayed with `@@`
+    last_macro: Option<char>,
+    /// `Q` or `@` waiting for a register
+    macro_prefix: Option<char>,
+    /// Keys still to replay, next first
+    macro_queue: VecDeque<KeyEvent>,
+    /// Keys run since the user started the current replay
+    macro_keys_replayed: usize,
+    /// Whether the key being handled comes from a replay
+    replaying_macro: bool,
+    /// Pause between replayed keys
+    macro_replay_delay: Duration,
+    /// When the next replayed key is due
+    next_macro_key_at: Option<Instant>,
+
+    /// Request highlighted in the timeline view
+    pub timeline_selected: Option<RequestId>,
+    /// Panic message from the last failed render; a plain fallback view is
+    /// drawn until rendering works again
+    pub render_error: Option<String>,
+    /// What the connection was tracking when the connection history was opened
+    pub state_snapshot: Option<ClientStateSnapshot>,
 
     // Add tunnel form state
     pub add_tunnel_type: TunnelType,
     pub add_tunnel_port: String,
     pub add_tunnel_subdomain: String,
+    pub add_tunnel_tags: String,
+    pub add_tunnel_headers: String,
     pub add_tunnel_field: AddTunnelField,
     pub add_tunnel_error: Option<String>,
 
+    // Switch server form state
+    pub switch_server_host: String,
+    /// New API token; empty keeps the current one
+    pub switch_server_token: String,
+    pub switch_server_field: SwitchServerField,
+    pub switch_server_error: Option<String>,
+
+    // Token prompt state
+    pub token_input: String,
+    pub token_error: Option<String>,
+    view_before_token_prompt: ViewMode,
+
     // Command channel to connection
     cmd_tx: mpsc::Sender<TuiCommand>,
+    /// When the last command was sent, for rate limiting
+    last_command_at: Option<Instant>,
+    /// Minimum time between commands
+    command_cooldown: Duration,
+    /// Recently sent commands, newest last; trimmed to those still queued
+    sent_commands: VecDeque<TuiCommand>,
+    /// Commands given while reconnecting, sent once connected again
+    pending_commands: VecDeque<TuiCommand>,
+    /// Local ports of the tunnels from before the reconnect, which the
+    /// connection registers again by itself
+    ports_before_reconnect: HashSet<u16>,
 }
 
 impl App {
-    pub fn new(cmd_tx: mpsc::Sender<TuiCommand>) -> Self {
+    pub fn new(cmd_tx: mpsc::Sender<TuiCommand>, config: &TuiConfig) -> Self {
         Self {
             tunnels: Vec::new(),
             tcp_tunnels: Vec::new(),
+            tcp_connect_latency: HashMap::new(),
+            tunnel_errors: HashMap::new(),
             requests: Vec::new(),
             table_state: TableState::default(),
+            visible_window: 0..0,
+            row_y_positions: Vec::new(),
+            status_bar_links: Vec::new(),
+            last_click: None,
+            page_rows: 10,
+            keybindings: Keybindings::for_mode(config.keyboard_mode),
+            theme: Theme::from_config(&config.theme),
             tunnel_list_state: TableState::default(),
             view_mode: ViewMode::TunnelList,
             connection_status: ConnectionStatus::Connecting,
+            reconnect_deadline: None,
             should_quit: false,
-            max_requests: 1000,
+            events_sent: 0,
+            events_dropped: 0,
+            total_bytes_in: 0,
+            total_bytes_out: 0,
+            message_queue_depth: 0,
+            heartbeat_sent: 0,
+            heartbeat_received: 0,
+            rtt_samples: VecDeque::new(),
+            connection_quality: None,
+            quality_updated_at: Instant::now(),
+            server_address: None,
+            no_tls: false,
+            heartbeat_rtt_ms: None,
+            terminal_size: (0, 0),
+            notification: None,
+            notification_level: NotificationLevel::default(),
+            time_display: config.time_display,
+            display_density: config.display_density,
+            detail_tab: DetailTab::default(),
+            diff_base: None,
+            diff_target: None,
+            diff_scroll: 0,
+            max_requests: config.max_requests.max(1),
+            pre_clear_position: None,
+            undo_clear: None,
+            undo_clear_limit: config.undo_clear_limit,
+            status_bar_slots: config.status_bar_slots.clone(),
+            request_list_columns: config.request_list_columns.clone(),
+            selected_column: 0,
+            column_width_adjustments: config.column_width_adjustments.clone(),
+            layout_save_due: None,
+            error_log: VecDeque::new(),
+            unseen_errors: 0,
+            view_before_error_log: ViewMode::TunnelList,
+            view_before_tunnel_stats: ViewMode::TunnelList,
+            connection_history: VecDeque::from([ConnectionHistoryEntry {
+                status: ConnectionStatus::Connecting,
+                occurred_at: Local::now(),
+            }]),
+            last_tunnel_list_selection: None,
+            last_request_list_selection: None,
+            search_query: String::new(),
+            searching: false,
+            search_history: VecDeque::new(),
+            history_cursor: None,
+            bookmarked: HashSet::new(),
+            bookmarks_only: false,
+            group_by: None,
+            expanded_groups: HashSet::new(),
+            list_cache: RefCell::new(None),
+            tag_edits: 0,
+            auto_scroll: true,
+            request_tags: HashMap::new(),
+            tagging: false,
+            tag_input: String::new(),
+            request_notes: HashMap::new(),
+            editing_note: None,
+            tunnel_path_input: None,
+            popup: None,
+            recording_macro: None,
+            macro_buffer: Vec::new(),
+            named_macros: HashMap::new(),
+            last_macro: None,
+            macro_prefix: None,
+            macro_queue: VecDeque::new(),
+            macro_keys_replayed: 0,
+            replaying_macro: false,
+            macro_replay_delay: Duration::from_millis(config.macro_replay_delay_ms),
+            next_macro_key_at: None,
+            timeline_selected: None,
+            render_error: None,
+            state_snapshot: None,
             add_tunnel_type: TunnelType::Http,
             add_tunnel_port: String::new(),
             add_tunnel_subdomain: String::new(),
+            add_tunnel_tags: String::new(),
+            add_tunnel_headers: String::new(),
             add_tunnel_field: AddTunnelField::Port,
             add_tunnel_error: None,
+            switch_server_host: String::new(),
+            switch_server_token: String::new(),
+            switch_server_field: SwitchServerField::Host,
+            switch_server_error: None,
+            token_input: String::new(),
+            token_error: None,
+            view_before_token_prompt: ViewMode::TunnelList,
             cmd_tx,
+            last_command_at: None,
+            command_cooldown: Duration::from_millis(500),
+            sent_commands: VecDeque::new(),
+            pending_commands: VecDeque::new(),
+            ports_before_reconnect: HashSet::new(),
+            pool_statuses: BTreeMap::new(),
+        }
+    }
+
+    /// Recompute the connection quality if it is due
+    pub fn update_connection_quality(&mut self) {
+        if self.quality_updated_at.elapsed() < QUALITY_UPDATE_INTERVAL {
+            return;
+        }
+        self.quality_updated_at = Instant::now();
+        if self.heartbeat_sent == 0 {
+            return;
+        }
+
+        let lost = self.heartbeat_sent - self.heartbeat_received;
+        let loss = f64::from(lost) / f64::from(self.heartbeat_sent);
+        self.connection_quality = Some(match self.average_rtt_ms() {
+            Some(rtt_ms) => ConnectionQuality::from_stats(rtt_ms, loss),
+            None => ConnectionQuality::Poor,
+        });
+    }
+
+    /// Average of the recent ping round-trip times
+    pub fn average_rtt_ms(&self) -> Option<u64> {
+        if self.rtt_samples.is_empty() {
+            return None;
+        }
+        Some(self.rtt_samples.iter().sum::<u64>() / self.rtt_samples.len() as u64)
+    }
+
+    /// Share of answered requests that got a 5xx or failed to forward
+    pub fn error_rate(&self) -> Option<f64> {
+        let answered = self.requests.iter().filter(|req| req.status.is_some());
+        let (total, errors) = answered.fold((0usize, 0usize), |(total, errors), req| {
+            let failed = req.error_kind.is_some() || req.status.is_some_and(|s| s >= 500);
+            (total + 1, errors + usize::from(failed))
+        });
+        (total > 0).then(|| errors as f64 / total as f64)
+    }
+
+    /// Average time the local service took to answer
+    pub fn average_latency_ms(&self) -> Option<u64> {
+        let durations: Vec<u64> = self.requests.iter().filter_map(|r| r.duration_ms).collect();
+        if durations.is_empty() {
+            return None;
+        }
+        Some(durations.iter().sum::<u64>() / durations.len() as u64)
+    }
+
+    /// Requests shown in the list after applying the filters: bookmarked
+    /// requests first, then the rest, each newest first
+    pub fn filtered_requests(&self) -> Vec<&RequestLog> {
+        self.filtered_list()
+            .indices
+            .iter()
+            .map(|&index| &self.requests[index])
+            .collect()
+    }
+
+    /// The filtered requests and the list rows over them, rebuilt only when
+    /// the requests, filters or grouping changed since the last call
+    pub fn filtered_list(&self) -> Arc<FilteredList> {
+        let key = ListKey {
+            len: self.requests.len(),
+            newest: self.requests.first().map(|req| req.id.clone()),
+            oldest: self.requests.last().map(|req| req.id.clone()),
+            search_query: self.search_query.clone(),
+            bookmarks_only: self.bookmarks_only,
+            bookmarked: self.bookmarked.len(),
+            group_by: self.group_by,
+            expanded_groups: self.expanded_groups.len(),
+            tag_edits: self.tag_edits,
+        };
+        if let Some((cached, list)) = &*self.list_cache.borrow() {
+            if *cached == key {
+                return list.clone();
+            }
+        }
+
+        let mut indices: Vec<usize> = (0..self.requests.len())
+            .filter(|&index| self.is_visible(&self.requests[index]))
+            .collect();
+        if !self.bookmarked.is_empty() {
+            indices.sort_by_key(|&index| !self.is_bookmarked(&self.requests[index]));
+        }
+        let rows = self.build_list_rows(&indices);
+        let list = Arc::new(FilteredList { indices, rows });
+        *self.list_cache.borrow_mut() = Some((key, list.clone()));
+        list
+    }
+
+    /// The request under the cursor in the (filtered) request list; for a
+    /// group, its newest request
+    pub fn selected_request(&self) -> Option<&RequestLog> {
+        let selected = self.table_state.selected()?;
+        let list = self.filtered_list();
+        let index = match list.rows.get(selected)? {
+            ListRow::Request { index, .. } => *index,
+            ListRow::Group {
+                requests: range, ..
+            } => range.start,
+        };
+        self.requests.get(list.indices[index])
+    }
+
+    /// Rows of the request list over the filtered requests at `indices`.
+    /// Without `group_by` there is one per request.
+    fn build_list_rows(&self, indices: &[usize]) -> Vec<ListRow> {
+        let Some(group_by) = self.group_by else {
+            return (0..indices.len())
+                .map(|index| ListRow::Request {
+                    index,
+                    in_group: false,
+                })
+                .collect();
+        };
+
+        let requests: Vec<&RequestLog> = indices.iter().map(|&i| &self.requests[i]).collect();
+        let mut rows = Vec::new();
+        let mut start = 0;
+        while start < requests.len() {
+            let key = group_by.key(requests[start]);
+            let len = requests[start..]
+                .iter()
+                .take_while(|req| group_by.key(req) == key)
+                .count();
+            let end = start + len;
+            if len == 1 {
+                rows.push(ListRow::Request {
+                    index: start,
+                    in_group: false,
+                });
+            } else {
+                let expanded = self.expanded_groups.contains(&requests[end - 1].id);
+                rows.push(ListRow::Group {
+                    key,
+                    requests: start..end,
+                    expanded,
+                });
+                if expanded {
+                    rows.extend((start..end).map(|index| ListRow::Request {
+                        index,
+                        in_group: true,
+                    }));
+                }
+            }
+            start = end;
+        }
+        rows
+    }
+
+    /// Row showing the request with this id, or the collapsed group it is in
+    fn row_of_request(&self, id: &RequestId) -> Option<usize> {
+        let list = self.filtered_list();
+        let position = list
+            .indices
+            .iter()
+            .position(|&index| self.requests[index].id == *id)?;
+        list.rows.iter().position(|row| match row {
+            ListRow::Request { index, .. } => *index == position,
+            ListRow::Group {
+                requests, expanded, ..
+            } => !expanded && requests.contains(&position),
+        })
+    }
+
+    /// Cycle the request list grouping: path, method and path, host, off
+    pub fn cycle_group_by(&mut self) {
+        self.group_by = GroupBy::next(self.group_by);
+        self.expanded_groups.clear();
+        self.reset_selection();
+        self.notification = Some(match self.group_by {
+            Some(group_by) => format!("Grouping requests by {}", group_by.label()),
+            None => "Grouping off".to_string(),
+        });
+        self.notification_level = NotificationLevel::Info;
+    }
+
+    /// Expand the selected group, or collapse the group the selection is in
+    pub fn toggle_group(&mut self) {
+        let Some(selected) = self.table_state.selected() else {
+            return;
+        };
+        let list = self.filtered_list();
+        let rows = &list.rows;
+        let group = match rows.get(selected) {
+            Some(ListRow::Group {
+                requests: range, ..
+            }) => Some(range.clone()),
+            Some(ListRow::Request {
+                index,
+                in_group: true,
+            }) => rows.iter().find_map(|row| match row {
+                ListRow::Group {
+                    requests: range, ..
+                } if range.contains(index) => Some(range.clone()),
+                _ => None,
+            }),
+            _ => None,
+        };
+        let Some(range) = group else {
+            return;
+        };
+        let anchor = self.requests[list.indices[range.end - 1]].id.clone();
+        if !self.expanded_groups.remove(&anchor) {
+            self.expanded_groups.insert(anchor.clone());
+        }
+        let selected = self.row_of_request(&anchor).map(|row| {
+            // An expanded group's own row sits above its oldest request
+            match self.filtered_list().rows[row] {
+                ListRow::Request { in_group: true, .. } => row - (range.end - range.start),
+                _ => row,
+            }
+        });
+        self.table_state.select(selected);
+    }
+
+    pub fn is_bookmarked(&self, req: &RequestLog) -> bool {
+        self.bookmarked.contains(&req.id)
+    }
+
+    /// Whether the search or bookmark filter hides any requests
+    pub fn is_filtered(&self) -> bool {
+        !self.search_query.is_empty() || self.bookmarks_only
+    }
+
+    fn is_visible(&self, req: &RequestLog) -> bool {
+        self.matches_search(req) && (!self.bookmarks_only || self.is_bookmarked(req))
+    }
+
+    fn matches_search(&self, req: &RequestLog) -> bool {
+        if self.search_query.is_empty() {
+            return true;
+        }
+        if let Some(tag) = self.search_query.strip_prefix("tag:") {
+            return req.tags.iter().any(|t| t.eq_ignore_ascii_case(tag.trim()));
+        }
+        format!("{} {}", req.method, req.path)
+            .to_lowercase()
+            .contains(&self.search_query.to_lowercase())
+    }
+
+    /// Scroll the request list so the selection is on screen, and update
+    /// `visible_window` to the rows shown in a list `list_height` lines tall
+    /// plus a margin either side
+    pub fn compute_visible_window(&mut self, list_height: u16) {
+        let count = self.visible_count();
+        let density = self.display_density;
+        let rows = ((list_height as usize)
+            .saturating_sub(REQUEST_LIST_CHROME_HEIGHT + density.header_margin() as usize)
+            / density.row_height() as usize)
+            .max(1);
+
+        let mut offset = self.table_state.offset();
+        if let Some(selected) = self.table_state.selected() {
+            if selected < offset {
+                offset = selected;
+            } else if selected >= offset + rows {
+                offset = selected + 1 - rows;
+            }
+        }
+        let offset = offset.min(count.saturating_sub(rows));
+        *self.table_state.offset_mut() = offset;
+        self.page_rows = rows;
+
+        self.visible_window = offset.saturating_sub(VISIBLE_WINDOW_MARGIN)
+            ..(offset + rows + VISIBLE_WINDOW_MARGIN).min(count);
+    }
+
+    pub fn visible_count(&self) -> usize {
+        if self.group_by.is_some() {
+            self.filtered_list().rows.len()
+        } else if !self.is_filtered() {
+            self.requests.len()
+        } else {
+            self.filtered_list().indices.len()
         }
     }
 
     pub fn next(&mut self) {
-        if self.requests.is_empty() {
+        self.auto_scroll = false;
+        let count = self.visible_count();
+        if count == 0 {
             return;
         }
         let i = match self.table_state.selected() {
             Some(i) => {
-                if i >= self.requests.len() - 1 {
+                if i >= count - 1 {
                     i // Stay at bottom
                 } else {
                     i + 1
@@ -118,7 +976,8 @@ impl App {
     }
 
     pub fn previous(&mut self) {
-        if self.requests.is_empty() {
+        self.auto_scroll = false;
+        if self.visible_count() == 0 {
             return;
         }
         let i = match self.table_state.selected() {
@@ -128,99 +987,1027 @@ impl App {
         self.table_state.select(Some(i));
     }
 
+    pub fn page_down(&mut self) {
+        self.auto_scroll = false;
+        let count = self.visible_count();
+        if count == 0 {
+            return;
+        }
+        let i = match self.table_state.selected() {
+            Some(i) => i + self.page_rows,
+            None => self.page_rows.saturating_sub(1),
+        };
+        self.table_state.select(Some(i.min(count - 1)));
+    }
+
+    pub fn page_up(&mut self) {
+        self.auto_scroll = false;
+        if self.visible_count() == 0 {
+            return;
+        }
+        let i = match self.table_state.selected() {
+            Some(i) => i.saturating_sub(self.page_rows),
+            None => 0,
+        };
+        self.table_state.select(Some(i));
+    }
+
+    /// Clear the text input that currently has focus
+    pub fn clear_input(&mut self) {
+        match self.view_mode {
+            ViewMode::AddTunnel => {
+                match self.add_tunnel_field {
+                    AddTunnelField::Port => self.add_tunnel_port.clear(),
+                    AddTunnelField::Subdomain => self.add_tunnel_subdomain.clear(),
+                    AddTunnelField::Tags => self.add_tunnel_tags.clear(),
+                    AddTunnelField::Headers => self.add_tunnel_headers.clear(),
+                    AddTunnelField::TunnelType => {}
+                }
+                self.add_tunnel_error = None;
+            }
+            ViewMode::SwitchServer => {
+                match self.switch_server_field {
+                    SwitchServerField::Host => self.switch_server_host.clear(),
+                    SwitchServerField::Token => self.switch_server_token.clear(),
+                }
+                self.switch_server_error = None;
+            }
+            ViewMode::UpdateToken => {
+                self.token_input.clear();
+                self.token_error = None;
+            }
+            ViewMode::RequestList if self.tagging => self.tag_input.clear(),
+            ViewMode::RequestList if self.searching => {
+                self.search_query.clear();
+                self.history_cursor = None;
+                self.reset_selection();
+            }
+            ViewMode::RequestDetail => {
+                if let Some((_, note)) = &mut self.editing_note {
+                    note.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+
     pub fn go_to_top(&mut self) {
-        if !self.requests.is_empty() {
+        self.auto_scroll = false;
+        if self.visible_count() > 0 {
             self.table_state.select(Some(0));
         }
     }
 
     pub fn go_to_bottom(&mut self) {
-        if !self.requests.is_empty() {
-            self.table_state.select(Some(self.requests.len() - 1));
+        self.auto_scroll = false;
+        let count = self.visible_count();
+        if count > 0 {
+            self.table_state.select(Some(count - 1));
+        }
+    }
+
+    // Request list search
+    pub fn start_search(&mut self) {
+        self.searching = true;
+        self.history_cursor = None;
+    }
+
+    pub fn search_input_char(&mut self, c: char) {
+        self.search_query.push(c);
+        self.history_cursor = None;
+        self.reset_selection();
+    }
+
+    pub fn search_backspace(&mut self) {
+        self.search_query.pop();
+        self.history_cursor = None;
+        self.reset_selection();
+    }
+
+    /// Keep the current filter and leave the search box
+    pub fn submit_search(&mut self) {
+        self.record_search();
+        self.searching = false;
+        self.history_cursor = None;
+    }
+
+    /// Clear the filter, remembering it in the history first
+    pub fn cancel_search(&mut self) {
+        self.record_search();
+        self.search_query.clear();
+        self.searching = false;
+        self.history_cursor = None;
+        self.reset_selection();
+    }
+
+    /// Step back to an older query in the search history
+    pub fn history_previous(&mut self) {
+        if self.search_history.is_empty() {
+            return;
+        }
+        let i = match self.history_cursor {
+            Some(i) => (i + 1).min(self.search_history.len() - 1),
+            None => 0,
+        };
+        self.history_cursor = Some(i);
+        self.search_query = self.search_history[i].clone();
+        self.reset_selection();
+    }
+
+    /// Step forward to a newer query, ending on an empty search box
+    pub fn history_next(&mut self) {
+        match self.history_cursor {
+            None => return,
+            Some(0) => {
+                self.history_cursor = None;
+                self.search_query.clear();
+            }
+            Some(i) => {
+                self.history_cursor = Some(i - 1);
+                self.search_query = self.search_history[i - 1].clone();
+            }
+        }
+        self.reset_selection();
+    }
+
+    /// Search for requests with the same method and path as the selected one
+    pub fn search_selected(&mut self) {
+        let Some(req) = self.selected_request() else {
+            return;
+        };
+        self.search_query = format!("{} {}", req.method, req.path);
+        self.start_search();
+        self.reset_selection();
+    }
+
+    /// Bookmark the selected request, or remove its bookmark
+    pub fn toggle_bookmark(&mut self) {
+        let Some(id) = self.selected_request().map(|req| req.id.clone()) else {
+            return;
+        };
+        if !self.bookmarked.remove(&id) {
+            self.bookmarked.insert(id.clone());
+        }
+
+        // Follow the request to its new position, if it is still shown
+        let count = self.visible_count();
+        let selected = match self.row_of_request(&id) {
+            Some(i) => Some(i),
+            None if count == 0 => None,
+            None => self.table_state.selected().map(|i| i.min(count - 1)),
+        };
+        self.table_state.select(selected);
+    }
+
+    /// Open the tag editor for the selected request
+    pub fn start_tagging(&mut self) {
+        let Some(req) = self.selected_request() else {
+            return;
+        };
+        self.tag_input = req.tags.join(", ");
+        self.tagging = true;
+    }
+
+    pub fn tag_input_char(&mut self, c: char) {
+        self.tag_input.push(c);
+    }
+
+    pub fn tag_backspace(&mut self) {
+        self.tag_input.pop();
+    }
+
+    pub fn cancel_tagging(&mut self) {
+        self.tag_input.clear();
+        self.tagging = false;
+    }
+
+    /// Replace the selected request's tags with the comma-separated input
+    pub fn submit_tags(&mut self) {
+        self.tagging = false;
+        let Some(id) = self.selected_request().map(|req| req.id.clone()) else {
+            return;
+        };
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tag_input.split(',').map(str::trim) {
+            if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        self.tag_input.clear();
+
+        if let Some(req) = self.requests.iter_mut().find(|req| req.id == id) {
+            req.tags = tags.clone();
+            self.tag_edits += 1;
+        }
+        if tags.is_empty() {
+            self.request_tags.remove(&id);
+        } else {
+            self.request_tags.insert(id, tags);
+        }
+    }
+
+    /// Open the note editor for the request shown in the detail view
+    pub fn start_note(&mut self) {
+        let Some(id) = self.selected_request().map(|req| req.id.clone()) else {
+            return;
+        };
+        let note = self.request_notes.get(&id).cloned().unwrap_or_default();
+        self.editing_note = Some((id, note));
+    }
+
+    pub fn note_input_char(&mut self, c: char) {
+        if let Some((_, note)) = &mut self.editing_note {
+            note.push(c);
+        }
+    }
+
+    pub fn note_backspace(&mut self) {
+        if let Some((_, note)) = &mut self.editing_note {
+            note.pop();
+        }
+    }
+
+    pub fn cancel_note(&mut self) {
+        self.editing_note = None;
+    }
+
+    /// Save the note being edited; an empty note removes it
+    pub fn submit_note(&mut self) {
+        let Some((id, note)) = self.editing_note.take() else {
+            return;
+        };
+        let note = note.trim();
+        if note.is_empty() {
+            self.request_notes.remove(&id);
+        } else {
+            self.request_notes.insert(id, note.to_string());
+        }
+    }
+
+    pub fn note(&self, req: &RequestLog) -> Option<&str> {
+        self.request_notes.get(&req.id).map(String::as_str)
+    }
+
+    pub fn toggle_bookmarks_only(&mut self) {
+        self.bookmarks_only = !self.bookmarks_only;
+        self.reset_selection();
+    }
+
+    /// Requests (after filtering) that overlap the timeline window, newest first
+    pub fn timeline_requests(&self, now: DateTime<Local>) -> Vec<&RequestLog> {
+        let window_start = now - chrono::Duration::seconds(TIMELINE_WINDOW_SECS);
+        self.requests
+            .iter()
+            .filter(|req| self.is_visible(req))
+            .filter(|req| {
+                // Requests still in flight reach up to now
+                let end = req.duration_ms.map_or(now, |ms| {
+                    req.timestamp + chrono::Duration::milliseconds(ms as i64)
+                });
+                end >= window_start
+            })
+            .take(TIMELINE_MAX_REQUESTS)
+            .collect()
+    }
+
+    pub fn enter_timeline(&mut self) {
+        self.timeline_selected = self
+            .timeline_requests(Local::now())
+            .first()
+            .map(|req| req.id.clone());
+        self.view_mode = ViewMode::Timeline;
+    }
+
+    /// Move the timeline highlight by `offset` rows, staying within the window
+    pub fn timeline_move(&mut self, offset: isize) {
+        let requests = self.timeline_requests(Local::now());
+        if requests.is_empty() {
+            self.timeline_selected = None;
+            return;
+        }
+        let current = self
+            .timeline_selected
+            .as_ref()
+            .and_then(|id| requests.iter().position(|req| req.id == *id));
+        let i = match current {
+            Some(i) => i.saturating_add_signed(offset).min(requests.len() - 1),
+            None => 0,
+        };
+        self.timeline_selected = Some(requests[i].id.clone());
+    }
+
+    fn record_search(&mut self) {
+        let query = self.search_query.trim();
+        if query.is_empty() {
+            return;
+        }
+        let query = query.to_string();
+        self.search_history.retain(|q| *q != query);
+        self.search_history.push_front(query);
+        self.search_history.truncate(history::MAX_SEARCH_HISTORY);
+    }
+
+    fn reset_selection(&mut self) {
+        let selected = if self.visible_count() > 0 {
+            Some(0)
+        } else {
+            None
+        };
+        self.table_state.select(selected);
+    }
+
+    /// Toggle following new requests, jumping to the newest when turned on
+    pub fn toggle_auto_scroll(&mut self) {
+        self.auto_scroll = !self.auto_scroll;
+        if self.auto_scroll {
+            let requests = self.filtered_requests();
+            let newest = requests
+                .iter()
+                .find(|req| !self.is_bookmarked(req))
+                .and_then(|req| self.row_of_request(&req.id));
+            if let Some(newest) = newest {
+                self.table_state.select(Some(newest));
+            }
+        }
+    }
+
+    pub fn toggle_time_display(&mut self) {
+        self.time_display = self.time_display.next();
+    }
+
+    pub fn toggle_display_density(&mut self) {
+        self.display_density = self.display_density.next();
+    }
+
+    /// Move the column selection for resizing left (`-1`) or right (`1`)
+    pub fn select_column(&mut self, delta: isize) {
+        let last = self.request_list_columns.len().saturating_sub(1);
+        self.selected_column = self.selected_column.saturating_add_signed(delta).min(last);
+    }
+
+    /// Widen (or with a negative `delta`, narrow) the selected column and
+    /// save the layout
+    pub fn resize_selected_column(&mut self, delta: i16) {
+        let Some(column) = self.request_list_columns.get(self.selected_column) else {
+            return;
+        };
+        let adjustment = self
+            .column_width_adjustments
+            .entry(column.column)
+            .or_default();
+        *adjustment = adjustment.saturating_add(delta);
+        if *adjustment == 0 {
+            self.column_width_adjustments.remove(&column.column);
+        }
+        self.save_layout();
+    }
+
+    /// Put every column back to its configured width and save the layout
+    pub fn reset_column_widths(&mut self) {
+        self.column_width_adjustments.clear();
+        self.save_layout();
+    }
+
+    /// Ask the connection to start reconnecting after it gave up
+    pub fn retry_connection(&mut self) {
+        if self.cmd_tx.try_send(TuiCommand::Retry).is_ok() {
+            self.notification = Some("Retrying connection…".to_string());
+            self.notification_level = NotificationLevel::Info;
+        } else {
+            self.notification = Some("Could not retry: the connection has stopped".to_string());
+            self.notification_level = NotificationLevel::Error;
+        }
+    }
+
+    /// Whether keys are going to a text input
+    fn is_typing(&self) -> bool {
+        matches!(
+            self.view_mode,
+            ViewMode::AddTunnel | ViewMode::SwitchServer | ViewMode::UpdateToken
+        ) || self.searching
+            || self.tagging
+            || self.editing_note.is_some()
+            || self.tunnel_path_input.is_some()
+    }
+
+    /// Handle `Q` and `@` and record keys while a macro is being recorded.
+    /// Returns whether the key was used up.
+    pub fn handle_macro_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(prefix) = self.macro_prefix.take() {
+            self.macro_register(prefix, key);
+            return true;
+        }
+        if !self.is_typing() && !key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('Q') if self.recording_macro.is_some() => {
+                    self.stop_macro();
+                    return true;
+                }
+                KeyCode::Char(c @ ('Q' | '@')) => {
+                    self.macro_prefix = Some(c);
+                    return true;
+                }
+                _ => {}
+            }
+        }
+        self.record_macro_key(key);
+        false
+    }
+
+    fn record_macro_key(&mut self, key: KeyEvent) {
+        if self.recording_macro.is_some() && !self.replaying_macro {
+            self.macro_buffer.push(key);
         }
     }
 
+    /// Finish `Q{register}` or `@{register}`
+    fn macro_register(&mut self, prefix: char, key: KeyEvent) {
+        let register = match key.code {
+            KeyCode::Esc => return,
+            KeyCode::Char('@') if prefix == '@' => match self.last_macro {
+                Some(register) => register,
+                None => {
+                    self.notification = Some("No macro recorded yet".to_string());
+                    self.notification_level = NotificationLevel::Warn;
+                    return;
+                }
+            },
+            KeyCode::Char(c) if c.is_ascii_alphanumeric() => c,
+            _ => {
+                self.notification = Some("Macro registers are letters and digits".to_string());
+                self.notification_level = NotificationLevel::Warn;
+                return;
+            }
+        };
+        if prefix == 'Q' {
+            self.start_macro(register);
+        } else {
+            self.record_macro_key(KeyEvent::new(KeyCode::Char('@'), KeyModifiers::NONE));
+            self.record_macro_key(key);
+            self.replay_macro(register);
+        }
+    }
+
+    fn start_macro(&mut self, register: char) {
+        if !self.named_macros.contains_key(&register) && self.named_macros.len() >= MAX_NAMED_MACROS
+        {
+            self.notification = Some(format!(
+                "Up to {} macros can be kept; record an empty one to free a register",
+                MAX_NAMED_MACROS
+            ));
+            self.notification_level = NotificationLevel::Warn;
+            return;
+        }
+        self.recording_macro = Some(register);
+        self.macro_buffer.clear();
+        self.notification = Some(format!("Recording macro '{}' (Q to stop)", register));
+        self.notification_level = NotificationLevel::Info;
+    }
+
+    /// Stop recording and keep the macro; an empty macro clears its register
+    fn stop_macro(&mut self) {
+        let Some(register) = self.recording_macro.take() else {
+            return;
+        };
+        let keys = std::mem::take(&mut self.macro_buffer);
+        if keys.is_empty() {
+            self.named_macros.remove(&register);
+            self.notification = Some(format!("Cleared macro '{}'", register));
+        } else {
+            let unsaved = keys.iter().filter(|key| !macros::can_save(key)).count();
+            self.notification = Some(if unsaved > 0 {
+                format!(
+                    "Recorded macro '{}' ({} keys, @{} to replay); {} of them can't be saved for the next session",
+                    register,
+                    keys.len(),
+                    register,
+                    unsaved
+                )
+            } else {
+                format!(
+                    "Recorded macro '{}' ({} keys, @{} to replay)",
+                    register,
+                    keys.len(),
+                    register
+                )
+            });
+            self.named_macros.insert(register, keys);
+            self.last_macro = Some(register);
+        }
+        self.notification_level = NotificationLevel::Info;
+    }
+
+    /// Queue a macro's keys ahead of any left from the macro replaying it
+    fn replay_macro(&mut self, register: char) {
+        let Some(keys) = self.named_macros.get(&register) else {
+            self.notification = Some(format!("No macro in register '{}'", register));
+            self.notification_level = NotificationLevel::Warn;
+            return;
+        };
+        if !self.replaying_macro {
+            self.macro_keys_replayed = 0;
+        }
+        if self.macro_keys_replayed + self.macro_queue.len() + keys.len() > MAX_MACRO_REPLAY_KEYS {
+            self.macro_queue.clear();
+            self.notification = Some(format!("Macro '{}' stopped: it replays itself", register));
+            self.notification_level = NotificationLevel::Error;
+            return;
+        }
+        for key in keys.iter().rev() {
+            self.macro_queue.push_front(*key);
+        }
+        if !self.replaying_macro {
+            self.next_macro_key_at = None;
+        }
+    }
+
+    /// The next key of a macro being replayed, once it is due
+    pub fn next_macro_key(&mut self) -> Option<KeyEvent> {
+        if self.next_macro_key_at.is_some_and(|at| Instant::now() < at) {
+            return None;
+        }
+        let key = self.macro_queue.pop_front()?;
+        self.macro_keys_replayed += 1;
+        self.next_macro_key_at =
+            (!self.macro_replay_delay.is_zero()).then(|| Instant::now() + self.macro_replay_delay);
+        Some(key)
+    }
+
+    /// Save the layout once the user stops resizing
+    fn save_layout(&mut self) {
+        self.layout_save_due = Some(Instant::now() + LAYOUT_SAVE_DELAY);
+    }
+
+    /// The column widths to save, if they changed and resizing has settled
+    /// by `now`
+    fn take_due_layout(&mut self, now: Instant) -> Option<BTreeMap<RequestListColumn, i16>> {
+        self.layout_save_due.take_if(|due| *due <= now)?;
+        Some(self.column_width_adjustments.clone())
+    }
+
+    /// Write the column widths to the config file, if they are due
+    fn write_due_layout(&mut self, now: Instant) {
+        let Some(column_width_adjustments) = self.take_due_layout(now) else {
+            return;
+        };
+        let saved = Config::load().and_then(|mut config| {
+            config.tui.column_width_adjustments = column_width_adjustments;
+            config.save()
+        });
+        match saved {
+            Ok(()) => {
+                self.notification = Some("[Layout saved]".to_string());
+                self.notification_level = NotificationLevel::Info;
+            }
+            Err(e) => {
+                warn!("Failed to save layout: {:#}", e);
+                self.notification = Some(format!("Failed to save layout: {:#}", e));
+                self.notification_level = NotificationLevel::Error;
+            }
+        }
+    }
+
+    /// Constraints for the request list columns in `available` characters:
+    /// the configured widths plus adjustments, at least
+    /// [`MIN_COLUMN_WIDTH`] and leaving room for the other columns
+    pub fn request_column_widths(&self, available: u16) -> Vec<Constraint> {
+        let widths: Vec<u16> = self
+            .request_list_columns
+            .iter()
+            .map(|c| {
+                let base = c.width.unwrap_or(FILL_COLUMN_MIN_WIDTH);
+                let adjustment = self
+                    .column_width_adjustments
+                    .get(&c.column)
+                    .copied()
+                    .unwrap_or(0);
+                base.saturating_add_signed(adjustment).max(MIN_COLUMN_WIDTH)
+            })
+            .collect();
+        let total: u16 = widths.iter().fold(0, |sum, w| sum.saturating_add(*w));
+
+        self.request_list_columns
+            .iter()
+            .zip(widths)
+            .map(|(c, width)| {
+                let others = total - width;
+                let width = width.min(available.saturating_sub(others).max(MIN_COLUMN_WIDTH));
+                match c.width {
+                    Some(_) => Constraint::Length(width),
+                    None => Constraint::Min(width),
+                }
+            })
+            .collect()
+    }
+
+    pub fn toggle_raw_detail(&mut self) {
+        self.detail_tab = match self.detail_tab {
+            DetailTab::Overview => DetailTab::Raw,
+            DetailTab::Raw => DetailTab::Overview,
+        };
+    }
+
     pub fn enter_request_detail(&mut self) {
-        if self.table_state.selected().is_some() {
+        if self.selected_request().is_some() {
             self.view_mode = ViewMode::RequestDetail;
         }
     }
 
+    /// Pick the selected request as the first side of a diff
+    pub fn start_diff(&mut self) {
+        self.diff_base = self.selected_request().map(|req| req.id.clone());
+    }
+
+    pub fn cancel_diff(&mut self) {
+        self.diff_base = None;
+        self.diff_target = None;
+    }
+
+    /// Compare the selected request against the one picked with `start_diff`
+    pub fn open_diff(&mut self) {
+        let Some(target) = self.selected_request().map(|req| req.id.clone()) else {
+            return;
+        };
+        if self.diff_base.as_ref() == Some(&target) {
+            return;
+        }
+        self.diff_target = Some(target);
+        self.diff_scroll = 0;
+        self.view_mode = ViewMode::RequestDiff;
+    }
+
+    /// The two requests being compared, if both are still logged
+    pub fn diff_requests(&self) -> Option<(&RequestLog, &RequestLog)> {
+        let find = |id: &Option<RequestId>| {
+            let id = id.as_ref()?;
+            self.requests.iter().find(|req| &req.id == id)
+        };
+        Some((find(&self.diff_base)?, find(&self.diff_target)?))
+    }
+
+    pub fn diff_scroll_by(&mut self, lines: i32) {
+        self.diff_scroll = self.diff_scroll.saturating_add_signed(lines as i16);
+    }
+
     pub fn back(&mut self) {
+        if self.view_mode == ViewMode::RequestDiff {
+            self.cancel_diff();
+        }
         self.view_mode = match self.view_mode {
             ViewMode::RequestDetail => ViewMode::RequestList,
             ViewMode::RequestList => ViewMode::TunnelList,
             ViewMode::AddTunnel => ViewMode::TunnelList,
             ViewMode::TunnelList => ViewMode::TunnelList,
+            ViewMode::ErrorLog => self.view_before_error_log,
+            ViewMode::ConnectionHistory => ViewMode::TunnelList,
+            ViewMode::SwitchServer => ViewMode::TunnelList,
+            ViewMode::Timeline => ViewMode::RequestList,
+            ViewMode::RequestDiff => ViewMode::RequestList,
+            ViewMode::UpdateToken => self.view_before_token_prompt,
+            ViewMode::TunnelStats => self.view_before_tunnel_stats,
         };
     }
 
+    pub fn enter_tunnel_stats(&mut self) {
+        self.view_before_tunnel_stats = self.view_mode;
+        self.view_mode = ViewMode::TunnelStats;
+    }
+
+    pub fn enter_error_log(&mut self) {
+        self.view_before_error_log = self.view_mode;
+        self.unseen_errors = 0;
+        self.view_mode = ViewMode::ErrorLog;
+    }
+
+    /// Empty the request list, keeping up to `undo_clear_limit` of the
+    /// newest requests for `undo_clear`
     pub fn clear(&mut self) {
-        self.requests.clear();
+        if !self.requests.is_empty() {
+            self.pre_clear_position = self.table_state.selected();
+            let mut cleared = std::mem::take(&mut self.requests);
+            cleared.truncate(self.undo_clear_limit);
+            self.undo_clear = (!cleared.is_empty()).then_some(cleared);
+        }
         self.table_state.select(None);
+        self.cancel_diff();
+    }
+
+    /// Forget the bookmarks, tags and notes of requests that are gone from
+    /// the list for good, so the saved files don't grow with every session
+    pub fn prune_annotations(&mut self) {
+        let known: HashSet<&RequestId> = self
+            .requests
+            .iter()
+            .chain(self.undo_clear.iter().flatten())
+            .map(|req| &req.id)
+            .collect();
+        self.bookmarked.retain(|id| known.contains(id));
+        self.request_tags.retain(|id, _| known.contains(id));
+        self.request_notes.retain(|id, _| known.contains(id));
+    }
+
+    /// Bring back the requests removed by the last `clear`, below any that
+    /// arrived since
+    pub fn undo_clear(&mut self) {
+        let Some(cleared) = self.undo_clear.take() else {
+            self.notification = Some("Nothing to restore".to_string());
+            self.notification_level = NotificationLevel::Info;
+            return;
+        };
+        let restored = cleared.len();
+        let selected = self.pre_clear_position.take().unwrap_or(0);
+        self.requests.extend(cleared);
+        self.requests.truncate(self.max_requests);
+
+        let count = self.visible_count();
+        self.table_state
+            .select((count > 0).then(|| selected.min(count - 1)));
+        self.notification = Some(format!(
+            "Restored {} request{}",
+            restored,
+            if restored == 1 { "" } else { "s" }
+        ));
+        self.notification_level = NotificationLevel::Info;
+    }
+
+    // Tunnel list navigation
+    pub fn tunnel_next(&mut self) {
+        let total = self.tunnels.len() + self.tcp_tunnels.len();
+        if total == 0 {
+            return;
+        }
+        let i = match self.tunnel_list_state.selected() {
+            Some(i) => {
+                if i >= total - 1 {
+                    i
+                } else {
+                    i + 1
+                }
+            }
+            None => 0,
+        };
+        self.tunnel_list_state.select(Some(i));
+    }
+
+    pub fn tunnel_previous(&mut self) {
+        let total = self.tunnels.len() + self.tcp_tunnels.len();
+        if total == 0 {
+            return;
+        }
+        let i = match self.tunnel_list_state.selected() {
+            Some(i) => i.saturating_sub(1),
+            None => 0,
+        };
+        self.tunnel_list_state.select(Some(i));
+    }
+
+    /// Show the connection history, asking the connection for a fresh
+    /// snapshot of its state to show alongside
+    pub fn enter_connection_history(&mut self) {
+        // Read-only, so it skips the command cooldown; without a connection
+        // the last snapshot is kept
+        let _ = self.cmd_tx.try_send(TuiCommand::RequestSnapshot);
+        self.view_mode = ViewMode::ConnectionHistory;
+    }
+
+    pub fn enter_add_tunnel(&mut self) {
+        self.add_tunnel_type = TunnelType::Http;
+        self.add_tunnel_port.clear();
+        self.add_tunnel_subdomain.clear();
+        self.add_tunnel_tags.clear();
+        self.add_tunnel_headers.clear();
+        self.add_tunnel_field = AddTunnelField::Port;
+        self.add_tunnel_error = None;
+        self.view_mode = ViewMode::AddTunnel;
+    }
+
+    /// Switch between the tunnel list and request list, keeping each one's selection
+    pub fn toggle_view(&mut self) {
+        match self.view_mode {
+            ViewMode::TunnelList => {
+                self.last_tunnel_list_selection = self.tunnel_list_state.selected();
+                self.view_mode = ViewMode::RequestList;
+                let count = self.visible_count();
+                let selected = match self.last_request_list_selection {
+                    _ if count == 0 => None,
+                    Some(i) => Some(i.min(count - 1)),
+                    None => Some(0),
+                };
+                self.table_state.select(selected);
+            }
+            ViewMode::RequestList => {
+                self.last_request_list_selection = self.table_state.selected();
+                self.view_mode = ViewMode::TunnelList;
+                let total = self.tunnels.len() + self.tcp_tunnels.len();
+                let selected = self
+                    .last_tunnel_list_selection
+                    .filter(|_| total > 0)
+                    .map(|i| i.min(total - 1));
+                self.tunnel_list_state.select(selected);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn view_tunnel_requests(&mut self) {
+        // Switch to request list view
+        self.view_mode = ViewMode::RequestList;
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        matches!(
+            self.connection_status,
+            ConnectionStatus::Disconnected { .. }
+        )
+    }
+
+    pub fn is_reconnecting(&self) -> bool {
+        matches!(
+            self.connection_status,
+            ConnectionStatus::Reconnecting { .. }
+        )
+    }
+
+    /// Seconds left until the next reconnect attempt, rounded up, if one is scheduled
+    pub fn reconnect_countdown(&self) -> Option<u64> {
+        self.reconnect_deadline.map(|deadline| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            (remaining.as_millis() as u64).div_ceil(1000)
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        matches!(self.connection_status, ConnectionStatus::Connected)
+    }
+
+    /// Whether the TUI is running without a server connection, showing
+    /// imported requests or those of a local proxy
+    pub fn is_offline(&self) -> bool {
+        matches!(
+            self.connection_status,
+            ConnectionStatus::Offline { .. } | ConnectionStatus::LocalProxy { .. }
+        )
+    }
+
+    /// The selected tunnel's public URL, or for a TCP tunnel the command
+    /// that connects to it
+    fn selected_tunnel_address(&self) -> Option<TunnelAddress> {
+        let i = self.tunnel_list_state.selected()?;
+        if let Some(tunnel) = self.tunnels.get(i) {
+            return Some(TunnelAddress::Url(tunnel.full_url.clone()));
+        }
+        let tcp = self.tcp_tunnels.get(i - self.tunnels.len())?;
+        Some(TunnelAddress::Command(format!(
+            "ssh -p {} user@{}",
+            tcp.server_port, tcp.server_host
+        )))
+    }
+
+    fn open_url(&mut self, url: String) {
+        match open::that_detached(&url) {
+            Ok(()) => {
+                self.notification = Some("Opening browser…".to_string());
+                self.notification_level = NotificationLevel::Info;
+            }
+            Err(e) => {
+                warn!("Failed to open {}: {}", url, e);
+                self.popup = Some((
+                    "Open in browser".to_string(),
+                    vec![
+                        format!("Could not open a browser: {}", e),
+                        String::new(),
+                        url,
+                        String::new(),
+                        "Press y in the tunnel list to copy it, or hold Shift \
+                         to select it with the mouse."
+                            .to_string(),
+                    ],
+                ));
+            }
+        }
+    }
+
+    /// Handle a left click: tunnel URLs in the status bar open in the
+    /// browser, and in the request list a click selects the row under it and
+    /// a double click opens it
+    pub fn click(&mut self, column: u16, row: u16) {
+        let position = Position::new(column, row);
+        if let Some((_, url)) = self
+            .status_bar_links
+            .iter()
+            .find(|(area, _)| area.contains(position))
+        {
+            self.open_url(url.clone());
+            return;
+        }
+        if self.view_mode != ViewMode::RequestList {
+            return;
+        }
+        let Some(index) = self.request_row_at(row) else {
+            return;
+        };
+
+        self.auto_scroll = false;
+        self.table_state.select(Some(index));
+        let now = Instant::now();
+        let double_click = self.last_click.is_some_and(|(at, last)| {
+            last == index && now.duration_since(at) <= DOUBLE_CLICK_INTERVAL
+        });
+        if double_click {
+            self.last_click = None;
+            if self.diff_base.is_some() {
+                self.open_diff();
+            } else {
+                self.enter_request_detail();
+            }
+        } else {
+            self.last_click = Some((now, index));
+        }
+    }
+
+    /// Position in the list's rows of the request list row drawn at screen row `y`
+    fn request_row_at(&self, y: u16) -> Option<usize> {
+        self.row_y_positions
+            .windows(2)
+            .position(|rows| (rows[0]..rows[1]).contains(&y))
+            .map(|i| self.table_state.offset() + i)
+    }
+
+    /// Open the selected HTTP tunnel's URL, with `path` appended, in the
+    /// browser; for a TCP tunnel, show how to connect to it instead
+    pub fn inspect_subdomain(&mut self, path: &str) {
+        match self.selected_tunnel_address() {
+            Some(TunnelAddress::Url(url)) => {
+                let url = match path.trim_start_matches('/') {
+                    "" => url,
+                    path => format!("{}/{}", url.trim_end_matches('/'), path),
+                };
+                self.open_url(url);
+            }
+            Some(TunnelAddress::Command(command)) => {
+                self.popup = Some((
+                    "Connect".to_string(),
+                    vec![
+                        "TCP tunnels can't be opened in a browser; connect with:".to_string(),
+                        String::new(),
+                        command,
+                    ],
+                ));
+            }
+            None => {}
+        }
     }
 
-    // Tunnel list navigation
-    pub fn tunnel_next(&mut self) {
-        let total = self.tunnels.len() + self.tcp_tunnels.len();
-        if total == 0 {
+    /// Copy the selected tunnel's URL (or connect command) to the clipboard
+    pub fn copy_url(&mut self) {
+        let Some(address) = self.selected_tunnel_address() else {
             return;
-        }
-        let i = match self.tunnel_list_state.selected() {
-            Some(i) => {
-                if i >= total - 1 {
-                    i
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
         };
-        self.tunnel_list_state.select(Some(i));
+        let (TunnelAddress::Url(text) | TunnelAddress::Command(text)) = address;
+        match copy_to_clipboard(&text) {
+            Ok(()) => {
+                self.notification = Some(format!("Copied {}", text));
+                self.notification_level = NotificationLevel::Info;
+            }
+            Err(e) => {
+                self.notification = Some(format!("Failed to copy: {}", e));
+                self.notification_level = NotificationLevel::Error;
+            }
+        }
     }
 
-    pub fn tunnel_previous(&mut self) {
-        let total = self.tunnels.len() + self.tcp_tunnels.len();
-        if total == 0 {
-            return;
+    pub fn start_tunnel_path(&mut self) {
+        if matches!(self.selected_tunnel_address(), Some(TunnelAddress::Url(_))) {
+            self.tunnel_path_input = Some("/".to_string());
         }
-        let i = match self.tunnel_list_state.selected() {
-            Some(i) => i.saturating_sub(1),
-            None => 0,
-        };
-        self.tunnel_list_state.select(Some(i));
     }
 
-    pub fn enter_add_tunnel(&mut self) {
-        self.add_tunnel_type = TunnelType::Http;
-        self.add_tunnel_port.clear();
-        self.add_tunnel_subdomain.clear();
-        self.add_tunnel_field = AddTunnelField::Port;
-        self.add_tunnel_error = None;
-        self.view_mode = ViewMode::AddTunnel;
+    pub fn tunnel_path_input_char(&mut self, c: char) {
+        if let Some(path) = &mut self.tunnel_path_input {
+            path.push(c);
+        }
     }
 
-    pub fn view_tunnel_requests(&mut self) {
-        // Switch to request list view
-        self.view_mode = ViewMode::RequestList;
+    pub fn tunnel_path_backspace(&mut self) {
+        if let Some(path) = &mut self.tunnel_path_input {
+            path.pop();
+        }
     }
 
-    pub fn is_disconnected(&self) -> bool {
-        matches!(
-            self.connection_status,
-            ConnectionStatus::Disconnected { .. }
-        )
+    pub fn cancel_tunnel_path(&mut self) {
+        self.tunnel_path_input = None;
     }
 
-    pub fn is_reconnecting(&self) -> bool {
-        matches!(
-            self.connection_status,
-            ConnectionStatus::Reconnecting { .. }
-        )
+    pub fn submit_tunnel_path(&mut self) {
+        if let Some(path) = self.tunnel_path_input.take() {
+            self.inspect_subdomain(&path);
+        }
     }
 
-    pub fn is_connected(&self) -> bool {
-        matches!(self.connection_status, ConnectionStatus::Connected)
+    fn notify_read_only(&mut self) {
+        self.notification = Some("Not available without a Burrow server".to_string());
+        self.notification_level = NotificationLevel::Warn;
     }
 
     // Add tunnel form navigation
@@ -234,7 +2021,9 @@ impl App {
                     AddTunnelField::TunnelType
                 }
             }
-            AddTunnelField::Subdomain => AddTunnelField::TunnelType,
+            AddTunnelField::Subdomain => AddTunnelField::Tags,
+            AddTunnelField::Tags => AddTunnelField::Headers,
+            AddTunnelField::Headers => AddTunnelField::TunnelType,
         };
     }
 
@@ -242,13 +2031,15 @@ impl App {
         self.add_tunnel_field = match self.add_tunnel_field {
             AddTunnelField::TunnelType => {
                 if self.add_tunnel_type == TunnelType::Http {
-                    AddTunnelField::Subdomain
+                    AddTunnelField::Headers
                 } else {
                     AddTunnelField::Port
                 }
             }
             AddTunnelField::Port => AddTunnelField::TunnelType,
             AddTunnelField::Subdomain => AddTunnelField::Port,
+            AddTunnelField::Tags => AddTunnelField::Subdomain,
+            AddTunnelField::Headers => AddTunnelField::Tags,
         };
     }
 
@@ -257,11 +2048,16 @@ impl App {
             TunnelType::Http => TunnelType::Tcp,
             TunnelType::Tcp => TunnelType::Http,
         };
-        // Clear subdomain when switching to TCP
+        // Clear HTTP-only fields when switching to TCP
         if self.add_tunnel_type == TunnelType::Tcp {
             self.add_tunnel_subdomain.clear();
-            // If on subdomain field, move to port
-            if self.add_tunnel_field == AddTunnelField::Subdomain {
+            self.add_tunnel_tags.clear();
+            self.add_tunnel_headers.clear();
+            // If on an HTTP-only field, move to port
+            if matches!(
+                self.add_tunnel_field,
+                AddTunnelField::Subdomain | AddTunnelField::Tags | AddTunnelField::Headers
+            ) {
                 self.add_tunnel_field = AddTunnelField::Port;
             }
         }
@@ -279,6 +2075,18 @@ impl App {
                     self.add_tunnel_subdomain.push(c.to_ascii_lowercase());
                 }
             }
+            AddTunnelField::Tags => {
+                if (c.is_ascii_alphanumeric() || matches!(c, '-' | ':' | ',' | ' '))
+                    && self.add_tunnel_tags.len() < 256
+                {
+                    self.add_tunnel_tags.push(c.to_ascii_lowercase());
+                }
+            }
+            AddTunnelField::Headers => {
+                if !c.is_control() && self.add_tunnel_headers.len() < 2048 {
+                    self.add_tunnel_headers.push(c);
+                }
+            }
             AddTunnelField::TunnelType => {
                 // Space or enter toggles type
             }
@@ -294,11 +2102,30 @@ impl App {
             AddTunnelField::Subdomain => {
                 self.add_tunnel_subdomain.pop();
             }
+            AddTunnelField::Tags => {
+                self.add_tunnel_tags.pop();
+            }
+            AddTunnelField::Headers => {
+                self.add_tunnel_headers.pop();
+            }
             AddTunnelField::TunnelType => {}
         }
         self.add_tunnel_error = None;
     }
 
+    /// Enter in the headers field starts a new line; on an empty line it
+    /// submits the form
+    pub async fn form_enter(&mut self) {
+        if self.add_tunnel_field == AddTunnelField::Headers
+            && !self.add_tunnel_headers.is_empty()
+            && !self.add_tunnel_headers.ends_with('\n')
+        {
+            self.add_tunnel_headers.push('\n');
+            return;
+        }
+        self.form_submit().await;
+    }
+
     pub async fn form_submit(&mut self) {
         // Validate port
         let port: u16 = match self.add_tunnel_port.parse() {
@@ -317,16 +2144,32 @@ impl App {
                 } else {
                     Some(self.add_tunnel_subdomain.clone())
                 };
+                let tags = match parse_tag_list(&self.add_tunnel_tags) {
+                    Ok(tags) => tags,
+                    Err(e) => {
+                        self.add_tunnel_error = Some(e);
+                        return;
+                    }
+                };
+                let extra_headers = match parse_header_lines(&self.add_tunnel_headers) {
+                    Ok(headers) => headers,
+                    Err(e) => {
+                        self.add_tunnel_error = Some(e);
+                        return;
+                    }
+                };
                 TuiCommand::AddHttpTunnel {
                     local_port: port,
                     subdomain,
+                    extra_headers,
+                    tags,
                 }
             }
             TunnelType::Tcp => TuiCommand::AddTcpTunnel { local_port: port },
         };
 
-        if self.cmd_tx.send(cmd).await.is_err() {
-            self.add_tunnel_error = Some("Failed to send command".to_string());
+        if let Err(e) = self.send_command(cmd).await {
+            self.add_tunnel_error = Some(e);
             return;
         }
 
@@ -334,6 +2177,212 @@ impl App {
         self.view_mode = ViewMode::TunnelList;
     }
 
+    pub fn enter_switch_server(&mut self) {
+        self.switch_server_host.clear();
+        self.switch_server_token.clear();
+        self.switch_server_field = SwitchServerField::Host;
+        self.switch_server_error = None;
+        self.view_mode = ViewMode::SwitchServer;
+    }
+
+    pub fn switch_server_next_field(&mut self) {
+        self.switch_server_field = match self.switch_server_field {
+            SwitchServerField::Host => SwitchServerField::Token,
+            SwitchServerField::Token => SwitchServerField::Host,
+        };
+    }
+
+    pub fn switch_server_input_char(&mut self, c: char) {
+        if c.is_whitespace() {
+            return;
+        }
+        match self.switch_server_field {
+            SwitchServerField::Host => self.switch_server_host.push(c),
+            SwitchServerField::Token => self.switch_server_token.push(c),
+        }
+        self.switch_server_error = None;
+    }
+
+    pub fn switch_server_backspace(&mut self) {
+        match self.switch_server_field {
+            SwitchServerField::Host => self.switch_server_host.pop(),
+            SwitchServerField::Token => self.switch_server_token.pop(),
+        };
+        self.switch_server_error = None;
+    }
+
+    pub async fn switch_server_submit(&mut self) {
+        if self.switch_server_host.is_empty() {
+            self.switch_server_error = Some("Server host is required".to_string());
+            return;
+        }
+
+        let new_host = self.switch_server_host.clone();
+        let new_token = if self.switch_server_token.is_empty() {
+            None
+        } else {
+            Some(self.switch_server_token.clone())
+        };
+        let cmd = TuiCommand::SetServerHost {
+            new_host: new_host.clone(),
+            new_token,
+        };
+
+        if let Err(e) = self.send_command(cmd).await {
+            self.switch_server_error = Some(e);
+            return;
+        }
+
+        // Tunnels belong to the old server and have to be added again
+        self.tunnels.clear();
+        self.tcp_tunnels.clear();
+        self.ports_before_reconnect.clear();
+        self.tunnel_errors.clear();
+        self.tunnel_list_state.select(None);
+        self.view_mode = ViewMode::TunnelList;
+        self.notification = Some(format!("Switching to {}...", new_host));
+        self.notification_level = NotificationLevel::Info;
+    }
+
+    /// Open the token prompt on top of the current view
+    fn enter_token_prompt(&mut self) {
+        if self.view_mode != ViewMode::UpdateToken {
+            self.view_before_token_prompt = self.view_mode;
+        }
+        self.token_input.clear();
+        self.token_error = None;
+        self.view_mode = ViewMode::UpdateToken;
+    }
+
+    pub fn token_input_char(&mut self, c: char) {
+        if !c.is_whitespace() {
+            self.token_input.push(c);
+            self.token_error = None;
+        }
+    }
+
+    pub fn token_backspace(&mut self) {
+        self.token_input.pop();
+        self.token_error = None;
+    }
+
+    pub async fn token_submit(&mut self) {
+        if !self.token_input.starts_with("brw_") || self.token_input.len() == "brw_".len() {
+            self.token_error = Some("Tokens start with 'brw_'".to_string());
+            return;
+        }
+
+        let cmd = TuiCommand::UpdateToken {
+            token: self.token_input.clone(),
+        };
+        if let Err(e) = self.send_command(cmd).await {
+            self.token_error = Some(e);
+            return;
+        }
+
+        self.token_input.clear();
+        self.view_mode = self.view_before_token_prompt;
+        self.notification = Some("Reconnecting with the new token...".to_string());
+        self.notification_level = NotificationLevel::Info;
+    }
+
+    /// Send a command to the connection, rejecting rapid repeats and
+    /// duplicates of commands that are still waiting in the channel. While
+    /// reconnecting, tunnel changes are held until the connection is back.
+    async fn send_command(&mut self, cmd: TuiCommand) -> std::result::Result<(), String> {
+        if self.is_reconnecting() && cmd.waits_for_connection() {
+            return self.queue_command(cmd);
+        }
+
+        if self
+            .last_command_at
+            .is_some_and(|at| at.elapsed() < self.command_cooldown)
+        {
+            return Err("Please wait...".to_string());
+        }
+
+        // The channel is FIFO, so the newest `queued` commands we sent are
+        // exactly the ones the connection hasn't picked up yet
+        let queued = self.cmd_tx.max_capacity() - self.cmd_tx.capacity();
+        while self.sent_commands.len() > queued {
+            self.sent_commands.pop_front();
+        }
+        if self.sent_commands.contains(&cmd) {
+            return Err("Already queued, waiting for connection".to_string());
+        }
+
+        if self.cmd_tx.send(cmd.clone()).await.is_err() {
+            if !cmd.waits_for_connection() {
+                return Err("The connection has stopped".to_string());
+            }
+            return self.queue_command(cmd);
+        }
+
+        self.last_command_at = Some(Instant::now());
+        self.sent_commands.push_back(cmd);
+        Ok(())
+    }
+
+    fn queue_command(&mut self, cmd: TuiCommand) -> std::result::Result<(), String> {
+        if self.pending_commands.contains(&cmd) {
+            return Err("Already queued, waiting for connection".to_string());
+        }
+        if self.pending_commands.len() >= MAX_PENDING_COMMANDS {
+            return Err(format!(
+                "{} commands already queued, waiting for connection",
+                MAX_PENDING_COMMANDS
+            ));
+        }
+        self.pending_commands.push_back(cmd);
+        self.notification = Some("Command queued, will execute on reconnect".to_string());
+        self.notification_level = NotificationLevel::Info;
+        Ok(())
+    }
+
+    /// Drop the tunnels of the pooled clients matching `lost`, which
+    /// register them again once reconnected
+    fn clear_pool_tunnels(&mut self, lost: impl Fn(u8) -> bool) {
+        self.tunnels.retain(|t| !lost(t.pool_id));
+        self.tcp_tunnels.retain(|t| !lost(t.pool_id));
+        let tunnels = &self.tunnels;
+        self.tunnel_errors
+            .retain(|id, _| tunnels.iter().any(|t| &t.tunnel_id == id));
+    }
+
+    /// Send the commands queued while reconnecting, skipping tunnels for
+    /// ports that are registered already
+    fn replay_pending_commands(&mut self) {
+        let mut registered: HashSet<u16> = self
+            .tunnels
+            .iter()
+            .map(|t| t.local_port)
+            .chain(self.tcp_tunnels.iter().map(|t| t.local_port))
+            .collect();
+        registered.extend(self.ports_before_reconnect.drain());
+
+        while let Some(cmd) = self.pending_commands.pop_front() {
+            let port = match &cmd {
+                TuiCommand::AddHttpTunnel { local_port, .. }
+                | TuiCommand::AddTcpTunnel { local_port } => Some(*local_port),
+                _ => None,
+            };
+            if let Some(port) = port {
+                if !registered.insert(port) {
+                    debug!(
+                        "Not replaying {:?}: port {} is registered already",
+                        cmd, port
+                    );
+                    continue;
+                }
+            }
+            debug!("Replaying queued command {:?}", cmd);
+            if self.cmd_tx.try_send(cmd).is_err() {
+                warn!("Failed to send a queued command");
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "trace", skip_all, fields(event_type = event.name()))]
     fn handle_event(&mut self, event: TuiEvent) {
         match event {
             TuiEvent::TunnelRegistered(tunnel) => {
@@ -342,6 +2391,29 @@ impl App {
             TuiEvent::TcpTunnelRegistered(tcp_tunnel) => {
                 self.tcp_tunnels.push(tcp_tunnel);
             }
+            TuiEvent::TunnelError {
+                tunnel_id,
+                code,
+                message,
+            } => {
+                self.tunnel_errors.insert(
+                    tunnel_id,
+                    TunnelErrorInfo {
+                        code,
+                        message,
+                        at: Local::now(),
+                    },
+                );
+            }
+            TuiEvent::TcpConnectionEstablished {
+                tcp_tunnel_id,
+                connect_latency_ms,
+            } => {
+                self.tcp_connect_latency
+                    .entry(tcp_tunnel_id)
+                    .or_default()
+                    .record(connect_latency_ms);
+            }
             TuiEvent::RequestReceived(req) => {
                 let log = RequestLog {
                     id: req.request_id.clone(),
@@ -354,8 +2426,35 @@ impl App {
                     response_headers: Vec::new(),
                     response_body: None,
                     duration_ms: None,
+                    timing_breakdown: None,
                     timestamp: req.timestamp,
                     client_ip: req.client_ip,
+                    error_kind: None,
+                    tunnel_url: req.tunnel_url,
+                    request_size_bytes: req.size_bytes,
+                    response_size_bytes: 0,
+                    tags: self
+                        .request_tags
+                        .get(&req.request_id)
+                        .cloned()
+                        .unwrap_or_default(),
+                };
+                self.total_bytes_in += req.size_bytes as u64;
+
+                // Requests hidden by a filter don't shift the selection
+                let visible = self.is_visible(&log);
+                // Groups may grow rather than add a row, so follow by id
+                let grouped_selection = self
+                    .group_by
+                    .and(self.selected_request())
+                    .map(|req| req.id.clone());
+
+                // The first request after a clear goes back to where the
+                // cursor was
+                let restore_position = if self.requests.is_empty() {
+                    self.pre_clear_position.take()
+                } else {
+                    None
                 };
 
                 // Insert at beginning (newest first)
@@ -366,32 +2465,158 @@ impl App {
                     self.requests.pop();
                 }
 
-                // Auto-select first item if nothing selected
-                if self.table_state.selected().is_none() && !self.requests.is_empty() {
-                    self.table_state.select(Some(0));
-                } else if let Some(selected) = self.table_state.selected() {
-                    // Keep selection on same item when new requests come in
-                    if selected < self.requests.len() - 1 {
-                        self.table_state.select(Some(selected + 1));
+                if visible {
+                    let count = self.visible_count();
+                    let filtered = self.filtered_requests();
+                    // New requests land below the bookmarked ones
+                    let pinned = filtered
+                        .iter()
+                        .take_while(|req| self.is_bookmarked(req))
+                        .count();
+                    // When every visible request is bookmarked, the last of them
+                    let newest = filtered
+                        .get(pinned)
+                        .or(filtered.last())
+                        .map(|req| req.id.clone());
+                    let request_open = matches!(
+                        self.view_mode,
+                        ViewMode::RequestDetail | ViewMode::RequestDiff
+                    );
+                    if self.auto_scroll && !request_open {
+                        if let Some(newest) = newest {
+                            self.table_state.select(self.row_of_request(&newest));
+                        }
+                    } else if let Some(id) = grouped_selection {
+                        self.table_state.select(self.row_of_request(&id));
+                    } else if let Some(position) = restore_position {
+                        self.table_state
+                            .select(Some(if position < count { position } else { 0 }));
+                    } else if self.table_state.selected().is_none() && count > 0 {
+                        // Auto-select first item if nothing selected
+                        self.table_state.select(Some(0));
+                    } else if let Some(selected) = self.table_state.selected() {
+                        // Keep selection on same item when new requests come in
+                        if selected >= pinned && selected < count - 1 {
+                            self.table_state.select(Some(selected + 1));
+                        }
                     }
                 }
             }
             TuiEvent::ResponseSent(resp) => {
                 // Find the request and update it
                 if let Some(req) = self.requests.iter_mut().find(|r| r.id == resp.request_id) {
+                    // The local service answered, so an earlier error for the
+                    // tunnel no longer applies
+                    if resp.error_kind.is_none() {
+                        if let Some(tunnel) = self
+                            .tunnels
+                            .iter()
+                            .find(|t| req.tunnel_url.as_ref() == Some(&t.full_url))
+                        {
+                            self.tunnel_errors.remove(&tunnel.tunnel_id);
+                        }
+                    }
                     req.status = Some(resp.status);
                     req.response_headers = resp.headers;
                     req.response_body = resp.body;
                     req.duration_ms = Some(resp.duration_ms);
+                    req.timing_breakdown = resp.timing;
+                    req.error_kind = resp.error_kind;
+                    req.response_size_bytes = resp.size_bytes;
                 }
+                self.total_bytes_out += resp.size_bytes as u64;
             }
             TuiEvent::ConnectionStatus(status) => {
                 // Clear stale tunnel display when reconnecting (will repopulate when re-registered)
                 if matches!(status, ConnectionStatus::Reconnecting { .. }) {
-                    self.tunnels.clear();
-                    self.tcp_tunnels.clear();
+                    // Only the first client of a pool reports here
+                    let ports = self.tunnels.iter().filter(|t| t.pool_id <= 1);
+                    let tcp_ports = self.tcp_tunnels.iter().filter(|t| t.pool_id <= 1);
+                    self.ports_before_reconnect.extend(
+                        ports
+                            .map(|t| t.local_port)
+                            .chain(tcp_ports.map(|t| t.local_port)),
+                    );
+                    self.clear_pool_tunnels(|pool_id| pool_id <= 1);
+                }
+                // Without a server, requests are all there is to look at
+                if matches!(
+                    status,
+                    ConnectionStatus::Offline { .. } | ConnectionStatus::LocalProxy { .. }
+                ) {
+                    self.view_mode = ViewMode::RequestList;
+                }
+                // Pings from an earlier connection say nothing about this one
+                if status == ConnectionStatus::Connected && status != self.connection_status {
+                    self.heartbeat_sent = 0;
+                    self.heartbeat_received = 0;
+                    self.rtt_samples.clear();
+                    self.connection_quality = None;
+                    self.heartbeat_rtt_ms = None;
+                }
+                if status != ConnectionStatus::Connected {
+                    self.server_address = None;
+                }
+                self.reconnect_deadline = match &status {
+                    ConnectionStatus::Reconnecting {
+                        next_retry_secs, ..
+                    } if *next_retry_secs > 0 => {
+                        Some(Instant::now() + Duration::from_secs(*next_retry_secs))
+                    }
+                    _ => None,
+                };
+                if status != self.connection_status {
+                    self.connection_history.push_back(ConnectionHistoryEntry {
+                        status: status.clone(),
+                        occurred_at: Local::now(),
+                    });
+                    if self.connection_history.len() > MAX_CONNECTION_HISTORY {
+                        self.connection_history.pop_front();
+                    }
                 }
                 self.connection_status = status;
+                if self.connection_status == ConnectionStatus::Connected {
+                    self.replay_pending_commands();
+                }
+            }
+            TuiEvent::PoolConnectionStatus { pool_id, status } => {
+                if matches!(status, ConnectionStatus::Reconnecting { .. }) {
+                    self.clear_pool_tunnels(|id| id == pool_id);
+                }
+                self.pool_statuses.insert(pool_id, status);
+            }
+            TuiEvent::ChannelStats { sent, dropped } => {
+                self.events_sent = sent;
+                self.events_dropped = dropped;
+            }
+            TuiEvent::ServerAddress(addr) => self.server_address = Some(addr),
+            TuiEvent::AppHeartbeat { rtt_ms } => self.heartbeat_rtt_ms = Some(rtt_ms),
+            TuiEvent::Heartbeat { rtt_ms } => {
+                self.heartbeat_sent += 1;
+                if let Some(rtt_ms) = rtt_ms {
+                    self.heartbeat_received += 1;
+                    self.rtt_samples.push_back(rtt_ms);
+                    if self.rtt_samples.len() > MAX_RTT_SAMPLES {
+                        self.rtt_samples.pop_front();
+                    }
+                }
+            }
+            TuiEvent::TokenRequired => self.enter_token_prompt(),
+            TuiEvent::StateSnapshot(snapshot) => self.state_snapshot = Some(snapshot),
+            TuiEvent::Notification { message, level } => {
+                self.notification = Some(message);
+                self.notification_level = level;
+            }
+            TuiEvent::Error { message, source } => {
+                self.error_log.push_front(ErrorLogEntry {
+                    timestamp: Local::now(),
+                    source,
+                    message,
+                });
+                self.error_log.truncate(MAX_ERROR_LOG);
+                if self.view_mode != ViewMode::ErrorLog {
+                    self.unseen_errors += 1;
+                }
             }
         }
     }
@@ -401,12 +2626,16 @@ pub struct Tui {
     terminal: Terminal<CrosstermBackend<io::Stdout>>,
     event_rx: mpsc::Receiver<TuiEvent>,
     cmd_tx: mpsc::Sender<TuiCommand>,
+    config: TuiConfig,
+    queue_depth: Option<Arc<AtomicU32>>,
+    server_scheme: ServerScheme,
 }
 
 impl Tui {
     pub fn new(
         event_rx: mpsc::Receiver<TuiEvent>,
         cmd_tx: mpsc::Sender<TuiCommand>,
+        config: &TuiConfig,
     ) -> Result<Self> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
@@ -418,35 +2647,119 @@ impl Tui {
             terminal,
             event_rx,
             cmd_tx,
+            config: config.clone(),
+            queue_depth: None,
+            server_scheme: ServerScheme::default(),
         })
     }
 
+    /// Warn in the status bar when the server is reached without TLS
+    pub fn set_server_scheme(&mut self, scheme: ServerScheme) {
+        self.server_scheme = scheme;
+    }
+
+    /// Show the connection's outgoing message queue depth in the status bar
+    pub fn watch_queue_depth(&mut self, queue_depth: Arc<AtomicU32>) {
+        self.queue_depth = Some(queue_depth);
+    }
+
     pub async fn run(&mut self) -> Result<()> {
-        let mut app = App::new(self.cmd_tx.clone());
+        let mut app = App::new(self.cmd_tx.clone(), &self.config);
+        app.no_tls = self.server_scheme == ServerScheme::Ws;
+        app.search_history = history::load_search_history();
+        app.bookmarked = store::load(store::BOOKMARKS_FILE);
+        app.request_tags = store::load(store::TAGS_FILE);
+        app.request_notes = store::load(store::NOTES_FILE);
+        app.named_macros = macros::load_macros();
+        let size = self.terminal.size()?;
+        app.terminal_size = (size.width, size.height);
 
+        let mut render_failed_at: Option<Instant> = None;
         loop {
-            // Draw UI
-            self.terminal.draw(|f| ui::draw(f, &mut app))?;
+            // Draw UI, falling back to plain text if rendering panics
+            if render_failed_at.is_none_or(|at| at.elapsed() >= RENDER_RETRY_DELAY) {
+                RENDERING.with(|rendering| rendering.set(true));
+                let drawn = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    self.terminal.draw(|f| ui::draw(f, &mut app)).map(|_| ())
+                }));
+                RENDERING.with(|rendering| rendering.set(false));
+                match drawn {
+                    Ok(result) => {
+                        result?;
+                        app.render_error = None;
+                        render_failed_at = None;
+                    }
+                    Err(payload) => {
+                        let message = panic_message(payload.as_ref());
+                        warn!("TUI render failed: {}", message);
+                        app.render_error = Some(message);
+                        render_failed_at = Some(Instant::now());
+                        self.terminal.clear()?;
+                    }
+                }
+            }
+            if let Some(error) = &app.render_error {
+                self.terminal
+                    .draw(|f| ui::draw_render_fallback(f, &app, error))?;
+            }
 
-            // Poll keyboard with short timeout, then check for TUI events
+            // Poll terminal events with short timeout, then check for TUI events
             if event::poll(Duration::from_millis(10))? {
-                if let Event::Key(key) = event::read()? {
-                    if key.kind == KeyEventKind::Press {
-                        handle_key(&mut app, key.code).await;
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => {
+                        handle_key(&mut app, key).await;
+                    }
+                    Event::Mouse(mouse) => handle_mouse(&mut app, mouse),
+                    Event::Resize(cols, rows) => {
+                        app.terminal_size = (cols, rows);
+                        self.terminal.autoresize()?;
+                        // Redraw right away with the new layout
+                        continue;
                     }
+                    _ => {}
                 }
             }
 
+            replay_macro_keys(&mut app).await;
+
             // Process all pending TUI events without blocking
             while let Ok(event) = self.event_rx.try_recv() {
                 app.handle_event(event);
             }
+            if let Some(depth) = &self.queue_depth {
+                app.message_queue_depth = depth.load(Ordering::Relaxed);
+            }
+            app.update_connection_quality();
+            app.write_due_layout(Instant::now());
 
             if app.should_quit {
                 break;
             }
         }
 
+        app.record_search();
+        // A resize still waiting to be saved is saved on the way out
+        app.write_due_layout(Instant::now() + LAYOUT_SAVE_DELAY);
+        if let Err(e) = history::save_search_history(&app.search_history) {
+            warn!("Failed to save search history: {:#}", e);
+        }
+        app.prune_annotations();
+        let bookmarks: BTreeSet<&RequestId> = app.bookmarked.iter().collect();
+        if let Err(e) = store::save(store::BOOKMARKS_FILE, &bookmarks) {
+            warn!("Failed to save bookmarks: {:#}", e);
+        }
+        let tags: BTreeMap<&RequestId, &Vec<String>> = app.request_tags.iter().collect();
+        if let Err(e) = store::save(store::TAGS_FILE, &tags) {
+            warn!("Failed to save tags: {:#}", e);
+        }
+        let notes: BTreeMap<&RequestId, &String> = app.request_notes.iter().collect();
+        if let Err(e) = store::save(store::NOTES_FILE, &notes) {
+            warn!("Failed to save notes: {:#}", e);
+        }
+        if let Err(e) = macros::save_macros(&app.named_macros) {
+            warn!("Failed to save macros: {:#}", e);
+        }
+
         Ok(())
     }
 }
@@ -463,17 +2776,83 @@ impl Drop for Tui {
     }
 }
 
-async fn handle_key(app: &mut App, key: KeyCode) {
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            if app.popup.take().is_some() {
+                return;
+            }
+            app.click(mouse.column, mouse.row);
+        }
+        MouseEventKind::ScrollDown if app.view_mode == ViewMode::RequestList => app.next(),
+        MouseEventKind::ScrollUp if app.view_mode == ViewMode::RequestList => app.previous(),
+        _ => {}
+    }
+}
+
+/// Dispatch the keys of a macro being replayed that are due
+async fn replay_macro_keys(app: &mut App) {
+    while let Some(key) = app.next_macro_key() {
+        app.replaying_macro = true;
+        handle_key(app, key).await;
+        app.replaying_macro = false;
+    }
+}
+
+async fn handle_key(app: &mut App, key_event: KeyEvent) {
+    app.notification = None;
+
+    let key = key_event.code;
+    let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+    let action = app.keybindings.action(key_event);
+    let input_action = app.keybindings.input_action(key_event);
+
+    if app.render_error.is_some() && key == KeyCode::Char('q') {
+        app.should_quit = true;
+        return;
+    }
+
+    if app.popup.take().is_some() {
+        return;
+    }
+
+    if app.handle_macro_key(key_event) {
+        return;
+    }
+
     match app.view_mode {
+        ViewMode::TunnelList if app.tunnel_path_input.is_some() => match key {
+            KeyCode::Esc => app.cancel_tunnel_path(),
+            KeyCode::Enter => app.submit_tunnel_path(),
+            KeyCode::Backspace => app.tunnel_path_backspace(),
+            KeyCode::Char(c) => app.tunnel_path_input_char(c),
+            _ => {}
+        },
         ViewMode::TunnelList => match key {
             KeyCode::Char('q') => app.should_quit = true,
+            KeyCode::Char('r') if app.is_disconnected() => app.retry_connection(),
             KeyCode::Char('a') if app.is_connected() => app.enter_add_tunnel(),
-            KeyCode::Char('j') | KeyCode::Down => app.tunnel_next(),
-            KeyCode::Char('k') | KeyCode::Up => app.tunnel_previous(),
+            KeyCode::Char('a') if app.is_offline() => app.notify_read_only(),
+            KeyCode::Char('S') if app.is_connected() => app.enter_switch_server(),
+            KeyCode::Char('S') if app.is_offline() => app.notify_read_only(),
+            _ if action == Some(Action::Next) => app.tunnel_next(),
+            _ if action == Some(Action::Previous) => app.tunnel_previous(),
+            KeyCode::Char('T') => app.toggle_time_display(),
+            KeyCode::Char('!') => app.enter_error_log(),
+            KeyCode::Char('h') => app.enter_connection_history(),
+            KeyCode::Char('s') => app.enter_tunnel_stats(),
+            KeyCode::Char('o') => app.inspect_subdomain(""),
+            KeyCode::Char('O') => app.start_tunnel_path(),
+            KeyCode::Char('y') => app.copy_url(),
+            KeyCode::Tab => app.toggle_view(),
             KeyCode::Enter => app.view_tunnel_requests(),
             _ => {}
         },
         ViewMode::AddTunnel => match key {
+            _ if input_action == Some(Action::DeleteToEnd) => app.clear_input(),
+            _ if input_action == Some(Action::Back) => app.back(),
+            _ if input_action == Some(Action::Next) => app.form_next_field(),
+            _ if input_action == Some(Action::Previous) => app.form_prev_field(),
             KeyCode::Esc => app.back(),
             KeyCode::Tab | KeyCode::Down => app.form_next_field(),
             KeyCode::BackTab | KeyCode::Up => app.form_prev_field(),
@@ -482,31 +2861,232 @@ async fn handle_key(app: &mut App, key: KeyCode) {
             }
             KeyCode::Char(c) => app.form_input_char(c),
             KeyCode::Backspace => app.form_backspace(),
-            KeyCode::Enter => app.form_submit().await,
+            KeyCode::Enter => app.form_enter().await,
+            _ => {}
+        },
+        ViewMode::SwitchServer => match key {
+            _ if input_action == Some(Action::DeleteToEnd) => app.clear_input(),
+            _ if input_action == Some(Action::Back) => app.back(),
+            _ if matches!(input_action, Some(Action::Next | Action::Previous)) => {
+                app.switch_server_next_field()
+            }
+            KeyCode::Esc => app.back(),
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Down | KeyCode::Up => {
+                app.switch_server_next_field()
+            }
+            KeyCode::Char(c) => app.switch_server_input_char(c),
+            KeyCode::Backspace => app.switch_server_backspace(),
+            KeyCode::Enter => app.switch_server_submit().await,
+            _ => {}
+        },
+        ViewMode::UpdateToken => match key {
+            _ if input_action == Some(Action::DeleteToEnd) => app.clear_input(),
+            _ if input_action == Some(Action::Back) => app.back(),
+            KeyCode::Esc => app.back(),
+            KeyCode::Char(c) => app.token_input_char(c),
+            KeyCode::Backspace => app.token_backspace(),
+            KeyCode::Enter => app.token_submit().await,
+            _ => {}
+        },
+        ViewMode::RequestList if app.tagging => match key {
+            _ if input_action == Some(Action::DeleteToEnd) => app.clear_input(),
+            _ if input_action == Some(Action::Back) => app.cancel_tagging(),
+            KeyCode::Esc => app.cancel_tagging(),
+            KeyCode::Enter => app.submit_tags(),
+            KeyCode::Backspace => app.tag_backspace(),
+            KeyCode::Char(c) => app.tag_input_char(c),
+            _ => {}
+        },
+        ViewMode::RequestList if app.searching => match key {
+            _ if input_action == Some(Action::DeleteToEnd) => app.clear_input(),
+            _ if input_action == Some(Action::Back) => app.cancel_search(),
+            _ if input_action == Some(Action::Previous) => app.history_previous(),
+            _ if input_action == Some(Action::Next) => app.history_next(),
+            KeyCode::Esc => app.cancel_search(),
+            KeyCode::Enter => app.submit_search(),
+            KeyCode::Up => app.history_previous(),
+            KeyCode::Down => app.history_next(),
+            KeyCode::Backspace => app.search_backspace(),
+            KeyCode::Char(c) => app.search_input_char(c),
             _ => {}
         },
         ViewMode::RequestList => match key {
+            _ if action == Some(Action::Next) => app.next(),
+            _ if action == Some(Action::Previous) => app.previous(),
+            _ if action == Some(Action::PageDown) => app.page_down(),
+            _ if action == Some(Action::PageUp) => app.page_up(),
+            _ if action == Some(Action::Back) && app.diff_base.is_some() => app.cancel_diff(),
+            _ if action == Some(Action::Back) && !app.search_query.is_empty() => {
+                app.cancel_search()
+            }
+            _ if action == Some(Action::Back) => app.back(),
             KeyCode::Char('q') => app.should_quit = true,
-            KeyCode::Char('j') | KeyCode::Down => app.next(),
-            KeyCode::Char('k') | KeyCode::Up => app.previous(),
+            KeyCode::Char('/') => app.start_search(),
+            KeyCode::Char('*') => app.search_selected(),
+            KeyCode::Char('b') => app.toggle_bookmark(),
+            KeyCode::Char('B') => app.toggle_bookmarks_only(),
+            KeyCode::Char('#') => app.start_tagging(),
             KeyCode::Char('g') => app.go_to_top(),
             KeyCode::Char('G') => app.go_to_bottom(),
+            KeyCode::Char('F') => app.toggle_auto_scroll(),
+            KeyCode::Char('z') if ctrl => app.undo_clear(),
+            KeyCode::Char('z') => app.cycle_group_by(),
+            KeyCode::Char('v') => app.toggle_display_density(),
+            KeyCode::Char('<') => app.select_column(-1),
+            KeyCode::Char('>') => app.select_column(1),
+            KeyCode::Right if ctrl => app.resize_selected_column(COLUMN_RESIZE_STEP),
+            KeyCode::Left if ctrl => app.resize_selected_column(-COLUMN_RESIZE_STEP),
+            KeyCode::Char('0') if ctrl => app.reset_column_widths(),
+            KeyCode::Char(' ') => app.toggle_group(),
             KeyCode::Char('c') => app.clear(),
+            KeyCode::Char('T') => app.toggle_time_display(),
+            KeyCode::Char('t') => app.enter_timeline(),
+            KeyCode::Char('s') => app.enter_tunnel_stats(),
+            KeyCode::Char('D') => app.start_diff(),
+            KeyCode::Char('!') => app.enter_error_log(),
+            KeyCode::Tab => app.toggle_view(),
+            KeyCode::Enter if app.diff_base.is_some() => app.open_diff(),
             KeyCode::Enter => app.enter_request_detail(),
+            KeyCode::Esc if app.diff_base.is_some() => app.cancel_diff(),
+            KeyCode::Esc if !app.search_query.is_empty() => app.cancel_search(),
             KeyCode::Esc => app.back(),
             _ => {}
         },
+        ViewMode::RequestDetail if app.editing_note.is_some() => match key {
+            _ if input_action == Some(Action::DeleteToEnd) => app.clear_input(),
+            _ if input_action == Some(Action::Back) => app.cancel_note(),
+            KeyCode::Esc => app.cancel_note(),
+            KeyCode::Enter => app.submit_note(),
+            KeyCode::Backspace => app.note_backspace(),
+            KeyCode::Char(c) => app.note_input_char(c),
+            _ => {}
+        },
         ViewMode::RequestDetail => match key {
+            KeyCode::Char('r') if ctrl => app.toggle_raw_detail(),
+            KeyCode::Char('N') => app.start_note(),
             KeyCode::Char('q') => app.should_quit = true,
+            _ if action == Some(Action::Back) => app.back(),
             KeyCode::Esc | KeyCode::Enter => app.back(),
             _ => {}
         },
+        ViewMode::ErrorLog => match key {
+            KeyCode::Char('q') => app.should_quit = true,
+            _ if action == Some(Action::Back) => app.back(),
+            KeyCode::Esc | KeyCode::Char('!') => app.back(),
+            _ => {}
+        },
+        ViewMode::Timeline => match key {
+            KeyCode::Char('q') => app.should_quit = true,
+            _ if action == Some(Action::Next) => app.timeline_move(1),
+            _ if action == Some(Action::Previous) => app.timeline_move(-1),
+            _ if action == Some(Action::PageDown) => app.timeline_move(10),
+            _ if action == Some(Action::PageUp) => app.timeline_move(-10),
+            _ if action == Some(Action::Back) => app.back(),
+            KeyCode::Esc | KeyCode::Char('t') => app.back(),
+            _ => {}
+        },
+        ViewMode::RequestDiff => match key {
+            KeyCode::Char('q') => app.should_quit = true,
+            _ if action == Some(Action::Next) => app.diff_scroll_by(1),
+            _ if action == Some(Action::Previous) => app.diff_scroll_by(-1),
+            _ if action == Some(Action::PageDown) => app.diff_scroll_by(10),
+            _ if action == Some(Action::PageUp) => app.diff_scroll_by(-10),
+            _ if action == Some(Action::Back) => app.back(),
+            KeyCode::Esc => app.back(),
+            _ => {}
+        },
+        ViewMode::TunnelStats => match key {
+            KeyCode::Char('q') => app.should_quit = true,
+            _ if action == Some(Action::Back) => app.back(),
+            KeyCode::Esc | KeyCode::Char('s') => app.back(),
+            _ => {}
+        },
+        ViewMode::ConnectionHistory => match key {
+            KeyCode::Char('q') => app.should_quit = true,
+            KeyCode::Char('r') => app.enter_connection_history(),
+            _ if action == Some(Action::Back) => app.back(),
+            KeyCode::Esc | KeyCode::Char('h') => app.back(),
+            _ => {}
+        },
+    }
+}
+
+/// Parse `Header-Name: Value` lines from the add-tunnel form, skipping blank
+/// lines. Names must be RFC 7230 tokens.
+fn parse_header_lines(text: &str) -> Result<Vec<(String, String)>, String> {
+    let is_tchar = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Expected 'Header-Name: Value', got '{}'", line))?;
+            let name = name.trim();
+            if name.is_empty() || !name.chars().all(is_tchar) {
+                return Err(format!("Invalid header name '{}'", name));
+            }
+            Ok((name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Parse the comma-separated tags from the add-tunnel form, skipping empty
+/// entries and duplicates
+fn parse_tag_list(text: &str) -> Result<Vec<String>, String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        check_tunnel_tag(tag)?;
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    Ok(tags)
+}
+
+/// Restore the terminal before a panic message is printed, so a crash
+/// doesn't leave it in raw mode on the alternate screen. Call it from the
+/// thread the TUI runs on; panics in other threads, such as spawned tasks
+/// tokio recovers from, leave the terminal alone.
+pub fn setup_panic_handler() {
+    let original_hook = std::panic::take_hook();
+    let tui_thread = std::thread::current().id();
+    std::panic::set_hook(Box::new(move |info| {
+        if RENDERING.with(|rendering| rendering.get()) {
+            return;
+        }
+        if std::thread::current().id() != tui_thread {
+            original_hook(info);
+            return;
+        }
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            Show
+        );
+        // The default hook prints the panic message to stderr
+        original_hook(info);
+    }));
+}
+
+/// The message a panic was raised with
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
     }
 }
 
-/// Creates a channel for sending events to the TUI
-pub fn create_event_channel() -> (mpsc::Sender<TuiEvent>, mpsc::Receiver<TuiEvent>) {
-    mpsc::channel(256)
+/// Creates a monitored channel for sending events to the TUI
+pub fn create_event_channel(capacity: usize) -> (ChannelMonitor, mpsc::Receiver<TuiEvent>) {
+    let (tx, rx) = mpsc::channel(capacity);
+    (ChannelMonitor::new(tx), rx)
 }
 
 /// Creates a channel for sending commands from TUI to connection
@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tracing::debug;
+
+use super::TuiEvent;
+
+/// How often channel statistics are reported to the TUI
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+struct ChannelCounters {
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    high_watermark: AtomicU64,
+}
+
+/// Wrapper around the TUI event sender that tracks throughput and drops.
+///
+/// High-rate telemetry is sent without waiting so a slow TUI never stalls
+/// the connection; what doesn't fit in the channel is counted as dropped.
+/// Every other event changes what the TUI shows and waits for room instead.
+#[derive(Debug, Clone)]
+pub struct ChannelMonitor {
+    tx: mpsc::Sender<TuiEvent>,
+    counters: Arc<ChannelCounters>,
+    /// Pooled client the events come from; see [`ChannelMonitor::set_pool_id`]
+    pool_id: u8,
+}
+
+impl ChannelMonitor {
+    pub fn new(tx: mpsc::Sender<TuiEvent>) -> Self {
+        Self {
+            tx,
+            counters: Arc::new(ChannelCounters::default()),
+            pool_id: 0,
+        }
+    }
+
+    /// Mark events as coming from a pooled client. The status bar describes
+    /// the first client only, so from the others connection status is sent
+    /// as [`TuiEvent::PoolConnectionStatus`] and connection details are left out.
+    pub(crate) fn set_pool_id(&mut self, pool_id: u8) {
+        self.pool_id = pool_id;
+    }
+
+    fn scoped(&self, event: TuiEvent) -> Option<TuiEvent> {
+        if self.pool_id <= 1 {
+            return Some(event);
+        }
+        match event {
+            TuiEvent::ConnectionStatus(status) => Some(TuiEvent::PoolConnectionStatus {
+                pool_id: self.pool_id,
+                status,
+            }),
+            TuiEvent::ServerAddress(_)
+            | TuiEvent::Heartbeat { .. }
+            | TuiEvent::AppHeartbeat { .. }
+            | TuiEvent::ChannelStats { .. } => None,
+            event => Some(event),
+        }
+    }
+
+    /// Send an event to the TUI, waiting for room unless it is telemetry,
+    /// which is counted as dropped if the channel is full
+    pub async fn send(&self, event: TuiEvent) {
+        let Some(event) = self.scoped(event) else {
+            return;
+        };
+        let sent = if !is_droppable(&event) {
+            self.tx.send(event).await.is_ok()
+        } else {
+            match self.tx.try_send(event) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        };
+        if sent {
+            self.counters.sent.fetch_add(1, Ordering::Relaxed);
+            let depth = (self.tx.max_capacity() - self.tx.capacity()) as u64;
+            self.counters
+                .high_watermark
+                .fetch_max(depth, Ordering::Relaxed);
+        }
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.counters.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.counters.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn high_watermark(&self) -> u64 {
+        self.counters.high_watermark.load(Ordering::Relaxed)
+    }
+
+    /// Periodically emit [`TuiEvent::ChannelStats`] until the TUI goes away
+    pub async fn report_stats(self) {
+        let mut interval = tokio::time::interval(STATS_INTERVAL);
+        // The first tick completes immediately; skip it so stats aren't all zero
+        interval.tick().await;
+        while !self.tx.is_closed() {
+            interval.tick().await;
+            debug!(
+                "TUI event channel: sent={} dropped={} high_watermark={}",
+                self.sent(),
+                self.dropped(),
+                self.high_watermark()
+            );
+            self.send(TuiEvent::ChannelStats {
+                sent: self.sent(),
+                dropped: self.dropped(),
+            })
+            .await;
+        }
+    }
+}
+
+/// Events that come often enough for the next one to stand in for a lost
+/// one. A request whose `RequestReceived` is lost is just not listed.
+fn is_droppable(event: &TuiEvent) -> bool {
+    matches!(
+        event,
+        TuiEvent::Heartbeat { .. }
+            | TuiEvent::AppHeartbeat { .. }
+            | TuiEvent::ChannelStats { .. }
+            | TuiEvent::RequestReceived(_)
+            | TuiEvent::TcpConnectionEstablished { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tui::{ConnectionStatus, TunnelEvent};
+    use crate::protocol::TunnelId;
+
+    #[tokio::test]
+    async fn test_full_channel_drops_only_what_can_be_lost() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let monitor = ChannelMonitor::new(tx);
+        monitor.send(TuiEvent::TokenRequired).await;
+        monitor.send(TuiEvent::Heartbeat { rtt_ms: Some(5) }).await;
+        assert_eq!(monitor.dropped(), 1);
+
+        let sending = tokio::spawn({
+            let monitor = monitor.clone();
+            async move {
+                monitor
+                    .send(TuiEvent::TunnelRegistered(TunnelEvent {
+                        tunnel_id: TunnelId("t1".to_string()),
+                        full_url: "https://a.example.com".to_string(),
+                        local_port: 3000,
+                        pool_id: 0,
+                        extra_header_count: 0,
+                        tags: Vec::new(),
+                    }))
+                    .await;
+                monitor
+                    .send(TuiEvent::ConnectionStatus(ConnectionStatus::Connected))
+                    .await;
+            }
+        });
+        assert!(matches!(rx.recv().await, Some(TuiEvent::TokenRequired)));
+        assert!(matches!(
+            rx.recv().await,
+            Some(TuiEvent::TunnelRegistered(tunnel)) if tunnel.local_port == 3000
+        ));
+        sending.await.unwrap();
+        assert!(matches!(
+            rx.recv().await,
+            Some(TuiEvent::ConnectionStatus(ConnectionStatus::Connected))
+        ));
+        assert_eq!((monitor.sent(), monitor.dropped()), (3, 1));
+    }
+}
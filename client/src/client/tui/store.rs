@@ -0,0 +1,45 @@
+//! Persistence for TUI state kept between sessions: bookmarks, tags, notes
+//! and macros, each stored as a JSON file in `~/.burrow`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use directories::BaseDirs;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub const BOOKMARKS_FILE: &str = "bookmarks.json";
+pub const TAGS_FILE: &str = "tags.json";
+pub const NOTES_FILE: &str = "notes.json";
+pub const MACROS_FILE: &str = "macros.json";
+
+fn store_path(file_name: &str) -> Result<PathBuf> {
+    let dirs = BaseDirs::new().context("Could not determine home directory")?;
+    Ok(dirs.home_dir().join(".burrow").join(file_name))
+}
+
+/// Load a saved value, or the default if there is none or it can't be read
+pub fn load<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let Ok(path) = store_path(file_name) else {
+        return T::default();
+    };
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Save a value so it is available in the next session. Pass sorted
+/// collections so the file doesn't reorder between saves.
+pub fn save<T: Serialize>(file_name: &str, value: &T) -> Result<()> {
+    let path = store_path(file_name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let contents = serde_json::to_string_pretty(value)?;
+    fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
@@ -0,0 +1,227 @@
+//! Colours used by the TUI, with dark, light and high-contrast presets.
+
+use ratatui::style::{Color, Modifier, Style};
+use tracing::warn;
+
+use crate::config::{ThemeConfig, ThemePreset};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub method_get: Color,
+    pub method_post: Color,
+    pub method_put: Color,
+    pub method_delete: Color,
+    pub status_success: Color,
+    pub status_redirect: Color,
+    pub status_warning: Color,
+    pub status_error: Color,
+    pub background: Color,
+    /// Headings, key hints and labels
+    pub highlight: Color,
+    /// Secondary highlight, e.g. URLs and bookmarks
+    pub accent: Color,
+    pub text_primary: Color,
+    pub text_secondary: Color,
+    /// Timestamps, disabled items and separators
+    pub text_dim: Color,
+    /// Background of every other request list row
+    pub row_alt_background: Color,
+    /// Emphasise with Bold/Underline/Reversed instead of colour
+    pub modifiers_only: bool,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            method_get: Color::Green,
+            method_post: Color::Blue,
+            method_put: Color::Yellow,
+            method_delete: Color::Red,
+            status_success: Color::Green,
+            status_redirect: Color::Cyan,
+            status_warning: Color::Yellow,
+            status_error: Color::Red,
+            background: Color::Reset,
+            highlight: Color::Yellow,
+            accent: Color::Cyan,
+            text_primary: Color::White,
+            text_secondary: Color::Gray,
+            text_dim: Color::DarkGray,
+            row_alt_background: if supports_truecolor() {
+                Color::Rgb(20, 20, 20)
+            } else {
+                Color::Indexed(235)
+            },
+            modifiers_only: false,
+        }
+    }
+
+    /// For light terminal backgrounds
+    pub fn light() -> Self {
+        Self {
+            method_get: Color::Green,
+            method_post: Color::Blue,
+            method_put: Color::Magenta,
+            method_delete: Color::Red,
+            status_success: Color::Green,
+            status_redirect: Color::Blue,
+            status_warning: Color::Rgb(176, 112, 0),
+            status_error: Color::Red,
+            background: Color::White,
+            highlight: Color::Blue,
+            accent: Color::Magenta,
+            text_primary: Color::Black,
+            text_secondary: Color::DarkGray,
+            text_dim: Color::Gray,
+            row_alt_background: if supports_truecolor() {
+                Color::Rgb(240, 240, 240)
+            } else {
+                Color::Indexed(255)
+            },
+            modifiers_only: false,
+        }
+    }
+
+    /// No colour at all, for monochrome terminals and screen readers
+    pub fn high_contrast() -> Self {
+        Self {
+            method_get: Color::Reset,
+            method_post: Color::Reset,
+            method_put: Color::Reset,
+            method_delete: Color::Reset,
+            status_success: Color::Reset,
+            status_redirect: Color::Reset,
+            status_warning: Color::Reset,
+            status_error: Color::Reset,
+            background: Color::Reset,
+            highlight: Color::Reset,
+            accent: Color::Reset,
+            text_primary: Color::Reset,
+            text_secondary: Color::Reset,
+            text_dim: Color::Reset,
+            row_alt_background: Color::Reset,
+            modifiers_only: true,
+        }
+    }
+
+    pub fn from_preset(preset: ThemePreset) -> Self {
+        match preset {
+            ThemePreset::Dark => Self::dark(),
+            ThemePreset::Light => Self::light(),
+            ThemePreset::HighContrast => Self::high_contrast(),
+        }
+    }
+
+    /// The preset, or a preset with the colours of a custom `[tui.theme]`
+    /// table applied; colours that don't parse are skipped with a warning
+    pub fn from_config(config: &ThemeConfig) -> Self {
+        let custom = match config {
+            ThemeConfig::Preset(preset) => return Self::from_preset(*preset),
+            ThemeConfig::Custom(custom) => custom,
+        };
+
+        let mut theme = Self::from_preset(custom.base);
+        let fields = [
+            (&custom.method_get, &mut theme.method_get),
+            (&custom.method_post, &mut theme.method_post),
+            (&custom.method_put, &mut theme.method_put),
+            (&custom.method_delete, &mut theme.method_delete),
+            (&custom.status_success, &mut theme.status_success),
+            (&custom.status_redirect, &mut theme.status_redirect),
+            (&custom.status_warning, &mut theme.status_warning),
+            (&custom.status_error, &mut theme.status_error),
+            (&custom.background, &mut theme.background),
+            (&custom.highlight, &mut theme.highlight),
+            (&custom.accent, &mut theme.accent),
+            (&custom.text_primary, &mut theme.text_primary),
+            (&custom.text_secondary, &mut theme.text_secondary),
+            (&custom.text_dim, &mut theme.text_dim),
+            (&custom.row_alt_background, &mut theme.row_alt_background),
+        ];
+        for (value, color) in fields {
+            let Some(value) = value else { continue };
+            match value.parse() {
+                Ok(parsed) => *color = parsed,
+                Err(_) => warn!("Ignoring unknown theme colour '{}'", value),
+            }
+        }
+        theme
+    }
+
+    /// `color` unless the theme only uses modifiers
+    pub fn color(&self, color: Color) -> Color {
+        if self.modifiers_only {
+            Color::Reset
+        } else {
+            color
+        }
+    }
+
+    pub fn method_style(&self, method: &str) -> Style {
+        let color = match method {
+            "GET" => self.method_get,
+            "POST" => self.method_post,
+            "PUT" | "PATCH" => self.method_put,
+            "DELETE" => self.method_delete,
+            _ => self.text_primary,
+        };
+        Style::default().fg(color)
+    }
+
+    pub fn status_style(&self, status: Option<u16>) -> Style {
+        let style = match status {
+            Some(s) if (200..300).contains(&s) => Style::default().fg(self.status_success),
+            Some(s) if (300..400).contains(&s) => Style::default().fg(self.status_redirect),
+            Some(s) if (400..500).contains(&s) => Style::default().fg(self.status_warning),
+            Some(s) if s >= 500 => Style::default().fg(self.status_error),
+            _ => Style::default().fg(self.text_secondary),
+        };
+        match status {
+            Some(s) if self.modifiers_only && s >= 500 => {
+                style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+            }
+            Some(s) if self.modifiers_only && s >= 400 => style.add_modifier(Modifier::BOLD),
+            _ => style,
+        }
+    }
+
+    /// Text drawn on a highlighted background, like the note banner
+    pub fn badge_style(&self) -> Style {
+        if self.modifiers_only {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(Color::Black).bg(self.highlight)
+        }
+    }
+}
+
+/// Whether the terminal advertises 24-bit colour through `COLORTERM`
+fn supports_truecolor() -> bool {
+    std::env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit")
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TuiConfig;
+
+    #[test]
+    fn test_theme_from_config() {
+        let preset: TuiConfig = toml::from_str("theme = \"high-contrast\"").unwrap();
+        assert_eq!(Theme::from_config(&preset.theme), Theme::high_contrast());
+
+        let custom: TuiConfig =
+            toml::from_str("[theme]\nbase = \"light\"\nhighlight = \"#ff8800\"\naccent = \"nope\"")
+                .unwrap();
+        let theme = Theme::from_config(&custom.theme);
+        assert_eq!(theme.highlight, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.accent, Theme::light().accent);
+        assert_eq!(theme.text_primary, Color::Black);
+    }
+}
@@ -1,42 +1,159 @@
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
-    text::{Line, Span},
-    widgets::{Block, Borders, Cell, Paragraph, Row, Table, Wrap},
+    symbols::Marker,
+    text::{Line, Span, Text},
+    widgets::{
+        canvas::{Canvas, Line as CanvasLine},
+        Block, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap,
+    },
     Frame,
 };
 
-use super::{AddTunnelField, App, ConnectionStatus, TunnelType, ViewMode};
+use chrono::{DateTime, Local};
+
+use super::theme::Theme;
+use super::{
+    AddTunnelField, App, ConnectionQuality, ConnectionStatus, DetailTab, ListRow,
+    NotificationLevel, RequestBodyKind, RequestLog, SwitchServerField, TimingBreakdown, TunnelType,
+    ViewMode, TIMELINE_WINDOW_SECS,
+};
+use crate::config::{
+    DisplayDensity, RequestListColumn, RequestListColumnConfig, StatusBarSlot, TimeDisplay,
+};
+
+/// Smallest terminal the layouts are designed for
+const MIN_WIDTH: u16 = 80;
+const MIN_HEIGHT: u16 = 24;
+
+/// Below these heights the detail view drops panels to keep the body readable
+const DETAIL_REQUEST_BODY_MIN_HEIGHT: u16 = 30;
+const DETAIL_RESPONSE_HEADERS_MIN_HEIGHT: u16 = 20;
+
+/// Bodies in the raw view are cut off after this many bytes
+const RAW_BODY_LIMIT: usize = 4096;
+
+/// Body lines compared in the diff view; the rest is left out
+const DIFF_BODY_MAX_LINES: usize = 300;
+
+/// Characters of the body previewed in comfortable request list rows
+const BODY_SNIPPET_CHARS: usize = 60;
 
 pub fn draw(frame: &mut Frame, app: &mut App) {
+    let (cols, rows) = app.terminal_size;
+    if cols < MIN_WIDTH || rows < MIN_HEIGHT {
+        return draw_too_small(frame, &app.theme);
+    }
+    if app.theme.background != Color::Reset {
+        frame.render_widget(
+            Block::default().style(Style::default().bg(app.theme.background)),
+            frame.area(),
+        );
+    }
+
+    // Only what this frame draws can be clicked
+    app.row_y_positions.clear();
+    app.status_bar_links.clear();
     match app.view_mode {
         ViewMode::TunnelList => draw_tunnel_list_view(frame, app),
         ViewMode::AddTunnel => draw_add_tunnel_view(frame, app),
         ViewMode::RequestList => draw_request_list_view(frame, app),
         ViewMode::RequestDetail => draw_detail_view(frame, app),
+        ViewMode::ErrorLog => draw_error_log_view(frame, app),
+        ViewMode::ConnectionHistory => draw_connection_history_view(frame, app),
+        ViewMode::SwitchServer => draw_switch_server_view(frame, app),
+        ViewMode::Timeline => draw_timeline_view(frame, app),
+        ViewMode::RequestDiff => draw_diff_view(frame, app),
+        ViewMode::UpdateToken => draw_token_prompt(frame, app),
+        ViewMode::TunnelStats => draw_tunnel_stats_view(frame, app),
+    }
+
+    if let Some((title, lines)) = &app.popup {
+        draw_popup(frame, app, title, lines);
     }
 }
 
+/// Message box over the current view, closed by any key
+fn draw_popup(frame: &mut Frame, app: &App, title: &str, lines: &[String]) {
+    let theme = app.theme;
+    let mut text: Vec<Line> = lines.iter().map(|line| Line::raw(line.clone())).collect();
+    text.push(Line::raw(""));
+    text.push(Line::styled(
+        "Press any key to close",
+        Style::default().fg(theme.text_dim),
+    ));
+
+    let area = centered_rect(70, text.len() as u16 + 2, frame.area());
+    let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} ", title))
+            .border_style(Style::default().fg(theme.highlight)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
+
+/// Plain-text view shown after `draw` panicked: the error, the connection
+/// status and the latest requests
+pub fn draw_render_fallback(frame: &mut Frame, app: &App, error: &str) {
+    let mut lines = vec![
+        Line::from(format!(
+            "TUI render error: {}. Press 'q' to quit or resize terminal.",
+            error
+        )),
+        Line::from(""),
+        Line::from(format!("Status: {}", app.connection_status)),
+        Line::from(""),
+    ];
+    lines.extend(app.requests.iter().take(5).map(|req| {
+        let status = req
+            .status
+            .map_or_else(|| "...".to_string(), |status| status.to_string());
+        Line::from(format!(
+            "{} {} {} {}",
+            status,
+            req.method,
+            req.path,
+            req.timestamp.format("%H:%M:%S")
+        ))
+    }));
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        frame.area(),
+    );
+}
+
+fn draw_too_small(frame: &mut Frame, theme: &Theme) {
+    let message = Paragraph::new(Line::from(Span::styled(
+        format!("Terminal too small (min {}x{})", MIN_WIDTH, MIN_HEIGHT),
+        Style::default().fg(theme.highlight),
+    )))
+    .wrap(Wrap { trim: true });
+    frame.render_widget(message, frame.area());
+}
+
 fn draw_tunnel_list_view(frame: &mut Frame, app: &mut App) {
     let show_banner = !matches!(
         app.connection_status,
         ConnectionStatus::Connected | ConnectionStatus::Connecting
     );
 
+    let footer_height = help_footer_height(app, frame.area().width);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(if show_banner {
             vec![
-                Constraint::Length(3), // Status bar
-                Constraint::Length(3), // Connection banner
-                Constraint::Min(5),    // Tunnel list
-                Constraint::Length(2), // Help footer
+                Constraint::Length(3),             // Status bar
+                Constraint::Length(3),             // Connection banner
+                Constraint::Min(5),                // Tunnel list
+                Constraint::Length(footer_height), // Help footer
             ]
         } else {
             vec![
-                Constraint::Length(3), // Status bar
-                Constraint::Min(5),    // Tunnel list
-                Constraint::Length(2), // Help footer
+                Constraint::Length(3),             // Status bar
+                Constraint::Min(5),                // Tunnel list
+                Constraint::Length(footer_height), // Help footer
             ]
         })
         .split(frame.area());
@@ -55,25 +172,38 @@ fn draw_tunnel_list_view(frame: &mut Frame, app: &mut App) {
 }
 
 fn draw_connection_banner(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
     let (message, style) = match &app.connection_status {
         ConnectionStatus::Reconnecting {
-            attempt,
-            reason,
-            next_retry_secs,
+            attempt, reason, ..
         } => {
-            let msg = if *next_retry_secs > 0 {
+            let msg = if let Some(secs) = app.reconnect_countdown() {
                 format!(
                     " Reconnecting (attempt {})... {} - retrying in {}s ",
-                    attempt, reason, next_retry_secs
+                    attempt, reason, secs
                 )
             } else {
                 format!(" Reconnecting (attempt {})... {} ", attempt, reason)
             };
-            (msg, Style::default().fg(Color::Yellow))
+            (msg, Style::default().fg(theme.highlight))
         }
         ConnectionStatus::Disconnected { reason } => {
             let msg = format!(" Disconnected: {} ", reason);
-            (msg, Style::default().fg(Color::Red))
+            (msg, Style::default().fg(theme.status_error))
+        }
+        ConnectionStatus::Offline { source } => {
+            let msg = format!(" Inspecting requests imported from {} (read-only) ", source);
+            (msg, Style::default().fg(theme.text_dim))
+        }
+        ConnectionStatus::LocalProxy {
+            listen_port,
+            target,
+        } => {
+            let msg = format!(
+                " Proxying port {} to {} without a Burrow server ",
+                listen_port, target
+            );
+            (msg, Style::default().fg(theme.text_dim))
         }
         _ => return,
     };
@@ -88,16 +218,25 @@ fn draw_connection_banner(frame: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_tunnel_list(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
     let total_tunnels = app.tunnels.len() + app.tcp_tunnels.len();
     let is_active = app.is_connected();
 
     if total_tunnels == 0 {
-        let empty_text = if app.is_disconnected() || app.is_reconnecting() {
+        let empty_text = if app.is_offline() {
+            vec![
+                Line::from(""),
+                Line::from(vec![Span::styled(
+                    "  No tunnels in inspect mode. Press Enter to view imported requests. ",
+                    Style::default().fg(theme.text_dim),
+                )]),
+            ]
+        } else if app.is_disconnected() || app.is_reconnecting() {
             vec![
                 Line::from(""),
                 Line::from(vec![Span::styled(
                     "  Waiting for connection... ",
-                    Style::default().fg(Color::DarkGray),
+                    Style::default().fg(theme.text_dim),
                 )]),
             ]
         } else {
@@ -105,12 +244,12 @@ fn draw_tunnel_list(frame: &mut Frame, app: &mut App, area: Rect) {
                 Line::from(""),
                 Line::from(vec![Span::styled(
                     "  No tunnels configured. ",
-                    Style::default().fg(Color::Gray),
+                    Style::default().fg(theme.text_secondary),
                 )]),
                 Line::from(""),
                 Line::from(vec![
                     Span::raw("  Press "),
-                    Span::styled("a", Style::default().fg(Color::Yellow).bold()),
+                    Span::styled("a", Style::default().fg(theme.highlight).bold()),
                     Span::raw(" to add a new tunnel."),
                 ]),
             ]
@@ -124,7 +263,7 @@ fn draw_tunnel_list(frame: &mut Frame, app: &mut App, area: Rect) {
 
     let header_cells = ["TYPE", "LOCAL", "REMOTE"]
         .iter()
-        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow).bold()));
+        .map(|h| Cell::from(*h).style(Style::default().fg(theme.highlight).bold()));
     let header = Row::new(header_cells).height(1).bottom_margin(1);
 
     // Combine HTTP and TCP tunnels into rows, gray out if not connected
@@ -133,41 +272,63 @@ fn draw_tunnel_list(frame: &mut Frame, app: &mut App, area: Rect) {
     for tunnel in &app.tunnels {
         let (type_style, url_style) = if is_active {
             (
-                Style::default().fg(Color::Green),
-                Style::default().fg(Color::Cyan),
+                Style::default().fg(theme.status_success),
+                Style::default().fg(theme.accent),
             )
         } else {
             (
-                Style::default().fg(Color::DarkGray),
-                Style::default().fg(Color::DarkGray),
+                Style::default().fg(theme.text_dim),
+                Style::default().fg(theme.text_dim),
             )
         };
 
+        let mut url = vec![Span::styled(
+            format!("{}{}", pool_prefix(tunnel.pool_id), tunnel.full_url),
+            url_style,
+        )];
+        if !tunnel.tags.is_empty() {
+            url.push(Span::raw(" "));
+            url.extend(tag_pills(&tunnel.tags, &theme));
+        }
         rows.push(Row::new(vec![
             Cell::from("HTTP").style(type_style),
             Cell::from(format!(":{}", tunnel.local_port))
-                .style(Style::default().fg(Color::DarkGray)),
-            Cell::from(tunnel.full_url.clone()).style(url_style),
+                .style(Style::default().fg(theme.text_dim)),
+            Cell::from(Line::from(url)),
         ]));
     }
 
     for tcp in &app.tcp_tunnels {
         let (type_style, url_style) = if is_active {
             (
-                Style::default().fg(Color::Magenta),
-                Style::default().fg(Color::Cyan),
+                Style::default().fg(theme.color(Color::Magenta)),
+                Style::default().fg(theme.accent),
             )
         } else {
             (
-                Style::default().fg(Color::DarkGray),
-                Style::default().fg(Color::DarkGray),
+                Style::default().fg(theme.text_dim),
+                Style::default().fg(theme.text_dim),
             )
         };
 
+        let latency = match app.tcp_connect_latency.get(&tcp.tcp_tunnel_id) {
+            Some(latency) => format!(
+                "  connect avg {}ms / max {}ms",
+                latency.average_ms(),
+                latency.max_ms
+            ),
+            None => String::new(),
+        };
         rows.push(Row::new(vec![
             Cell::from("TCP").style(type_style),
-            Cell::from(format!(":{}", tcp.local_port)).style(Style::default().fg(Color::DarkGray)),
-            Cell::from(format!("server:{}", tcp.server_port)).style(url_style),
+            Cell::from(format!(":{}", tcp.local_port)).style(Style::default().fg(theme.text_dim)),
+            Cell::from(format!(
+                "{}server:{}{}",
+                pool_prefix(tcp.pool_id),
+                tcp.server_port,
+                latency
+            ))
+            .style(url_style),
         ]));
     }
 
@@ -177,59 +338,280 @@ fn draw_tunnel_list(frame: &mut Frame, app: &mut App, area: Rect) {
         Constraint::Min(20),
     ];
 
+    // The selected tunnel's last error, in full
+    let mut block = Block::default().borders(Borders::ALL).title(" Tunnels ");
+    let selected_error = app
+        .tunnel_list_state
+        .selected()
+        .and_then(|i| app.tunnels.get(i))
+        .and_then(|tunnel| app.tunnel_errors.get(&tunnel.tunnel_id));
+    if let Some(error) = selected_error {
+        block = block.title_bottom(Span::styled(
+            format!(
+                " {} {}: {} ",
+                format_absolute_time(error.at),
+                error.code,
+                error.message
+            ),
+            Style::default().fg(theme.status_error),
+        ));
+    }
+
     let table = Table::new(rows, widths)
         .header(header)
-        .block(Block::default().borders(Borders::ALL).title(" Tunnels "))
+        .block(block)
         .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
         .highlight_symbol("► ");
 
     frame.render_stateful_widget(table, area, &mut app.tunnel_list_state);
 }
 
+/// `[P1] ` etc. for tunnels owned by a client in a connection pool
+fn pool_prefix(pool_id: u8) -> String {
+    if pool_id == 0 {
+        String::new()
+    } else {
+        format!("[P{}] ", pool_id)
+    }
+}
+
+/// ` +N headers` for tunnels that add custom headers to forwarded requests
+fn extra_headers_badge(count: usize) -> String {
+    match count {
+        0 => String::new(),
+        1 => " +1 header".to_string(),
+        n => format!(" +{} headers", n),
+    }
+}
+
 fn draw_tunnel_list_help(frame: &mut Frame, app: &App, area: Rect) {
-    let help_text = if app.is_disconnected() {
-        Line::from(vec![
-            Span::styled(" q ", Style::default().fg(Color::Yellow)),
+    let theme = app.theme;
+    if let Some(path) = &app.tunnel_path_input {
+        let input = Line::from(vec![
+            Span::styled(" Open path: ", Style::default().fg(theme.highlight)),
+            Span::raw(format!("{}█ ", path)),
+            Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+            Span::raw("Open "),
+            Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+            Span::raw("Cancel"),
+        ]);
+        let editor = Paragraph::new(input).block(Block::default().borders(Borders::TOP));
+        frame.render_widget(editor, area);
+        return;
+    }
+    draw_help_footer(frame, app, Line::from(help_text_for_mode(app)), area);
+}
+
+/// Key hints for the footer of the views whose keys depend on the
+/// connection or the focused field
+fn help_text_for_mode(app: &App) -> Vec<Span<'static>> {
+    match app.view_mode {
+        ViewMode::TunnelList => tunnel_list_help(app),
+        ViewMode::AddTunnel => add_tunnel_help(app),
+        ViewMode::RequestDetail => request_detail_help(app),
+        _ => Vec::new(),
+    }
+}
+
+/// Footer height for `help_text_for_mode`: a second line of hints when they
+/// don't fit on one
+fn help_footer_height(app: &App, width: u16) -> u16 {
+    let hints: usize = help_text_for_mode(app).iter().map(Span::width).sum();
+    if app.notification.is_none() && hints > width as usize {
+        3
+    } else {
+        2
+    }
+}
+
+fn tunnel_list_help(app: &App) -> Vec<Span<'static>> {
+    let theme = app.theme;
+    if app.is_disconnected() {
+        return vec![
+            Span::styled(" r ", Style::default().fg(theme.highlight)),
+            Span::raw("Retry "),
+            Span::styled(" q ", Style::default().fg(theme.highlight)),
             Span::raw("Quit"),
-        ])
-    } else if app.is_reconnecting() {
-        Line::from(vec![
-            Span::styled(" q ", Style::default().fg(Color::Yellow)),
+        ];
+    }
+    let help_text = if app.is_reconnecting() {
+        vec![
+            Span::styled(" q ", Style::default().fg(theme.highlight)),
             Span::raw("Quit "),
-            Span::styled("(reconnecting...)", Style::default().fg(Color::DarkGray)),
-        ])
+            Span::styled("(reconnecting...)", Style::default().fg(theme.text_dim)),
+        ]
+    } else if app.is_offline() {
+        let disabled = Style::default().fg(theme.text_dim);
+        vec![
+            Span::styled(" a ", disabled),
+            Span::styled("Add tunnel ", disabled),
+            Span::styled(" Enter/Tab ", Style::default().fg(theme.highlight)),
+            Span::raw("View requests "),
+            Span::styled(" q ", Style::default().fg(theme.highlight)),
+            Span::raw("Quit"),
+        ]
     } else {
-        Line::from(vec![
-            Span::styled(" a ", Style::default().fg(Color::Yellow)),
+        vec![
+            Span::styled(" a ", Style::default().fg(theme.highlight)),
             Span::raw("Add tunnel "),
-            Span::styled(" Enter ", Style::default().fg(Color::Yellow)),
+            Span::styled(" o/O ", Style::default().fg(theme.highlight)),
+            Span::raw("Open "),
+            Span::styled(" y ", Style::default().fg(theme.highlight)),
+            Span::raw("Copy URL "),
+            Span::styled(" S ", Style::default().fg(theme.highlight)),
+            Span::raw("Server "),
+            Span::styled(" Enter/Tab ", Style::default().fg(theme.highlight)),
             Span::raw("View requests "),
-            Span::styled(" j/k ", Style::default().fg(Color::Yellow)),
+            Span::styled(" j/k ", Style::default().fg(theme.highlight)),
             Span::raw("Navigate "),
-            Span::styled(" q ", Style::default().fg(Color::Yellow)),
+            Span::styled(" q ", Style::default().fg(theme.highlight)),
             Span::raw("Quit"),
-        ])
+        ]
     };
 
-    let help = Paragraph::new(help_text).block(Block::default().borders(Borders::TOP));
+    let mut spans = error_log_key(app);
+    spans.extend([
+        Span::styled(" h ", Style::default().fg(theme.highlight)),
+        Span::raw("History "),
+        Span::styled(" s ", Style::default().fg(theme.highlight)),
+        Span::raw("Status-details "),
+    ]);
+    spans.extend(help_text);
+    spans
+}
+
+fn add_tunnel_help(app: &App) -> Vec<Span<'static>> {
+    let theme = app.theme;
+    let mut spans = match app.add_tunnel_field {
+        AddTunnelField::TunnelType => vec![
+            Span::styled(" Space ", Style::default().fg(theme.highlight)),
+            Span::raw("Toggle HTTP/TCP "),
+        ],
+        AddTunnelField::Port => vec![
+            Span::styled(" 0-9 ", Style::default().fg(theme.highlight)),
+            Span::raw("Local port "),
+        ],
+        AddTunnelField::Subdomain => vec![Span::styled(
+            " Optional, random if empty ",
+            Style::default().fg(theme.text_dim),
+        )],
+        AddTunnelField::Tags => vec![Span::styled(
+            " Comma-separated, e.g. staging, team:api ",
+            Style::default().fg(theme.text_dim),
+        )],
+        AddTunnelField::Headers => vec![
+            Span::styled(" Header-Name: Value ", Style::default().fg(theme.text_dim)),
+            Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+            Span::raw("New line (twice to create) "),
+        ],
+    };
+    spans.extend([
+        Span::styled(" Tab/↓ ", Style::default().fg(theme.highlight)),
+        Span::raw("Next field "),
+        Span::styled(" ⇧Tab/↑ ", Style::default().fg(theme.highlight)),
+        Span::raw("Previous "),
+    ]);
+    if app.add_tunnel_field != AddTunnelField::Headers {
+        spans.extend([
+            Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+            Span::raw("Create "),
+        ]);
+    }
+    spans.extend([
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+        Span::raw("Cancel"),
+    ]);
+    spans
+}
+
+fn request_detail_help(app: &App) -> Vec<Span<'static>> {
+    let theme = app.theme;
+    let tab = |label: &'static str, active: bool| {
+        if active {
+            Span::styled(
+                format!("[{}]", label),
+                Style::default().fg(theme.accent).bold(),
+            )
+        } else {
+            Span::styled(label, Style::default().fg(theme.text_dim))
+        }
+    };
+    vec![
+        Span::raw(" "),
+        tab("Overview", app.detail_tab == DetailTab::Overview),
+        Span::raw(" "),
+        tab("Raw", app.detail_tab == DetailTab::Raw),
+        Span::styled(" Ctrl-R ", Style::default().fg(theme.highlight)),
+        Span::raw("Switch tab "),
+        Span::styled(" N ", Style::default().fg(theme.highlight)),
+        Span::raw("Note "),
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+        Span::raw("Back "),
+        Span::styled(" q ", Style::default().fg(theme.highlight)),
+        Span::raw("Quit"),
+    ]
+}
+
+/// Help entry for the error log, highlighted while there are unseen errors
+fn error_log_key(app: &App) -> Vec<Span<'static>> {
+    let theme = app.theme;
+    if app.unseen_errors > 0 {
+        vec![
+            Span::styled(" ! ", Style::default().fg(theme.status_error).bold()),
+            Span::styled(
+                format!("Errors ({}) ", app.unseen_errors),
+                Style::default().fg(theme.status_error),
+            ),
+        ]
+    } else {
+        vec![
+            Span::styled(" ! ", Style::default().fg(theme.highlight)),
+            Span::raw("Errors "),
+        ]
+    }
+}
+
+/// Render a help footer, or the pending notification in its place
+fn draw_help_footer(frame: &mut Frame, app: &App, help_text: Line, area: Rect) {
+    let theme = app.theme;
+    let line = match &app.notification {
+        Some(message) => {
+            let color = match app.notification_level {
+                NotificationLevel::Info => theme.accent,
+                NotificationLevel::Warn => theme.highlight,
+                NotificationLevel::Error => theme.status_error,
+            };
+            Line::from(Span::styled(
+                format!(" {} ", message),
+                Style::default().fg(color),
+            ))
+        }
+        None => help_text,
+    };
+
+    let help = Paragraph::new(line)
+        .block(Block::default().borders(Borders::TOP))
+        .wrap(Wrap { trim: true });
     frame.render_widget(help, area);
 }
 
 fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3),  // Status bar
-            Constraint::Length(12), // Form
-            Constraint::Min(1),     // Spacer
-            Constraint::Length(2),  // Help footer
+            Constraint::Length(3),                                           // Status bar
+            Constraint::Length(18),                                          // Form
+            Constraint::Min(1),                                              // Spacer
+            Constraint::Length(help_footer_height(app, frame.area().width)), // Help footer
         ])
         .split(frame.area());
 
     draw_status_bar(frame, app, chunks[0]);
 
-    // Form area - center it
-    let form_area = centered_rect(50, 10, chunks[1]);
+    // Form area - center it, growing with the header lines
+    let header_lines = app.add_tunnel_headers.split('\n').count() as u16;
+    let form_area = centered_rect(50, (13 + header_lines).min(20), chunks[1]);
 
     let type_label = match app.add_tunnel_type {
         TunnelType::Http => "[ HTTP ]  TCP  ",
@@ -237,21 +619,21 @@ fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
     };
 
     let type_style = if app.add_tunnel_field == AddTunnelField::TunnelType {
-        Style::default().fg(Color::Yellow).bold()
+        Style::default().fg(theme.highlight).bold()
     } else {
-        Style::default().fg(Color::White)
+        Style::default().fg(theme.text_primary)
     };
 
     let port_style = if app.add_tunnel_field == AddTunnelField::Port {
-        Style::default().fg(Color::Yellow).bold()
+        Style::default().fg(theme.highlight).bold()
     } else {
-        Style::default().fg(Color::White)
+        Style::default().fg(theme.text_primary)
     };
 
     let subdomain_style = if app.add_tunnel_field == AddTunnelField::Subdomain {
-        Style::default().fg(Color::Yellow).bold()
+        Style::default().fg(theme.highlight).bold()
     } else {
-        Style::default().fg(Color::Gray)
+        Style::default().fg(theme.text_secondary)
     };
 
     let port_cursor = if app.add_tunnel_field == AddTunnelField::Port {
@@ -268,15 +650,29 @@ fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
         ""
     };
 
+    let tags_focused = app.add_tunnel_field == AddTunnelField::Tags;
+    let tags_style = if tags_focused {
+        Style::default().fg(theme.highlight).bold()
+    } else {
+        Style::default().fg(theme.text_secondary)
+    };
+
+    let headers_focused = app.add_tunnel_field == AddTunnelField::Headers;
+    let headers_style = if headers_focused {
+        Style::default().fg(theme.highlight).bold()
+    } else {
+        Style::default().fg(theme.text_secondary)
+    };
+
     let mut form_lines = vec![
         Line::from(""),
         Line::from(vec![
-            Span::styled("  Type:      ", Style::default().fg(Color::Gray)),
+            Span::styled("  Type:      ", Style::default().fg(theme.text_secondary)),
             Span::styled(type_label, type_style),
         ]),
         Line::from(""),
         Line::from(vec![
-            Span::styled("  Port:      ", Style::default().fg(Color::Gray)),
+            Span::styled("  Port:      ", Style::default().fg(theme.text_secondary)),
             Span::styled(
                 format!("{}{}", app.add_tunnel_port, port_cursor),
                 port_style,
@@ -287,7 +683,7 @@ fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
     if app.add_tunnel_type == TunnelType::Http {
         form_lines.push(Line::from(""));
         form_lines.push(Line::from(vec![
-            Span::styled("  Subdomain: ", Style::default().fg(Color::Gray)),
+            Span::styled("  Subdomain: ", Style::default().fg(theme.text_secondary)),
             Span::styled(
                 format!(
                     "{}{}",
@@ -301,6 +697,50 @@ fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
                 subdomain_style,
             ),
         ]));
+
+        form_lines.push(Line::from(""));
+        form_lines.push(Line::from(vec![
+            Span::styled("  Tags:      ", Style::default().fg(theme.text_secondary)),
+            Span::styled(
+                format!(
+                    "{}{}",
+                    if app.add_tunnel_tags.is_empty() && !tags_focused {
+                        "(none)"
+                    } else {
+                        &app.add_tunnel_tags
+                    },
+                    if tags_focused { "█" } else { "" }
+                ),
+                tags_style,
+            ),
+        ]));
+
+        form_lines.push(Line::from(""));
+        if app.add_tunnel_headers.is_empty() && !headers_focused {
+            form_lines.push(Line::from(vec![
+                Span::styled("  Headers:   ", Style::default().fg(theme.text_secondary)),
+                Span::styled("(none)", headers_style),
+            ]));
+        } else {
+            let lines: Vec<&str> = app.add_tunnel_headers.split('\n').collect();
+            let last = lines.len() - 1;
+            for (i, line) in lines.into_iter().enumerate() {
+                let label = if i == 0 {
+                    "  Headers:   "
+                } else {
+                    "             "
+                };
+                let cursor = if headers_focused && i == last {
+                    "█"
+                } else {
+                    ""
+                };
+                form_lines.push(Line::from(vec![
+                    Span::styled(label, Style::default().fg(theme.text_secondary)),
+                    Span::styled(format!("{}{}", line, cursor), headers_style),
+                ]));
+            }
+        }
     }
 
     // Show error if any
@@ -308,7 +748,7 @@ fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
         form_lines.push(Line::from(""));
         form_lines.push(Line::from(vec![Span::styled(
             format!("  Error: {}", error),
-            Style::default().fg(Color::Red),
+            Style::default().fg(theme.status_error),
         )]));
     }
 
@@ -316,19 +756,95 @@ fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
         Block::default()
             .borders(Borders::ALL)
             .title(" Add Tunnel ")
-            .border_style(Style::default().fg(Color::Cyan)),
+            .border_style(Style::default().fg(theme.accent)),
+    );
+    frame.render_widget(form, form_area);
+
+    draw_help_footer(frame, app, Line::from(help_text_for_mode(app)), chunks[3]);
+}
+
+fn draw_switch_server_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),  // Status bar
+            Constraint::Length(10), // Form
+            Constraint::Min(1),     // Spacer
+            Constraint::Length(2),  // Help footer
+        ])
+        .split(frame.area());
+
+    draw_status_bar(frame, app, chunks[0]);
+
+    let form_area = centered_rect(50, 8, chunks[1]);
+
+    let field_style = |field| {
+        if app.switch_server_field == field {
+            Style::default().fg(theme.highlight).bold()
+        } else {
+            Style::default().fg(theme.text_primary)
+        }
+    };
+    let cursor = |field| {
+        if app.switch_server_field == field {
+            "█"
+        } else {
+            ""
+        }
+    };
+
+    let token = if app.switch_server_token.is_empty() {
+        "(keep current)".to_string()
+    } else {
+        "•".repeat(app.switch_server_token.chars().count())
+    };
+
+    let mut form_lines = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("  Server: ", Style::default().fg(theme.text_secondary)),
+            Span::styled(
+                format!(
+                    "{}{}",
+                    app.switch_server_host,
+                    cursor(SwitchServerField::Host)
+                ),
+                field_style(SwitchServerField::Host),
+            ),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("  Token:  ", Style::default().fg(theme.text_secondary)),
+            Span::styled(
+                format!("{}{}", token, cursor(SwitchServerField::Token)),
+                field_style(SwitchServerField::Token),
+            ),
+        ]),
+    ];
+
+    if let Some(ref error) = app.switch_server_error {
+        form_lines.push(Line::from(""));
+        form_lines.push(Line::from(vec![Span::styled(
+            format!("  Error: {}", error),
+            Style::default().fg(theme.status_error),
+        )]));
+    }
+
+    let form = Paragraph::new(form_lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Switch Server ")
+            .border_style(Style::default().fg(theme.accent)),
     );
     frame.render_widget(form, form_area);
 
-    // Help footer
     let help_text = Line::from(vec![
-        Span::styled(" Tab/↓ ", Style::default().fg(Color::Yellow)),
+        Span::styled(" Tab/↓ ", Style::default().fg(theme.highlight)),
         Span::raw("Next field "),
-        Span::styled(" Space ", Style::default().fg(Color::Yellow)),
-        Span::raw("Toggle type "),
-        Span::styled(" Enter ", Style::default().fg(Color::Yellow)),
-        Span::raw("Create "),
-        Span::styled(" Esc ", Style::default().fg(Color::Yellow)),
+        Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+        Span::raw("Switch "),
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
         Span::raw("Cancel"),
     ]);
 
@@ -336,6 +852,52 @@ fn draw_add_tunnel_view(frame: &mut Frame, app: &mut App) {
     frame.render_widget(help, chunks[3]);
 }
 
+/// Token prompt drawn over the tunnel list, shown when the token expired
+fn draw_token_prompt(frame: &mut Frame, app: &mut App) {
+    draw_tunnel_list_view(frame, app);
+    let theme = app.theme;
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "  The API token expired. Enter a new one to reconnect.",
+            Style::default().fg(theme.text_secondary),
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("  Token: ", Style::default().fg(theme.text_secondary)),
+            Span::styled(
+                format!("{}█", "•".repeat(app.token_input.chars().count())),
+                Style::default().fg(theme.highlight).bold(),
+            ),
+        ]),
+    ];
+    if let Some(ref error) = app.token_error {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("  Error: {}", error),
+            Style::default().fg(theme.status_error),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("  Enter ", Style::default().fg(theme.highlight)),
+        Span::raw("Reconnect "),
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+        Span::raw("Later"),
+    ]));
+
+    let area = centered_rect(60, 10, frame.area());
+    let prompt = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Token Expired ")
+            .border_style(Style::default().fg(theme.status_error)),
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(prompt, area);
+}
+
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -368,48 +930,102 @@ fn draw_request_list_view(frame: &mut Frame, app: &mut App) {
 
     draw_status_bar(frame, app, chunks[0]);
     draw_request_list(frame, app, chunks[1]);
-    draw_request_list_help(frame, chunks[2]);
+    draw_request_list_help(frame, app, chunks[2]);
 }
 
-fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
-    let status_color = match &app.connection_status {
-        ConnectionStatus::Connected => Color::Green,
-        ConnectionStatus::Connecting => Color::Yellow,
-        ConnectionStatus::Reconnecting { .. } => Color::Yellow,
-        ConnectionStatus::Disconnected { .. } => Color::Red,
-    };
+fn connection_status_color(status: &ConnectionStatus, theme: &Theme) -> Color {
+    match status {
+        ConnectionStatus::Connected => theme.status_success,
+        ConnectionStatus::Connecting => theme.status_warning,
+        ConnectionStatus::Reconnecting { .. } => theme.status_warning,
+        ConnectionStatus::Disconnected { .. } => theme.status_error,
+        ConnectionStatus::Offline { .. } | ConnectionStatus::LocalProxy { .. } => theme.text_dim,
+    }
+}
 
-    let mut status_parts = vec![
-        Span::styled(" burrow ", Style::default().fg(Color::Cyan).bold()),
-        Span::raw("│ "),
-        Span::styled(
-            format!("{}", app.connection_status),
-            Style::default().fg(status_color).bold(),
-        ),
-    ];
+/// Signal-strength bars and colour for the connection quality
+fn connection_quality_icon(quality: ConnectionQuality, theme: &Theme) -> (&'static str, Color) {
+    match quality {
+        ConnectionQuality::Excellent => ("▁▂▃▄", theme.status_success),
+        ConnectionQuality::Good => ("▁▂▃ ", theme.status_warning),
+        ConnectionQuality::Fair => ("▁▂  ", theme.color(Color::Rgb(255, 165, 0))),
+        ConnectionQuality::Poor => ("▁   ", theme.status_error),
+    }
+}
 
-    // Show tunnel URLs
-    for tunnel in &app.tunnels {
-        status_parts.push(Span::raw(" │ "));
-        status_parts.push(Span::styled(
-            format!("{} → :{}", tunnel.full_url, tunnel.local_port),
-            Style::default().fg(Color::Green),
+fn draw_status_bar(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
+    let mut groups: Vec<(Option<StatusBarSlot>, Vec<Span>)> = app
+        .status_bar_slots
+        .iter()
+        .map(|&slot| (Some(slot), status_bar_slot(app, slot)))
+        .filter(|(_, spans)| !spans.is_empty())
+        .collect();
+
+    if app.events_dropped > 0 {
+        groups.push((
+            None,
+            vec![Span::styled(
+                format!("⚠ Drops: {}", app.events_dropped),
+                Style::default().fg(theme.highlight).bold(),
+            )],
         ));
     }
 
-    for tcp in &app.tcp_tunnels {
-        status_parts.push(Span::raw(" │ "));
+    let mut status_parts = vec![Span::styled(
+        " burrow ",
+        Style::default().fg(theme.accent).bold(),
+    )];
+    if app.recording_macro.is_some() {
         status_parts.push(Span::styled(
-            format!("tcp:{} → :{}", tcp.server_port, tcp.local_port),
-            Style::default().fg(Color::Magenta),
+            "[REC] ",
+            Style::default().fg(theme.status_error).bold(),
         ));
     }
+    let available = area.width.saturating_sub(2) as usize;
+    let mut used: usize = status_parts.iter().map(Span::width).sum();
 
-    status_parts.push(Span::raw(" │ "));
-    status_parts.push(Span::styled(
-        format!("Reqs: {}", app.requests.len()),
-        Style::default().fg(Color::White),
-    ));
+    // Tunnels give way to the other slots, showing as many as still fit
+    let tunnel_slot = groups
+        .iter()
+        .position(|(slot, _)| *slot == Some(StatusBarSlot::TunnelUrl));
+    if let Some(tunnel_slot) = tunnel_slot {
+        let others: usize = groups
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != tunnel_slot)
+            .map(|(_, (_, group))| 3 + group.iter().map(Span::width).sum::<usize>())
+            .sum();
+        let budget = available.saturating_sub(used + others + 3);
+        groups[tunnel_slot].1 = truncate_status_spans(tunnel_status_spans(app), budget, &theme);
+    }
+
+    // Drop slots from the right rather than wrapping or clipping mid-slot
+    for (i, (_, group)) in groups.into_iter().enumerate() {
+        let separator = if i == 0 { "│ " } else { " │ " };
+        let width = separator.chars().count() + group.iter().map(Span::width).sum::<usize>();
+        if used + width > available {
+            break;
+        }
+        used += width;
+        status_parts.push(Span::raw(separator));
+        status_parts.extend(group);
+    }
+
+    // Remember where tunnel URLs ended up so clicking one opens it
+    let mut x = area.x + 1;
+    for span in &status_parts {
+        for tunnel in &app.tunnels {
+            if let Some(start) = span.content.find(&tunnel.full_url) {
+                let offset = span.content[..start].chars().count() as u16;
+                let width = tunnel.full_url.chars().count() as u16;
+                let link = Rect::new(x.saturating_add(offset), area.y + 1, width, 1);
+                app.status_bar_links
+                    .push((link.intersection(area), tunnel.full_url.clone()));
+            }
+        }
+        x = x.saturating_add(span.width() as u16);
+    }
 
     let status_line = Line::from(status_parts);
     let status =
@@ -418,91 +1034,980 @@ fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(status, area);
 }
 
-fn draw_request_list(frame: &mut Frame, app: &mut App, area: Rect) {
-    let header_cells = ["TIME", "METHOD", "PATH", "STATUS", "DURATION"]
-        .iter()
-        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow).bold()));
-    let header = Row::new(header_cells).height(1).bottom_margin(1);
+/// Spans for one status bar slot; empty if there is nothing to show
+fn status_bar_slot(app: &App, slot: StatusBarSlot) -> Vec<Span<'static>> {
+    let theme = app.theme;
+    let white = Style::default().fg(theme.text_primary);
+    match slot {
+        StatusBarSlot::ConnectionStatus => {
+            let status_color = connection_status_color(&app.connection_status, &theme);
+            let status_text = match (&app.connection_status, app.reconnect_countdown()) {
+                (
+                    ConnectionStatus::Reconnecting {
+                        attempt,
+                        max_attempts,
+                        ..
+                    },
+                    Some(secs),
+                ) => format!(
+                    "Reconnecting in {}s (attempt {}/{})",
+                    secs, attempt, max_attempts
+                ),
+                (ConnectionStatus::Connected, _) => match app.server_address {
+                    Some(addr) if addr.is_ipv6() => "Connected (IPv6)".to_string(),
+                    Some(_) => "Connected (IPv4)".to_string(),
+                    None => ConnectionStatus::Connected.to_string(),
+                },
+                (status, _) => status.to_string(),
+            };
 
-    let rows = app.requests.iter().map(|req| {
-        let method_style = method_color(&req.method);
-        let status_style = status_color(req.status);
-        let duration = req
-            .duration_ms
-            .map(|d| format!("{}ms", d))
-            .unwrap_or_else(|| "...".to_string());
-        let timestamp = req.timestamp.format("%H:%M:%S").to_string();
+            let mut spans = vec![Span::styled(
+                status_text,
+                Style::default().fg(status_color).bold(),
+            )];
+            if let (ConnectionStatus::Connected, Some(quality)) =
+                (&app.connection_status, app.connection_quality)
+            {
+                let (bars, color) = connection_quality_icon(quality, &theme);
+                spans.push(Span::styled(
+                    format!(" {}", bars),
+                    Style::default().fg(color),
+                ));
+            }
+            if app.no_tls {
+                spans.push(Span::styled(
+                    " ⚠ No TLS",
+                    Style::default().fg(theme.status_error).bold(),
+                ));
+            }
+            // Other pooled connections are only mentioned when they are down
+            for (pool_id, status) in &app.pool_statuses {
+                if *status != ConnectionStatus::Connected {
+                    spans.push(Span::styled(
+                        format!(" {}{}", pool_prefix(*pool_id), status),
+                        Style::default().fg(connection_status_color(status, &theme)),
+                    ));
+                }
+            }
+            spans
+        }
+        StatusBarSlot::TunnelUrl => {
+            truncate_status_spans(tunnel_status_spans(app), usize::MAX, &theme)
+        }
+        StatusBarSlot::RequestCount => {
+            let text = if !app.is_filtered() {
+                format!("Reqs: {}", app.requests.len())
+            } else {
+                format!("Reqs: {}/{}", app.visible_count(), app.requests.len())
+            };
+            vec![Span::styled(text, white)]
+        }
+        StatusBarSlot::BytesIn => vec![Span::styled(
+            format!("In: {}", format_size(app.total_bytes_in)),
+            white,
+        )],
+        StatusBarSlot::BytesOut => vec![Span::styled(
+            format!("Out: {}", format_size(app.total_bytes_out)),
+            white,
+        )],
+        StatusBarSlot::Rtt => match (app.average_rtt_ms(), app.heartbeat_rtt_ms) {
+            (Some(rtt), Some(app_rtt)) => vec![Span::styled(
+                format!("RTT: {}ms (app {}ms)", rtt, app_rtt),
+                white,
+            )],
+            (Some(rtt), None) => vec![Span::styled(format!("RTT: {}ms", rtt), white)],
+            (None, Some(app_rtt)) => {
+                vec![Span::styled(format!("RTT: app {}ms", app_rtt), white)]
+            }
+            (None, None) => Vec::new(),
+        },
+        StatusBarSlot::ErrorRate => app
+            .error_rate()
+            .map(|rate| {
+                let color = if rate > 0.0 {
+                    theme.status_error
+                } else {
+                    theme.text_primary
+                };
+                vec![Span::styled(
+                    format!("Errors: {:.1}%", rate * 100.0),
+                    Style::default().fg(color),
+                )]
+            })
+            .unwrap_or_default(),
+        StatusBarSlot::Latency => app
+            .average_latency_ms()
+            .map(|ms| vec![Span::styled(format!("Avg: {}ms", ms), white)])
+            .unwrap_or_default(),
+        StatusBarSlot::QueueDepth if app.message_queue_depth > 10 => {
+            let color = if app.message_queue_depth > 100 {
+                theme.status_error
+            } else {
+                theme.color(Color::Yellow)
+            };
+            vec![Span::styled(
+                format!("Q: {}", app.message_queue_depth),
+                Style::default().fg(color),
+            )]
+        }
+        StatusBarSlot::QueueDepth => Vec::new(),
+    }
+}
 
-        Row::new(vec![
-            Cell::from(timestamp).style(Style::default().fg(Color::DarkGray)),
-            Cell::from(req.method.clone()).style(method_style),
-            Cell::from(truncate_path(&req.path, 40)),
-            Cell::from(
-                req.status
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| "...".to_string()),
-            )
-            .style(status_style),
-            Cell::from(duration),
-        ])
+/// Spans for each HTTP and TCP tunnel in the status bar
+fn tunnel_status_spans(app: &App) -> Vec<Vec<Span<'static>>> {
+    let theme = app.theme;
+    let http = app.tunnels.iter().map(|tunnel| {
+        let mut spans = vec![Span::styled(
+            format!(
+                "{}{} → :{}{}",
+                pool_prefix(tunnel.pool_id),
+                tunnel.full_url,
+                tunnel.local_port,
+                extra_headers_badge(tunnel.extra_header_count)
+            ),
+            Style::default().fg(theme.status_success),
+        )];
+        if let Some(error) = app.tunnel_errors.get(&tunnel.tunnel_id) {
+            spans.push(Span::styled(
+                format!(" [ERR: {}]", error.code),
+                Style::default().fg(theme.status_error).bold(),
+            ));
+        }
+        spans
+    });
+    let tcp = app.tcp_tunnels.iter().map(|tcp| {
+        vec![Span::styled(
+            format!(
+                "{}tcp:{} → :{}",
+                pool_prefix(tcp.pool_id),
+                tcp.server_port,
+                tcp.local_port
+            ),
+            Style::default().fg(theme.color(Color::Magenta)),
+        )]
     });
+    http.chain(tcp).collect()
+}
 
-    let widths = [
-        Constraint::Length(10),
-        Constraint::Length(8),
-        Constraint::Min(20),
-        Constraint::Length(8),
-        Constraint::Length(10),
-    ];
+/// Join the tunnels' spans, keeping as many whole tunnels as fit in
+/// `max_width` and ending with ` +N more` for the rest
+fn truncate_status_spans<'a>(
+    tunnels: Vec<Vec<Span<'a>>>,
+    max_width: usize,
+    theme: &Theme,
+) -> Vec<Span<'a>> {
+    const SEPARATOR: &str = " │ ";
+    let total = tunnels.len();
+    let mut spans = Vec::new();
+    let mut used = 0;
+    for (i, tunnel) in tunnels.into_iter().enumerate() {
+        let separator = if i == 0 { 0 } else { SEPARATOR.chars().count() };
+        let width = separator + tunnel.iter().map(Span::width).sum::<usize>();
+        let rest = total - i - 1;
+        let marker = if rest > 0 {
+            format!(" +{} more", rest).len()
+        } else {
+            0
+        };
+        if used + width + marker > max_width {
+            let hidden = total - i;
+            let text = if i == 0 {
+                format!("{} tunnels", hidden)
+            } else {
+                format!(" +{} more", hidden)
+            };
+            spans.push(Span::styled(text, Style::default().fg(theme.text_dim)));
+            break;
+        }
+        if i > 0 {
+            spans.push(Span::raw(SEPARATOR));
+        }
+        spans.extend(tunnel);
+        used += width;
+    }
+    spans
+}
 
+fn draw_request_list(frame: &mut Frame, app: &mut App, area: Rect) {
+    let theme = app.theme;
+    // Borders, the highlight symbol and the spaces between columns
+    let spacing = 4 + app.request_list_columns.len().saturating_sub(1) as u16;
+    let widths = app.request_column_widths(area.width.saturating_sub(spacing));
+    // Fixed columns carry their adjusted width so cells are truncated to it
+    let columns: Vec<RequestListColumnConfig> = app
+        .request_list_columns
+        .iter()
+        .zip(&widths)
+        .map(|(c, width)| RequestListColumnConfig {
+            column: c.column,
+            width: match width {
+                Constraint::Length(width) => Some(*width),
+                _ => None,
+            },
+        })
+        .collect();
+    let selected_column = app.selected_column;
+    let header_cells = columns.iter().enumerate().map(|(i, c)| {
+        let style = Style::default().fg(theme.highlight).bold();
+        let style = if i == selected_column {
+            style.underlined()
+        } else {
+            style
+        };
+        Cell::from(c.column.title()).style(style)
+    });
+    let density = app.display_density;
+    let header = Row::new(header_cells)
+        .height(1)
+        .bottom_margin(density.header_margin());
+
+    app.compute_visible_window(area.height);
+    let window = app.visible_window.clone();
+
+    let now = Local::now();
+    let time_display = app.time_display;
+    let list = app.filtered_list();
+    let list_rows = &list.rows;
+    let request_at = |index: usize| &app.requests[list.indices[index]];
+    let request_row = |req: &RequestLog, in_group: bool, base: Style| {
+        let bookmarked = app.is_bookmarked(req);
+        let method_style = theme.method_style(&req.method);
+        let status_style = theme.status_style(req.status);
+        let duration = req
+            .duration_ms
+            .map(|d| format!("{}ms", d))
+            .unwrap_or_else(|| "...".to_string());
+        let timestamp = match time_display {
+            TimeDisplay::Relative => format_relative_time(req.timestamp, now),
+            TimeDisplay::Absolute | TimeDisplay::Both => format_absolute_time(req.timestamp),
+        };
+        let timestamp = if bookmarked {
+            format!("★ {}", timestamp)
+        } else {
+            timestamp
+        };
+
+        let diff_base = app.diff_base.as_ref() == Some(&req.id);
+        let row = Row::new(columns.iter().map(|c| {
+            match c.column {
+                RequestListColumn::Time => {
+                    Cell::from(timestamp.clone()).style(Style::default().fg(theme.text_dim))
+                }
+                RequestListColumn::Method => Cell::from(req.method.clone()).style(method_style),
+                RequestListColumn::Path => {
+                    let indent = if in_group { "  " } else { "" };
+                    let width = c.width.map_or(40, usize::from).saturating_sub(indent.len());
+                    let path = format!("{}{}", indent, truncate_path(&req.path, width));
+                    match density {
+                        DisplayDensity::Comfortable => Cell::from(Text::from(vec![
+                            Line::raw(path),
+                            Line::styled(
+                                format!("{}{}", indent, body_snippet(req)),
+                                Style::default().fg(theme.text_dim),
+                            ),
+                        ])),
+                        DisplayDensity::Compact | DisplayDensity::Normal => Cell::from(path),
+                    }
+                }
+                RequestListColumn::Status => Cell::from(match req.status {
+                    Some(status) if req.is_event_stream() => format!("{} SSE", status),
+                    Some(status) => status.to_string(),
+                    None => "...".to_string(),
+                })
+                .style(status_style),
+                RequestListColumn::Duration => Cell::from(duration.clone()),
+                RequestListColumn::Size => size_cell(req, &theme),
+                RequestListColumn::Note => {
+                    Cell::from(if app.note(req).is_some() { "📝" } else { "" })
+                }
+                RequestListColumn::ClientIp => {
+                    Cell::from(req.client_ip.clone().unwrap_or_else(|| "-".to_string()))
+                }
+                RequestListColumn::Tunnel => Cell::from(
+                    req.tunnel_url
+                        .as_deref()
+                        .map(|url| url.split_once("://").map_or(url, |(_, host)| host))
+                        .unwrap_or("-")
+                        .to_string(),
+                ),
+                RequestListColumn::Tags => tags_cell(req, &theme),
+            }
+        }))
+        .height(density.row_height());
+        if diff_base {
+            // Second cursor while the other side of the diff is picked
+            row.style(
+                base.fg(theme.highlight)
+                    .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            )
+        } else if bookmarked {
+            row.style(Style::default().bg(theme.accent))
+        } else {
+            row.style(base)
+        }
+    };
+    let rows = list_rows[window.clone()]
+        .iter()
+        .zip(window.clone())
+        .map(|(row, i)| {
+            // Stripe by position in the whole list so rows keep their colour
+            // while scrolling
+            let base = if i % 2 == 1 {
+                Style::default().bg(theme.row_alt_background)
+            } else {
+                Style::default()
+            };
+            match row {
+                ListRow::Request { index, in_group } => {
+                    request_row(request_at(*index), *in_group, base)
+                }
+                ListRow::Group {
+                    key,
+                    requests: range,
+                    expanded,
+                } => {
+                    let requests: Vec<&RequestLog> = range.clone().map(request_at).collect();
+                    group_row(&requests, key, *expanded, &columns, app, now).style(base)
+                }
+            }
+        });
+
+    let kind = if app.bookmarks_only {
+        "Bookmarked requests"
+    } else {
+        "Requests"
+    };
+    let mut title = if app.search_query.is_empty() {
+        format!(" {} ", kind)
+    } else {
+        format!(" {} matching \"{}\" ", kind, app.search_query)
+    };
+    if app.auto_scroll {
+        title.push_str("[FOLLOW] ");
+    }
+    if app.diff_base.is_some() {
+        title.push_str("[DIFF] ");
+    }
+    if let Some(group_by) = app.group_by {
+        title.push_str(&format!("[GROUPED: {}] ", group_by.label()));
+    }
+
+    let row_heights: Vec<u16> = list_rows[window.clone()]
+        .iter()
+        .map(|row| match row {
+            ListRow::Request { .. } => density.row_height(),
+            ListRow::Group { .. } => 1,
+        })
+        .collect();
     let table = Table::new(rows, widths)
         .header(header)
-        .block(Block::default().borders(Borders::ALL).title(" Requests "))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
         .highlight_symbol("► ");
 
-    frame.render_stateful_widget(table, area, &mut app.table_state);
+    // Rows were only built for the window, so render with a state relative to it
+    let mut window_state = TableState::default()
+        .with_offset(app.table_state.offset() - window.start)
+        .with_selected(
+            app.table_state
+                .selected()
+                .map(|selected| selected.saturating_sub(window.start)),
+        );
+    frame.render_stateful_widget(table, area, &mut window_state);
+
+    // Record where each row landed so clicks can be mapped back to it
+    let offset = window.start + window_state.offset();
+    *app.table_state.offset_mut() = offset;
+    let bottom = area.bottom().saturating_sub(1);
+    let mut y = area.y + 2 + density.header_margin();
+    app.row_y_positions.push(y);
+    for height in row_heights.into_iter().skip(offset - window.start) {
+        if y >= bottom {
+            break;
+        }
+        y = (y + height).min(bottom);
+        app.row_y_positions.push(y);
+    }
 }
 
-fn draw_request_list_help(frame: &mut Frame, area: Rect) {
-    let help_text = Line::from(vec![
-        Span::styled(" j/↓ ", Style::default().fg(Color::Yellow)),
+/// Summary row for a run of similar requests, newest first
+fn group_row(
+    requests: &[&RequestLog],
+    key: &str,
+    expanded: bool,
+    columns: &[RequestListColumnConfig],
+    app: &App,
+    now: DateTime<Local>,
+) -> Row<'static> {
+    let theme = app.theme;
+    let newest = requests[0];
+    let durations: Vec<u64> = requests.iter().filter_map(|req| req.duration_ms).collect();
+    let duration = match durations.iter().max() {
+        Some(max) => format!(
+            "{}/{}ms",
+            durations.iter().sum::<u64>() / durations.len() as u64,
+            max
+        ),
+        None => "...".to_string(),
+    };
+    let method = if requests.iter().all(|req| req.method == newest.method) {
+        newest.method.clone()
+    } else {
+        "*".to_string()
+    };
+
+    Row::new(columns.iter().map(|c| {
+        match c.column {
+            RequestListColumn::Time => Cell::from(match app.time_display {
+                TimeDisplay::Relative => format_relative_time(newest.timestamp, now),
+                TimeDisplay::Absolute | TimeDisplay::Both => format_absolute_time(newest.timestamp),
+            })
+            .style(Style::default().fg(theme.text_dim)),
+            RequestListColumn::Method => {
+                Cell::from(method.clone()).style(theme.method_style(&method))
+            }
+            RequestListColumn::Path => {
+                let marker = if expanded { "▼" } else { "►" };
+                let count = format!(" (×{})", requests.len());
+                let width = c.width.map_or(40, usize::from).saturating_sub(count.len());
+                Cell::from(format!("{} {}{}", marker, truncate_path(key, width), count))
+                    .style(Style::default().bold())
+            }
+            RequestListColumn::Status => Cell::from(
+                newest
+                    .status
+                    .map_or("...".to_string(), |status| status.to_string()),
+            )
+            .style(theme.status_style(newest.status)),
+            RequestListColumn::Duration => Cell::from(duration.clone()),
+            _ => Cell::from(""),
+        }
+    }))
+    .height(app.display_density.row_height())
+}
+
+/// Start of the body shown under the path with `DisplayDensity::Comfortable`:
+/// the request body for methods that send one, otherwise the response body
+fn body_snippet(req: &RequestLog) -> String {
+    let sends_body = matches!(req.method.as_str(), "POST" | "PUT" | "PATCH");
+    let body = match &req.request_body {
+        Some(RequestBodyKind::Streaming(_)) if sends_body => return "[streamed]".to_string(),
+        Some(RequestBodyKind::Buffered(body)) if sends_body => Some(body.as_slice()),
+        _ if sends_body => None,
+        _ => req.response_body.as_deref(),
+    };
+    let Some(body) = body.filter(|body| !body.is_empty()) else {
+        return String::new();
+    };
+    let text = String::from_utf8_lossy(body);
+    let mut snippet: String = text
+        .chars()
+        .take(BODY_SNIPPET_CHARS)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    if text.chars().nth(BODY_SNIPPET_CHARS).is_some() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+/// Response size, green under 10 KB, yellow under 1 MB and red above
+fn size_cell(req: &RequestLog, theme: &Theme) -> Cell<'static> {
+    if req.status.is_none() {
+        return Cell::from("...");
+    }
+    let size = req.response_size_bytes;
+    let color = if size < 10 * 1024 {
+        theme.status_success
+    } else if size < 1024 * 1024 {
+        theme.status_warning
+    } else {
+        theme.status_error
+    };
+    Cell::from(format_size(size as u64)).style(Style::default().fg(color))
+}
+
+fn tags_cell(req: &RequestLog, theme: &Theme) -> Cell<'static> {
+    Cell::from(Line::from(tag_pills(&req.tags, theme)))
+}
+
+/// Tags as coloured `[tag]` pills, each tag always getting the same colour
+fn tag_pills(tags: &[String], theme: &Theme) -> Vec<Span<'static>> {
+    const COLORS: [Color; 6] = [
+        Color::Cyan,
+        Color::Yellow,
+        Color::Magenta,
+        Color::Green,
+        Color::Blue,
+        Color::LightRed,
+    ];
+
+    tags.iter()
+        .map(|tag| {
+            let hash = tag
+                .to_lowercase()
+                .bytes()
+                .fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+            Span::styled(
+                format!("[{}]", tag),
+                Style::default().fg(theme.color(COLORS[hash % COLORS.len()])),
+            )
+        })
+        .collect()
+}
+
+fn draw_request_list_help(frame: &mut Frame, app: &App, area: Rect) {
+    let theme = app.theme;
+    if app.tagging {
+        let tag_line = Line::from(vec![
+            Span::styled(" Tags: ", Style::default().fg(theme.highlight)),
+            Span::raw(format!("{}█ ", app.tag_input)),
+            Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+            Span::raw("Save (comma-separated) "),
+            Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+            Span::raw("Cancel"),
+        ]);
+        return draw_help_footer(frame, app, tag_line, area);
+    }
+
+    if app.searching {
+        let search_line = Line::from(vec![
+            Span::styled(" / ", Style::default().fg(theme.highlight)),
+            Span::raw(format!("{}█ ", app.search_query)),
+            Span::styled(" ↑/↓ ", Style::default().fg(theme.highlight)),
+            Span::raw("History "),
+            Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+            Span::raw("Apply "),
+            Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+            Span::raw("Cancel"),
+        ]);
+        return draw_help_footer(frame, app, search_line, area);
+    }
+
+    if app.diff_base.is_some() {
+        let diff_line = Line::from(vec![
+            Span::styled(" Diff: ", Style::default().fg(theme.highlight)),
+            Span::raw("select the request to compare with "),
+            Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+            Span::raw("Compare "),
+            Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+            Span::raw("Cancel"),
+        ]);
+        return draw_help_footer(frame, app, diff_line, area);
+    }
+
+    let mut spans = vec![
+        Span::styled(" j/↓ ", Style::default().fg(theme.highlight)),
         Span::raw("Down "),
-        Span::styled(" k/↑ ", Style::default().fg(Color::Yellow)),
+        Span::styled(" k/↑ ", Style::default().fg(theme.highlight)),
         Span::raw("Up "),
-        Span::styled(" Enter ", Style::default().fg(Color::Yellow)),
+        Span::styled(" Enter ", Style::default().fg(theme.highlight)),
         Span::raw("Details "),
-        Span::styled(" c ", Style::default().fg(Color::Yellow)),
+        Span::styled(" / ", Style::default().fg(theme.highlight)),
+        Span::raw("Search "),
+        Span::styled(" * ", Style::default().fg(theme.highlight)),
+        Span::raw("Same "),
+        Span::styled(" b/B ", Style::default().fg(theme.highlight)),
+        Span::raw("Bookmark "),
+        Span::styled(" # ", Style::default().fg(theme.highlight)),
+        Span::raw("Tag "),
+        Span::styled(" F ", Style::default().fg(theme.highlight)),
+        Span::raw("Follow "),
+        Span::styled(" z ", Style::default().fg(theme.highlight)),
+        Span::raw("Group "),
+        Span::styled(" v ", Style::default().fg(theme.highlight)),
+        Span::raw("Density "),
+        Span::styled(" </> ^←/^→ ", Style::default().fg(theme.highlight)),
+        Span::raw("Width "),
+        Span::styled(" c ", Style::default().fg(theme.highlight)),
         Span::raw("Clear "),
-        Span::styled(" Esc ", Style::default().fg(Color::Yellow)),
+        Span::styled(" T ", Style::default().fg(theme.highlight)),
+        Span::raw("Time "),
+        Span::styled(" t ", Style::default().fg(theme.highlight)),
+        Span::raw("Timeline "),
+        Span::styled(" D ", Style::default().fg(theme.highlight)),
+        Span::raw("Diff "),
+        Span::styled(" s ", Style::default().fg(theme.highlight)),
+        Span::raw("Status-details "),
+    ];
+    spans.extend(error_log_key(app));
+    spans.extend([
+        Span::styled(" Tab/Esc ", Style::default().fg(theme.highlight)),
         Span::raw("Tunnels "),
-        Span::styled(" q ", Style::default().fg(Color::Yellow)),
+        Span::styled(" q ", Style::default().fg(theme.highlight)),
         Span::raw("Quit"),
     ]);
 
-    let help = Paragraph::new(help_text).block(Block::default().borders(Borders::TOP));
-
-    frame.render_widget(help, area);
+    draw_help_footer(frame, app, Line::from(spans), area);
 }
 
-fn draw_detail_view(frame: &mut Frame, app: &mut App) {
-    let Some(selected) = app.table_state.selected() else {
-        return draw_request_list_view(frame, app);
+fn draw_timeline_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // Status bar
+            Constraint::Min(5),    // Timeline
+            Constraint::Length(2), // Help footer
+        ])
+        .split(frame.area());
+
+    draw_status_bar(frame, app, chunks[0]);
+
+    let now = Local::now();
+    let window = TIMELINE_WINDOW_SECS as f64;
+    let requests = app.timeline_requests(now);
+    let rows = requests.len().max(1) as f64;
+    let selected = requests
+        .iter()
+        .find(|req| app.timeline_selected.as_ref() == Some(&req.id));
+
+    // x is seconds relative to now; the newest request is on the top row
+    let bars: Vec<CanvasLine> = requests
+        .iter()
+        .enumerate()
+        .map(|(i, req)| {
+            let start = (req.timestamp - now).num_milliseconds() as f64 / 1000.0;
+            let end = req.duration_ms.map_or(0.0, |ms| start + ms as f64 / 1000.0);
+            let y = rows - i as f64 - 0.5;
+            let color = if app.timeline_selected.as_ref() == Some(&req.id) {
+                theme.text_primary
+            } else {
+                timeline_color(req.status, &theme)
+            };
+            CanvasLine::new(start.max(-window), y, end.min(0.0), y, color)
+        })
+        .collect();
+
+    let details = match selected {
+        Some(req) => format!(
+            " {} {} {} {} ",
+            req.method,
+            truncate_path(&req.path, 40),
+            req.status.map_or("...".to_string(), |s| s.to_string()),
+            req.duration_ms
+                .map_or("...".to_string(), |ms| format!("{}ms", ms))
+        ),
+        None => format!(" No requests in the last {}s ", TIMELINE_WINDOW_SECS),
     };
 
-    let Some(req) = app.requests.get(selected).cloned() else {
-        return;
+    let canvas = Canvas::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Timeline (last {}s) ", TIMELINE_WINDOW_SECS))
+                .title_bottom(details),
+        )
+        .marker(Marker::HalfBlock)
+        .x_bounds([-window, 0.0])
+        .y_bounds([0.0, rows])
+        .paint(|ctx| {
+            for bar in &bars {
+                ctx.draw(bar);
+            }
+        });
+    frame.render_widget(canvas, chunks[1]);
+
+    let help_text = Line::from(vec![
+        Span::styled(" j/k ", Style::default().fg(theme.highlight)),
+        Span::raw("Select "),
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+        Span::raw("Back "),
+        Span::styled(" q ", Style::default().fg(theme.highlight)),
+        Span::raw("Quit"),
+    ]);
+    draw_help_footer(frame, app, help_text, chunks[2]);
+}
+
+/// Bar colour in the timeline view; requests still in flight are grey
+fn timeline_color(status: Option<u16>, theme: &Theme) -> Color {
+    match status {
+        Some(200..=299) => theme.status_success,
+        Some(300..=399) => theme.status_warning,
+        Some(400..=499) => theme.status_error,
+        Some(500..) => theme.color(Color::Magenta),
+        _ => theme.text_dim,
+    }
+}
+
+fn draw_connection_history_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // Status bar
+            Constraint::Min(5),    // History
+            Constraint::Length(1), // State snapshot
+            Constraint::Length(2), // Help footer
+        ])
+        .split(frame.area());
+
+    draw_status_bar(frame, app, chunks[0]);
+
+    let header_cells = ["TIME", "STATUS", "DETAILS", "DURATION"]
+        .iter()
+        .map(|h| Cell::from(*h).style(Style::default().fg(theme.highlight).bold()));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    // Each entry lasts until the next one; the latest is still ongoing
+    let now = Local::now();
+    let ends = app
+        .connection_history
+        .iter()
+        .skip(1)
+        .map(|entry| entry.occurred_at)
+        .chain(std::iter::once(now));
+
+    let mut rows: Vec<Row> = app
+        .connection_history
+        .iter()
+        .zip(ends)
+        .map(|(entry, ended_at)| {
+            let details = match &entry.status {
+                ConnectionStatus::Reconnecting {
+                    attempt, reason, ..
+                } => format!("attempt {}: {}", attempt, reason),
+                ConnectionStatus::Disconnected { reason } => reason.clone(),
+                ConnectionStatus::Offline { source } => source.clone(),
+                ConnectionStatus::LocalProxy { target, .. } => target.clone(),
+                ConnectionStatus::Connecting | ConnectionStatus::Connected => String::new(),
+            };
+            let duration = format_duration(ended_at - entry.occurred_at);
+            let duration = match &entry.status {
+                ConnectionStatus::Connected => format!("Up: {}", duration),
+                ConnectionStatus::Disconnected { .. } => format!("Down: {}", duration),
+                _ => String::new(),
+            };
+
+            Row::new(vec![
+                Cell::from(format_absolute_time(entry.occurred_at))
+                    .style(Style::default().fg(theme.text_dim)),
+                Cell::from(entry.status.to_string())
+                    .style(Style::default().fg(connection_status_color(&entry.status, &theme))),
+                Cell::from(details),
+                Cell::from(duration),
+            ])
+        })
+        .collect();
+    // Newest first
+    rows.reverse();
+
+    let widths = [
+        Constraint::Length(12),
+        Constraint::Length(18),
+        Constraint::Min(20),
+        Constraint::Length(16),
+    ];
+
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Connection History "),
+    );
+    frame.render_widget(table, chunks[1]);
+
+    let snapshot = match &app.state_snapshot {
+        Some(s) => format!(
+            " Tracking: {} tunnels ({} pending), {} TCP tunnels ({} pending), \
+             {} TCP connections, {} WebSockets",
+            s.active_tunnel_count,
+            s.pending_tunnel_count,
+            s.active_tcp_tunnel_count,
+            s.pending_tcp_tunnel_count,
+            s.active_tcp_connection_count,
+            s.active_ws_proxy_count
+        ),
+        None => " Tracking: no snapshot yet".to_string(),
     };
+    frame.render_widget(
+        Paragraph::new(Span::styled(
+            snapshot,
+            Style::default().fg(theme.text_secondary),
+        )),
+        chunks[2],
+    );
+
+    let help_text = Line::from(vec![
+        Span::styled(" r ", Style::default().fg(theme.highlight)),
+        Span::raw("Refresh "),
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+        Span::raw("Back "),
+        Span::styled(" q ", Style::default().fg(theme.highlight)),
+        Span::raw("Quit"),
+    ]);
+    draw_help_footer(frame, app, help_text, chunks[3]);
+}
 
+fn draw_tunnel_stats_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3), // Title bar
-            Constraint::Min(5),    // Content
+            Constraint::Length(3), // Status bar
+            Constraint::Min(5),    // Tunnels
             Constraint::Length(2), // Help footer
         ])
         .split(frame.area());
 
+    draw_status_bar(frame, app, chunks[0]);
+
+    let header_cells = ["TUNNEL", "LOCAL", "REQUESTS", "ERRORS", "LAST ERROR"]
+        .iter()
+        .map(|h| Cell::from(*h).style(Style::default().fg(theme.highlight).bold()));
+    let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+    let http = app.tunnels.iter().map(|tunnel| {
+        let requests: Vec<&RequestLog> = app
+            .requests
+            .iter()
+            .filter(|req| req.tunnel_url.as_deref() == Some(tunnel.full_url.as_str()))
+            .collect();
+        let errors = requests
+            .iter()
+            .filter(|req| req.status.is_some_and(|status| status >= 500))
+            .count();
+        let last_error = app
+            .tunnel_errors
+            .get(&tunnel.tunnel_id)
+            .map(|error| {
+                format!(
+                    "{} {}: {}",
+                    format_absolute_time(error.at),
+                    error.code,
+                    error.message
+                )
+            })
+            .unwrap_or_default();
+        let error_style = if errors > 0 {
+            Style::default().fg(theme.status_error)
+        } else {
+            Style::default()
+        };
+        Row::new(vec![
+            Cell::from(format!(
+                "{}{}",
+                pool_prefix(tunnel.pool_id),
+                tunnel.full_url
+            ))
+            .style(Style::default().fg(theme.status_success)),
+            Cell::from(format!(":{}", tunnel.local_port)),
+            Cell::from(requests.len().to_string()),
+            Cell::from(errors.to_string()).style(error_style),
+            Cell::from(last_error).style(Style::default().fg(theme.status_error)),
+        ])
+    });
+    let tcp = app.tcp_tunnels.iter().map(|tcp| {
+        Row::new(vec![
+            Cell::from(format!(
+                "{}tcp:{}",
+                pool_prefix(tcp.pool_id),
+                tcp.server_port
+            ))
+            .style(Style::default().fg(theme.color(Color::Magenta))),
+            Cell::from(format!(":{}", tcp.local_port)),
+            Cell::from("-"),
+            Cell::from("-"),
+            Cell::from(""),
+        ])
+    });
+    let rows: Vec<Row> = http.chain(tcp).collect();
+
+    let widths = [
+        Constraint::Min(30),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(7),
+        Constraint::Min(20),
+    ];
+    let title = format!(" Tunnels ({}) ", rows.len());
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_widget(table, chunks[1]);
+
+    let help_text = Line::from(vec![
+        Span::styled(" Esc/s ", Style::default().fg(theme.highlight)),
+        Span::raw("Back "),
+        Span::styled(" q ", Style::default().fg(theme.highlight)),
+        Span::raw("Quit"),
+    ]);
+    draw_help_footer(frame, app, help_text, chunks[2]);
+}
+
+fn draw_error_log_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // Status bar
+            Constraint::Min(5),    // Error log
+            Constraint::Length(2), // Help footer
+        ])
+        .split(frame.area());
+
+    draw_status_bar(frame, app, chunks[0]);
+
+    if app.error_log.is_empty() {
+        let empty = Paragraph::new(Line::from(Span::styled(
+            "  No errors logged.",
+            Style::default().fg(theme.text_secondary),
+        )))
+        .block(Block::default().borders(Borders::ALL).title(" Errors "));
+        frame.render_widget(empty, chunks[1]);
+    } else {
+        let header_cells = ["TIME", "SOURCE", "MESSAGE"]
+            .iter()
+            .map(|h| Cell::from(*h).style(Style::default().fg(theme.highlight).bold()));
+        let header = Row::new(header_cells).height(1).bottom_margin(1);
+
+        let rows = app.error_log.iter().map(|entry| {
+            Row::new(vec![
+                Cell::from(format_absolute_time(entry.timestamp))
+                    .style(Style::default().fg(theme.text_dim)),
+                Cell::from(entry.source.to_string()).style(Style::default().fg(theme.status_error)),
+                Cell::from(entry.message.clone()),
+            ])
+        });
+
+        let widths = [
+            Constraint::Length(12),
+            Constraint::Length(16),
+            Constraint::Min(20),
+        ];
+
+        let table = Table::new(rows, widths).header(header).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Errors ({}) ", app.error_log.len())),
+        );
+        frame.render_widget(table, chunks[1]);
+    }
+
+    let help_text = Line::from(vec![
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+        Span::raw("Back "),
+        Span::styled(" q ", Style::default().fg(theme.highlight)),
+        Span::raw("Quit"),
+    ]);
+    draw_help_footer(frame, app, help_text, chunks[2]);
+}
+
+fn draw_detail_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
+    let Some(req) = app.selected_request().cloned() else {
+        return draw_request_list_view(frame, app);
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),                                           // Title bar
+            Constraint::Min(5),                                              // Content
+            Constraint::Length(help_footer_height(app, frame.area().width)), // Help footer
+        ])
+        .split(frame.area());
+
     // Title bar
     let status_text = req
         .status
@@ -518,11 +2023,11 @@ fn draw_detail_view(frame: &mut Frame, app: &mut App) {
     let title = Line::from(vec![
         Span::styled(
             format!(" {} ", req.method),
-            method_color(&req.method).bold(),
+            theme.method_style(&req.method).bold(),
         ),
         Span::raw(truncate_string(&full_path, 60)),
         Span::raw(" │ "),
-        Span::styled(status_text, status_color(req.status)),
+        Span::styled(status_text, theme.status_style(req.status)),
     ]);
 
     let title_bar = Paragraph::new(title).block(
@@ -532,58 +2037,124 @@ fn draw_detail_view(frame: &mut Frame, app: &mut App) {
     );
     frame.render_widget(title_bar, chunks[0]);
 
-    // Content area split into sections
-    let has_request_body = req
-        .request_body
-        .as_ref()
-        .map(|b| !b.is_empty())
-        .unwrap_or(false);
+    if app.detail_tab == DetailTab::Raw {
+        draw_raw_detail(frame, &req, chunks[1]);
+    } else {
+        draw_detail_panels(frame, app, &req, chunks[1]);
+    }
+
+    // Help footer, or the note editor while editing
+    if let Some((_, note)) = &app.editing_note {
+        let note_line = Line::from(vec![
+            Span::styled(" Note: ", Style::default().fg(theme.highlight)),
+            Span::raw(format!("{}█ ", note)),
+            Span::styled(" Enter ", Style::default().fg(theme.highlight)),
+            Span::raw("Save "),
+            Span::styled(" Esc ", Style::default().fg(theme.highlight)),
+            Span::raw("Cancel"),
+        ]);
+        let editor = Paragraph::new(note_line).block(Block::default().borders(Borders::TOP));
+        frame.render_widget(editor, chunks[2]);
+        return;
+    }
+
+    draw_help_footer(frame, app, Line::from(help_text_for_mode(app)), chunks[2]);
+}
+
+fn draw_detail_panels(frame: &mut Frame, app: &App, req: &RequestLog, area: Rect) {
+    let theme = app.theme;
+    // Content area split into sections, dropping panels on short terminals
+    let height = app.terminal_size.1;
+    let has_request_body = match &req.request_body {
+        Some(RequestBodyKind::Buffered(body)) => !body.is_empty(),
+        Some(RequestBodyKind::Streaming(_)) => true,
+        None => false,
+    } && height >= DETAIL_REQUEST_BODY_MIN_HEIGHT;
+    let show_response_headers = height >= DETAIL_RESPONSE_HEADERS_MIN_HEIGHT;
+
+    let note = app.note(req);
+    // Summary info, with a line for the note if there is one
+    let mut constraints = vec![Constraint::Length(if note.is_some() { 6 } else { 5 })];
+    if has_request_body {
+        constraints.push(Constraint::Length(5)); // Request headers
+        constraints.push(Constraint::Length(5)); // Request body
+    } else {
+        constraints.push(Constraint::Length(6)); // Request headers
+    }
+    if show_response_headers {
+        // Response headers
+        constraints.push(Constraint::Length(if has_request_body { 5 } else { 6 }));
+    }
+    constraints.push(Constraint::Min(3)); // Response body
+
     let content_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(if has_request_body {
-            vec![
-                Constraint::Length(5), // Summary info
-                Constraint::Length(5), // Request headers
-                Constraint::Length(5), // Request body
-                Constraint::Length(5), // Response headers
-                Constraint::Min(3),    // Response body
-            ]
-        } else {
-            vec![
-                Constraint::Length(5), // Summary info
-                Constraint::Length(6), // Request headers
-                Constraint::Length(6), // Response headers
-                Constraint::Min(3),    // Response body
-            ]
-        })
-        .split(chunks[1]);
+        .constraints(constraints)
+        .split(area);
 
     // Summary section with key details
     let user_agent =
         get_header_value(&req.request_headers, "user-agent").unwrap_or("-".to_string());
     let client_ip = req.client_ip.as_deref().unwrap_or("-");
-    let duration = req
-        .duration_ms
-        .map(|d| format!("{}ms", d))
-        .unwrap_or_else(|| "...".to_string());
-    let timestamp = req.timestamp.format("%H:%M:%S").to_string();
+    let duration = match (req.duration_ms, req.timing_breakdown) {
+        (Some(total), Some(timing)) => {
+            format!("{}ms (upstream {}ms)", total, timing.first_byte_ms)
+        }
+        (Some(total), None) => format!("{}ms", total),
+        (None, _) => "...".to_string(),
+    };
+    let timestamp = match app.time_display {
+        TimeDisplay::Absolute => format_absolute_time(req.timestamp),
+        TimeDisplay::Relative => format_relative_time(req.timestamp, Local::now()),
+        TimeDisplay::Both => format!(
+            "{} ({})",
+            format_absolute_time(req.timestamp),
+            format_relative_time(req.timestamp, Local::now())
+        ),
+    };
 
-    let summary_lines = vec![
+    let mut summary_lines = Vec::new();
+    if let Some(note) = note {
+        summary_lines.push(Line::from(Span::styled(
+            format!(" 📝 {} ", note),
+            theme.badge_style(),
+        )));
+    }
+    summary_lines.extend([
         Line::from(vec![
-            Span::styled("  Client IP: ", Style::default().fg(Color::Yellow)),
+            Span::styled("  Client IP: ", Style::default().fg(theme.highlight)),
             Span::raw(client_ip),
             Span::raw("    "),
-            Span::styled("Time: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Time: ", Style::default().fg(theme.highlight)),
             Span::raw(&timestamp),
             Span::raw("    "),
-            Span::styled("Duration: ", Style::default().fg(Color::Yellow)),
+            Span::styled("Duration: ", Style::default().fg(theme.highlight)),
             Span::raw(&duration),
+            Span::raw("    "),
+            Span::styled("Size: ", Style::default().fg(theme.highlight)),
+            Span::raw(format!(
+                "{} in / {} out",
+                format_size(req.request_size_bytes as u64),
+                format_size(req.response_size_bytes as u64)
+            )),
         ]),
         Line::from(vec![
-            Span::styled("  User-Agent: ", Style::default().fg(Color::Yellow)),
+            Span::styled("  User-Agent: ", Style::default().fg(theme.highlight)),
             Span::raw(truncate_string(&user_agent, 80)),
         ]),
-    ];
+    ]);
+
+    if let Some(kind) = req.error_kind {
+        summary_lines.push(Line::from(vec![
+            Span::styled("  Error: ", Style::default().fg(theme.status_error)),
+            Span::raw(kind.to_string()),
+        ]));
+    } else if let Some(timing) = req.timing_breakdown {
+        summary_lines.push(Line::from(vec![
+            Span::styled("  Timing: ", Style::default().fg(theme.highlight)),
+            Span::raw(format_timing(&timing)),
+        ]));
+    }
 
     let summary = Paragraph::new(summary_lines)
         .block(Block::default().borders(Borders::ALL).title(" Summary "));
@@ -600,14 +2171,15 @@ fn draw_detail_view(frame: &mut Frame, app: &mut App) {
         .wrap(Wrap { trim: false });
     frame.render_widget(req_headers, content_chunks[1]);
 
-    // Dynamic indices based on whether request body exists
-    let (resp_headers_idx, resp_body_idx) = if has_request_body {
+    // Dynamic indices based on which panels are shown
+    let mut next_idx = 2;
+    if has_request_body {
         // Request body section
-        let req_body_text = req
-            .request_body
-            .as_ref()
-            .map(|b| format_body(b))
-            .unwrap_or_else(|| "No body".to_string());
+        let req_body_text = match &req.request_body {
+            Some(RequestBodyKind::Buffered(body)) => format_body(body),
+            Some(RequestBodyKind::Streaming(len)) => format_streaming_body(*len),
+            None => "No body".to_string(),
+        };
         let req_body = Paragraph::new(req_body_text)
             .block(
                 Block::default()
@@ -615,22 +2187,23 @@ fn draw_detail_view(frame: &mut Frame, app: &mut App) {
                     .title(" Request Body "),
             )
             .wrap(Wrap { trim: false });
-        frame.render_widget(req_body, content_chunks[2]);
-        (3, 4)
-    } else {
-        (2, 3)
-    };
+        frame.render_widget(req_body, content_chunks[next_idx]);
+        next_idx += 1;
+    }
 
     // Response headers
-    let resp_headers_text = format_headers(&req.response_headers);
-    let resp_headers = Paragraph::new(resp_headers_text)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" Response Headers "),
-        )
-        .wrap(Wrap { trim: false });
-    frame.render_widget(resp_headers, content_chunks[resp_headers_idx]);
+    if show_response_headers {
+        let resp_headers_text = format_headers(&req.response_headers);
+        let resp_headers = Paragraph::new(resp_headers_text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Response Headers "),
+            )
+            .wrap(Wrap { trim: false });
+        frame.render_widget(resp_headers, content_chunks[next_idx]);
+        next_idx += 1;
+    }
 
     // Response body
     let body_text = req
@@ -645,41 +2218,382 @@ fn draw_detail_view(frame: &mut Frame, app: &mut App) {
                 .title(" Response Body "),
         )
         .wrap(Wrap { trim: false });
-    frame.render_widget(body, content_chunks[resp_body_idx]);
+    frame.render_widget(body, content_chunks[next_idx]);
+}
+
+fn draw_raw_detail(frame: &mut Frame, req: &RequestLog, area: Rect) {
+    let halves = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+
+    let request = Paragraph::new(format_raw_request(req))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Raw Request "),
+        )
+        .wrap(Wrap { trim: false });
+    frame.render_widget(request, halves[0]);
+
+    let response = Paragraph::new(format_raw_response(req))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(" Raw Response "),
+        )
+        .wrap(Wrap { trim: false });
+    frame.render_widget(response, halves[1]);
+}
+
+/// Reconstruct the request roughly as it appeared on the wire
+fn format_raw_request(req: &RequestLog) -> Vec<Line<'static>> {
+    let target = if req.query_string.is_empty() {
+        req.path.clone()
+    } else {
+        format!("{}?{}", req.path, req.query_string)
+    };
+
+    let mut lines = vec![Line::from(format!("{} {} HTTP/1.1", req.method, target))];
+
+    // Prefer the public host the request arrived on over whatever was forwarded
+    let host = req
+        .tunnel_url
+        .as_deref()
+        .and_then(|u| url::Url::parse(u).ok())
+        .and_then(|u| {
+            u.host_str().map(|h| match u.port() {
+                Some(port) => format!("{}:{}", h, port),
+                None => h.to_string(),
+            })
+        })
+        .or_else(|| get_header_value(&req.request_headers, "host"));
+    if let Some(host) = host {
+        lines.push(Line::from(format!("Host: {}", host)));
+    }
+
+    lines.extend(
+        req.request_headers
+            .iter()
+            .filter(|(k, _)| !k.eq_ignore_ascii_case("host"))
+            .map(|(k, v)| Line::from(format!("{}: {}", k, v))),
+    );
+    lines.push(Line::from(""));
+    match &req.request_body {
+        Some(RequestBodyKind::Buffered(body)) => lines.extend(format_raw_body(Some(body))),
+        Some(RequestBodyKind::Streaming(len)) => {
+            lines.push(Line::from(format_streaming_body(*len)))
+        }
+        None => {}
+    }
+    lines
+}
+
+/// Reconstruct the response roughly as it appeared on the wire
+fn format_raw_response(req: &RequestLog) -> Vec<Line<'static>> {
+    let Some(status) = req.status else {
+        return vec![Line::from("(awaiting response)")];
+    };
+
+    let mut lines = vec![Line::from(
+        format!("HTTP/1.1 {} {}", status, status_text(status))
+            .trim_end()
+            .to_string(),
+    )];
+    lines.extend(
+        req.response_headers
+            .iter()
+            .map(|(k, v)| Line::from(format!("{}: {}", k, v))),
+    );
+    lines.push(Line::from(""));
+    lines.extend(format_raw_body(req.response_body.as_deref()));
+    lines
+}
+
+/// How a line differs between the two requests being compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffKind {
+    Unchanged,
+    /// Only on the right
+    Added,
+    /// Only on the left
+    Removed,
+}
+
+/// One row of a side-by-side diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DiffLine {
+    kind: DiffKind,
+    left: String,
+    right: String,
+}
+
+impl DiffLine {
+    fn unchanged(text: String) -> Self {
+        DiffLine {
+            kind: DiffKind::Unchanged,
+            left: text.clone(),
+            right: text,
+        }
+    }
+
+    fn added(text: String) -> Self {
+        DiffLine {
+            kind: DiffKind::Added,
+            left: String::new(),
+            right: text,
+        }
+    }
+
+    fn removed(text: String) -> Self {
+        DiffLine {
+            kind: DiffKind::Removed,
+            left: text,
+            right: String::new(),
+        }
+    }
+}
+
+/// Compare header lists by name (case-insensitively), keeping the order of `a`.
+/// A header whose value changed shows up as a removal followed by an addition.
+fn diff_headers(a: &[(String, String)], b: &[(String, String)]) -> Vec<DiffLine> {
+    let line = |(name, value): &(String, String)| format!("{}: {}", name, value);
+    let mut unmatched: Vec<&(String, String)> = b.iter().collect();
+    let mut take = |pred: &dyn Fn(&(String, String)) -> bool| {
+        let index = unmatched.iter().position(|header| pred(header))?;
+        Some(unmatched.remove(index))
+    };
+
+    let mut lines = Vec::new();
+    for header in a {
+        let (name, value) = header;
+        if take(&|(n, v)| n.eq_ignore_ascii_case(name) && v == value).is_some() {
+            lines.push(DiffLine::unchanged(line(header)));
+        } else if let Some(other) = take(&|(n, _)| n.eq_ignore_ascii_case(name)) {
+            lines.push(DiffLine::removed(line(header)));
+            lines.push(DiffLine::added(line(other)));
+        } else {
+            lines.push(DiffLine::removed(line(header)));
+        }
+    }
+    lines.extend(
+        unmatched
+            .into_iter()
+            .map(|header| DiffLine::added(line(header))),
+    );
+    lines
+}
+
+/// Line-by-line diff of two texts, from their longest common subsequence
+fn diff_text(a: &str, b: &str) -> Vec<DiffLine> {
+    let a: Vec<&str> = a.lines().take(DIFF_BODY_MAX_LINES).collect();
+    let b: Vec<&str> = b.lines().take(DIFF_BODY_MAX_LINES).collect();
+
+    // common[i][j]: length of the common subsequence of a[i..] and b[j..]
+    let mut common = vec![vec![0u16; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            lines.push(DiffLine::unchanged(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(DiffLine::removed(a[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(DiffLine::added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        a[i..]
+            .iter()
+            .map(|line| DiffLine::removed(line.to_string())),
+    );
+    lines.extend(b[j..].iter().map(|line| DiffLine::added(line.to_string())));
+    lines
+}
+
+fn draw_diff_view(frame: &mut Frame, app: &mut App) {
+    let theme = app.theme;
+    let Some((left, right)) = app.diff_requests() else {
+        return draw_request_list_view(frame, app);
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // Title bar
+            Constraint::Min(5),    // Diff
+            Constraint::Length(2), // Help footer
+        ])
+        .split(frame.area());
+
+    let summary = |req: &RequestLog| {
+        vec![
+            Span::styled(
+                format!(" {} ", req.method),
+                theme.method_style(&req.method).bold(),
+            ),
+            Span::raw(format!("{} ", truncate_path(&req.path, 30))),
+            Span::styled(
+                req.status
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| "...".to_string()),
+                theme.status_style(req.status),
+            ),
+        ]
+    };
+    let mut title = summary(left);
+    title.push(Span::raw("  ⇄ "));
+    title.extend(summary(right));
+    let title_bar = Paragraph::new(Line::from(title)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(" Request Diff "),
+    );
+    frame.render_widget(title_bar, chunks[0]);
+
+    let body = |req: &RequestLog| {
+        req.response_body
+            .as_deref()
+            .map(format_body)
+            .unwrap_or_default()
+    };
+    let sections = [
+        (
+            "Request headers",
+            diff_headers(&left.request_headers, &right.request_headers),
+        ),
+        (
+            "Response headers",
+            diff_headers(&left.response_headers, &right.response_headers),
+        ),
+        ("Response body", diff_text(&body(left), &body(right))),
+    ];
+
+    let half = (chunks[1].width.saturating_sub(3) / 2) as usize;
+    let cell = |text: &str| {
+        let text: String = text.chars().take(half).collect();
+        format!("{:<width$}", text, width = half)
+    };
+    let unchanged = Style::default().bg(theme.color(Color::DarkGray));
+    let added = Style::default()
+        .bg(theme.color(Color::Green))
+        .add_modifier(Modifier::BOLD);
+    let removed = Style::default()
+        .bg(theme.color(Color::Red))
+        .add_modifier(Modifier::BOLD);
+
+    let mut lines = Vec::new();
+    for (heading, diff) in sections {
+        lines.push(Line::from(Span::styled(
+            heading,
+            Style::default().fg(theme.highlight).bold(),
+        )));
+        for line in diff {
+            let (left_style, right_style) = match line.kind {
+                DiffKind::Unchanged => (unchanged, unchanged),
+                DiffKind::Added => (Style::default(), added),
+                DiffKind::Removed => (removed, Style::default()),
+            };
+            lines.push(Line::from(vec![
+                Span::styled(cell(&line.left), left_style),
+                Span::raw(" │ "),
+                Span::styled(cell(&line.right), right_style),
+            ]));
+        }
+        lines.push(Line::default());
+    }
+
+    let diff = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL))
+        .scroll((app.diff_scroll, 0));
+    frame.render_widget(diff, chunks[1]);
 
-    // Help footer
     let help_text = Line::from(vec![
-        Span::styled(" Esc ", Style::default().fg(Color::Yellow)),
+        Span::styled(" j/k ", Style::default().fg(theme.highlight)),
+        Span::raw("Scroll "),
+        Span::styled(" Esc ", Style::default().fg(theme.highlight)),
         Span::raw("Back "),
-        Span::styled(" q ", Style::default().fg(Color::Yellow)),
+        Span::styled(" q ", Style::default().fg(theme.highlight)),
         Span::raw("Quit"),
     ]);
+    draw_help_footer(frame, app, help_text, chunks[2]);
+}
 
-    let help = Paragraph::new(help_text).block(Block::default().borders(Borders::TOP));
-    frame.render_widget(help, chunks[2]);
+fn format_timing(timing: &TimingBreakdown) -> String {
+    format!(
+        "[queue:{}ms|connect:{}ms|wait:{}ms|read:{}ms]",
+        timing.queued_ms,
+        timing.connect_ms,
+        timing.wait_ms(),
+        timing.read_ms()
+    )
 }
 
-fn method_color(method: &str) -> Style {
-    match method {
-        "GET" => Style::default().fg(Color::Green),
-        "POST" => Style::default().fg(Color::Blue),
-        "PUT" => Style::default().fg(Color::Yellow),
-        "PATCH" => Style::default().fg(Color::Yellow),
-        "DELETE" => Style::default().fg(Color::Red),
-        _ => Style::default().fg(Color::White),
+/// Human-readable byte count, e.g. `512 B` or `1.5 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
     }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
 }
 
-fn status_color(status: Option<u16>) -> Style {
-    match status {
-        Some(s) if (200..300).contains(&s) => Style::default().fg(Color::Green),
-        Some(s) if (300..400).contains(&s) => Style::default().fg(Color::Cyan),
-        Some(s) if (400..500).contains(&s) => Style::default().fg(Color::Yellow),
-        Some(s) if s >= 500 => Style::default().fg(Color::Red),
-        _ => Style::default().fg(Color::Gray),
+/// Streamed bodies were never held in memory, so only their size is known
+fn format_streaming_body(len: usize) -> String {
+    if len == 0 {
+        "[Streaming: unknown size]".to_string()
+    } else {
+        format!("[Streaming: {} bytes]", len)
     }
 }
 
+fn format_raw_body(body: Option<&[u8]>) -> Vec<Line<'static>> {
+    let Some(body) = body.filter(|b| !b.is_empty()) else {
+        return Vec::new();
+    };
+
+    let shown = &body[..body.len().min(RAW_BODY_LIMIT)];
+    let mut lines: Vec<Line> = match std::str::from_utf8(shown) {
+        Ok(text) => text.lines().map(|l| Line::from(l.to_string())).collect(),
+        // A multi-byte character may straddle the cut-off point
+        Err(e) if e.valid_up_to() > 0 && e.error_len().is_none() => {
+            String::from_utf8_lossy(&shown[..e.valid_up_to()])
+                .lines()
+                .map(|l| Line::from(l.to_string()))
+                .collect()
+        }
+        Err(_) => vec![Line::from(format!("[Binary data: {} bytes]", body.len()))],
+    };
+
+    if body.len() > RAW_BODY_LIMIT {
+        lines.push(Line::from(format!(
+            "... ({} more bytes)",
+            body.len() - RAW_BODY_LIMIT
+        )));
+    }
+    lines
+}
+
 fn status_text(status: u16) -> &'static str {
     match status {
         200 => "OK",
@@ -703,6 +2617,40 @@ fn status_text(status: u16) -> &'static str {
     }
 }
 
+fn format_absolute_time(timestamp: DateTime<Local>) -> String {
+    timestamp.format("%H:%M:%S").to_string()
+}
+
+/// Format how long ago `timestamp` was, e.g. "3.2s ago" or "4m 10s ago"
+fn format_relative_time(timestamp: DateTime<Local>, now: DateTime<Local>) -> String {
+    let elapsed_ms = (now - timestamp).num_milliseconds().max(0);
+    let secs = elapsed_ms / 1000;
+
+    if elapsed_ms < 1000 {
+        "just now".to_string()
+    } else if secs < 10 {
+        format!("{:.1}s ago", elapsed_ms as f64 / 1000.0)
+    } else if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 3600 {
+        format!("{}m {}s ago", secs / 60, secs % 60)
+    } else {
+        format!("{}h {}m ago", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Format a duration as e.g. "1h 2m 3s", "2m 3s" or "3s"
+fn format_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {}m {}s", secs / 3600, (secs % 3600) / 60, secs % 60)
+    }
+}
+
 fn truncate_path(path: &str, max_len: usize) -> String {
     if path.len() <= max_len {
         path.to_string()
@@ -759,3 +2707,728 @@ fn truncate_string(s: &str, max_len: usize) -> String {
         format!("{}...", &s[..max_len - 3])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tui::{
+        create_command_channel, ConnectionQuality, RequestEvent, TuiCommand, TuiEvent, TunnelEvent,
+        LAYOUT_SAVE_DELAY, MAX_NAMED_MACROS, QUALITY_UPDATE_INTERVAL,
+    };
+    use crate::config::{ConnectionConfig, TuiConfig};
+    use crate::protocol::{RequestId, TunnelId};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::{backend::TestBackend, Terminal};
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[test]
+    fn test_diff_headers_pairs_changed_values() {
+        let header = |name: &str, value: &str| (name.to_string(), value.to_string());
+        let a = [header("Accept", "*/*"), header("X-Token", "old")];
+        let b = [
+            header("x-token", "new"),
+            header("accept", "*/*"),
+            header("Cookie", "c"),
+        ];
+
+        let kinds: Vec<(DiffKind, String, String)> = diff_headers(&a, &b)
+            .into_iter()
+            .map(|line| (line.kind, line.left, line.right))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (
+                    DiffKind::Unchanged,
+                    "Accept: */*".into(),
+                    "Accept: */*".into()
+                ),
+                (DiffKind::Removed, "X-Token: old".into(), String::new()),
+                (DiffKind::Added, String::new(), "x-token: new".into()),
+                (DiffKind::Added, String::new(), "Cookie: c".into()),
+            ]
+        );
+    }
+
+    fn request(i: usize, path: String) -> RequestLog {
+        RequestLog {
+            id: RequestId(format!("req-{}", i)),
+            method: "GET".to_string(),
+            path,
+            query_string: String::new(),
+            request_headers: Vec::new(),
+            request_body: None,
+            status: Some(200),
+            response_headers: Vec::new(),
+            response_body: None,
+            duration_ms: Some(5),
+            timing_breakdown: None,
+            timestamp: Local::now(),
+            client_ip: None,
+            error_kind: None,
+            tunnel_url: None,
+            request_size_bytes: 0,
+            response_size_bytes: 0,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_group_by_collapses_consecutive_requests() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = ["/health", "/health", "/users", "/health"]
+            .iter()
+            .enumerate()
+            .map(|(i, path)| request(i, path.to_string()))
+            .collect();
+        app.cycle_group_by();
+
+        let group = ListRow::Group {
+            key: "/health".to_string(),
+            requests: 0..2,
+            expanded: false,
+        };
+        let single = |index| ListRow::Request {
+            index,
+            in_group: false,
+        };
+        assert_eq!(app.filtered_list().rows, [group, single(2), single(3)]);
+
+        app.toggle_group();
+        assert_eq!(app.visible_count(), 5);
+        app.table_state.select(Some(2));
+        app.toggle_group();
+        assert_eq!(app.visible_count(), 3);
+        assert_eq!(app.table_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_tunnel_error_cleared_by_next_response() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        for (id, url) in [
+            ("t1", "https://a.example.com"),
+            ("t2", "https://b.example.com"),
+        ] {
+            app.tunnels.push(TunnelEvent {
+                tunnel_id: TunnelId(id.to_string()),
+                full_url: url.to_string(),
+                local_port: 3000,
+                pool_id: 0,
+                extra_header_count: 0,
+                tags: Vec::new(),
+            });
+            app.handle_event(TuiEvent::TunnelError {
+                tunnel_id: TunnelId(id.to_string()),
+                code: "bad_gateway".to_string(),
+                message: "Connection refused".to_string(),
+            });
+        }
+        app.requests = (1..=2)
+            .map(|i| RequestLog {
+                tunnel_url: Some("https://a.example.com".to_string()),
+                status: None,
+                ..request(i, "/".to_string())
+            })
+            .collect();
+        let response = |i: usize, error_kind| {
+            TuiEvent::ResponseSent(crate::client::tui::ResponseEvent {
+                request_id: RequestId(format!("req-{}", i)),
+                status: 502,
+                headers: Vec::new(),
+                body: None,
+                duration_ms: 5,
+                timing: None,
+                error_kind,
+                size_bytes: 0,
+            })
+        };
+
+        // Still failing
+        app.handle_event(response(1, Some(crate::client::ProxyErrorKind::Upstream)));
+        assert!(app.tunnel_errors.contains_key(&TunnelId("t1".to_string())));
+
+        // Any answer from the local service, even an error status, means it is back
+        app.handle_event(response(2, None));
+        assert!(!app.tunnel_errors.contains_key(&TunnelId("t1".to_string())));
+        assert!(app.tunnel_errors.contains_key(&TunnelId("t2".to_string())));
+    }
+
+    #[test]
+    fn test_pooled_connection_status_kept_apart() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.connection_status = ConnectionStatus::Connected;
+        for (pool_id, local_port) in [(1, 3000), (2, 4000)] {
+            app.tunnels.push(TunnelEvent {
+                tunnel_id: TunnelId(format!("t{}", pool_id)),
+                full_url: format!("https://p{}.example.com", pool_id),
+                local_port,
+                pool_id,
+                extra_header_count: 0,
+                tags: Vec::new(),
+            });
+        }
+
+        app.handle_event(TuiEvent::PoolConnectionStatus {
+            pool_id: 2,
+            status: ConnectionStatus::Reconnecting {
+                attempt: 1,
+                max_attempts: 10,
+                reason: "Connection lost".to_string(),
+                next_retry_secs: 0,
+            },
+        });
+
+        // The first connection is still up, with its tunnel
+        assert_eq!(app.connection_status, ConnectionStatus::Connected);
+        let ports: Vec<u16> = app.tunnels.iter().map(|t| t.local_port).collect();
+        assert_eq!(ports, [3000]);
+
+        app.terminal_size = (160, 40);
+        let mut terminal = Terminal::new(TestBackend::new(160, 40)).unwrap();
+        terminal.draw(|f| draw(f, &mut app)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("[P2] Reconnecting"));
+    }
+
+    #[tokio::test]
+    async fn test_commands_queued_while_reconnecting() {
+        let (cmd_tx, mut cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.tunnels.push(TunnelEvent {
+            tunnel_id: TunnelId("t1".to_string()),
+            full_url: "https://a.example.com".to_string(),
+            local_port: 3000,
+            pool_id: 0,
+            extra_header_count: 0,
+            tags: Vec::new(),
+        });
+        app.handle_event(TuiEvent::ConnectionStatus(ConnectionStatus::Reconnecting {
+            attempt: 1,
+            max_attempts: 10,
+            reason: "Connection lost".to_string(),
+            next_retry_secs: 0,
+        }));
+
+        for local_port in [3000, 5432] {
+            assert!(app
+                .send_command(TuiCommand::AddTcpTunnel { local_port })
+                .await
+                .is_ok());
+        }
+        assert!(cmd_rx.try_recv().is_err());
+
+        // Switching token is what lets the connection come back, so it is
+        // not held back
+        let update = TuiCommand::UpdateToken {
+            token: "new-token".to_string(),
+        };
+        assert!(app.send_command(update.clone()).await.is_ok());
+        assert_eq!(cmd_rx.try_recv().ok(), Some(update));
+
+        // The connection registers port 3000 again by itself
+        app.handle_event(TuiEvent::ConnectionStatus(ConnectionStatus::Connected));
+        assert_eq!(
+            cmd_rx.try_recv().ok(),
+            Some(TuiCommand::AddTcpTunnel { local_port: 5432 })
+        );
+        assert!(cmd_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_help_text_for_mode() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        let text = |app: &App| -> String {
+            help_text_for_mode(app)
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect()
+        };
+
+        app.connection_status = ConnectionStatus::Disconnected {
+            reason: "gone".to_string(),
+        };
+        assert_eq!(text(&app), " r Retry  q Quit");
+        app.connection_status = ConnectionStatus::Connected;
+        assert!(text(&app).contains("Add tunnel"));
+
+        app.view_mode = ViewMode::AddTunnel;
+        app.add_tunnel_field = AddTunnelField::Headers;
+        assert!(text(&app).contains("New line"));
+        assert!(!text(&app).contains("Create"));
+
+        // Hints wrap onto a second line when the terminal is too narrow
+        assert_eq!(help_footer_height(&app, 40), 3);
+        assert_eq!(help_footer_height(&app, 300), 2);
+    }
+
+    #[test]
+    fn test_truncate_status_spans() {
+        let tunnels = || {
+            [
+                "https://a.example.com",
+                "https://b.example.com",
+                "tcp:40001",
+            ]
+            .iter()
+            .map(|url| vec![Span::raw(*url)])
+            .collect::<Vec<_>>()
+        };
+        let text = |spans: Vec<Span>| {
+            spans
+                .iter()
+                .map(|s| s.content.to_string())
+                .collect::<String>()
+        };
+        let theme = Theme::default();
+
+        assert_eq!(
+            text(truncate_status_spans(tunnels(), usize::MAX, &theme)),
+            "https://a.example.com │ https://b.example.com │ tcp:40001"
+        );
+        assert_eq!(
+            text(truncate_status_spans(tunnels(), 40, &theme)),
+            "https://a.example.com +2 more"
+        );
+        assert_eq!(
+            text(truncate_status_spans(tunnels(), 10, &theme)),
+            "3 tunnels"
+        );
+    }
+
+    #[test]
+    fn test_column_width_adjustments() {
+        let (cmd_tx, mut cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.request_list_columns = vec![
+            RequestListColumnConfig {
+                column: RequestListColumn::Method,
+                width: Some(8),
+            },
+            RequestListColumnConfig {
+                column: RequestListColumn::Path,
+                width: None,
+            },
+        ];
+
+        app.resize_selected_column(-2);
+        assert_eq!(
+            app.request_column_widths(100),
+            [Constraint::Length(6), Constraint::Min(20)]
+        );
+        // Saved once resizing has settled, not for every step
+        app.resize_selected_column(-2);
+        let now = Instant::now();
+        assert_eq!(app.take_due_layout(now), None);
+        assert_eq!(
+            app.take_due_layout(now + LAYOUT_SAVE_DELAY),
+            Some([(RequestListColumn::Method, -4)].into_iter().collect())
+        );
+        assert_eq!(app.take_due_layout(now + LAYOUT_SAVE_DELAY), None);
+        app.resize_selected_column(2);
+        assert!(cmd_rx.try_recv().is_err());
+
+        // Columns keep a minimum width and leave room for the others
+        app.resize_selected_column(-20);
+        app.select_column(1);
+        app.resize_selected_column(100);
+        assert_eq!(
+            app.request_column_widths(50),
+            [Constraint::Length(4), Constraint::Min(46)]
+        );
+
+        app.reset_column_widths();
+        assert!(app.column_width_adjustments.is_empty());
+    }
+
+    #[test]
+    fn test_click_selects_request() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.view_mode = ViewMode::RequestList;
+        app.requests = (0..5)
+            .map(|i| request(i, format!("/items/{}", i)))
+            .collect();
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 20)).unwrap();
+        terminal
+            .draw(|frame| draw_request_list(frame, &mut app, frame.area()))
+            .unwrap();
+
+        // Below the border, the header and its margin
+        app.click(10, 4);
+        assert_eq!(app.table_state.selected(), Some(1));
+        assert_eq!(app.view_mode, ViewMode::RequestList);
+        app.click(10, 15);
+        assert_eq!(app.table_state.selected(), Some(1));
+
+        // A second click on the same row opens it
+        app.click(10, 4);
+        assert_eq!(app.view_mode, ViewMode::RequestDetail);
+    }
+
+    #[test]
+    fn test_record_and_replay_macro() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        for c in ['Q', 'a', 'j', 'j'] {
+            app.handle_macro_key(key(c));
+        }
+        assert_eq!(app.recording_macro, Some('a'));
+        assert!(app.handle_macro_key(key('Q')));
+        assert_eq!(app.recording_macro, None);
+        assert_eq!(app.named_macros[&'a'], vec![key('j'), key('j')]);
+
+        assert!(app.handle_macro_key(key('@')));
+        assert!(app.handle_macro_key(key('@')));
+        assert_eq!(app.next_macro_key(), Some(key('j')));
+        assert_eq!(app.next_macro_key(), Some(key('j')));
+        assert_eq!(app.next_macro_key(), None);
+
+        // Typed text is left alone
+        app.searching = true;
+        assert!(!app.handle_macro_key(key('Q')));
+    }
+
+    #[tokio::test]
+    async fn test_replayed_macro_drives_the_request_list() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = (0..10).map(|i| request(i, format!("/{}", i))).collect();
+        app.view_mode = ViewMode::RequestList;
+        app.table_state.select(Some(0));
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+
+        for c in ['Q', 'a', 'j', 'j', 'Q'] {
+            super::super::handle_key(&mut app, key(c)).await;
+        }
+        assert_eq!(app.table_state.selected(), Some(2));
+
+        for c in ['@', 'a'] {
+            super::super::handle_key(&mut app, key(c)).await;
+        }
+        super::super::replay_macro_keys(&mut app).await;
+        assert_eq!(app.table_state.selected(), Some(4));
+
+        // @@ replays the macro recorded last
+        for c in ['@', '@'] {
+            super::super::handle_key(&mut app, key(c)).await;
+        }
+        super::super::replay_macro_keys(&mut app).await;
+        assert_eq!(app.table_state.selected(), Some(6));
+    }
+
+    #[test]
+    fn test_macro_registers_limited() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let registers: Vec<char> = ('a'..='z').take(MAX_NAMED_MACROS).collect();
+        for register in &registers {
+            for c in ['Q', *register, 'j', 'Q'] {
+                app.handle_macro_key(key(c));
+            }
+        }
+        assert_eq!(app.named_macros.len(), MAX_NAMED_MACROS);
+
+        // No room for another register, but existing ones can be re-recorded
+        app.handle_macro_key(key('Q'));
+        app.handle_macro_key(key('z'));
+        assert_eq!(app.recording_macro, None);
+        assert_eq!(app.notification_level, NotificationLevel::Warn);
+        for c in ['Q', 'a', 'k', 'Q'] {
+            app.handle_macro_key(key(c));
+        }
+        assert_eq!(app.named_macros[&'a'], vec![key('k')]);
+
+        // Recording an empty macro frees its register
+        for c in ['Q', 'b', 'Q', 'Q', 'z', 'j', 'Q'] {
+            app.handle_macro_key(key(c));
+        }
+        assert!(!app.named_macros.contains_key(&'b'));
+        assert_eq!(app.named_macros[&'z'], vec![key('j')]);
+    }
+
+    #[tokio::test]
+    async fn test_macro_replaying_itself_stops() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = (0..10).map(|i| request(i, format!("/{}", i))).collect();
+        app.view_mode = ViewMode::RequestList;
+        app.table_state.select(Some(0));
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        app.named_macros
+            .insert('a', vec![key('j'), key('@'), key('a')]);
+
+        for c in ['@', 'a'] {
+            super::super::handle_key(&mut app, key(c)).await;
+        }
+        super::super::replay_macro_keys(&mut app).await;
+
+        assert!(app.next_macro_key().is_none());
+        assert_eq!(app.notification_level, NotificationLevel::Error);
+        assert!(app
+            .notification
+            .as_ref()
+            .unwrap()
+            .contains("replays itself"));
+        assert_eq!(app.table_state.selected(), Some(9));
+    }
+
+    #[test]
+    fn test_unsaveable_macro_keys_reported() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        let key = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        app.handle_macro_key(key('Q'));
+        app.handle_macro_key(key('a'));
+        app.handle_macro_key(KeyEvent::new(KeyCode::Insert, KeyModifiers::NONE));
+        app.handle_macro_key(key('Q'));
+
+        assert_eq!(app.named_macros[&'a'].len(), 1);
+        assert!(app
+            .notification
+            .as_ref()
+            .unwrap()
+            .contains("1 of them can't be saved"));
+    }
+
+    #[test]
+    fn test_request_list_redraws_large_lists_without_refiltering() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = (0..10_000)
+            .map(|i| request(i, format!("/items/{}", i)))
+            .collect();
+        app.bookmarked.insert(RequestId("req-9999".to_string()));
+        app.table_state.select(Some(5_000));
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal
+            .draw(|frame| draw_request_list(frame, &mut app, frame.area()))
+            .unwrap();
+        // Rows are only built for 36 visible rows plus the margin either side
+        assert_eq!(app.visible_window, 4_955..5_011);
+
+        let list = app.filtered_list();
+        assert_eq!(list.indices[0], 9_999);
+        terminal
+            .draw(|frame| draw_request_list(frame, &mut app, frame.area()))
+            .unwrap();
+        assert!(
+            Arc::ptr_eq(&list, &app.filtered_list()),
+            "an unchanged list was filtered again"
+        );
+
+        app.requests.insert(0, request(10_000, "/new".to_string()));
+        assert!(!Arc::ptr_eq(&list, &app.filtered_list()));
+        app.search_query = "/items/1".to_string();
+        assert_eq!(app.filtered_list().indices.len(), 1_111);
+    }
+
+    #[test]
+    fn test_paging_stops_at_the_ends_of_the_list() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = (0..25).map(|i| request(i, format!("/{}", i))).collect();
+        app.table_state.select(None);
+
+        // 10 rows to a page until the list is drawn
+        app.page_down();
+        assert_eq!(app.table_state.selected(), Some(9));
+        app.page_down();
+        app.page_down();
+        assert_eq!(app.table_state.selected(), Some(24));
+        app.page_up();
+        assert_eq!(app.table_state.selected(), Some(14));
+        app.page_up();
+        app.page_up();
+        assert_eq!(app.table_state.selected(), Some(0));
+        assert!(!app.auto_scroll);
+    }
+
+    #[test]
+    fn test_auto_scroll_keeps_open_detail_view() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = vec![request(1, "/a".to_string()), request(2, "/b".to_string())];
+        app.auto_scroll = true;
+        app.table_state.select(Some(1));
+        let opened = app.selected_request().unwrap().id.clone();
+        app.view_mode = ViewMode::RequestDetail;
+
+        app.handle_event(TuiEvent::RequestReceived(RequestEvent {
+            request_id: RequestId("req-3".to_string()),
+            method: "GET".to_string(),
+            path: "/c".to_string(),
+            query_string: String::new(),
+            headers: Vec::new(),
+            body: None,
+            timestamp: Local::now(),
+            client_ip: None,
+            tunnel_url: None,
+            size_bytes: 0,
+        }));
+
+        assert_eq!(
+            app.selected_request().map(|req| req.id.clone()),
+            Some(opened)
+        );
+    }
+
+    #[test]
+    fn test_bookmarked_request_received_when_all_are_bookmarked() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = vec![request(1, "/a".to_string())];
+        // Imported ids repeat across sessions, so a new request can already
+        // be bookmarked
+        app.bookmarked = [
+            RequestId("req-1".to_string()),
+            RequestId("req-2".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        app.handle_event(TuiEvent::RequestReceived(RequestEvent {
+            request_id: RequestId("req-2".to_string()),
+            method: "GET".to_string(),
+            path: "/b".to_string(),
+            query_string: String::new(),
+            headers: Vec::new(),
+            body: None,
+            timestamp: Local::now(),
+            client_ip: None,
+            tunnel_url: None,
+            size_bytes: 0,
+        }));
+
+        assert_eq!(app.filtered_requests().len(), 2);
+        assert_eq!(app.table_state.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_annotations_pruned_to_known_requests() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = vec![request(1, "/a".to_string()), request(2, "/b".to_string())];
+        app.clear();
+        app.requests = vec![request(3, "/c".to_string())];
+        // Annotations loaded from an earlier session
+        for i in 1..=4 {
+            let id = RequestId(format!("req-{}", i));
+            app.bookmarked.insert(id.clone());
+            app.request_tags
+                .insert(id.clone(), vec!["auth".to_string()]);
+            app.request_notes.insert(id, "note".to_string());
+        }
+
+        app.prune_annotations();
+
+        // Cleared requests can still be restored, so theirs are kept
+        let mut bookmarked: Vec<&str> = app.bookmarked.iter().map(|id| id.0.as_str()).collect();
+        bookmarked.sort();
+        assert_eq!(bookmarked, ["req-1", "req-2", "req-3"]);
+        assert_eq!(app.request_tags.len(), 3);
+        assert!(!app
+            .request_notes
+            .contains_key(&RequestId("req-4".to_string())));
+    }
+
+    #[test]
+    fn test_default_ping_interval_feeds_quality_updates() {
+        // Each quality update needs a few pings answered since the last one
+        let ping_interval = ConnectionConfig::default().ws_ping_interval_secs;
+        let pings = QUALITY_UPDATE_INTERVAL.as_secs() / ping_interval;
+        assert!(
+            pings >= 2,
+            "{}s pings for {:?} updates",
+            ping_interval,
+            QUALITY_UPDATE_INTERVAL
+        );
+
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        for _ in 0..pings {
+            app.handle_event(TuiEvent::Heartbeat { rtt_ms: Some(20) });
+        }
+        app.quality_updated_at = Instant::now() - QUALITY_UPDATE_INTERVAL;
+        app.update_connection_quality();
+        assert_eq!(app.connection_quality, Some(ConnectionQuality::Excellent));
+    }
+
+    #[test]
+    fn test_undo_clear_restores_requests_and_position() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let config = TuiConfig {
+            undo_clear_limit: 4,
+            ..TuiConfig::default()
+        };
+        let mut app = App::new(cmd_tx, &config);
+        app.undo_clear();
+        assert_eq!(app.notification.as_deref(), Some("Nothing to restore"));
+
+        app.requests = (0..6).map(|i| request(i, format!("/{}", i))).collect();
+        app.table_state.select(Some(2));
+        app.clear();
+        assert!(app.requests.is_empty());
+        assert_eq!(app.table_state.selected(), None);
+
+        // A request arriving after the clear stays on top
+        app.requests.insert(0, request(10, "/new".to_string()));
+        app.undo_clear();
+        let ids: Vec<&str> = app.requests.iter().map(|r| r.id.0.as_str()).collect();
+        assert_eq!(ids, ["req-10", "req-0", "req-1", "req-2", "req-3"]);
+        assert_eq!(app.table_state.selected(), Some(2));
+        assert_eq!(app.notification.as_deref(), Some("Restored 4 requests"));
+
+        // Only the last clear can be undone
+        app.undo_clear();
+        assert_eq!(app.requests.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_ctrl_z_undoes_clear() {
+        let (cmd_tx, _cmd_rx) = create_command_channel();
+        let mut app = App::new(cmd_tx, &TuiConfig::default());
+        app.requests = (0..3).map(|i| request(i, format!("/{}", i))).collect();
+        app.view_mode = ViewMode::RequestList;
+        app.table_state.select(Some(1));
+
+        super::super::handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE),
+        )
+        .await;
+        assert!(app.requests.is_empty());
+        super::super::handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL),
+        )
+        .await;
+        assert_eq!(app.requests.len(), 3);
+        assert_eq!(app.table_state.selected(), Some(1));
+        // Without Ctrl, z still cycles the grouping
+        assert_eq!(app.group_by, None);
+
+        super::super::handle_key(
+            &mut app,
+            KeyEvent::new(KeyCode::Char('z'), KeyModifiers::NONE),
+        )
+        .await;
+        assert!(app.group_by.is_some());
+        assert_eq!(app.requests.len(), 3);
+    }
+}
@@ -1,16 +1,110 @@
 use anyhow::Result;
 use base64::Engine;
 use futures_util::{SinkExt, StreamExt};
-use std::sync::Arc;
-use tokio::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, Mutex, Notify};
 use tokio_tungstenite::{
     connect_async,
-    tungstenite::{client::IntoClientRequest, Message},
+    tungstenite::{
+        client::IntoClientRequest,
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
 };
-use tracing::{debug, info};
+use tracing::{debug, info, trace, warn};
 
 use crate::protocol::{OutgoingMessage, WsId};
 
+/// Upper bounds of the frame size histogram buckets; the last bucket is unbounded
+const SIZE_BUCKETS: [(usize, &str); 7] = [
+    (64, "<64B"),
+    (256, "<256B"),
+    (1024, "<1KB"),
+    (4 * 1024, "<4KB"),
+    (16 * 1024, "<16KB"),
+    (64 * 1024, "<64KB"),
+    (256 * 1024, "<256KB"),
+];
+
+/// Width of the longest bar in [`WsMetrics::histogram_chart`]
+const HISTOGRAM_BAR_WIDTH: u64 = 40;
+
+/// Payload of keepalive pings, so their pongs aren't passed on to the server
+const KEEPALIVE_PAYLOAD: &[u8] = b"burrow-keepalive";
+
+/// When idle local WebSocket connections are pinged
+#[derive(Debug, Clone, Copy)]
+pub struct WsKeepalive {
+    /// Idle time before a ping is sent; zero turns keepalive off
+    pub interval: Duration,
+    pub pong_timeout: Duration,
+}
+
+fn epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Traffic counters for one proxied WebSocket session
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WsMetrics {
+    /// Frames and bytes sent to the local service
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+    /// Frames and bytes received from the local service
+    pub frames_received: u64,
+    pub bytes_received: u64,
+    /// Sizes of frames received from the local service, bucketed as
+    /// <64B, <256B, <1KB, <4KB, <16KB, <64KB, <256KB and ≥256KB
+    pub size_histogram: [u64; 8],
+    /// Mean time between frames received from the local service
+    pub avg_frame_interval_ms: f64,
+}
+
+impl WsMetrics {
+    fn record_received(&mut self, size: usize, since_last: Option<f64>) {
+        self.frames_received += 1;
+        self.bytes_received += size as u64;
+        let bucket = SIZE_BUCKETS
+            .iter()
+            .position(|&(limit, _)| size < limit)
+            .unwrap_or(SIZE_BUCKETS.len());
+        self.size_histogram[bucket] += 1;
+
+        if let Some(interval_ms) = since_last {
+            // Running mean over the intervals seen so far
+            let intervals = (self.frames_received - 1) as f64;
+            self.avg_frame_interval_ms += (interval_ms - self.avg_frame_interval_ms) / intervals;
+        }
+    }
+
+    /// The size histogram as one ASCII bar per bucket, scaled to the largest
+    pub fn histogram_chart(&self) -> Vec<String> {
+        let max = self
+            .size_histogram
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let labels = SIZE_BUCKETS
+            .iter()
+            .map(|&(_, label)| label)
+            .chain(std::iter::once("≥256KB"));
+
+        labels
+            .zip(self.size_histogram)
+            .map(|(label, count)| {
+                let width = (count * HISTOGRAM_BAR_WIDTH).div_ceil(max) as usize;
+                format!("{:>7} │{} {}", label, "#".repeat(width), count)
+            })
+            .collect()
+    }
+}
+
 /// Proxy for WebSocket connections between server and local service
 pub struct WebSocketProxy {
     /// Channel to send frames from server to local
@@ -19,6 +113,15 @@ pub struct WebSocketProxy {
     from_local_rx: Arc<Mutex<mpsc::Receiver<Message>>>,
     /// Channel to send messages to server
     msg_tx: mpsc::Sender<String>,
+    /// Subprotocol chosen by the local server, if any
+    subprotocol: Option<String>,
+    metrics: std::sync::Mutex<WsMetrics>,
+    /// When a frame last went either way, in seconds since the epoch
+    last_frame_at: Arc<AtomicU64>,
+    /// Set while a keepalive ping is waiting for its pong
+    awaiting_pong: AtomicBool,
+    /// Signalled when the local service didn't answer a keepalive ping
+    keepalive_failed: Notify,
 }
 
 impl WebSocketProxy {
@@ -29,7 +132,8 @@ impl WebSocketProxy {
         path: &str,
         headers: Vec<Vec<String>>,
         msg_tx: mpsc::Sender<String>,
-    ) -> Result<Self> {
+        keepalive: WsKeepalive,
+    ) -> Result<Arc<Self>> {
         // Build WebSocket URL
         let url = format!("ws://{}:{}{}", local_host, local_port, path);
         debug!("Connecting to local WebSocket: {}", url);
@@ -41,8 +145,11 @@ impl WebSocketProxy {
         for header in &headers {
             if header.len() >= 2 {
                 let name_lower = header[0].to_lowercase();
-                // Only forward cookie and authorization headers
-                if name_lower == "cookie" || name_lower == "authorization" {
+                // Only forward cookie, authorization and subprotocol headers
+                if name_lower == "cookie"
+                    || name_lower == "authorization"
+                    || name_lower == "sec-websocket-protocol"
+                {
                     if let (Ok(name), Ok(value)) = (
                         header[0].parse::<http::header::HeaderName>(),
                         header[1].parse(),
@@ -55,7 +162,16 @@ impl WebSocketProxy {
 
         // Connect to local WebSocket
         let (ws_stream, response) = connect_async(request).await?;
-        info!("Local WebSocket connected, status: {}", response.status());
+        let subprotocol = response
+            .headers()
+            .get("sec-websocket-protocol")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        info!(
+            "Local WebSocket connected, status: {}, subprotocol: {}",
+            response.status(),
+            subprotocol.as_deref().unwrap_or("none")
+        );
         let (write, read) = ws_stream.split();
 
         // Create channels
@@ -73,6 +189,11 @@ impl WebSocketProxy {
                     "binary" => Message::Binary(data),
                     "ping" => Message::Ping(data),
                     "pong" => Message::Pong(data),
+                    // Close payloads are a big-endian code followed by the reason
+                    "close" if data.len() >= 2 => Message::Close(Some(CloseFrame {
+                        code: CloseCode::from(u16::from_be_bytes([data[0], data[1]])),
+                        reason: String::from_utf8_lossy(&data[2..]).into_owned().into(),
+                    })),
                     "close" => Message::Close(None),
                     _ => Message::Binary(data),
                 };
@@ -101,29 +222,134 @@ impl WebSocketProxy {
             }
         });
 
-        Ok(Self {
+        let proxy = Arc::new(Self {
             to_local_tx,
             from_local_rx: Arc::new(Mutex::new(from_local_rx)),
             msg_tx,
-        })
+            subprotocol,
+            metrics: std::sync::Mutex::new(WsMetrics::default()),
+            last_frame_at: Arc::new(AtomicU64::new(epoch_secs())),
+            awaiting_pong: AtomicBool::new(false),
+            keepalive_failed: Notify::new(),
+        });
+        if !keepalive.interval.is_zero() {
+            tokio::spawn(ws_keepalive_task(Arc::downgrade(&proxy), keepalive));
+        }
+        Ok(proxy)
+    }
+
+    /// Subprotocol negotiated with the local server
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
+    /// Response headers to report back to the server in `ws_upgraded`
+    pub fn upgrade_headers(&self) -> Vec<[String; 2]> {
+        self.subprotocol
+            .iter()
+            .map(|p| ["sec-websocket-protocol".to_string(), p.clone()])
+            .collect()
     }
 
     /// Send a frame from server to local
     pub async fn send_to_local(&self, opcode: &str, data: Vec<u8>) {
+        self.last_frame_at.store(epoch_secs(), Ordering::Relaxed);
+        trace!(
+            "{} frame to local WebSocket: {}",
+            opcode,
+            WsFramePreview::preview(opcode == "text", &data)
+        );
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.frames_sent += 1;
+            metrics.bytes_sent += data.len() as u64;
+        }
         let _ = self.to_local_tx.send((opcode.to_string(), data)).await;
     }
 
+    /// Traffic counters for this session so far
+    pub fn metrics(&self) -> WsMetrics {
+        *self.metrics.lock().unwrap()
+    }
+
+    /// Ping the local service to keep an idle connection open. Returns
+    /// false if the connection is already gone.
+    pub fn send_ping(&self) -> bool {
+        let ping = ("ping".to_string(), KEEPALIVE_PAYLOAD.to_vec());
+        let sent = self.to_local_tx.try_send(ping).is_ok();
+        if sent {
+            self.awaiting_pong.store(true, Ordering::Relaxed);
+        }
+        sent
+    }
+
     /// Close the local WebSocket connection
-    pub async fn close(&self, _code: u16, _reason: &str) {
+    pub async fn close(&self, code: u16, reason: &str) {
+        let mut payload = code.to_be_bytes().to_vec();
+        payload.extend_from_slice(reason.as_bytes());
         // Send close through the channel to avoid lock-across-await
-        let _ = self.to_local_tx.send(("close".to_string(), vec![])).await;
+        let _ = self.to_local_tx.send(("close".to_string(), payload)).await;
     }
 
     /// Run the proxy, forwarding frames from local to server
     pub async fn run(&self, ws_id: &WsId) {
         let mut rx = self.from_local_rx.lock().await;
+        let mut last_received: Option<Instant> = None;
+
+        loop {
+            let msg = tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                _ = self.keepalive_failed.notified() => {
+                    let msg = OutgoingMessage::WsClose {
+                        ws_id: ws_id.clone(),
+                        code: 1001,
+                        reason: "keepalive timeout".to_string(),
+                    };
+                    if let Ok(json) = msg.to_json() {
+                        let _ = self.msg_tx.try_send(json);
+                    }
+                    break;
+                }
+            };
+            self.last_frame_at.store(epoch_secs(), Ordering::Relaxed);
+            if matches!(&msg, Message::Pong(data) if data.as_slice() == KEEPALIVE_PAYLOAD) {
+                self.awaiting_pong.store(false, Ordering::Relaxed);
+                continue;
+            }
+
+            let now = Instant::now();
+            let since_last = last_received.map(|t| (now - t).as_secs_f64() * 1000.0);
+            last_received = Some(now);
+            let size = match &msg {
+                Message::Text(text) => Some(text.len()),
+                Message::Binary(data) | Message::Ping(data) | Message::Pong(data) => {
+                    Some(data.len())
+                }
+                _ => None,
+            };
+            if let Some(size) = size {
+                self.metrics
+                    .lock()
+                    .unwrap()
+                    .record_received(size, since_last);
+            }
+            match &msg {
+                Message::Text(text) => trace!(
+                    "WebSocket {} text frame from local: {}",
+                    ws_id,
+                    WsFramePreview::preview_text(text)
+                ),
+                Message::Binary(data) => trace!(
+                    "WebSocket {} binary frame from local: {}",
+                    ws_id,
+                    WsFramePreview::preview_hex(data)
+                ),
+                _ => {}
+            }
 
-        while let Some(msg) = rx.recv().await {
             let result = match msg {
                 Message::Text(text) => {
                     let msg = OutgoingMessage::WsFrame {
@@ -193,3 +419,146 @@ impl WebSocketProxy {
         }
     }
 }
+
+/// Ping the local service whenever the connection has been idle for the
+/// keepalive interval, closing it if the pong doesn't come back in time.
+/// Ends once the proxy is dropped.
+async fn ws_keepalive_task(weak: Weak<WebSocketProxy>, keepalive: WsKeepalive) {
+    let mut interval = tokio::time::interval(keepalive.interval);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let Some(proxy) = weak.upgrade() else {
+            return;
+        };
+        let idle_secs = epoch_secs().saturating_sub(proxy.last_frame_at.load(Ordering::Relaxed));
+        if idle_secs < keepalive.interval.as_secs() {
+            continue;
+        }
+        if !proxy.send_ping() {
+            return;
+        }
+        drop(proxy);
+
+        tokio::time::sleep(keepalive.pong_timeout).await;
+        let Some(proxy) = weak.upgrade() else {
+            return;
+        };
+        if proxy.awaiting_pong.load(Ordering::Relaxed) {
+            warn!("Local WebSocket didn't answer a keepalive ping, closing it");
+            proxy.close(1001, "keepalive timeout").await;
+            proxy.keepalive_failed.notify_one();
+            return;
+        }
+    }
+}
+
+/// One-line previews of WebSocket frame payloads, logged at trace level
+pub struct WsFramePreview;
+
+impl WsFramePreview {
+    /// Bytes of a binary frame shown in a preview
+    const BINARY_BYTES: usize = 16;
+    /// Characters of a text frame shown in a preview
+    const TEXT_CHARS: usize = 80;
+
+    /// Preview a frame's payload as text or, for binary frames, as hex
+    pub fn preview(is_text: bool, data: &[u8]) -> String {
+        if is_text {
+            Self::preview_text(&String::from_utf8_lossy(data))
+        } else {
+            Self::preview_hex(data)
+        }
+    }
+
+    /// The first 16 bytes as hex and ASCII, like `tcpdump -X`
+    pub fn preview_hex(data: &[u8]) -> String {
+        let shown = &data[..data.len().min(Self::BINARY_BYTES)];
+
+        let hex = shown
+            .chunks(2)
+            .map(|pair| {
+                pair.iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii: String = shown
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        // 16 bytes take 39 columns of hex; pad so the ASCII lines up
+        let mut preview = format!("{:<39}  {}", hex, ascii);
+        if data.len() > shown.len() {
+            preview.push('…');
+        }
+        preview
+    }
+
+    /// The first 80 characters of a text frame
+    pub fn preview_text(text: &str) -> String {
+        let mut chars = text.chars();
+        let mut preview: String = chars.by_ref().take(Self::TEXT_CHARS).collect();
+        if chars.next().is_some() {
+            preview.push('…');
+        }
+        preview
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_histogram() {
+        let mut metrics = WsMetrics::default();
+        metrics.record_received(10, None);
+        metrics.record_received(63, Some(10.0));
+        metrics.record_received(64, Some(20.0));
+        metrics.record_received(300 * 1024, Some(30.0));
+
+        assert_eq!(metrics.frames_received, 4);
+        assert_eq!(metrics.size_histogram, [2, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(metrics.avg_frame_interval_ms, 20.0);
+
+        let chart = metrics.histogram_chart();
+        assert_eq!(chart.len(), 8);
+        assert_eq!(chart[0], format!("   <64B │{} 2", "#".repeat(40)));
+        assert_eq!(chart[2], "   <1KB │ 0");
+    }
+
+    #[test]
+    fn test_frame_previews() {
+        assert_eq!(
+            WsFramePreview::preview_hex(b"Hello\x00"),
+            format!("{:<39}  Hello.", "4865 6c6c 6f00")
+        );
+
+        let data: Vec<u8> = (0..20).collect();
+        let preview = WsFramePreview::preview_hex(&data);
+        assert!(preview.starts_with("0001 0203 0405 0607 0809 0a0b 0c0d 0e0f  "));
+        assert!(preview.ends_with('…'));
+        assert!(preview.chars().count() <= 60);
+
+        assert_eq!(WsFramePreview::preview(true, b"short"), "short");
+        assert_eq!(
+            WsFramePreview::preview(false, b"hi"),
+            format!("{:<39}  hi", "6869")
+        );
+        assert_eq!(
+            WsFramePreview::preview_text(&"x".repeat(100))
+                .chars()
+                .count(),
+            81
+        );
+    }
+}
@@ -0,0 +1,151 @@
+//! `burrow completion-check`: tell whether shell completions are installed
+//! and loaded in the current shell.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use directories::UserDirs;
+
+/// Exported by the scripts `burrow completions` prints, so a shell that has
+/// sourced one passes it on to `burrow`
+pub const LOADED_ENV_VAR: &str = "BURROW_COMPLETIONS_LOADED";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(name = "powershell", alias = "pwsh")]
+    Pwsh,
+}
+
+impl Shell {
+    /// The shell from `$SHELL` (or `$0` when a shell exports it), falling
+    /// back to PowerShell when its module path is set
+    pub fn detect() -> Option<Shell> {
+        let from_env = ["SHELL", "0"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find_map(|value| Shell::from_path(&value));
+        from_env.or_else(|| env::var_os("PSModulePath").map(|_| Shell::Pwsh))
+    }
+
+    fn from_path(path: &str) -> Option<Shell> {
+        let name = Path::new(path.trim_start_matches('-'))
+            .file_stem()?
+            .to_str()?;
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "pwsh" | "powershell" => Some(Shell::Pwsh),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Pwsh => "powershell",
+        }
+    }
+
+    /// The startup file completions are usually appended to
+    fn rc_file(self) -> &'static str {
+        match self {
+            Shell::Bash => "~/.bashrc",
+            Shell::Zsh => "~/.zshrc",
+            Shell::Fish => "~/.config/fish/config.fish",
+            Shell::Pwsh => "$PROFILE",
+        }
+    }
+
+    /// Where completion scripts for this shell are commonly installed,
+    /// relative to the home directory, and whether the file only holds
+    /// completions (rather than being a startup file that may source them)
+    fn known_files(self) -> &'static [(&'static str, bool)] {
+        match self {
+            Shell::Bash => &[
+                (".bashrc", false),
+                (".bash_profile", false),
+                (".local/share/bash-completion/completions/burrow", true),
+                (".bash_completion", false),
+            ],
+            Shell::Zsh => &[
+                (".zshrc", false),
+                (".zfunc/_burrow", true),
+                (".oh-my-zsh/completions/_burrow", true),
+            ],
+            Shell::Fish => &[
+                (".config/fish/completions/burrow.fish", true),
+                (".config/fish/config.fish", false),
+            ],
+            Shell::Pwsh => &[
+                (".config/powershell/Microsoft.PowerShell_profile.ps1", false),
+                (
+                    "Documents/PowerShell/Microsoft.PowerShell_profile.ps1",
+                    false,
+                ),
+                (
+                    "Documents/WindowsPowerShell/Microsoft.PowerShell_profile.ps1",
+                    false,
+                ),
+            ],
+        }
+    }
+}
+
+/// The first well-known file that installs burrow's completions
+fn find_completion_script(shell: Shell, home: &Path) -> Option<PathBuf> {
+    shell
+        .known_files()
+        .iter()
+        .map(|(file, dedicated)| (home.join(file), *dedicated))
+        .find(|(path, dedicated)| match fs::read_to_string(path) {
+            Ok(_) if *dedicated => true,
+            Ok(contents) => mentions_completions(&contents),
+            Err(_) => false,
+        })
+        .map(|(path, _)| path)
+}
+
+/// Whether a startup file sources or defines burrow's completions
+fn mentions_completions(contents: &str) -> bool {
+    contents.contains("burrow completions") || contents.contains("_burrow")
+}
+
+/// Print whether completions are set up for the current shell
+pub fn run() {
+    let Some(shell) = Shell::detect() else {
+        println!("✗ Could not detect your shell (bash, zsh, fish and PowerShell are supported)");
+        return;
+    };
+    let Some(home) = UserDirs::new().map(|dirs| dirs.home_dir().to_path_buf()) else {
+        println!("✗ Could not find your home directory");
+        return;
+    };
+
+    let install = format!("burrow completions {} >> {}", shell.name(), shell.rc_file());
+    if env::var_os(LOADED_ENV_VAR).is_some() {
+        println!("✓ Completions loaded");
+        return;
+    }
+    match find_completion_script(shell, &home) {
+        Some(path) => println!(
+            "⚠ Completions file found but not loaded (run: source {})",
+            display_home(&path, &home)
+        ),
+        None => println!("✗ No completion script found (run: {})", install),
+    }
+}
+
+fn display_home(path: &Path, home: &Path) -> String {
+    match path.strip_prefix(home) {
+        Ok(rest) => format!("~/{}", rest.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
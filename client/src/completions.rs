@@ -0,0 +1,175 @@
+//! `burrow completions <shell>`: print a completion script for the shell.
+//!
+//! The scripts complete subcommands and long options, and export
+//! [`LOADED_ENV_VAR`] so `burrow completion-check` can tell they were sourced.
+
+use clap::Command;
+
+use crate::completion_check::{Shell, LOADED_ENV_VAR};
+
+/// Subcommand paths (like `burrow/tunnel/stats`) and the words completed
+/// after each
+fn completion_words(cmd: &mut Command) -> Vec<(String, Vec<String>)> {
+    cmd.build();
+    let mut paths = Vec::new();
+    collect_words(cmd, cmd.get_name().to_string(), &mut paths);
+    paths
+}
+
+fn collect_words(cmd: &Command, path: String, paths: &mut Vec<(String, Vec<String>)>) {
+    let subcommands: Vec<&Command> = cmd
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+        .collect();
+    let mut words: Vec<String> = subcommands
+        .iter()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    words.extend(
+        cmd.get_arguments()
+            .filter(|arg| !arg.is_hide_set())
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{}", long)),
+    );
+    paths.push((path.clone(), words));
+
+    for sub in subcommands {
+        collect_words(sub, format!("{}/{}", path, sub.get_name()), paths);
+    }
+}
+
+/// The completion script for `shell`
+pub fn script(shell: Shell, cmd: &mut Command) -> String {
+    let paths = completion_words(cmd);
+    match shell {
+        Shell::Bash => bash_script(&paths),
+        Shell::Zsh => format!(
+            "autoload -U +X bashcompinit && bashcompinit\n{}",
+            bash_script(&paths)
+        ),
+        Shell::Fish => fish_script(&paths),
+        Shell::Pwsh => powershell_script(&paths),
+    }
+}
+
+fn bash_script(paths: &[(String, Vec<String>)]) -> String {
+    let known: Vec<&str> = paths.iter().map(|(path, _)| path.as_str()).collect();
+    let mut script = String::from(
+        "_burrow() {\n    \
+         local cur=\"${COMP_WORDS[COMP_CWORD]}\" path=\"burrow\" word\n    \
+         for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n        \
+         case \"$path/$word\" in\n",
+    );
+    script.push_str(&format!(
+        "            {}) path=\"$path/$word\" ;;\n",
+        known.join("|")
+    ));
+    script.push_str("        esac\n    done\n    case \"$path\" in\n");
+    for (path, words) in paths {
+        script.push_str(&format!(
+            "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;\n",
+            path,
+            words.join(" ")
+        ));
+    }
+    script.push_str("    esac\n}\ncomplete -o default -F _burrow burrow\n");
+    script.push_str(&format!("export {}=1\n", LOADED_ENV_VAR));
+    script
+}
+
+fn fish_script(paths: &[(String, Vec<String>)]) -> String {
+    let known: Vec<&str> = paths.iter().map(|(path, _)| path.as_str()).collect();
+    let mut script = format!(
+        "function __burrow_path\n    \
+         set -l path burrow\n    \
+         for word in (commandline -opc)[2..-1]\n        \
+         if contains -- \"$path/$word\" {}\n            \
+         set path \"$path/$word\"\n        \
+         end\n    \
+         end\n    \
+         test \"$path\" = \"$argv[1]\"\n\
+         end\n",
+        known.join(" ")
+    );
+    for (path, words) in paths {
+        script.push_str(&format!(
+            "complete -c burrow -n '__burrow_path {}' -a '{}'\n",
+            path,
+            words.join(" ")
+        ));
+    }
+    script.push_str(&format!("set -gx {} 1\n", LOADED_ENV_VAR));
+    script
+}
+
+fn powershell_script(paths: &[(String, Vec<String>)]) -> String {
+    let mut script = String::from(
+        "Register-ArgumentCompleter -Native -CommandName burrow -ScriptBlock {\n    \
+         param($wordToComplete, $commandAst, $cursorPosition)\n    \
+         $words = @{\n",
+    );
+    for (path, words) in paths {
+        let words: Vec<String> = words.iter().map(|word| format!("'{}'", word)).collect();
+        script.push_str(&format!("        '{}' = @({})\n", path, words.join(", ")));
+    }
+    script.push_str(
+        "    }\n    \
+         $path = 'burrow'\n    \
+         foreach ($element in $commandAst.CommandElements | Select-Object -Skip 1) {\n        \
+         if ($element.Extent.EndOffset -ge $cursorPosition) { break }\n        \
+         $next = \"$path/$element\"\n        \
+         if ($words.ContainsKey($next)) { $path = $next }\n    \
+         }\n    \
+         $words[$path] | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n        \
+         [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n    \
+         }\n\
+         }\n",
+    );
+    script.push_str(&format!("$env:{} = '1'\n", LOADED_ENV_VAR));
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    fn test_command() -> Command {
+        Command::new("burrow")
+            .arg(Arg::new("server").long("server").global(true))
+            .subcommand(Command::new("start").arg(Arg::new("port").long("port")))
+            .subcommand(Command::new("tunnel").subcommand(Command::new("stats")))
+            .subcommand(Command::new("completion-check").hide(true))
+    }
+
+    #[test]
+    fn test_completion_words_follow_subcommands() {
+        let paths = completion_words(&mut test_command());
+        let words = |path: &str| {
+            paths
+                .iter()
+                .find(|(p, _)| p == path)
+                .map(|(_, words)| words.clone())
+                .unwrap()
+        };
+
+        assert!(words("burrow").contains(&"start".to_string()));
+        assert!(words("burrow").contains(&"tunnel".to_string()));
+        assert!(!words("burrow").contains(&"completion-check".to_string()));
+        assert!(words("burrow/start").contains(&"--port".to_string()));
+        // Global options are offered after subcommands too
+        assert!(words("burrow/tunnel/stats").contains(&"--server".to_string()));
+        assert!(!paths
+            .iter()
+            .any(|(path, _)| path.contains("completion-check")));
+    }
+
+    #[test]
+    fn test_scripts_mark_completions_loaded() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Pwsh] {
+            let script = script(shell, &mut test_command());
+            assert!(script.contains(LOADED_ENV_VAR), "{:?}", shell);
+            assert!(script.contains("burrow/tunnel/stats"), "{:?}", shell);
+        }
+    }
+}
@@ -1,38 +1,801 @@
 //! Configuration management for the Burrow client.
 //!
-//! Handles loading and saving configuration from `~/.burrow/config.toml`.
+//! Handles loading and saving configuration from `~/.burrow/config.toml`,
+//! merged with a project `.burrow.toml` and `BURROW_*` environment variables.
 
 use anyhow::{Context, Result};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+/// Project config in the current directory, layered over the user config
+const PROJECT_CONFIG_FILE: &str = ".burrow.toml";
+/// Settings whose values are never shown in a [`ConfigDiff`]
+const REDACTED_FIELDS: [&str; 1] = ["auth.token"];
+
+/// One setting that differs between two configs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub field: String,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+impl std::fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field, self.old_value, self.new_value
+        )
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub tui: TuiConfig,
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    /// Tunnels registered as soon as the client connects
+    #[serde(default)]
+    pub tunnels: Vec<TunnelSpec>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct AuthConfig {
     pub token: Option<String>,
     pub server: Option<String>,
+    /// `ws` for servers without TLS, e.g. behind a TLS-terminating proxy
+    #[serde(default)]
+    pub server_scheme: ServerScheme,
+}
+
+/// WebSocket scheme used to reach the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerScheme {
+    #[default]
+    Wss,
+    /// Unencrypted; the token and all traffic are sent in the clear
+    Ws,
+}
+
+impl ServerScheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ServerScheme::Wss => "wss",
+            ServerScheme::Ws => "ws",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogConfig {
+    /// Log level or filter directives used when neither `--log-filter`,
+    /// `RUST_LOG` nor `-v` is given
+    pub level: Option<String>,
+}
+
+/// A tunnel to open on startup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TunnelSpec {
+    Http {
+        port: u16,
+        subdomain: Option<String>,
+        /// Labels the server can filter tunnels by, e.g. `team:frontend`
+        #[serde(default)]
+        tags: Vec<String>,
+        /// Server to open the tunnel on when `start` is given several;
+        /// the first one if unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        server: Option<String>,
+    },
+    Tcp {
+        port: u16,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        server: Option<String>,
+    },
+}
+
+impl TunnelSpec {
+    /// Server the tunnel is meant for, if it names one
+    pub fn server(&self) -> Option<&str> {
+        match self {
+            TunnelSpec::Http { server, .. } | TunnelSpec::Tcp { server, .. } => server.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TuiConfig {
+    /// Capacity of the connection -> TUI event channel
+    #[serde(default = "default_event_channel_capacity")]
+    pub event_channel_capacity: usize,
+    /// How request timestamps are displayed
+    #[serde(default)]
+    pub time_display: TimeDisplay,
+    /// Requests kept in the request list; older ones are dropped
+    #[serde(default = "default_max_requests")]
+    pub max_requests: usize,
+    /// Cleared requests kept so Ctrl-Z can bring them back
+    #[serde(default = "default_undo_clear_limit")]
+    pub undo_clear_limit: usize,
+    /// What the status bar shows, left to right
+    #[serde(default = "default_status_bar_slots")]
+    pub status_bar_slots: Vec<StatusBarSlot>,
+    /// Columns of the request list, left to right
+    #[serde(default = "default_request_list_columns")]
+    pub request_list_columns: Vec<RequestListColumnConfig>,
+    /// Characters added to (or, when negative, taken from) request list
+    /// columns with Ctrl-Left/Right
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub column_width_adjustments: BTreeMap<RequestListColumn, i16>,
+    /// Spacing of the request list rows
+    #[serde(default)]
+    pub display_density: DisplayDensity,
+    /// Navigation key preset
+    #[serde(default)]
+    pub keyboard_mode: KeyboardMode,
+    /// Colour preset name, or a `[tui.theme]` table overriding a preset
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    /// Pause between keys when replaying a keyboard macro
+    #[serde(default)]
+    pub macro_replay_delay_ms: u64,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            event_channel_capacity: default_event_channel_capacity(),
+            time_display: TimeDisplay::default(),
+            max_requests: default_max_requests(),
+            undo_clear_limit: default_undo_clear_limit(),
+            status_bar_slots: default_status_bar_slots(),
+            request_list_columns: default_request_list_columns(),
+            column_width_adjustments: BTreeMap::new(),
+            display_density: DisplayDensity::default(),
+            keyboard_mode: KeyboardMode::default(),
+            theme: ThemeConfig::default(),
+            macro_replay_delay_ms: 0,
+        }
+    }
+}
+
+/// Request timestamp display format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeDisplay {
+    /// Local wall-clock time (`14:03:27`)
+    #[default]
+    Absolute,
+    /// Time since the request arrived (`3.2s ago`)
+    Relative,
+    /// Absolute in lists, both side by side in the detail view
+    Both,
+}
+
+impl TimeDisplay {
+    pub fn next(self) -> Self {
+        match self {
+            TimeDisplay::Absolute => TimeDisplay::Relative,
+            TimeDisplay::Relative => TimeDisplay::Both,
+            TimeDisplay::Both => TimeDisplay::Absolute,
+        }
+    }
+}
+
+/// Spacing of the request list rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayDensity {
+    /// One line per request and no gap under the header
+    Compact,
+    /// One line per request
+    #[default]
+    Normal,
+    /// A second line per request with the start of its body
+    Comfortable,
+}
+
+impl DisplayDensity {
+    pub fn next(self) -> Self {
+        match self {
+            DisplayDensity::Compact => DisplayDensity::Normal,
+            DisplayDensity::Normal => DisplayDensity::Comfortable,
+            DisplayDensity::Comfortable => DisplayDensity::Compact,
+        }
+    }
+
+    /// Lines taken by each request list row
+    pub fn row_height(self) -> u16 {
+        match self {
+            DisplayDensity::Comfortable => 2,
+            DisplayDensity::Compact | DisplayDensity::Normal => 1,
+        }
+    }
+
+    /// Blank lines between the request list header and the first row
+    pub fn header_margin(self) -> u16 {
+        match self {
+            DisplayDensity::Compact => 0,
+            DisplayDensity::Normal | DisplayDensity::Comfortable => 1,
+        }
+    }
+}
+
+/// Navigation key preset for the TUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyboardMode {
+    /// Arrow keys plus `j`/`k`
+    #[default]
+    Default,
+    /// Adds `Ctrl-D`/`Ctrl-U` and `Ctrl-F`/`Ctrl-B` paging
+    Vim,
+    /// `Ctrl-N`/`Ctrl-P`, `Ctrl-F`/`Ctrl-B`, `Ctrl-G` and `Ctrl-K`
+    Emacs,
+}
+
+/// Built-in TUI colour schemes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    Light,
+    /// No colours, only bold, underline and reverse video
+    HighContrast,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ThemeConfig {
+    Preset(ThemePreset),
+    Custom(Box<CustomTheme>),
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig::Preset(ThemePreset::default())
+    }
+}
+
+/// Colours overriding a preset. Values are colour names (`red`,
+/// `lightblue`), indexes (`208`) or hex (`#ff8800`).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CustomTheme {
+    #[serde(default)]
+    pub base: ThemePreset,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method_get: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method_post: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method_put: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method_delete: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_success: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_redirect: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_warning: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_primary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_secondary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_dim: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_alt_background: Option<String>,
+}
+
+fn default_event_channel_capacity() -> usize {
+    256
+}
+
+fn default_max_requests() -> usize {
+    1000
+}
+
+fn default_undo_clear_limit() -> usize {
+    1000
+}
+
+/// An item in the status bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusBarSlot {
+    /// Public URLs of the HTTP and TCP tunnels
+    TunnelUrl,
+    /// Connection status and quality
+    ConnectionStatus,
+    RequestCount,
+    BytesIn,
+    BytesOut,
+    /// Average ping round-trip time to the server
+    Rtt,
+    /// Share of requests answered with a 5xx or a proxy error
+    ErrorRate,
+    /// Average response time of the local service
+    Latency,
+    /// Messages waiting to be sent to the server, once more than 10 back up
+    QueueDepth,
+}
+
+fn default_status_bar_slots() -> Vec<StatusBarSlot> {
+    vec![
+        StatusBarSlot::ConnectionStatus,
+        StatusBarSlot::TunnelUrl,
+        StatusBarSlot::RequestCount,
+        StatusBarSlot::BytesIn,
+        StatusBarSlot::BytesOut,
+        StatusBarSlot::QueueDepth,
+    ]
+}
+
+/// A column in the request list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestListColumn {
+    Time,
+    Method,
+    Path,
+    Status,
+    Duration,
+    /// Response size
+    Size,
+    /// `📝` when the request has a note
+    Note,
+    ClientIp,
+    /// Public URL of the tunnel the request arrived through
+    Tunnel,
+    Tags,
+}
+
+impl RequestListColumn {
+    pub fn title(self) -> &'static str {
+        match self {
+            RequestListColumn::Time => "TIME",
+            RequestListColumn::Method => "METHOD",
+            RequestListColumn::Path => "PATH",
+            RequestListColumn::Status => "STATUS",
+            RequestListColumn::Duration => "DURATION",
+            RequestListColumn::Size => "SIZE",
+            RequestListColumn::Note => "",
+            RequestListColumn::ClientIp => "CLIENT IP",
+            RequestListColumn::Tunnel => "TUNNEL",
+            RequestListColumn::Tags => "TAGS",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestListColumnConfig {
+    pub column: RequestListColumn,
+    /// Width in characters; without one the column fills the remaining space
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u16>,
+}
+
+fn default_request_list_columns() -> Vec<RequestListColumnConfig> {
+    let column = |column, width| RequestListColumnConfig { column, width };
+    vec![
+        column(RequestListColumn::Time, Some(14)),
+        column(RequestListColumn::Method, Some(8)),
+        column(RequestListColumn::Path, None),
+        column(RequestListColumn::Status, Some(8)),
+        column(RequestListColumn::Duration, Some(10)),
+        column(RequestListColumn::Size, Some(9)),
+        column(RequestListColumn::Note, Some(2)),
+        column(RequestListColumn::Tags, Some(20)),
+    ]
+}
+
+/// Timeouts applied when forwarding requests to the local service.
+///
+/// A value of `0` disables the corresponding timeout.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(default = "default_connect_timeout_ms")]
+    pub connect_timeout_ms: Option<u64>,
+    #[serde(default = "default_read_timeout_ms")]
+    pub read_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub total_timeout_ms: Option<u64>,
+    /// Headers rewritten or added on requests sent to the local service
+    #[serde(default = "default_inject_headers")]
+    pub inject_headers: Vec<InjectedHeader>,
+    /// Streamed request bodies at least this large (or of unknown size) are
+    /// passed to the local service as they arrive instead of being buffered
+    #[serde(default = "default_streaming_threshold_bytes")]
+    pub streaming_threshold_bytes: usize,
+    /// If set, only response headers matching one of these names are sent
+    /// back through the tunnel. A trailing `*` matches any suffix.
+    #[serde(default)]
+    pub response_header_allowlist: Option<Vec<String>>,
+    /// Request headers never forwarded to the local service. A trailing `*`
+    /// matches any suffix.
+    #[serde(default)]
+    pub request_header_denylist: Vec<String>,
+    /// Answer repeated GET requests from a cache when the local service
+    /// marks the response as cacheable
+    #[serde(default)]
+    pub cache_responses: bool,
+    /// Size limit for cached responses, applied separately to this cache and
+    /// to the responses kept for replayed requests
+    #[serde(default = "default_cache_max_size_mb")]
+    pub cache_max_size_mb: u32,
+    /// Ping local WebSocket connections that have been idle this long, so
+    /// the local service's idle timeout doesn't close them (0 to disable)
+    #[serde(default = "default_ws_keepalive_interval_secs")]
+    pub ws_keepalive_interval_secs: u64,
+    /// How long the local service gets to answer a keepalive ping before
+    /// the connection is closed
+    #[serde(default = "default_ws_pong_timeout_secs")]
+    pub ws_pong_timeout_secs: u64,
+    /// Server-Sent Events streams are closed after this long (0 to disable)
+    #[serde(default = "default_max_sse_duration_secs")]
+    pub max_sse_duration_secs: u64,
+    /// Messages from the server larger than this are dropped, answering a
+    /// request with a 413; twice this size drops the connection
+    #[serde(default = "default_max_message_bytes")]
+    pub max_incoming_message_bytes: usize,
+    /// Responses larger than this as one message are sent in chunks
+    #[serde(default = "default_max_message_bytes")]
+    pub max_outgoing_message_bytes: usize,
+    /// HTML page returned when the local service fails, with `{status}`,
+    /// `{reason}`, `{local_port}`, `{message}` and `{details}` filled in
+    #[serde(default)]
+    pub custom_error_template: Option<PathBuf>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_ms: default_connect_timeout_ms(),
+            read_timeout_ms: default_read_timeout_ms(),
+            total_timeout_ms: None,
+            inject_headers: default_inject_headers(),
+            streaming_threshold_bytes: default_streaming_threshold_bytes(),
+            response_header_allowlist: None,
+            request_header_denylist: Vec::new(),
+            cache_responses: false,
+            cache_max_size_mb: default_cache_max_size_mb(),
+            ws_keepalive_interval_secs: default_ws_keepalive_interval_secs(),
+            ws_pong_timeout_secs: default_ws_pong_timeout_secs(),
+            max_sse_duration_secs: default_max_sse_duration_secs(),
+            max_incoming_message_bytes: default_max_message_bytes(),
+            max_outgoing_message_bytes: default_max_message_bytes(),
+            custom_error_template: None,
+        }
+    }
+}
+
+/// A header the proxy sets on requests forwarded to the local service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InjectedHeader {
+    /// `Host: <local_host>:<local_port>`, so virtual-hosted services route correctly
+    Host,
+    /// `X-Forwarded-Host` carrying the public host the request was sent to
+    XForwardedHost,
+}
+
+fn default_inject_headers() -> Vec<InjectedHeader> {
+    vec![InjectedHeader::Host, InjectedHeader::XForwardedHost]
+}
+
+fn default_ws_keepalive_interval_secs() -> u64 {
+    30
+}
+
+fn default_ws_pong_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_message_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_max_sse_duration_secs() -> u64 {
+    300
+}
+
+fn default_streaming_threshold_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_cache_max_size_mb() -> u32 {
+    64
+}
+
+fn default_connect_timeout_ms() -> Option<u64> {
+    Some(5000)
+}
+
+fn default_read_timeout_ms() -> Option<u64> {
+    Some(30_000)
+}
+
+/// Settings for the WebSocket connection to the Burrow server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionConfig {
+    /// Chunk size requested from the server when streaming large request bodies
+    #[serde(default = "default_chunk_size_bytes")]
+    pub chunk_size_bytes: usize,
+    /// How long to wait for the server to confirm a tunnel before retrying
+    #[serde(default = "default_registration_timeout_secs")]
+    pub registration_timeout_secs: u64,
+    /// How long a response is kept for answering requests the server replays
+    #[serde(default = "default_response_cache_ttl_secs")]
+    pub response_cache_ttl_secs: u64,
+    /// How long to wait for in-flight requests when shutting down
+    #[serde(default = "default_graceful_shutdown_timeout_secs")]
+    pub graceful_shutdown_timeout_secs: u64,
+    /// Give up after this many consecutive failed reconnect attempts
+    #[serde(default = "default_max_reconnect_attempts")]
+    pub max_reconnect_attempts: u32,
+    /// Wait before the first reconnect attempt
+    #[serde(default = "default_reconnect_initial_backoff_ms")]
+    pub reconnect_initial_backoff_ms: u64,
+    /// Longest wait between reconnect attempts
+    #[serde(default = "default_reconnect_max_backoff_ms")]
+    pub reconnect_max_backoff_ms: u64,
+    /// Factor the wait grows by after each failed reconnect attempt
+    #[serde(default = "default_reconnect_backoff_multiplier")]
+    pub reconnect_backoff_multiplier: f64,
+    /// How often an application heartbeat message is sent to the server
+    #[serde(
+        default = "default_heartbeat_interval_secs",
+        alias = "app_heartbeat_interval_secs"
+    )]
+    pub heartbeat_interval_secs: u64,
+    /// How often a WebSocket ping frame is sent to measure round-trip time;
+    /// the connection quality indicator needs a few per 10s update
+    #[serde(default = "default_ws_ping_interval_secs")]
+    pub ws_ping_interval_secs: u64,
+    /// Try the server's IPv6 addresses before its IPv4 ones
+    #[serde(default = "default_prefer_ipv6")]
+    pub prefer_ipv6: bool,
+    /// How long a connection attempt gets before the next address is tried
+    /// alongside it
+    #[serde(default = "default_happy_eyeballs_timeout_ms")]
+    pub happy_eyeballs_timeout_ms: u64,
+    /// Send queued messages together in one `batch` message when several
+    /// are waiting
+    #[serde(default)]
+    pub message_batching: bool,
+    #[serde(default = "default_max_batch_size")]
+    pub max_batch_size: usize,
+    /// How long a batch waits for another message before it is sent
+    #[serde(default = "default_batch_timeout_ms")]
+    pub batch_timeout_ms: u64,
+    /// Warn when a TCP tunnel connection to the local service takes longer
+    /// than this to be accepted
+    #[serde(default = "default_slow_tcp_connect_threshold_ms")]
+    pub slow_tcp_connect_threshold_ms: u64,
+    /// How often a tunnel whose local port stopped accepting connections is
+    /// checked again
+    #[serde(default = "default_local_port_health_check_interval_secs")]
+    pub local_port_health_check_interval_secs: u64,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size_bytes: default_chunk_size_bytes(),
+            registration_timeout_secs: default_registration_timeout_secs(),
+            response_cache_ttl_secs: default_response_cache_ttl_secs(),
+            graceful_shutdown_timeout_secs: default_graceful_shutdown_timeout_secs(),
+            max_reconnect_attempts: default_max_reconnect_attempts(),
+            reconnect_initial_backoff_ms: default_reconnect_initial_backoff_ms(),
+            reconnect_max_backoff_ms: default_reconnect_max_backoff_ms(),
+            reconnect_backoff_multiplier: default_reconnect_backoff_multiplier(),
+            heartbeat_interval_secs: default_heartbeat_interval_secs(),
+            ws_ping_interval_secs: default_ws_ping_interval_secs(),
+            prefer_ipv6: default_prefer_ipv6(),
+            happy_eyeballs_timeout_ms: default_happy_eyeballs_timeout_ms(),
+            message_batching: false,
+            max_batch_size: default_max_batch_size(),
+            batch_timeout_ms: default_batch_timeout_ms(),
+            slow_tcp_connect_threshold_ms: default_slow_tcp_connect_threshold_ms(),
+            local_port_health_check_interval_secs: default_local_port_health_check_interval_secs(),
+        }
+    }
+}
+
+fn default_max_batch_size() -> usize {
+    10
+}
+
+fn default_batch_timeout_ms() -> u64 {
+    5
+}
+
+fn default_slow_tcp_connect_threshold_ms() -> u64 {
+    1000
+}
+
+fn default_local_port_health_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_prefer_ipv6() -> bool {
+    true
+}
+
+fn default_happy_eyeballs_timeout_ms() -> u64 {
+    250
+}
+
+fn default_chunk_size_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_registration_timeout_secs() -> u64 {
+    60
+}
+
+fn default_response_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_graceful_shutdown_timeout_secs() -> u64 {
+    5
+}
+
+fn default_max_reconnect_attempts() -> u32 {
+    10
+}
+
+fn default_reconnect_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_reconnect_max_backoff_ms() -> u64 {
+    60_000
+}
+
+fn default_reconnect_backoff_multiplier() -> f64 {
+    1.5
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    25
+}
+
+fn default_ws_ping_interval_secs() -> u64 {
+    5
 }
 
 impl Config {
     pub fn load() -> Result<Self> {
-        let path = Self::config_path()?;
+        Self::load_from(&Self::config_path()?)
+    }
+
+    /// The config as it was before the last save, if it has been saved over
+    pub fn load_previous() -> Result<Option<Self>> {
+        let path = Self::previous_path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Self::load_from(&path).map(Some)
+    }
 
+    fn load_from(path: &Path) -> Result<Self> {
         if !path.exists() {
             return Ok(Self::default());
         }
 
-        let contents = fs::read_to_string(&path)
+        let contents = fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path.display()))?;
 
-        toml::from_str(&contents)
-            .with_context(|| format!("Failed to parse config file: {}", path.display()))
+        let config: Config = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The user config, overridden by a project `.burrow.toml` in the current
+    /// directory, overridden by environment variables. Without either file
+    /// (e.g. in a container) this is the defaults plus the environment.
+    pub fn load_merged() -> Result<Self> {
+        let mut merged = toml::Value::Table(Default::default());
+        let project_path = PathBuf::from(PROJECT_CONFIG_FILE);
+        for path in [Self::config_path()?, project_path.clone()] {
+            if !path.exists() {
+                continue;
+            }
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+            let value = toml::from_str(&contents)
+                .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+            if path == project_path {
+                check_project_config(&value)?;
+            }
+            merge_toml(&mut merged, value);
+        }
+
+        let mut config: Config = merged.try_into().context("Invalid configuration")?;
+        config.apply_env(|name| std::env::var(name).ok())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check settings that parse but can't be used together
+    pub fn validate(&self) -> Result<()> {
+        let columns = &self.tui.request_list_columns;
+        if columns.is_empty() {
+            anyhow::bail!("tui.request_list_columns must list at least one column");
+        }
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|c| c.column == column.column) {
+                anyhow::bail!(
+                    "tui.request_list_columns lists {:?} more than once",
+                    column.column
+                );
+            }
+        }
+        if columns.iter().filter(|c| c.width.is_none()).count() > 1 {
+            anyhow::bail!("tui.request_list_columns: only one column may leave out its width");
+        }
+        let multiplier = self.connection.reconnect_backoff_multiplier;
+        if multiplier.is_nan() || multiplier < 1.0 {
+            anyhow::bail!("connection.reconnect_backoff_multiplier must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// Override settings with the `BURROW_*` variables found by `var`
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+
+        if let Some(server) = var("BURROW_SERVER") {
+            self.auth.server = Some(server);
+        }
+        if let Some(token) = var("BURROW_TOKEN") {
+            self.auth.token = Some(token);
+        }
+        if let Some(tunnels) = var("BURROW_TUNNELS") {
+            self.tunnels = serde_json::from_str(&tunnels).context(
+                "Invalid BURROW_TUNNELS, expected a JSON array like \
+                 [{\"type\": \"http\", \"port\": 3000, \"subdomain\": \"myapp\"}]",
+            )?;
+        }
+        if let Some(level) = var("BURROW_LOG_LEVEL") {
+            self.log.level = Some(level);
+        }
+        if let Some(attempts) = parse_env(var, "BURROW_MAX_RECONNECT_ATTEMPTS")? {
+            self.connection.max_reconnect_attempts = attempts;
+        }
+        if let Some(secs) = parse_env(var, "BURROW_HEARTBEAT_INTERVAL_SECS")? {
+            self.connection.heartbeat_interval_secs = secs;
+        }
+        if let Some(max) = parse_env(var, "BURROW_MAX_REQUESTS")? {
+            self.tui.max_requests = max;
+        }
+        Ok(())
     }
 
     pub fn save(&self) -> Result<()> {
@@ -45,11 +808,78 @@ impl Config {
         }
 
         let contents = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        self.verify_round_trip()?;
+
+        // Keep what is being replaced for `burrow config diff`
+        if path.exists() {
+            let previous = Self::previous_path()?;
+            fs::copy(&path, &previous)
+                .with_context(|| format!("Failed to write {}", previous.display()))?;
+        }
+
+        // Write to a temp file first so a crash mid-write can't corrupt the config
+        let tmp_path = path.with_extension("tmp");
+        write_private(&tmp_path, &contents)
+            .with_context(|| format!("Failed to write config file: {}", tmp_path.display()))?;
 
-        fs::write(&path, contents)
+        replace_file(&tmp_path, &path, &Self::save_backup_path()?)
             .with_context(|| format!("Failed to write config file: {}", path.display()))
     }
 
+    /// Serialize and parse the config back, failing if anything would be lost
+    pub fn verify_round_trip(&self) -> Result<()> {
+        let contents = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        let parsed: Config =
+            toml::from_str(&contents).context("Failed to parse serialized config")?;
+
+        if &parsed != self {
+            anyhow::bail!("Config does not survive a save/load round trip");
+        }
+        Ok(())
+    }
+
+    /// Where the previous config is kept while it is being replaced
+    pub fn save_backup_path() -> Result<PathBuf> {
+        Ok(Self::config_path()?.with_extension("bak"))
+    }
+
+    /// Copy of the config from before the last save
+    pub fn previous_path() -> Result<PathBuf> {
+        Ok(Self::config_path()?.with_extension("toml.prev"))
+    }
+
+    /// Settings that differ from `other`, as dotted paths such as
+    /// `auth.server`, with the API token redacted
+    pub fn diff(&self, other: &Config) -> Vec<ConfigDiff> {
+        let mut old = BTreeMap::new();
+        let mut new = BTreeMap::new();
+        if let Ok(value) = serde_json::to_value(self) {
+            flatten_json("", value, &mut old);
+        }
+        if let Ok(value) = serde_json::to_value(other) {
+            flatten_json("", value, &mut new);
+        }
+
+        let fields: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+        fields
+            .into_iter()
+            .filter(|field| old.get(*field) != new.get(*field))
+            .map(|field| {
+                let show = |value: Option<&serde_json::Value>| match value {
+                    None | Some(serde_json::Value::Null) => "(unset)".to_string(),
+                    Some(_) if REDACTED_FIELDS.contains(&field.as_str()) => "<token>".to_string(),
+                    Some(serde_json::Value::String(s)) => s.clone(),
+                    Some(value) => value.to_string(),
+                };
+                ConfigDiff {
+                    field: field.clone(),
+                    old_value: show(old.get(field)),
+                    new_value: show(new.get(field)),
+                }
+            })
+            .collect()
+    }
+
     pub fn config_path() -> Result<PathBuf> {
         let proj_dirs =
             ProjectDirs::from("", "", "burrow").context("Could not determine config directory")?;
@@ -57,3 +887,264 @@ impl Config {
         Ok(proj_dirs.config_dir().join("config.toml"))
     }
 }
+
+fn parse_env<T: FromStr>(var: impl Fn(&str) -> Option<String>, name: &str) -> Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    var(name)
+        .map(|value| {
+            value
+                .parse()
+                .with_context(|| format!("Invalid {} '{}'", name, value))
+        })
+        .transpose()
+}
+
+/// A project `.burrow.toml` comes with whatever repository is checked out, so
+/// it may not choose the server (or scheme) the API token is sent to
+fn check_project_config(value: &toml::Value) -> Result<()> {
+    if value.get("auth").is_some() {
+        anyhow::bail!(
+            "{} can't set [auth] settings; set the server and token in the user \
+             config, BURROW_* variables or flags",
+            PROJECT_CONFIG_FILE
+        );
+    }
+    Ok(())
+}
+
+/// Merge `overlay` into `base`, recursing into tables so that only the keys
+/// set in `overlay` are replaced
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_toml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Collect the leaves of `value` under their dotted paths; arrays count as
+/// one value
+fn flatten_json(
+    prefix: &str,
+    value: serde_json::Value,
+    out: &mut BTreeMap<String, serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_json(&path, value, out);
+            }
+        }
+        value => {
+            out.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+/// Write a file only the current user can read, as it holds the API token
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // The mode only applies to new files
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents.as_bytes())
+    }
+    #[cfg(not(unix))]
+    {
+        fs::write(path, contents)
+    }
+}
+
+/// Move `src` over `dst`.
+///
+/// `rename` replaces the destination atomically on Unix, but fails on Windows
+/// when `dst` exists. In that case the old file is moved to `backup` first and
+/// restored if the rename still fails.
+fn replace_file(src: &Path, dst: &Path, backup: &Path) -> std::io::Result<()> {
+    match fs::rename(src, dst) {
+        Ok(()) => return Ok(()),
+        Err(e) if !dst.exists() => return Err(e),
+        Err(_) => {}
+    }
+
+    fs::copy(dst, backup)?;
+    fs::remove_file(dst)?;
+    if let Err(e) = fs::rename(src, dst) {
+        fs::rename(backup, dst)?;
+        return Err(e);
+    }
+    fs::remove_file(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_round_trips() {
+        Config::default().verify_round_trip().unwrap();
+        Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Config::default();
+        let mut new = Config::default();
+        assert!(old.diff(&new).is_empty());
+
+        new.auth.server = Some("prod.example.com".to_string());
+        new.auth.token = Some("secret".to_string());
+        new.connection.max_reconnect_attempts = 3;
+        let diff: Vec<String> = old.diff(&new).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            diff,
+            [
+                "auth.server: (unset) -> prod.example.com",
+                "auth.token: (unset) -> <token>",
+                "connection.max_reconnect_attempts: 10 -> 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_request_list_columns() {
+        let config: Config = toml::from_str(
+            "[tui]\nrequest_list_columns = [{ column = \"method\", width = 8 }, { column = \"path\" }]",
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        let twice: Config = toml::from_str(
+            "[tui]\nrequest_list_columns = [{ column = \"path\" }, { column = \"path\", width = 10 }]",
+        )
+        .unwrap();
+        assert!(twice.validate().is_err());
+
+        let two_fills: Config = toml::from_str(
+            "[tui]\nrequest_list_columns = [{ column = \"path\" }, { column = \"tags\" }]",
+        )
+        .unwrap();
+        assert!(two_fills.validate().is_err());
+
+        let shrinking: Config =
+            toml::from_str("[connection]\nreconnect_backoff_multiplier = 0.5").unwrap();
+        assert!(shrinking.validate().is_err());
+    }
+
+    #[test]
+    fn test_env_overrides_merged_files() {
+        let mut merged: toml::Value = toml::from_str(
+            "[auth]\nserver = \"user.example.com\"\ntoken = \"user-token\"\n\
+             [tui]\nmax_requests = 50",
+        )
+        .unwrap();
+        merge_toml(
+            &mut merged,
+            toml::from_str("[tui]\nmax_requests = 80").unwrap(),
+        );
+        let mut config: Config = merged.try_into().unwrap();
+
+        let env = std::collections::HashMap::from([
+            ("BURROW_TOKEN", "env-token"),
+            (
+                "BURROW_TUNNELS",
+                r#"[{"type": "http", "port": 3000}, {"type": "tcp", "port": 5432, "server": "tcp.example.com"}]"#,
+            ),
+            ("BURROW_MAX_RECONNECT_ATTEMPTS", "3"),
+        ]);
+        config
+            .apply_env(|name| env.get(name).map(|v| v.to_string()))
+            .unwrap();
+
+        assert_eq!(config.auth.server.as_deref(), Some("user.example.com"));
+        assert_eq!(config.auth.token.as_deref(), Some("env-token"));
+        assert_eq!(config.tui.max_requests, 80);
+        assert_eq!(config.connection.max_reconnect_attempts, 3);
+        assert_eq!(
+            config.tunnels,
+            vec![
+                TunnelSpec::Http {
+                    port: 3000,
+                    subdomain: None,
+                    tags: Vec::new(),
+                    server: None,
+                },
+                TunnelSpec::Tcp {
+                    port: 5432,
+                    server: Some("tcp.example.com".to_string()),
+                },
+            ]
+        );
+        config.verify_round_trip().unwrap();
+
+        let bad =
+            config.apply_env(|name| (name == "BURROW_MAX_REQUESTS").then(|| "lots".to_string()));
+        assert!(bad.is_err());
+    }
+
+    #[test]
+    fn test_project_config_cannot_set_auth() {
+        let project = |contents: &str| check_project_config(&toml::from_str(contents).unwrap());
+        assert!(project("[tui]\nmax_requests = 80").is_ok());
+        assert!(project("[auth]\nserver = \"evil.example.com\"").is_err());
+        assert!(project("[auth]\nserver_scheme = \"ws\"").is_err());
+        assert!(project("auth.token = \"brw_x\"").is_err());
+    }
+
+    #[test]
+    fn test_replace_file_overwrites_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let dst = dir.path().join("config.toml");
+        let src = dst.with_extension("tmp");
+        let backup = dst.with_extension("bak");
+
+        fs::write(&dst, "old").unwrap();
+        fs::write(&src, "new").unwrap();
+        replace_file(&src, &dst, &backup).unwrap();
+
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "new");
+        assert!(!src.exists());
+        assert!(!backup.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_restricts_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.tmp");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private(&path, "token = \"secret\"").unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "token = \"secret\"");
+    }
+}
@@ -0,0 +1,288 @@
+//! `burrow doctor`: diagnose common setup problems.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use crossterm::style::Stylize;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Instant};
+use tokio_tungstenite::connect_async;
+
+use burrow_client::client::tui::{ConnectionStatus, TuiCommand, TuiEvent};
+use burrow_client::client::TunnelClient;
+use burrow_client::config::{Config, ServerScheme};
+
+/// How long to wait on each network check
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: String,
+    status: CheckStatus,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    fn print(&self) {
+        let mark = match self.status {
+            CheckStatus::Pass => "✓".green(),
+            CheckStatus::Warn => "⚠".yellow(),
+            CheckStatus::Fail => "✗".red(),
+        };
+        println!("  {} {}: {}", mark, self.name, self.detail);
+    }
+}
+
+/// What `burrow doctor` should check
+pub struct DoctorOptions<'a> {
+    pub token: Option<String>,
+    pub server: &'a str,
+    pub server_port: u16,
+    pub server_scheme: ServerScheme,
+    pub local_host: &'a str,
+    pub local_ports: &'a [u16],
+    pub fix: bool,
+}
+
+/// Run all checks, printing each result. Returns whether none of them failed.
+pub async fn run(options: DoctorOptions<'_>) -> Result<bool> {
+    println!("Running diagnostics...");
+    println!();
+
+    let mut checks = Vec::new();
+
+    let (config_check, config) = check_config(options.fix);
+    checks.push(config_check);
+
+    let token = options.token.or_else(|| config.and_then(|c| c.auth.token));
+    checks.push(check_token(token.as_deref()));
+
+    let server_reachable = check_server_tcp(options.server, options.server_port).await;
+    let reachable = server_reachable.status == CheckStatus::Pass;
+    checks.push(server_reachable);
+    if reachable {
+        checks.push(
+            check_websocket(options.server, options.server_port, options.server_scheme).await,
+        );
+    } else {
+        checks.push(Check::new(
+            "WebSocket handshake",
+            CheckStatus::Warn,
+            "skipped, server unreachable",
+        ));
+    }
+
+    checks.push(check_dns(options.local_host).await);
+    for &port in options.local_ports {
+        checks.push(check_local_port(options.local_host, port).await);
+    }
+
+    for check in &checks {
+        check.print();
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    println!();
+    if failed == 0 {
+        println!("All checks passed.");
+    } else {
+        println!("{} check(s) failed.", failed);
+    }
+
+    Ok(failed == 0)
+}
+
+/// Connect `client` and write what it is tracking to
+/// `burrow-debug-<timestamp>.json` in the current directory. `client` must
+/// send its events to `events`, take commands from `cmd_tx`, and have no
+/// tunnels, so that running doctor never takes over anyone's subdomain.
+pub async fn dump_state(
+    client: TunnelClient,
+    cmd_tx: mpsc::Sender<TuiCommand>,
+    mut events: mpsc::Receiver<TuiEvent>,
+) -> Result<()> {
+    println!();
+    println!("Connecting to capture the client state...");
+    let handle = tokio::spawn(client.run());
+
+    let connected = Instant::now() + NETWORK_TIMEOUT;
+    loop {
+        match tokio::time::timeout_at(connected, events.recv()).await {
+            Ok(Some(TuiEvent::ConnectionStatus(ConnectionStatus::Connected))) => break,
+            Ok(Some(_)) => continue,
+            _ => {
+                handle.abort();
+                anyhow::bail!("The client did not connect, so there is no state to dump");
+            }
+        }
+    }
+
+    cmd_tx
+        .send(TuiCommand::RequestSnapshot)
+        .await
+        .context("The connection stopped")?;
+    let answered = Instant::now() + NETWORK_TIMEOUT;
+    let snapshot = loop {
+        match tokio::time::timeout_at(answered, events.recv()).await {
+            Ok(Some(TuiEvent::StateSnapshot(snapshot))) => break snapshot,
+            Ok(Some(_)) => continue,
+            _ => {
+                handle.abort();
+                anyhow::bail!("The client did not answer the state request");
+            }
+        }
+    };
+    handle.abort();
+
+    let path = PathBuf::from(format!(
+        "burrow-debug-{}.json",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let json = serde_json::to_string_pretty(&snapshot)?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("Client state written to {}", path.display());
+    Ok(())
+}
+
+fn check_config(fix: bool) -> (Check, Option<Config>) {
+    const NAME: &str = "Config file";
+
+    let path = match Config::config_path() {
+        Ok(path) => path,
+        Err(e) => return (Check::new(NAME, CheckStatus::Fail, e.to_string()), None),
+    };
+
+    if !path.exists() {
+        if fix {
+            return match Config::default().save() {
+                Ok(()) => (
+                    Check::new(
+                        NAME,
+                        CheckStatus::Pass,
+                        format!("created default config at {}", path.display()),
+                    ),
+                    Some(Config::default()),
+                ),
+                Err(e) => (
+                    Check::new(NAME, CheckStatus::Fail, format!("{:#}", e)),
+                    None,
+                ),
+            };
+        }
+        return (
+            Check::new(
+                NAME,
+                CheckStatus::Warn,
+                format!(
+                    "{} not found, using defaults (run with --fix to create it)",
+                    path.display()
+                ),
+            ),
+            None,
+        );
+    }
+
+    match Config::load() {
+        Ok(config) => (
+            Check::new(NAME, CheckStatus::Pass, path.display().to_string()),
+            Some(config),
+        ),
+        Err(e) => (
+            Check::new(NAME, CheckStatus::Fail, format!("{:#}", e)),
+            None,
+        ),
+    }
+}
+
+fn check_token(token: Option<&str>) -> Check {
+    const NAME: &str = "API token";
+
+    match token {
+        None => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "not set (run 'burrow login', use --token or set BURROW_TOKEN)",
+        ),
+        Some(token) if token.starts_with("brw_") && token.len() > "brw_".len() => {
+            Check::new(NAME, CheckStatus::Pass, "valid format")
+        }
+        Some(_) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            "invalid format, tokens should start with 'brw_'",
+        ),
+    }
+}
+
+async fn check_server_tcp(server: &str, port: u16) -> Check {
+    let name = "Server reachable";
+    match connect_tcp(server, port).await {
+        Ok(()) => Check::new(name, CheckStatus::Pass, format!("{}:{}", server, port)),
+        Err(e) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("{}:{}: {:#}", server, port, e),
+        ),
+    }
+}
+
+async fn check_websocket(server: &str, port: u16, scheme: ServerScheme) -> Check {
+    let name = "WebSocket handshake";
+    let ws_url = format!("{}://{}:{}/tunnel/ws", scheme.as_str(), server, port);
+
+    match timeout(NETWORK_TIMEOUT, connect_async(&ws_url)).await {
+        Ok(Ok((mut ws_stream, _))) => {
+            let _ = ws_stream.close(None).await;
+            Check::new(name, CheckStatus::Pass, ws_url)
+        }
+        Ok(Err(e)) => Check::new(name, CheckStatus::Fail, format!("{}: {}", ws_url, e)),
+        Err(_) => Check::new(name, CheckStatus::Fail, format!("{}: timed out", ws_url)),
+    }
+}
+
+async fn check_dns(host: &str) -> Check {
+    let name = format!("DNS for {}", host);
+    match tokio::net::lookup_host((host, 0)).await {
+        Ok(mut addrs) => match addrs.next() {
+            Some(addr) => Check::new(name, CheckStatus::Pass, addr.ip().to_string()),
+            None => Check::new(name, CheckStatus::Fail, "no addresses found"),
+        },
+        Err(e) => Check::new(name, CheckStatus::Fail, e.to_string()),
+    }
+}
+
+async fn check_local_port(host: &str, port: u16) -> Check {
+    let name = format!("Local service {}:{}", host, port);
+    match connect_tcp(host, port).await {
+        Ok(()) => Check::new(name, CheckStatus::Pass, "accepting connections"),
+        Err(e) => Check::new(name, CheckStatus::Fail, format!("{:#}", e)),
+    }
+}
+
+async fn connect_tcp(host: &str, port: u16) -> Result<()> {
+    timeout(NETWORK_TIMEOUT, TcpStream::connect((host, port)))
+        .await
+        .context("timed out")?
+        .context("connection failed")?;
+    Ok(())
+}
@@ -11,6 +11,60 @@ pub enum BurrowError {
 
     #[error("Crypto error: {0}")]
     Crypto(String),
+
+    #[error("Authentication failed")]
+    AuthFailed,
+
+    #[error("Server requires a newer version of the burrow client. Run 'cargo install burrow-client' to update.")]
+    ProtocolVersionTooOld,
+
+    #[error("Switching server")]
+    SwitchingServer,
+
+    #[error("{}", http_summary(url, *status))]
+    HttpRequest {
+        url: String,
+        /// Set when the server answered with an error status
+        status: Option<u16>,
+        #[source]
+        source: reqwest::Error,
+    },
+}
+
+impl BurrowError {
+    /// Wrap a failed request, keeping the URL and status code
+    pub fn http(url: impl Into<String>, source: reqwest::Error) -> Self {
+        BurrowError::HttpRequest {
+            url: url.into(),
+            status: source.status().map(|status| status.as_u16()),
+            source,
+        }
+    }
+
+    /// Whether the same request may succeed if tried again later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BurrowError::HttpRequest {
+                status: Some(status),
+                ..
+            } => matches!(status, 429 | 502 | 503 | 504),
+            BurrowError::HttpRequest { source, .. } => source.is_connect() || source.is_timeout(),
+            BurrowError::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::TimedOut
+            ),
+            _ => false,
+        }
+    }
+}
+
+fn http_summary(url: &str, status: Option<u16>) -> String {
+    match status {
+        Some(status) => format!("HTTP {}: {}", status, url),
+        None => format!("HTTP request failed: {}", url),
+    }
 }
 
 #[allow(dead_code)]
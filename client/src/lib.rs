@@ -0,0 +1,42 @@
+//! Burrow Tunnel Client library
+//!
+//! Everything the `burrow` CLI uses to expose local services through a
+//! Burrow server, for embedding tunnels in other programs (e.g. a web server
+//! that opens its own tunnel on startup).
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut client = burrow_client::TunnelClient::builder()
+//!     .server_host("tunnel.example.com")
+//!     .token("your-api-token")
+//!     .build()?;
+//! client.register_tunnel(3000, Some("myapp".to_string()), Vec::new())?;
+//! client.run().await
+//! # }
+//! ```
+//!
+//! Pass a [`client::tui::create_event_channel`] sender to
+//! [`TunnelClientBuilder::tui_sender`] to receive [`TuiEvent`]s, such as the
+//! tunnel URL once the server assigned one. [`TunnelRegistration::id`] waits
+//! for the id of a tunnel registered before [`TunnelClient::run`].
+
+#![deny(clippy::correctness)]
+#![warn(clippy::suspicious)]
+#![warn(clippy::style)]
+#![warn(clippy::complexity)]
+#![warn(clippy::perf)]
+
+pub mod client;
+pub mod config;
+pub mod crypto;
+pub mod error;
+pub mod protocol;
+
+pub use client::tui::{TcpTunnelEvent, TuiCommand, TuiEvent, TunnelEvent};
+pub use client::{
+    ConnectionPool, ReconnectConfig, TunnelClient, TunnelClientBuilder, TunnelConfig,
+    TunnelRegistration,
+};
+pub use config::{Config, TunnelSpec};
+pub use error::BurrowError;
+pub use protocol::{IncomingMessage, OutgoingMessage};
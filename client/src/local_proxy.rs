@@ -0,0 +1,454 @@
+//! `burrow tunnel proxy`: a local reverse proxy for trying out a service
+//! setup without connecting to a Burrow server.
+
+use std::convert::Infallible;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use chrono::Local;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use serde_json::json;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use burrow_client::client::tui::{
+    message_size, ChannelMonitor, RequestBodyKind, RequestEvent, ResponseEvent, TimingBreakdown,
+    TuiEvent,
+};
+use burrow_client::client::{forward_http_request, ForwardedResponse, ProxyClient, ProxyErrorKind};
+use burrow_client::config::ProxyConfig;
+use burrow_client::protocol::RequestId;
+
+/// Where to listen and where to forward to
+pub struct LocalProxyOptions {
+    pub from_port: u16,
+    pub to_host: String,
+    pub to_port: u16,
+    /// Append one HAR entry per request to this file (`-` for stdout), so the
+    /// log can be opened with `burrow inspect`
+    pub log: Option<PathBuf>,
+    /// Receives the requests when running with the TUI
+    pub tui_tx: Option<ChannelMonitor>,
+    /// Timeouts, headers and caching for forwarded requests
+    pub proxy: ProxyConfig,
+}
+
+/// State shared by all proxied HTTP connections
+struct ProxyContext {
+    proxy: ProxyClient,
+    options: LocalProxyOptions,
+    log: Option<Mutex<Box<dyn Write + Send>>>,
+    next_request: AtomicU64,
+}
+
+/// An HTTP proxy listening on its port, ready to [`run`](Self::run)
+pub struct HttpProxy {
+    listener: TcpListener,
+    ctx: Arc<ProxyContext>,
+}
+
+/// Listen on `from_port` and open the request log, so problems with either
+/// are reported before the proxy (and the TUI) starts
+pub async fn bind_http(mut options: LocalProxyOptions) -> Result<HttpProxy> {
+    let listener = bind(options.from_port).await?;
+    options.from_port = listener.local_addr()?.port();
+    let log = match &options.log {
+        Some(path) => Some(Mutex::new(open_log(path)?)),
+        None => None,
+    };
+    let ctx = Arc::new(ProxyContext {
+        proxy: ProxyClient::new(&options.proxy),
+        options,
+        log,
+        next_request: AtomicU64::new(0),
+    });
+    Ok(HttpProxy { listener, ctx })
+}
+
+/// Accept HTTP requests on `from_port` and forward them to the local service
+/// until interrupted
+pub async fn run_http(options: LocalProxyOptions) -> Result<()> {
+    bind_http(options).await?.run().await
+}
+
+impl HttpProxy {
+    /// The port the proxy listens on
+    pub fn port(&self) -> u16 {
+        self.ctx.options.from_port
+    }
+
+    /// Forward requests to the local service until interrupted
+    pub async fn run(self) -> Result<()> {
+        let options = &self.ctx.options;
+        info!(
+            "Proxying HTTP on port {} to {}:{}",
+            options.from_port, options.to_host, options.to_port
+        );
+        loop {
+            let stream = tokio::select! {
+                accepted = self.listener.accept() => {
+                    accepted.context("Failed to accept connection")?.0
+                }
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            };
+            self.serve(stream);
+        }
+    }
+
+    fn serve(&self, stream: TcpStream) {
+        let ctx = self.ctx.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle_request(req, ctx.clone()));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("HTTP connection error: {}", e);
+            }
+        });
+    }
+}
+
+/// Forward raw TCP connections on `from_port` to the local service until
+/// interrupted
+pub async fn run_tcp(options: LocalProxyOptions) -> Result<()> {
+    let listener = bind(options.from_port).await?;
+    info!(
+        "Proxying TCP on port {} to {}:{}",
+        options.from_port, options.to_host, options.to_port
+    );
+
+    loop {
+        let (mut inbound, peer) = tokio::select! {
+            accepted = listener.accept() => accepted.context("Failed to accept connection")?,
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        };
+
+        let target = (options.to_host.clone(), options.to_port);
+        tokio::spawn(async move {
+            let mut outbound = match TcpStream::connect((target.0.as_str(), target.1)).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("TCP connect to {}:{} failed: {}", target.0, target.1, e);
+                    return;
+                }
+            };
+            info!("TCP connect: {} -> {}:{}", peer, target.0, target.1);
+            match tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await {
+                Ok((sent, received)) => info!(
+                    "TCP connection closed: {} ({} bytes sent, {} received)",
+                    peer, sent, received
+                ),
+                Err(e) => debug!("TCP connection error for {}: {}", peer, e),
+            }
+        });
+    }
+}
+
+async fn bind(port: u16) -> Result<TcpListener> {
+    TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Failed to listen on port {}", port))
+}
+
+fn open_log(path: &PathBuf) -> Result<Box<dyn Write + Send>> {
+    if path.as_os_str() == "-" {
+        return Ok(Box::new(io::stdout()));
+    }
+    let file: File = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file {}", path.display()))?;
+    Ok(Box::new(file))
+}
+
+async fn handle_request(
+    req: Request<Incoming>,
+    ctx: Arc<ProxyContext>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let options = &ctx.options;
+    let request_id = RequestId(format!(
+        "local-{}",
+        ctx.next_request.fetch_add(1, Ordering::Relaxed)
+    ));
+    let (parts, body) = req.into_parts();
+    let method = parts.method.to_string();
+    let path = parts.uri.path().to_string();
+    let query_string = parts.uri.query().unwrap_or("").to_string();
+    let headers: Vec<(String, String)> = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes().to_vec(),
+        Err(e) => {
+            debug!("Failed to read request body: {}", e);
+            return Ok(plain_response(400, "Bad Request: unreadable body".into()));
+        }
+    };
+    let body = (!body.is_empty()).then_some(body);
+
+    let received_at = Instant::now();
+    let request = RequestEvent {
+        request_id: request_id.clone(),
+        method: method.clone(),
+        path: path.clone(),
+        query_string: query_string.clone(),
+        size_bytes: message_size(&headers, body.as_ref().map_or(0, Vec::len)),
+        headers: headers.clone(),
+        body: body.clone().map(RequestBodyKind::Buffered),
+        timestamp: Local::now(),
+        client_ip: None,
+        tunnel_url: Some(format!("http://localhost:{}", options.from_port)),
+    };
+    if let Some(tx) = &options.tui_tx {
+        tx.send(TuiEvent::RequestReceived(request.clone())).await;
+    }
+    let queued_ms = received_at.elapsed().as_millis() as u64;
+
+    let started = Instant::now();
+    let forwarded = forward_http_request(
+        &ctx.proxy,
+        &options.to_host,
+        options.to_port,
+        &method,
+        &path,
+        &query_string,
+        headers,
+        body.map(Into::into),
+    )
+    .await;
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    let (status, headers, body, timing, error_kind) = match forwarded {
+        Ok(ForwardedResponse {
+            status,
+            headers,
+            mut body,
+            upstream,
+            events,
+        }) => {
+            // There is no tunnel to stream through, so the events are passed
+            // on once the stream ends or hits `max_sse_duration_secs`
+            if let Some(mut events) = events {
+                let mut collected = Vec::new();
+                while let Some(chunk) = events.next_chunk().await {
+                    collected.extend(chunk);
+                }
+                body = (!collected.is_empty()).then_some(collected);
+            }
+            let timing =
+                upstream.map(|upstream| TimingBreakdown::new(queued_ms, upstream, duration_ms));
+            (status, headers, body, timing, None)
+        }
+        Err(e) => {
+            warn!("{} {} -> error: {:#}", method, path, e);
+            let kind = ProxyErrorKind::of(&e);
+            let headers = vec![(
+                "content-type".to_string(),
+                "text/html; charset=utf-8".to_string(),
+            )];
+            (
+                kind.status(),
+                headers,
+                Some(
+                    kind.error_page(&ctx.proxy, &e, options.to_port)
+                        .into_bytes(),
+                ),
+                None,
+                Some(kind),
+            )
+        }
+    };
+    info!("{} {} -> {} ({}ms)", method, path, status, duration_ms);
+
+    let response = ResponseEvent {
+        request_id,
+        status,
+        size_bytes: message_size(&headers, body.as_ref().map_or(0, Vec::len)),
+        headers,
+        body,
+        duration_ms,
+        timing,
+        error_kind,
+    };
+    if let Some(log) = &ctx.log {
+        let entry = har_entry(&request, &response, options.from_port);
+        let mut log = log.lock().unwrap();
+        if let Err(e) = writeln!(log, "{}", entry).and_then(|_| log.flush()) {
+            warn!("Failed to write request log: {}", e);
+        }
+    }
+
+    let mut builder = Response::builder().status(response.status);
+    for (name, value) in &response.headers {
+        builder = builder.header(name, value);
+    }
+    let body = Full::new(Bytes::from(response.body.clone().unwrap_or_default()));
+    if let Some(tx) = &options.tui_tx {
+        tx.send(TuiEvent::ResponseSent(response)).await;
+    }
+
+    Ok(builder
+        .body(body)
+        .unwrap_or_else(|_| plain_response(502, "Bad Gateway: invalid response".into())))
+}
+
+fn plain_response(status: u16, body: String) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = hyper::StatusCode::from_u16(status).unwrap_or_default();
+    response
+}
+
+/// A HAR entry for the request, in the form `burrow inspect` reads
+fn har_entry(request: &RequestEvent, response: &ResponseEvent, port: u16) -> serde_json::Value {
+    let headers = |headers: &[(String, String)]| {
+        headers
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect::<Vec<_>>()
+    };
+    let query = if request.query_string.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", request.query_string)
+    };
+    let post_data = match &request.body {
+        Some(RequestBodyKind::Buffered(body)) => {
+            json!({ "text": String::from_utf8_lossy(body) })
+        }
+        _ => serde_json::Value::Null,
+    };
+    let content = response
+        .body
+        .as_ref()
+        .map_or(serde_json::Value::Null, |body| {
+            json!({
+                "text": base64::engine::general_purpose::STANDARD.encode(body),
+                "encoding": "base64",
+            })
+        });
+
+    json!({
+        "startedDateTime": request.timestamp.to_rfc3339(),
+        "time": response.duration_ms,
+        "request": {
+            "method": request.method,
+            "url": format!("http://localhost:{}{}{}", port, request.path, query),
+            "headers": headers(&request.headers),
+            "postData": post_data,
+        },
+        "response": {
+            "status": response.status,
+            "headers": headers(&response.headers),
+            "content": content,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burrow_client::client::tui::create_event_channel;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn options(to_port: u16) -> LocalProxyOptions {
+        LocalProxyOptions {
+            from_port: 0,
+            to_host: "127.0.0.1".to_string(),
+            to_port,
+            log: None,
+            tui_tx: None,
+            proxy: ProxyConfig::default(),
+        }
+    }
+
+    async fn spawn_local_service() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                    )
+                    .await;
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_bind_fails_on_taken_port() {
+        let taken = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut options = options(1);
+        options.from_port = taken.local_addr().unwrap().port();
+
+        let Err(e) = bind_http(options).await else {
+            panic!("bound a port that is already taken");
+        };
+        assert!(e.to_string().contains("Failed to listen"), "{:#}", e);
+    }
+
+    #[tokio::test]
+    async fn test_requests_forwarded_logged_and_shown() {
+        let service_port = spawn_local_service().await;
+        let log = tempfile::NamedTempFile::new().unwrap();
+        let (tui_tx, mut tui_rx) = create_event_channel(16);
+        let mut options = options(service_port);
+        options.log = Some(log.path().to_path_buf());
+        options.tui_tx = Some(tui_tx);
+
+        let proxy = bind_http(options).await.unwrap();
+        let port = proxy.port();
+        let handle = tokio::spawn(proxy.run());
+
+        let body = reqwest::Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("http://127.0.0.1:{}/hello?x=1", port))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "hello");
+
+        let Some(TuiEvent::RequestReceived(request)) = tui_rx.recv().await else {
+            panic!("expected the request to be shown");
+        };
+        assert_eq!(request.path, "/hello");
+        let Some(TuiEvent::ResponseSent(response)) = tui_rx.recv().await else {
+            panic!("expected the response to be shown");
+        };
+        assert_eq!(response.status, 200);
+        handle.abort();
+
+        let logged = std::fs::read_to_string(log.path()).unwrap();
+        let entry: serde_json::Value =
+            serde_json::from_str(logged.lines().next().unwrap()).unwrap();
+        assert_eq!(entry["response"]["status"], 200);
+        assert_eq!(
+            entry["request"]["url"],
+            format!("http://localhost:{}/hello?x=1", port)
+        );
+    }
+}
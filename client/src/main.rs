@@ -16,37 +16,84 @@
 #![warn(clippy::perf)]
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use futures_util::FutureExt;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-mod client;
-mod config;
-mod crypto;
-mod error;
-mod protocol;
-
-use client::tui::{create_event_channel, Tui};
-use client::TunnelClient;
-use config::Config;
+mod completion_check;
+mod completions;
+mod doctor;
+mod local_proxy;
+mod stats;
+mod subdomains;
+mod util;
+
+use burrow_client::client::tui::{
+    create_event_channel, ConnectionStatus, ErrorSource, Tui, TuiEvent,
+};
+use burrow_client::client::{self, ConnectionPool, TunnelClient};
+use burrow_client::config::{Config, ServerScheme, TunnelSpec};
+use burrow_client::error::BurrowError;
+
+/// Environment variables listed at the end of `--help`
+const ENV_HELP: &str = "\
+Environment variables (override the config file and ./.burrow.toml):
+  BURROW_SERVER                    Server hostname (--server)
+  BURROW_TOKEN                     API token (--token)
+  BURROW_TUNNELS                   Tunnels to open on start, as JSON:
+                                   [{\"type\": \"http\", \"port\": 3000, \"subdomain\": \"myapp\"},
+                                    {\"type\": \"tcp\", \"port\": 5432}]
+  BURROW_LOG_LEVEL                 Default log level or filter (--log-filter, -v)
+  BURROW_TRACE                     Set to 1 to log burrow's spans and events at trace level
+  BURROW_MAX_RECONNECT_ATTEMPTS    Give up after this many failed reconnects
+  BURROW_HEARTBEAT_INTERVAL_SECS   Seconds between heartbeats to the server
+  BURROW_MAX_REQUESTS              Requests kept in the TUI request list
+  HTTPS_PROXY, ALL_PROXY           HTTP proxy to reach the server through (--no-proxy)
+  NO_PROXY                         Hosts reached without the proxy";
 
 #[derive(Parser, Debug)]
 #[command(name = "burrow")]
 #[command(author, version, about = "Tunnel your local services to the internet")]
+#[command(after_help = ENV_HELP)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// Server hostname
+    /// Server hostname; `start` accepts it several times to connect to
+    /// several servers at once
     #[arg(short, long, global = true, env = "BURROW_SERVER")]
-    server: Option<String>,
+    server: Vec<String>,
 
     /// API token for authentication (from Burrow web UI)
     #[arg(short = 'k', long, global = true, env = "BURROW_TOKEN")]
     token: Option<String>,
 
-    /// Enable verbose logging
-    #[arg(short, long, global = true)]
-    verbose: bool,
+    /// Increase logging verbosity (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Log filter directives, overriding RUST_LOG and --verbose
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILTER",
+        long_help = "Log filter directives, overriding RUST_LOG and --verbose.\n\n\
+                     BURROW_LOG_LEVEL (or `level` under [log] in the config file) \
+                     sets the level used when none of these is given.\n\n\
+                     Uses the tracing EnvFilter syntax: a comma-separated list of \
+                     `target=level` directives, or a bare level that applies to \
+                     everything. Levels are error, warn, info, debug and trace.\n\n\
+                     Examples:\n  \
+                     --log-filter debug\n  \
+                     --log-filter burrow=trace,burrow_client=trace,reqwest=warn\n\n\
+                     Tunnel and TUI logs come from the burrow_client target; \
+                     burrow only covers the command line itself."
+    )]
+    log_filter: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -55,13 +102,136 @@ enum Commands {
     Start(StartArgs),
 
     /// Authenticate and save your API token
-    Login,
+    Login {
+        /// Check the token with the server before saving it, without asking
+        #[arg(long)]
+        verify: bool,
+    },
 
     /// Manage your subdomain reservations
     Subdomains {
         #[command(subcommand)]
         action: Option<SubdomainCommands>,
     },
+
+    /// Browse requests from a HAR (or JSONL) file without connecting
+    Inspect {
+        /// Path to the HAR or JSONL file to load
+        file: PathBuf,
+    },
+
+    /// Check configuration, credentials and connectivity
+    Doctor(DoctorArgs),
+
+    /// Inspect tunnels without the TUI
+    Tunnel {
+        #[command(subcommand)]
+        action: TunnelCommands,
+    },
+
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommands,
+    },
+
+    /// Print a shell completion script, e.g. `burrow completions bash >> ~/.bashrc`
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: completion_check::Shell,
+    },
+
+    /// Check whether shell completions are installed and loaded
+    #[command(hide = true)]
+    CompletionCheck,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Show what the last save changed in the config file
+    Diff,
+}
+
+#[derive(Subcommand, Debug)]
+enum TunnelCommands {
+    /// Show traffic statistics for a tunnel
+    Stats {
+        /// ID of the tunnel
+        tunnel_id: String,
+
+        /// Keep polling and updating the stats until interrupted
+        #[arg(long)]
+        live: bool,
+
+        /// Seconds between polls with --live
+        #[arg(long, default_value = "5")]
+        interval_secs: u32,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: stats::OutputFormat,
+
+        /// Only include traffic from this far back (e.g. 30m, 1h, 2d)
+        #[arg(long, value_parser = stats::parse_since)]
+        since: Option<u64>,
+    },
+
+    /// Run a local reverse proxy without a Burrow server, to try out a
+    /// service setup before exposing it
+    Proxy(ProxyArgs),
+}
+
+#[derive(Parser, Debug)]
+struct ProxyArgs {
+    /// Local port to listen on
+    #[arg(long)]
+    from_port: u16,
+
+    /// Host to forward to
+    #[arg(long, default_value = "localhost")]
+    to_host: String,
+
+    /// Port to forward to
+    #[arg(long)]
+    to_port: u16,
+
+    /// Forward raw TCP instead of HTTP (implies --no-tui)
+    #[arg(long)]
+    tcp: bool,
+
+    /// Append requests to this file as HAR entries, one per line (`-` for
+    /// stdout, with --no-tui); open it later with `burrow inspect`
+    #[arg(long, value_name = "FILE")]
+    log: Option<PathBuf>,
+
+    /// Disable TUI and log requests to the terminal
+    #[arg(long)]
+    no_tui: bool,
+}
+
+#[derive(Parser, Debug)]
+struct DoctorArgs {
+    /// Local host your services run on
+    #[arg(short = 'H', long, default_value = "localhost")]
+    host: String,
+
+    /// Local port to check is reachable (can be repeated)
+    #[arg(short, long = "port")]
+    ports: Vec<u16>,
+
+    /// Server port
+    #[arg(long, default_value = "443")]
+    server_port: u16,
+
+    /// Try to fix problems that can be fixed automatically
+    #[arg(long)]
+    fix: bool,
+
+    /// Connect (without opening any tunnels) and write what the connection
+    /// tracks to burrow-debug-<timestamp>.json
+    #[arg(long)]
+    dump_state: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -74,13 +244,26 @@ struct StartArgs {
     #[arg(long, default_value = "443")]
     server_port: u16,
 
-    /// Disable TUI and use plain text output
+    /// Disable TUI and log to the terminal; tunnels come from the config
+    /// file or BURROW_TUNNELS
     #[arg(long)]
     no_tui: bool,
+
+    /// Connect to the server directly, ignoring HTTPS_PROXY and ALL_PROXY
+    #[arg(long)]
+    no_proxy: bool,
+
+    /// Connect over ws:// instead of wss://, for servers without TLS
+    /// (e.g. behind a TLS-terminating load balancer)
+    #[arg(long, visible_alias = "no-tls")]
+    insecure: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum SubdomainCommands {
+    /// List your reserved subdomains (the default)
+    List(SubdomainListArgs),
+
     /// Release a subdomain reservation
     Release {
         /// The subdomain to release
@@ -88,25 +271,86 @@ enum SubdomainCommands {
     },
 }
 
+#[derive(Parser, Debug, Default)]
+struct SubdomainListArgs {
+    /// Sort client-side instead of keeping the server's order
+    #[arg(long, value_enum)]
+    sort_by: Option<subdomains::SortField>,
+
+    #[arg(long, value_enum, default_value_t)]
+    sort_dir: subdomains::SortDir,
+
+    /// Only list subdomains starting with this prefix
+    #[arg(long)]
+    filter: Option<String>,
+
+    #[arg(long, value_enum, default_value_t)]
+    format: subdomains::ListFormat,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    let config = Config::load().unwrap_or_default();
+    let config = Config::load_merged().unwrap_or_else(|e| {
+        eprintln!("Warning: {:#}; using the default configuration", e);
+        Config::default()
+    });
 
     // Resolve server from CLI > config > error
-    let server = cli
-        .server
-        .or(config.auth.server.clone())
-        .unwrap_or_else(|| "localhost".to_string());
+    let servers = if cli.server.is_empty() {
+        vec![config
+            .auth
+            .server
+            .clone()
+            .unwrap_or_else(|| "localhost".to_string())]
+    } else {
+        cli.server
+    };
+    // Only `start` connects to more than one server
+    let server = servers[0].clone();
+
+    let log_filter = cli.log_filter.as_deref();
+    let log_level = config.log.level.as_deref();
 
     match cli.command {
         Some(Commands::Start(args)) => {
-            run_start(cli.token, cli.verbose, &server, args, &config).await
+            // In TUI mode, only log errors unless asked otherwise
+            let default_level = if args.no_tui { "info" } else { "error" };
+            init_logging(cli.verbose, log_filter, log_level.unwrap_or(default_level))?;
+            run_start(cli.token, &servers, args, &config).await
         }
-        Some(Commands::Login) => run_login(&server).await,
+        Some(Commands::Login { verify }) => run_login(&server, verify).await,
         Some(Commands::Subdomains { action }) => {
+            init_logging(cli.verbose, log_filter, log_level.unwrap_or("info"))?;
             run_subdomains(cli.token, &server, action, &config).await
         }
+        Some(Commands::Inspect { file }) => {
+            init_logging(cli.verbose, log_filter, log_level.unwrap_or("error"))?;
+            run_inspect(&util::expand_tilde(file), &config).await
+        }
+        Some(Commands::Doctor(args)) => {
+            init_logging(cli.verbose, log_filter, log_level.unwrap_or("error"))?;
+            run_doctor(cli.token, &server, args, &config).await
+        }
+        Some(Commands::Tunnel { action }) => {
+            let default_level = match &action {
+                TunnelCommands::Proxy(args) if args.tcp || args.no_tui => "info",
+                _ => "error",
+            };
+            init_logging(cli.verbose, log_filter, log_level.unwrap_or(default_level))?;
+            run_tunnel(cli.token, &server, action, &config).await
+        }
+        Some(Commands::Config {
+            action: ConfigCommands::Diff,
+        }) => run_config_diff(),
+        Some(Commands::Completions { shell }) => {
+            print!("{}", completions::script(shell, &mut Cli::command()));
+            Ok(())
+        }
+        Some(Commands::CompletionCheck) => {
+            completion_check::run();
+            Ok(())
+        }
         None => {
             // If no subcommand, show help
             eprintln!("No command specified. Use --help for usage information.");
@@ -117,6 +361,8 @@ async fn main() -> Result<()> {
             );
             eprintln!("  burrow login -s <server>              Authenticate");
             eprintln!("  burrow subdomains -s <server>         List your subdomains");
+            eprintln!("  burrow inspect <file.har>             Browse requests from a HAR file");
+            eprintln!("  burrow doctor -s <server>             Diagnose setup problems");
             std::process::exit(1);
         }
     }
@@ -124,53 +370,302 @@ async fn main() -> Result<()> {
 
 async fn run_start(
     cli_token: Option<String>,
-    _verbose: bool,
-    server: &str,
+    servers: &[String],
     args: StartArgs,
     config: &Config,
 ) -> Result<()> {
-    if args.no_tui {
-        anyhow::bail!("--no-tui mode requires tunnels to be configured via CLI flags, which have been removed. Use TUI mode instead.");
+    if args.no_tui && config.tunnels.is_empty() {
+        anyhow::bail!(
+            "--no-tui mode requires tunnels to be configured under [[tunnels]] in {:?} \
+             or with the BURROW_TUNNELS environment variable. Use TUI mode instead.",
+            Config::config_path().unwrap_or_default()
+        );
     }
 
-    // In TUI mode, only log errors
-    let filter = EnvFilter::new("error");
-    tracing_subscriber::registry()
-        .with(filter)
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    let (tui_tx, tui_rx) = create_event_channel();
-
     let token = cli_token.or(config.auth.token.clone()).ok_or_else(|| {
         anyhow::anyhow!(
             "API token required. Use --token, set BURROW_TOKEN environment variable, \n\
              or add token to config file at {:?}.\n\
              Get a token from the Burrow web UI at https://{}/account",
             Config::config_path().unwrap_or_default(),
-            server
+            servers[0]
         )
     })?;
 
+    let server_scheme = if args.insecure {
+        ServerScheme::Ws
+    } else {
+        config.auth.server_scheme
+    };
+    let builder = |server: &str| {
+        TunnelClient::builder()
+            .server_host(server)
+            .server_port(args.server_port)
+            .server_scheme(server_scheme)
+            .local_host(&args.host)
+            .token(token.clone())
+            .proxy_config(config.proxy.clone())
+            .connection_config(config.connection.clone())
+            .use_env_proxy(!args.no_proxy)
+    };
+
+    if args.no_tui {
+        let mut pool = ConnectionPool::new(None);
+        for server in servers {
+            pool.add(builder(server))?;
+        }
+        register_config_tunnels(pool.clients_mut(), servers, config)?;
+        return pool.run().await;
+    }
+
+    let (tui_tx, tui_rx) = create_event_channel(config.tui.event_channel_capacity.max(1));
     let (cmd_tx, cmd_rx) = client::tui::create_command_channel();
 
-    let client = TunnelClient::new(
+    // Tunnels added in the TUI go to the first server
+    let mut pool = ConnectionPool::new(Some(tui_tx));
+    pool.add(builder(&servers[0]).command_receiver(cmd_rx))?;
+    for server in &servers[1..] {
+        pool.add(builder(server))?;
+    }
+    register_config_tunnels(pool.clients_mut(), servers, config)?;
+
+    client::tui::setup_panic_handler();
+    let mut tui = Tui::new(tui_rx, cmd_tx, &config.tui)?;
+    if let Some(queue_depth) = pool.queue_depth() {
+        tui.watch_queue_depth(queue_depth);
+    }
+    tui.set_server_scheme(server_scheme);
+    let pool_handle = tokio::spawn(pool.run());
+    let tui_result = run_tui(&mut tui).await;
+    pool_handle.abort();
+    tui_result
+}
+
+/// Run the TUI, turning a panic into an error so `Tui::drop` still
+/// restores the terminal on the way out
+async fn run_tui(tui: &mut Tui) -> Result<()> {
+    AssertUnwindSafe(tui.run())
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("The TUI crashed")))
+}
+
+/// Register the tunnels from the config file and BURROW_TUNNELS with the
+/// client for their server, `clients` being in the order of `servers`
+fn register_config_tunnels(
+    clients: &mut [TunnelClient],
+    servers: &[String],
+    config: &Config,
+) -> Result<()> {
+    for tunnel in &config.tunnels {
+        let index = match tunnel.server() {
+            None => 0,
+            Some(server) => servers.iter().position(|s| s == server).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "A tunnel is configured for server {}, which is not one of --server",
+                    server
+                )
+            })?,
+        };
+        let client = &mut clients[index];
+        match tunnel {
+            TunnelSpec::Http {
+                port,
+                subdomain,
+                tags,
+                ..
+            } => {
+                client.register_tunnel(*port, subdomain.clone(), tags.clone())?;
+            }
+            TunnelSpec::Tcp { port, .. } => {
+                client.register_tcp_tunnel(*port)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Launch the TUI over requests loaded from a file, without a server connection
+async fn run_inspect(file: &Path, config: &Config) -> Result<()> {
+    let events = client::tui::load_har(file)?;
+
+    let source = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file.display().to_string());
+
+    // Feed imported requests through the regular event path. A plain sender
+    // is used so nothing is dropped while the TUI catches up.
+    let (event_tx, event_rx) = mpsc::channel(config.tui.event_channel_capacity.max(1));
+    let (cmd_tx, _cmd_rx) = client::tui::create_command_channel();
+
+    client::tui::setup_panic_handler();
+    let mut tui = Tui::new(event_rx, cmd_tx, &config.tui)?;
+    let feed_handle = tokio::spawn(async move {
+        let status = TuiEvent::ConnectionStatus(ConnectionStatus::Offline { source });
+        if event_tx.send(status).await.is_err() {
+            return;
+        }
+        for event in events {
+            if event_tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+    let tui_result = run_tui(&mut tui).await;
+    feed_handle.abort();
+    tui_result
+}
+
+async fn run_doctor(
+    cli_token: Option<String>,
+    server: &str,
+    args: DoctorArgs,
+    config: &Config,
+) -> Result<()> {
+    let passed = doctor::run(doctor::DoctorOptions {
+        token: cli_token.clone(),
         server,
-        args.server_port,
-        &args.host,
-        token,
-        Some(tui_tx),
-        cmd_rx,
-    )?;
-
-    let mut tui = Tui::new(tui_rx, cmd_tx)?;
-    let client_handle = tokio::spawn(async move { client.run().await });
-    let tui_result = tui.run().await;
-    client_handle.abort();
+        server_port: args.server_port,
+        server_scheme: config.auth.server_scheme,
+        local_host: &args.host,
+        local_ports: &args.ports,
+        fix: args.fix,
+    })
+    .await?;
+
+    if args.dump_state {
+        let token = cli_token
+            .or(config.auth.token.clone())
+            .context("An API token is required to dump the client state")?;
+        let (tui_tx, tui_rx) = create_event_channel(config.tui.event_channel_capacity.max(1));
+        let (cmd_tx, cmd_rx) = client::tui::create_command_channel();
+        // No tunnels: they would compete with a running client for the
+        // same subdomains
+        let client = TunnelClient::builder()
+            .server_host(server)
+            .server_port(args.server_port)
+            .server_scheme(config.auth.server_scheme)
+            .local_host(&args.host)
+            .token(token)
+            .proxy_config(config.proxy.clone())
+            .connection_config(config.connection.clone())
+            .tui_sender(tui_tx)
+            .command_receiver(cmd_rx)
+            .build()?;
+        doctor::dump_state(client, cmd_tx, tui_rx).await?;
+    }
+
+    if !passed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn run_tunnel(
+    cli_token: Option<String>,
+    server: &str,
+    action: TunnelCommands,
+    config: &Config,
+) -> Result<()> {
+    match action {
+        TunnelCommands::Stats {
+            tunnel_id,
+            live,
+            interval_secs,
+            format,
+            since,
+        } => {
+            let token = cli_token.or(config.auth.token.clone()).ok_or_else(|| {
+                anyhow::anyhow!("API token required. Run 'burrow login' first or use --token")
+            })?;
+            stats::run(stats::StatsOptions {
+                server,
+                token: &token,
+                tunnel_id: &tunnel_id,
+                live,
+                interval_secs,
+                format,
+                since_secs: since,
+            })
+            .await
+        }
+        TunnelCommands::Proxy(args) => run_local_proxy(args, config).await,
+    }
+}
+
+/// `burrow config diff`: compare the config file with its copy from before
+/// the last save
+fn run_config_diff() -> Result<()> {
+    let Some(previous) = Config::load_previous()? else {
+        println!(
+            "No previous config to compare with; one is kept from the next save on ({})",
+            Config::previous_path()?.display()
+        );
+        return Ok(());
+    };
+    let diff = previous.diff(&Config::load()?);
+    if diff.is_empty() {
+        println!("No changes since the last save");
+    }
+    for change in diff {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+/// `burrow tunnel proxy`: forward a local port without a server connection
+async fn run_local_proxy(args: ProxyArgs, config: &Config) -> Result<()> {
+    let mut options = local_proxy::LocalProxyOptions {
+        from_port: args.from_port,
+        to_host: args.to_host,
+        to_port: args.to_port,
+        log: args.log.map(util::expand_tilde),
+        tui_tx: None,
+        proxy: config.proxy.clone(),
+    };
+    if args.tcp {
+        return local_proxy::run_tcp(options).await;
+    }
+    if args.no_tui {
+        return local_proxy::run_http(options).await;
+    }
+    if options.log.as_deref() == Some(Path::new("-")) {
+        anyhow::bail!(
+            "--log - writes to stdout, which the TUI draws on; log to a file or add --no-tui"
+        );
+    }
+
+    let (tui_tx, tui_rx) = create_event_channel(config.tui.event_channel_capacity.max(1));
+    let (cmd_tx, _cmd_rx) = client::tui::create_command_channel();
+    let target = format!("{}:{}", options.to_host, options.to_port);
+    options.tui_tx = Some(tui_tx.clone());
+    // Bind before the TUI takes over the terminal so a busy port is reported
+    let proxy = local_proxy::bind_http(options).await?;
+    let status = ConnectionStatus::LocalProxy {
+        listen_port: proxy.port(),
+        target,
+    };
+    tui_tx.send(TuiEvent::ConnectionStatus(status)).await;
+
+    client::tui::setup_panic_handler();
+    let mut tui = Tui::new(tui_rx, cmd_tx, &config.tui)?;
+    let proxy_handle = tokio::spawn(async move {
+        if let Err(e) = proxy.run().await {
+            tui_tx
+                .send(TuiEvent::Error {
+                    message: format!("Local proxy stopped: {:#}", e),
+                    source: ErrorSource::Proxy,
+                })
+                .await;
+        }
+    });
+    let tui_result = run_tui(&mut tui).await;
+    proxy_handle.abort();
     tui_result
 }
 
-async fn run_login(server: &str) -> Result<()> {
+async fn run_login(server: &str, verify: bool) -> Result<()> {
     let account_url = format!("https://{}/account", server);
 
     println!("To authenticate, visit the following URL in your browser:");
@@ -202,6 +697,10 @@ async fn run_login(server: &str) -> Result<()> {
         anyhow::bail!("Invalid token format. Tokens should start with 'brw_'");
     }
 
+    if verify || ask_yes_no("Verify token before saving? [Y/n] ", true)? {
+        verify_token(server, &token).await?;
+    }
+
     // Save to config
     let mut config = Config::load().unwrap_or_default();
     config.auth.token = Some(token);
@@ -218,14 +717,70 @@ async fn run_login(server: &str) -> Result<()> {
     Ok(())
 }
 
+/// Check a token against `GET /api/account`, bailing out if the server
+/// rejects it; when the server can't say either way, it is left to the user
+/// whether to save anyway
+async fn verify_token(server: &str, token: &str) -> Result<()> {
+    let url = format!("https://{}/api/account", server);
+    let resp = match reqwest::Client::new()
+        .get(&url)
+        .bearer_auth(token)
+        .send()
+        .await
+    {
+        Ok(resp) => resp,
+        Err(e) => {
+            eprintln!("Warning: {}", BurrowError::http(&url, e));
+            return confirm_unverified("network error");
+        }
+    };
+
+    if resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+        anyhow::bail!("Token is invalid");
+    }
+    if let Some(err) = resp.error_for_status_ref().err() {
+        eprintln!("Warning: {}", BurrowError::http(&url, err));
+        return confirm_unverified(&format!("server error {}", resp.status().as_u16()));
+    }
+
+    let body: serde_json::Value = resp.json().await.unwrap_or_default();
+    let username = body["username"].as_str().unwrap_or("unknown");
+    println!("Token verified for user: {}", username);
+    if let Some(created_at) = body["created_at"].as_str() {
+        println!("Account created: {}", created_at);
+    }
+    Ok(())
+}
+
+/// Ask whether to save a token that couldn't be verified, failing if not
+fn confirm_unverified(reason: &str) -> Result<()> {
+    let prompt = format!("Could not verify ({}) – save anyway? [y/N] ", reason);
+    if ask_yes_no(&prompt, false)? {
+        return Ok(());
+    }
+    anyhow::bail!("Token not saved");
+}
+
+/// Ask a yes/no question on stdin; an empty answer picks `default`
+fn ask_yes_no(prompt: &str, default: bool) -> Result<bool> {
+    use std::io::{self, Write};
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        answer => answer.starts_with('y'),
+    })
+}
+
 async fn run_subdomains(
     cli_token: Option<String>,
     server: &str,
     action: Option<SubdomainCommands>,
     config: &Config,
 ) -> Result<()> {
-    init_logging(false);
-
     let token = cli_token.or(config.auth.token.clone()).ok_or_else(|| {
         anyhow::anyhow!("API token required. Run 'burrow login' first or use --token")
     })?;
@@ -235,72 +790,114 @@ async fn run_subdomains(
 
     match action {
         Some(SubdomainCommands::Release { subdomain }) => {
+            let url = format!("{}/api/subdomains/{}", base_url, subdomain);
             let resp = client
-                .delete(format!("{}/api/subdomains/{}", base_url, subdomain))
+                .delete(&url)
                 .bearer_auth(&token)
                 .send()
                 .await
+                .map_err(|e| BurrowError::http(&url, e))
                 .context("Failed to contact server")?;
 
-            if resp.status().is_success() {
-                println!("Subdomain '{}' released", subdomain);
-            } else {
-                let status = resp.status();
+            if let Some(err) = resp.error_for_status_ref().err() {
                 let body: serde_json::Value = resp.json().await.unwrap_or_default();
                 let msg = body["error"]["message"].as_str().unwrap_or("Unknown error");
-                anyhow::bail!("Failed to release subdomain: {} - {}", status, msg);
+                return Err(anyhow::Error::new(BurrowError::http(url, err))
+                    .context(format!("Failed to release subdomain: {}", msg)));
             }
+            println!("Subdomain '{}' released", subdomain);
         }
-        None => {
-            let resp = client
-                .get(format!("{}/api/subdomains", base_url))
-                .bearer_auth(&token)
-                .send()
-                .await
-                .context("Failed to contact server")?;
+        Some(SubdomainCommands::List(args)) => {
+            list_subdomains(&client, &base_url, &token, args).await?
+        }
+        None => list_subdomains(&client, &base_url, &token, SubdomainListArgs::default()).await?,
+    }
 
-            if resp.status().is_success() {
-                let body: serde_json::Value = resp.json().await?;
-                let subdomains = body["subdomains"].as_array();
-
-                match subdomains {
-                    Some(list) if !list.is_empty() => {
-                        println!("Your reserved subdomains:");
-                        println!();
-                        for sub in list {
-                            let name = sub["subdomain"].as_str().unwrap_or("?");
-                            let created = sub["created_at"].as_str().unwrap_or("?");
-                            println!("  {} (reserved {})", name, created);
-                        }
-                        println!();
-                        println!("To release a subdomain: burrow subdomains release <name>");
-                    }
-                    _ => {
-                        println!("No subdomains reserved yet.");
-                        println!("Subdomains are automatically reserved when you start a tunnel.");
-                    }
-                }
-            } else {
-                let status = resp.status();
-                let body: serde_json::Value = resp.json().await.unwrap_or_default();
-                let msg = body["error"]["message"].as_str().unwrap_or("Unknown error");
-                anyhow::bail!("Failed to list subdomains: {} - {}", status, msg);
-            }
+    Ok(())
+}
+
+async fn list_subdomains(
+    client: &reqwest::Client,
+    base_url: &str,
+    token: &str,
+    args: SubdomainListArgs,
+) -> Result<()> {
+    let url = format!("{}/api/subdomains", base_url);
+    let resp = client
+        .get(&url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .map_err(|e| BurrowError::http(&url, e))
+        .context("Failed to contact server")?;
+
+    if let Some(err) = resp.error_for_status_ref().err() {
+        let body: serde_json::Value = resp.json().await.unwrap_or_default();
+        let msg = body["error"]["message"].as_str().unwrap_or("Unknown error");
+        return Err(anyhow::Error::new(BurrowError::http(url, err))
+            .context(format!("Failed to list subdomains: {}", msg)));
+    }
+
+    let body: serde_json::Value = resp.json().await?;
+    let list = subdomains::filter_and_sort(
+        subdomains::Subdomain::from_response(&body),
+        args.filter.as_deref(),
+        args.sort_by,
+        args.sort_dir,
+    );
+
+    if list.is_empty() && args.format == subdomains::ListFormat::Table {
+        if args.filter.is_some() {
+            println!("No subdomains match the filter.");
+        } else {
+            println!("No subdomains reserved yet.");
+            println!("Subdomains are automatically reserved when you start a tunnel.");
         }
+        return Ok(());
     }
 
+    print!("{}", subdomains::render(&list, args.format));
+    if args.format == subdomains::ListFormat::Table {
+        println!();
+        println!("To release a subdomain: burrow subdomains release <name>");
+    }
     Ok(())
 }
 
-fn init_logging(verbose: bool) {
-    let filter = if verbose {
-        EnvFilter::new("debug")
-    } else {
-        EnvFilter::new("info")
+/// Set up logging from --log-filter, then RUST_LOG, then the verbosity count.
+///
+/// `BURROW_TRACE=1` additionally turns on `trace` for burrow itself, which
+/// logs when the spans around the connection loop, server messages and TUI
+/// events open and close, without the noise of tracing every dependency.
+/// Most of burrow lives in the library, whose target is `burrow_client`;
+/// `burrow` only covers the CLI.
+fn init_logging(verbose: u8, log_filter: Option<&str>, default_level: &str) -> Result<()> {
+    let mut filter = match log_filter {
+        Some(directives) => EnvFilter::try_new(directives)
+            .with_context(|| format!("Invalid --log-filter '{}'", directives))?,
+        None => match EnvFilter::try_from_default_env() {
+            Ok(filter) => filter,
+            Err(_) => EnvFilter::new(match verbose {
+                0 => default_level,
+                1 => "info",
+                2 => "debug",
+                _ => "trace",
+            }),
+        },
     };
+    let trace = std::env::var("BURROW_TRACE").is_ok_and(|value| value == "1");
+    let mut span_events = FmtSpan::NONE;
+    if trace {
+        filter = filter
+            .add_directive("burrow=trace".parse()?)
+            .add_directive("burrow_client=trace".parse()?);
+        span_events = FmtSpan::NEW | FmtSpan::CLOSE;
+    }
 
     let _ = tracing_subscriber::registry()
         .with(filter)
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_span_events(span_events))
         .try_init();
+
+    Ok(())
 }
@@ -4,7 +4,9 @@
 //! at compile time (e.g., passing a TunnelId where a RequestId is expected).
 
 use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
 use std::fmt;
+use std::ops::Deref;
 
 /// Unique identifier for an HTTP tunnel
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -24,7 +26,7 @@ impl From<String> for TunnelId {
 }
 
 /// Unique identifier for an HTTP request
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct RequestId(pub String);
 
@@ -90,3 +92,74 @@ impl From<String> for TcpId {
         TcpId(s)
     }
 }
+
+/// String conversions shared by all ID types, so maps keyed by an ID can be
+/// queried with a plain `&str`
+macro_rules! impl_str_conversions {
+    ($($id:ident),*) => {$(
+        impl From<&str> for $id {
+            fn from(s: &str) -> Self {
+                $id(s.to_string())
+            }
+        }
+
+        impl AsRef<str> for $id {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<[u8]> for $id {
+            fn as_ref(&self) -> &[u8] {
+                self.0.as_bytes()
+            }
+        }
+
+        impl Borrow<str> for $id {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $id {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $id {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $id {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+    )*};
+}
+
+impl_str_conversions!(TunnelId, RequestId, WsId, TcpTunnelId, TcpId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_lookup_by_str() {
+        let mut map: HashMap<TunnelId, u32> = HashMap::new();
+        map.insert(TunnelId::from("id"), 1);
+        assert_eq!(map.get("id"), Some(&1));
+        assert_eq!(map.get("other"), None);
+
+        let id = RequestId::from("req-1");
+        assert_eq!(id, "req-1");
+        assert_eq!(&*id, "req-1");
+        assert!(id.starts_with("req-"));
+    }
+}
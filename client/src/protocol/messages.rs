@@ -1,17 +1,32 @@
 use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
 
 use super::ids::{RequestId, TcpId, TcpTunnelId, TunnelId, WsId};
 
+/// Version of the tunnel protocol this client speaks, sent with each
+/// registration so the server can turn away clients it no longer supports
+pub const PROTOCOL_VERSION: u8 = 1;
+
 /// Outgoing message types (Client -> Server)
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum OutgoingMessage {
     RegisterTunnel {
         token: String,
+        /// Identifies this client process across reconnects; see `QueryTunnels`
+        session_id: String,
+        /// Echoed in the reply; a retry reuses it so the server answers with
+        /// the tunnel it already registered instead of adding another
+        registration_id: String,
         local_host: String,
         local_port: u16,
         #[serde(skip_serializing_if = "Option::is_none")]
         requested_subdomain: Option<String>,
+        /// Preferred size of `tunnel_request_chunk` payloads
+        chunk_size_bytes: usize,
+        protocol_version: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tags: Option<Vec<String>>,
     },
     TunnelResponse {
         request_id: RequestId,
@@ -21,6 +36,17 @@ pub enum OutgoingMessage {
         body: Option<String>,
         #[serde(skip_serializing_if = "Option::is_none")]
         body_encoding: Option<String>,
+        /// The body follows as `TunnelResponseChunk` messages
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        streaming: bool,
+    },
+    TunnelResponseChunk {
+        request_id: RequestId,
+        sequence: u32,
+        data: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data_encoding: Option<String>,
+        is_last: bool,
     },
     WsUpgraded {
         ws_id: WsId,
@@ -54,13 +80,51 @@ pub enum OutgoingMessage {
         reason: String,
     },
     Heartbeat {},
+    /// Several already-serialized messages sent as one
+    Batch {
+        messages: Vec<Box<RawValue>>,
+    },
+    /// Sent on connect, before re-registering: the server hands over any
+    /// of these tunnels it still has from an earlier connection with the
+    /// same session id and answers with a `TunnelList` of them
+    QueryTunnels {
+        token: String,
+        session_id: String,
+        tunnels: Vec<TunnelQuery>,
+        protocol_version: u8,
+    },
+}
+
+/// A tunnel the client had registered before reconnecting
+#[derive(Debug, Clone, Serialize)]
+pub struct TunnelQuery {
+    pub local_port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_subdomain: Option<String>,
+}
+
+/// A tunnel the server kept across a reconnect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListedTunnel {
+    pub tunnel_id: TunnelId,
+    pub subdomain: String,
+    pub full_url: String,
+    pub local_port: u16,
 }
 
 /// Incoming message types (Server -> Client)
-#[derive(Debug, Clone, Deserialize)]
+///
+/// Fields this client doesn't know about are ignored so newer servers stay
+/// compatible, and unknown message types become [`IncomingMessage::Unknown`].
+/// `Serialize` is only used to spot ignored fields in debug builds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum IncomingMessage {
     TunnelRegistered {
+        /// The `registration_id` of the `RegisterTunnel` this answers; older
+        /// servers answer in the order registrations were sent
+        #[serde(default)]
+        registration_id: Option<String>,
         tunnel_id: TunnelId,
         #[allow(dead_code)]
         subdomain: String,
@@ -72,6 +136,7 @@ pub enum IncomingMessage {
         method: String,
         path: String,
         query_string: String,
+        #[serde(default)]
         headers: Vec<Vec<String>>,
         #[serde(default)]
         body: Option<String>,
@@ -79,6 +144,21 @@ pub enum IncomingMessage {
         body_encoding: Option<String>,
         #[serde(default)]
         client_ip: Option<String>,
+        /// Body follows in `tunnel_request_chunk` messages
+        #[serde(default)]
+        body_chunked: bool,
+    },
+    TunnelRequestChunk {
+        request_id: RequestId,
+        sequence: u32,
+        data: String,
+        #[serde(default)]
+        data_encoding: Option<String>,
+        is_last: bool,
+    },
+    /// Stop streaming a response, e.g. because the browser disconnected
+    TunnelResponseCancel {
+        request_id: RequestId,
     },
     WsUpgrade {
         ws_id: WsId,
@@ -119,27 +199,30 @@ pub enum IncomingMessage {
         tcp_id: TcpId,
     },
     Heartbeat {},
+    /// Answer to `QueryTunnels`: the tunnels now routed to this connection
+    TunnelList {
+        tunnels: Vec<ListedTunnel>,
+    },
     Error {
         code: String,
         message: String,
+        /// Set when the error only concerns one tunnel
+        #[serde(default)]
+        tunnel_id: Option<TunnelId>,
+        /// Set when the error rejects a `RegisterTunnel`
+        #[serde(default)]
+        registration_id: Option<String>,
     },
+    /// Several messages sent as one, handled in order
+    Batch {
+        messages: Vec<serde_json::Value>,
+    },
+    /// A message type this client doesn't understand
+    #[serde(other)]
+    Unknown,
 }
 
 impl OutgoingMessage {
-    pub fn register_tunnel(
-        token: &str,
-        local_host: &str,
-        local_port: u16,
-        requested_subdomain: Option<String>,
-    ) -> Self {
-        OutgoingMessage::RegisterTunnel {
-            token: token.to_string(),
-            local_host: local_host.to_string(),
-            local_port,
-            requested_subdomain,
-        }
-    }
-
     pub fn tunnel_response(
         request_id: &RequestId,
         status: u16,
@@ -153,6 +236,59 @@ impl OutgoingMessage {
             headers: headers.into_iter().map(|(k, v)| [k, v]).collect(),
             body: body_str,
             body_encoding: encoding,
+            streaming: false,
+        }
+    }
+
+    /// Status and headers of a response whose body is sent with
+    /// [`tunnel_response_chunk`](Self::tunnel_response_chunk)
+    pub fn tunnel_response_head(
+        request_id: &RequestId,
+        status: u16,
+        headers: Vec<(String, String)>,
+    ) -> Self {
+        OutgoingMessage::TunnelResponse {
+            request_id: request_id.clone(),
+            status,
+            headers: headers.into_iter().map(|(k, v)| [k, v]).collect(),
+            body: None,
+            body_encoding: None,
+            streaming: true,
+        }
+    }
+
+    pub fn tunnel_response_chunk(
+        request_id: &RequestId,
+        sequence: u32,
+        data: Vec<u8>,
+        is_last: bool,
+    ) -> Self {
+        let (data, data_encoding) = encode_body(Some(data));
+        OutgoingMessage::TunnelResponseChunk {
+            request_id: request_id.clone(),
+            sequence,
+            data: data.unwrap_or_default(),
+            data_encoding,
+            is_last,
+        }
+    }
+
+    /// Combine messages that were already serialized with `to_json`
+    pub fn batch(messages: Vec<String>) -> serde_json::Result<Self> {
+        Ok(OutgoingMessage::Batch {
+            messages: messages
+                .into_iter()
+                .map(RawValue::from_string)
+                .collect::<serde_json::Result<_>>()?,
+        })
+    }
+
+    pub fn query_tunnels(token: &str, session_id: &str, tunnels: Vec<TunnelQuery>) -> Self {
+        OutgoingMessage::QueryTunnels {
+            token: token.to_string(),
+            session_id: session_id.to_string(),
+            tunnels,
+            protocol_version: PROTOCOL_VERSION,
         }
     }
 
@@ -188,7 +324,38 @@ impl OutgoingMessage {
 
 impl IncomingMessage {
     pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+        let msg = serde_json::from_str(json)?;
+        #[cfg(debug_assertions)]
+        warn_unknown_fields(json, &msg);
+        Ok(msg)
+    }
+}
+
+/// Warn about fields the server sent that this client ignored, so drift
+/// between server and client is noticed early during development
+#[cfg(debug_assertions)]
+fn warn_unknown_fields(json: &str, msg: &IncomingMessage) {
+    if matches!(msg, IncomingMessage::Unknown) {
+        return;
+    }
+
+    let (Ok(serde_json::Value::Object(received)), Ok(serde_json::Value::Object(known))) =
+        (serde_json::from_str(json), serde_json::to_value(msg))
+    else {
+        return;
+    };
+
+    let unknown: Vec<&str> = received
+        .keys()
+        .filter(|key| !known.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        tracing::warn!(
+            "Ignoring unknown fields in {} message: {}",
+            received["type"],
+            unknown.join(", ")
+        );
     }
 }
 
@@ -221,3 +388,43 @@ pub fn decode_body(body: Option<&str>, encoding: Option<&str>) -> Option<Vec<u8>
         _ => Some(body.as_bytes().to_vec()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incoming_message_tolerates_unknown_fields_and_types() {
+        let msg = IncomingMessage::from_json(
+            r#"{"type": "tunnel_request", "request_id": "r1", "tunnel_id": "t1",
+                "method": "GET", "path": "/", "query_string": "", "priority": 5}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            msg,
+            IncomingMessage::TunnelRequest { ref headers, .. } if headers.is_empty()
+        ));
+
+        let msg = IncomingMessage::from_json(r#"{"type": "brand_new", "x": 1}"#).unwrap();
+        assert!(matches!(msg, IncomingMessage::Unknown));
+    }
+
+    #[test]
+    fn test_batch_embeds_serialized_messages() {
+        let heartbeat = OutgoingMessage::Heartbeat {}.to_json().unwrap();
+        let close = OutgoingMessage::tcp_close(&TcpId("c1".into()), "done")
+            .to_json()
+            .unwrap();
+        let batch = OutgoingMessage::batch(vec![heartbeat, close])
+            .unwrap()
+            .to_json()
+            .unwrap();
+        assert_eq!(
+            batch,
+            r#"{"type":"batch","messages":[{"type":"heartbeat"},{"type":"tcp_close","tcp_id":"c1","reason":"done"}]}"#
+        );
+
+        let msg = IncomingMessage::from_json(&batch).unwrap();
+        assert!(matches!(msg, IncomingMessage::Batch { ref messages } if messages.len() == 2));
+    }
+}
@@ -0,0 +1,195 @@
+//! `burrow tunnel stats`: tunnel traffic statistics without the TUI.
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use burrow_client::client::tui::format_size;
+use burrow_client::error::BurrowError;
+
+/// How stats are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable summary line
+    #[default]
+    Text,
+    /// One JSON object per line, for piping to tools like `jq`
+    Json,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TunnelStats {
+    requests_per_minute: f64,
+    latency_ms: LatencyPercentiles,
+    /// Fraction of requests that failed, from 0.0 to 1.0
+    error_rate: f64,
+    bytes_per_second: f64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct LatencyPercentiles {
+    p50: f64,
+    p95: f64,
+    p99: f64,
+}
+
+impl TunnelStats {
+    fn summary(&self) -> String {
+        format!(
+            "{:.1} req/min | p50 {:.0}ms p95 {:.0}ms p99 {:.0}ms | {:.1}% errors | {}/s",
+            self.requests_per_minute,
+            self.latency_ms.p50,
+            self.latency_ms.p95,
+            self.latency_ms.p99,
+            self.error_rate * 100.0,
+            format_size(self.bytes_per_second.round() as u64)
+        )
+    }
+}
+
+/// What `burrow tunnel stats` should fetch and how to print it
+pub struct StatsOptions<'a> {
+    pub server: &'a str,
+    pub token: &'a str,
+    pub tunnel_id: &'a str,
+    pub live: bool,
+    pub interval_secs: u32,
+    pub format: OutputFormat,
+    /// Only include traffic from this many seconds back
+    pub since_secs: Option<u64>,
+}
+
+/// Fetch stats once, or keep polling with `live` until interrupted
+pub async fn run(options: StatsOptions<'_>) -> Result<()> {
+    let client = reqwest::Client::new();
+    let mut url = url::Url::parse(&format!(
+        "https://{}/api/tunnels/{}/stats",
+        options.server, options.tunnel_id
+    ))
+    .context("Invalid server or tunnel id")?;
+    if let Some(since) = options.since_secs {
+        url.query_pairs_mut()
+            .append_pair("since", &since.to_string());
+    }
+
+    if !options.live {
+        let stats = fetch_stats(&client, &url, options.token).await?;
+        return print_stats(&stats, options.format, false);
+    }
+
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(u64::from(options.interval_secs.max(1))));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                match fetch_stats(&client, &url, options.token).await {
+                    Ok(stats) => print_stats(&stats, options.format, true)?,
+                    // Keep polling through restarts and rate limiting
+                    Err(e) if is_retryable(&e) => eprintln!("\n{:#}; retrying", e),
+                    Err(e) => return Err(e),
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                if options.format == OutputFormat::Text {
+                    println!();
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn fetch_stats(
+    client: &reqwest::Client,
+    url: &url::Url,
+    token: &str,
+) -> Result<serde_json::Value> {
+    let resp = client
+        .get(url.clone())
+        .bearer_auth(token)
+        .send()
+        .await
+        .map_err(|e| BurrowError::http(url.as_str(), e))
+        .context("Failed to contact server")?;
+
+    if let Some(err) = resp.error_for_status_ref().err() {
+        let body: serde_json::Value = resp.json().await.unwrap_or_default();
+        let msg = body["error"]["message"].as_str().unwrap_or("Unknown error");
+        return Err(anyhow::Error::new(BurrowError::http(url.as_str(), err))
+            .context(format!("Failed to fetch tunnel stats: {}", msg)));
+    }
+
+    resp.json().await.context("Invalid stats response")
+}
+
+fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<BurrowError>())
+        .any(BurrowError::is_retryable)
+}
+
+fn print_stats(stats: &serde_json::Value, format: OutputFormat, live: bool) -> Result<()> {
+    let mut stdout = io::stdout();
+    match format {
+        // Pass the server's stats through untouched so nothing is lost
+        OutputFormat::Json => writeln!(stdout, "{}", stats)?,
+        OutputFormat::Text => {
+            let summary = TunnelStats::deserialize(stats)
+                .context("Invalid stats response")?
+                .summary();
+            if live {
+                // Overwrite the previous line, clearing anything left over from it
+                write!(stdout, "\r{}\x1b[K", summary)?;
+            } else {
+                writeln!(stdout, "{}", summary)?;
+            }
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Parse a duration such as `90s`, `15m`, `1h` or `2d` into seconds
+pub fn parse_since(input: &str) -> Result<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+    let value: u64 = value
+        .parse()
+        .with_context(|| format!("Invalid duration '{}', expected e.g. 30m or 1h", input))?;
+
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => anyhow::bail!(
+            "Invalid duration unit '{}' in '{}', expected s, m, h or d",
+            unit,
+            input
+        ),
+    };
+    Ok(value * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("90").unwrap(), 90);
+        assert_eq!(parse_since("15m").unwrap(), 15 * 60);
+        assert_eq!(parse_since("1h").unwrap(), 3600);
+        assert_eq!(parse_since("2d").unwrap(), 2 * 24 * 3600);
+        assert!(parse_since("h").is_err());
+        assert!(parse_since("3w").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! `burrow subdomains list`: sorting, filtering and formatting reservations.
+
+use chrono::{DateTime, FixedOffset};
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// A subdomain reservation as returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Subdomain {
+    pub subdomain: String,
+    /// ISO 8601 timestamp; older servers may leave it out
+    pub created_at: Option<String>,
+}
+
+impl Subdomain {
+    /// Read the reservations from a `GET /api/subdomains` response body
+    pub fn from_response(body: &serde_json::Value) -> Vec<Subdomain> {
+        body["subdomains"]
+            .as_array()
+            .map(|list| {
+                list.iter()
+                    .map(|sub| Subdomain {
+                        subdomain: sub["subdomain"].as_str().unwrap_or("?").to_string(),
+                        created_at: sub["created_at"].as_str().map(str::to_string),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn created(&self) -> Option<DateTime<FixedOffset>> {
+        DateTime::parse_from_rfc3339(self.created_at.as_deref()?).ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortField {
+    Name,
+    Created,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SortDir {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How the list is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ListFormat {
+    /// Aligned columns with a total at the bottom
+    #[default]
+    Table,
+    Json,
+    /// `subdomain,created_at` rows after a header row
+    Csv,
+}
+
+/// Keep the subdomains starting with `prefix`, then sort them. Without a
+/// sort field the server's order is kept; reservations without a (valid)
+/// creation date sort last either way.
+pub fn filter_and_sort(
+    mut list: Vec<Subdomain>,
+    prefix: Option<&str>,
+    sort_by: Option<SortField>,
+    dir: SortDir,
+) -> Vec<Subdomain> {
+    if let Some(prefix) = prefix {
+        list.retain(|sub| sub.subdomain.starts_with(prefix));
+    }
+
+    match sort_by {
+        Some(SortField::Name) => list.sort_by(|a, b| a.subdomain.cmp(&b.subdomain)),
+        Some(SortField::Created) => {
+            list.sort_by_key(|sub| (sub.created().is_none(), sub.created()))
+        }
+        None => return list,
+    }
+    if dir == SortDir::Desc {
+        // Reverse only the dated part so undated entries stay at the end
+        let dated = match sort_by {
+            Some(SortField::Created) => list.iter().take_while(|s| s.created().is_some()).count(),
+            _ => list.len(),
+        };
+        list[..dated].reverse();
+    }
+    list
+}
+
+pub fn render(list: &[Subdomain], format: ListFormat) -> String {
+    match format {
+        ListFormat::Json => serde_json::to_string_pretty(list).unwrap_or_default(),
+        ListFormat::Csv => {
+            let mut out = String::from("subdomain,created_at\n");
+            for sub in list {
+                out.push_str(&format!(
+                    "{},{}\n",
+                    csv_field(&sub.subdomain),
+                    csv_field(sub.created_at.as_deref().unwrap_or(""))
+                ));
+            }
+            out
+        }
+        ListFormat::Table => {
+            let name_width = list
+                .iter()
+                .map(|sub| sub.subdomain.len())
+                .chain(["SUBDOMAIN".len()])
+                .max()
+                .unwrap_or(0);
+            let mut out = format!("{:<name_width$}  CREATED\n", "SUBDOMAIN");
+            for sub in list {
+                out.push_str(&format!(
+                    "{:<name_width$}  {}\n",
+                    sub.subdomain,
+                    sub.created_at.as_deref().unwrap_or("-")
+                ));
+            }
+            let noun = if list.len() == 1 {
+                "subdomain"
+            } else {
+                "subdomains"
+            };
+            out.push_str(&format!("\nTotal: {} {}\n", list.len(), noun));
+            out
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_by_created_desc() {
+        let sub = |name: &str, created: Option<&str>| Subdomain {
+            subdomain: name.to_string(),
+            created_at: created.map(str::to_string),
+        };
+        let list = vec![
+            sub("api", Some("2024-01-02T10:00:00Z")),
+            sub("old", None),
+            sub("app", Some("2024-03-01T10:00:00Z")),
+            sub("web", Some("2024-02-01T10:00:00Z")),
+        ];
+
+        let sorted = filter_and_sort(list, Some("a"), Some(SortField::Created), SortDir::Desc);
+        let names: Vec<_> = sorted.iter().map(|s| s.subdomain.as_str()).collect();
+        assert_eq!(names, ["app", "api"]);
+
+        assert_eq!(
+            render(&sorted, ListFormat::Csv),
+            "subdomain,created_at\napp,2024-03-01T10:00:00Z\napi,2024-01-02T10:00:00Z\n"
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Small helpers shared by the commands.
+
+use std::path::PathBuf;
+
+use directories::UserDirs;
+
+/// Replace a leading `~` with the home directory. Other paths are returned
+/// unchanged, as is `~` when the home directory can't be found.
+pub fn expand_tilde(path: PathBuf) -> PathBuf {
+    let Ok(rest) = path.strip_prefix("~") else {
+        return path;
+    };
+    let Some(dirs) = UserDirs::new() else {
+        return path;
+    };
+    if rest.as_os_str().is_empty() {
+        dirs.home_dir().to_path_buf()
+    } else {
+        dirs.home_dir().join(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = UserDirs::new().unwrap().home_dir().to_path_buf();
+
+        assert_eq!(expand_tilde("~/foo".into()), home.join("foo"));
+        assert_eq!(expand_tilde("~".into()), home);
+        assert_eq!(
+            expand_tilde("/absolute/path".into()),
+            PathBuf::from("/absolute/path")
+        );
+        assert_eq!(
+            expand_tilde("relative/path".into()),
+            PathBuf::from("relative/path")
+        );
+        assert_eq!(expand_tilde("~user/x".into()), PathBuf::from("~user/x"));
+    }
+}
@@ -0,0 +1,432 @@
+//! Drive a `TunnelClient` against a stand-in server, without the TUI.
+
+use std::time::Duration;
+
+use burrow_client::client::tui::{create_command_channel, create_event_channel, ConnectionStatus};
+use burrow_client::config::ServerScheme;
+use burrow_client::protocol::decode_body;
+use burrow_client::{
+    ConnectionPool, ReconnectConfig, TuiCommand, TuiEvent, TunnelClient, TunnelConfig,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+const TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A local service answering every request with `hello`
+async fn spawn_local_service() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf).await;
+            let _ = stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                )
+                .await;
+        }
+    });
+    port
+}
+
+/// The next JSON message from the client, skipping pings and pongs
+async fn next_message(ws: &mut WebSocketStream<TcpStream>) -> Value {
+    loop {
+        if let Message::Text(text) = ws.next().await.unwrap().unwrap() {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+/// The next message of type `kind` from the client, skipping heartbeats and
+/// anything else sent in between
+async fn wait_for_message(ws: &mut WebSocketStream<TcpStream>, kind: &str) -> Value {
+    loop {
+        let message = next_message(ws).await;
+        if message["type"] == kind {
+            return message;
+        }
+    }
+}
+
+#[test]
+fn test_builder_requires_host_and_token() {
+    assert!(TunnelClient::builder().token("token").build().is_err());
+    assert!(TunnelClient::builder()
+        .server_host("tunnel.example.com")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_register_tunnels() {
+    let mut client = TunnelClient::builder()
+        .server_host("tunnel.example.com")
+        .token("token")
+        .build()
+        .unwrap();
+    client
+        .register_tunnel(3000, Some("myapp".to_string()), vec!["api".to_string()])
+        .unwrap();
+    client.register_tcp_tunnel(5432).unwrap();
+
+    assert!(client.register_tunnel(3000, None, Vec::new()).is_err());
+    // The server's subdomain rules apply
+    for subdomain in ["Not Valid", "-app", "app-", "a", "www"] {
+        assert!(
+            client
+                .register_tunnel(3001, Some(subdomain.to_string()), Vec::new())
+                .is_err(),
+            "{}",
+            subdomain
+        );
+    }
+    assert_eq!(
+        client.registered_tunnels(),
+        [
+            TunnelConfig::Http {
+                local_port: 3000,
+                subdomain: Some("myapp".to_string()),
+                extra_headers: Vec::new(),
+                tags: vec!["api".to_string()],
+            },
+            TunnelConfig::Tcp { local_port: 5432 },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_forwards_request_to_local_service() {
+    let local_port = spawn_local_service().await;
+    let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_port = server.local_addr().unwrap().port();
+
+    let (tui_tx, mut tui_rx) = create_event_channel(256);
+    let mut client = TunnelClient::builder()
+        .server_host("127.0.0.1")
+        .server_port(server_port)
+        .server_scheme(ServerScheme::Ws)
+        .use_env_proxy(false)
+        .local_host("127.0.0.1")
+        .token("test-token")
+        .tui_sender(tui_tx)
+        .build()
+        .unwrap();
+    client
+        .register_tunnel(local_port, Some("myapp".to_string()), Vec::new())
+        .unwrap();
+    let client_handle = tokio::spawn(client.run());
+
+    let exchange = async {
+        let (stream, _) = server.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+        // Tunnels are first looked up in case the server kept them
+        let query = wait_for_message(&mut ws, "query_tunnels").await;
+        let list = json!({"type": "tunnel_list", "tunnels": []});
+        ws.send(Message::Text(list.to_string())).await.unwrap();
+
+        let register = wait_for_message(&mut ws, "register_tunnel").await;
+        assert_eq!(register["token"], "test-token");
+        assert_eq!(register["local_port"], local_port);
+        assert_eq!(register["requested_subdomain"], "myapp");
+        // Only tunnels of the same session are handed over on reconnect
+        assert_eq!(register["session_id"].as_str().map(str::len), Some(32));
+        assert_eq!(register["session_id"], query["session_id"]);
+
+        let reply = json!({
+            "type": "tunnel_registered",
+            "tunnel_id": "tunnel-1",
+            "subdomain": "myapp",
+            "full_url": "https://myapp.example.com",
+        });
+        ws.send(Message::Text(reply.to_string())).await.unwrap();
+        let request = json!({
+            "type": "tunnel_request",
+            "request_id": "request-1",
+            "tunnel_id": "tunnel-1",
+            "method": "GET",
+            "path": "/",
+            "query_string": "",
+            "headers": [["host", "myapp.example.com"]],
+        });
+        ws.send(Message::Text(request.to_string())).await.unwrap();
+        wait_for_message(&mut ws, "tunnel_response").await
+    };
+    let response = timeout(TEST_TIMEOUT, exchange).await.unwrap();
+    client_handle.abort();
+
+    assert_eq!(response["request_id"], "request-1");
+    assert_eq!(response["status"], 200);
+    let body = decode_body(
+        response["body"].as_str(),
+        response["body_encoding"].as_str(),
+    );
+    assert_eq!(body.as_deref(), Some(&b"hello"[..]));
+
+    let registered = timeout(TEST_TIMEOUT, async {
+        loop {
+            if let TuiEvent::TunnelRegistered(event) = tui_rx.recv().await.unwrap() {
+                return event;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(registered.full_url, "https://myapp.example.com");
+    assert_eq!(registered.local_port, local_port);
+}
+
+#[tokio::test]
+async fn test_registrations_resolve_to_tunnel_ids() {
+    let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_port = server.local_addr().unwrap().port();
+    let mut client = quick_retry_client(server_port, 1).build().unwrap();
+    let mut http = client
+        .register_tunnel(3000, Some("myapp".to_string()), Vec::new())
+        .unwrap();
+    let mut tcp = client.register_tcp_tunnel(5432).unwrap();
+    let client_handle = tokio::spawn(client.run());
+
+    let server_handle = tokio::spawn(async move {
+        let (stream, _) = server.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        loop {
+            let message = next_message(&mut ws).await;
+            let reply = match message["type"].as_str() {
+                Some("query_tunnels") => json!({"type": "tunnel_list", "tunnels": []}),
+                Some("register_tunnel") => json!({
+                    "type": "tunnel_registered",
+                    "registration_id": message["registration_id"],
+                    "tunnel_id": "tunnel-1",
+                    "subdomain": "myapp",
+                    "full_url": "https://myapp.example.com",
+                }),
+                Some("register_tcp_tunnel") => json!({
+                    "type": "tcp_tunnel_registered",
+                    "tcp_tunnel_id": "tcp-1",
+                    "server_port": 40000,
+                    "local_port": 5432,
+                }),
+                _ => continue,
+            };
+            ws.send(Message::Text(reply.to_string())).await.unwrap();
+        }
+    });
+
+    let (http_id, tcp_id) = timeout(TEST_TIMEOUT, async {
+        (http.id().await.unwrap(), tcp.id().await.unwrap())
+    })
+    .await
+    .unwrap();
+    client_handle.abort();
+    server_handle.abort();
+
+    assert_eq!(http_id.0, "tunnel-1");
+    assert_eq!(tcp_id.0, "tcp-1");
+}
+
+#[tokio::test]
+async fn test_registration_fails_when_client_stops() {
+    let mut client = quick_retry_client(1, 1).build().unwrap();
+    let mut registration = client.register_tunnel(3000, None, Vec::new()).unwrap();
+    drop(client);
+
+    assert!(registration.id().await.is_err());
+}
+
+/// A client for a stand-in server on `server_port` that retries quickly
+fn quick_retry_client(server_port: u16, max_attempts: u32) -> burrow_client::TunnelClientBuilder {
+    TunnelClient::builder()
+        .server_host("127.0.0.1")
+        .server_port(server_port)
+        .server_scheme(ServerScheme::Ws)
+        .use_env_proxy(false)
+        .token("test-token")
+        .reconnect_config(ReconnectConfig {
+            max_attempts,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 10,
+            backoff_multiplier: 1.0,
+        })
+}
+
+#[tokio::test]
+async fn test_commands_survive_failed_connect() {
+    let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_port = server.local_addr().unwrap().port();
+    let (cmd_tx, cmd_rx) = create_command_channel();
+    let client = quick_retry_client(server_port, 5)
+        .command_receiver(cmd_rx)
+        .build()
+        .unwrap();
+    cmd_tx
+        .send(TuiCommand::AddTcpTunnel { local_port: 5432 })
+        .await
+        .unwrap();
+    let client_handle = tokio::spawn(client.run());
+
+    let register = timeout(TEST_TIMEOUT, async {
+        // Fail the first attempt before the WebSocket handshake
+        let (stream, _) = server.accept().await.unwrap();
+        drop(stream);
+
+        let (stream, _) = server.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        wait_for_message(&mut ws, "register_tcp_tunnel").await
+    })
+    .await
+    .unwrap();
+    client_handle.abort();
+
+    assert_eq!(register["local_port"], 5432);
+}
+
+#[tokio::test]
+async fn test_retry_after_giving_up() {
+    let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_port = server.local_addr().unwrap().port();
+    let (cmd_tx, cmd_rx) = create_command_channel();
+    let (tui_tx, mut tui_rx) = create_event_channel(256);
+    let client = quick_retry_client(server_port, 1)
+        .command_receiver(cmd_rx)
+        .tui_sender(tui_tx)
+        .build()
+        .unwrap();
+    let client_handle = tokio::spawn(client.run());
+
+    timeout(TEST_TIMEOUT, async {
+        let (stream, _) = server.accept().await.unwrap();
+        drop(stream);
+        loop {
+            if let TuiEvent::ConnectionStatus(ConnectionStatus::Disconnected { .. }) =
+                tui_rx.recv().await.unwrap()
+            {
+                break;
+            }
+        }
+
+        // Commands other than retry are kept while waiting
+        cmd_tx
+            .send(TuiCommand::AddTcpTunnel { local_port: 5432 })
+            .await
+            .unwrap();
+        cmd_tx.send(TuiCommand::Retry).await.unwrap();
+        let (stream, _) = server.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let register = wait_for_message(&mut ws, "register_tcp_tunnel").await;
+        assert_eq!(register["local_port"], 5432);
+    })
+    .await
+    .unwrap();
+    client_handle.abort();
+}
+
+#[tokio::test]
+async fn test_token_switch_cuts_backoff_short() {
+    let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server_port = server.local_addr().unwrap().port();
+    let (cmd_tx, cmd_rx) = create_command_channel();
+    let (tui_tx, mut tui_rx) = create_event_channel(256);
+    let mut client = quick_retry_client(server_port, 5)
+        .reconnect_config(ReconnectConfig {
+            max_attempts: 5,
+            initial_backoff_ms: 60_000,
+            max_backoff_ms: 60_000,
+            backoff_multiplier: 1.0,
+        })
+        .command_receiver(cmd_rx)
+        .tui_sender(tui_tx)
+        .build()
+        .unwrap();
+    client
+        .register_tunnel(3000, Some("myapp".to_string()), Vec::new())
+        .unwrap();
+    let client_handle = tokio::spawn(client.run());
+
+    let query = timeout(TEST_TIMEOUT, async {
+        let (stream, _) = server.accept().await.unwrap();
+        drop(stream);
+        loop {
+            if let TuiEvent::ConnectionStatus(ConnectionStatus::Reconnecting { .. }) =
+                tui_rx.recv().await.unwrap()
+            {
+                break;
+            }
+        }
+
+        // A new token is tried right away rather than after the backoff
+        cmd_tx
+            .send(TuiCommand::UpdateToken {
+                token: "new-token".to_string(),
+            })
+            .await
+            .unwrap();
+        let (stream, _) = server.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        wait_for_message(&mut ws, "query_tunnels").await
+    })
+    .await
+    .unwrap();
+    client_handle.abort();
+
+    assert_eq!(query["token"], "new-token");
+}
+
+#[tokio::test]
+async fn test_pool_registers_tunnels_with_their_server() {
+    let first = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let second = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (tui_tx, mut tui_rx) = create_event_channel(256);
+    let mut pool = ConnectionPool::new(Some(tui_tx));
+    for server in [&first, &second] {
+        let port = server.local_addr().unwrap().port();
+        pool.add(quick_retry_client(port, 5)).unwrap();
+    }
+    pool.clients_mut()[0].register_tcp_tunnel(5432).unwrap();
+    pool.clients_mut()[1].register_tcp_tunnel(6543).unwrap();
+    let pool_handle = tokio::spawn(pool.run());
+
+    let registered_port = |server: TcpListener| async move {
+        let (stream, _) = server.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        wait_for_message(&mut ws, "register_tcp_tunnel").await["local_port"].clone()
+    };
+    let ports = timeout(
+        TEST_TIMEOUT,
+        futures_util::future::join(registered_port(first), registered_port(second)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(ports, (json!(5432), json!(6543)));
+
+    // Only the first client reports the connection status
+    let statuses = timeout(TEST_TIMEOUT, async {
+        let mut first = None;
+        let mut second = None;
+        while first.is_none() || second.is_none() {
+            match tui_rx.recv().await.unwrap() {
+                TuiEvent::ConnectionStatus(status) => first = Some(status),
+                TuiEvent::PoolConnectionStatus { pool_id, status } => {
+                    assert_eq!(pool_id, 2);
+                    second = Some(status);
+                }
+                _ => {}
+            }
+        }
+        (first, second)
+    })
+    .await
+    .unwrap();
+    pool_handle.abort();
+
+    assert_eq!(statuses.0, Some(ConnectionStatus::Connecting));
+    assert_eq!(statuses.1, Some(ConnectionStatus::Connecting));
+}
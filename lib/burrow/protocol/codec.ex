@@ -12,7 +12,8 @@ defmodule Burrow.Protocol.Codec do
     method path query_string headers body body_encoding status
     attestation public_key signature requested_subdomain
     local_host local_port code message
-    ws_id opcode data data_encoding reason
+    ws_id opcode data data_encoding reason messages
+    protocol_version tags streaming sequence is_last tunnels
   )a
 
   @doc """
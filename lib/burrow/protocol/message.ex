@@ -7,13 +7,18 @@ defmodule Burrow.Protocol.Message do
   ## Message Types
 
   - `register_tunnel` - Client → Server: Request tunnel registration
-  - `tunnel_registered` - Server → Client: Tunnel created with subdomain
+  - `tunnel_registered` - Server → Client: Tunnel created with subdomain; echoes the
+    registration's `registration_id`, and a repeated id gets the same tunnel back
   - `tunnel_request` - Server → Client: Incoming HTTP request
   - `tunnel_response` - Client → Server: HTTP response
+  - `tunnel_response_chunk` - Client → Server: Part of a streamed response body
+  - `tunnel_response_cancel` - Server → Client: Stop streaming a response nobody reads any more
   - `ws_upgrade` - Server → Client: Request WebSocket upgrade to local service
   - `ws_upgraded` - Client → Server: WebSocket upgrade successful
   - `ws_frame` - Bidirectional: Forward a WebSocket frame
   - `ws_close` - Bidirectional: Close the proxied WebSocket
+  - `query_tunnels` - Client → Server: Take over tunnels the same client session kept from a previous connection
+  - `tunnel_list` - Server → Client: The tunnels taken over
   - `heartbeat` - Bidirectional: Keep-alive ping
   - `error` - Bidirectional: Error notification
   """
@@ -23,6 +28,8 @@ defmodule Burrow.Protocol.Message do
           | :tunnel_registered
           | :tunnel_request
           | :tunnel_response
+          | :tunnel_response_chunk
+          | :tunnel_response_cancel
           | :ws_upgrade
           | :ws_upgraded
           | :ws_frame
@@ -33,8 +40,11 @@ defmodule Burrow.Protocol.Message do
           | :tcp_connected
           | :tcp_data
           | :tcp_close
+          | :query_tunnels
+          | :tunnel_list
           | :heartbeat
           | :error
+          | :batch
 
   @doc """
   Builds a register_tunnel message.
@@ -169,6 +179,25 @@ defmodule Burrow.Protocol.Message do
     }
   end
 
+  @doc """
+  Builds a tunnel_list response to `query_tunnels`.
+
+  Each tunnel is a map with `tunnel_id`, `subdomain`, `full_url` and `local_port`.
+
+  ## Examples
+
+      iex> msg = Burrow.Protocol.Message.tunnel_list([])
+      iex> msg.type
+      "tunnel_list"
+  """
+  @spec tunnel_list([map()]) :: map()
+  def tunnel_list(tunnels) do
+    %{
+      type: "tunnel_list",
+      tunnels: tunnels
+    }
+  end
+
   @doc """
   Builds an error message.
 
@@ -202,6 +231,8 @@ defmodule Burrow.Protocol.Message do
   def type(%{type: "tunnel_registered"}), do: :tunnel_registered
   def type(%{type: "tunnel_request"}), do: :tunnel_request
   def type(%{type: "tunnel_response"}), do: :tunnel_response
+  def type(%{type: "tunnel_response_chunk"}), do: :tunnel_response_chunk
+  def type(%{type: "tunnel_response_cancel"}), do: :tunnel_response_cancel
   def type(%{type: "heartbeat"}), do: :heartbeat
   def type(%{type: "error"}), do: :error
   def type(%{type: "ws_upgrade"}), do: :ws_upgrade
@@ -214,6 +245,9 @@ defmodule Burrow.Protocol.Message do
   def type(%{type: "tcp_connected"}), do: :tcp_connected
   def type(%{type: "tcp_data"}), do: :tcp_data
   def type(%{type: "tcp_close"}), do: :tcp_close
+  def type(%{type: "batch"}), do: :batch
+  def type(%{type: "query_tunnels"}), do: :query_tunnels
+  def type(%{type: "tunnel_list"}), do: :tunnel_list
   def type(_), do: :unknown
 
   # WebSocket Passthrough Messages
@@ -292,6 +326,26 @@ defmodule Burrow.Protocol.Message do
   defp encode_frame_data(:pong, data), do: {Base.encode64(data), "base64"}
   defp encode_frame_data(_opcode, data), do: {Base.encode64(data), "base64"}
 
+  @doc """
+  Builds a tunnel_response_cancel message (Server → Client).
+
+  Tells the client to stop sending `tunnel_response_chunk` messages for a
+  response, e.g. because the browser reading it disconnected.
+
+  ## Examples
+
+      iex> msg = Burrow.Protocol.Message.tunnel_response_cancel("rid")
+      iex> msg.type
+      "tunnel_response_cancel"
+  """
+  @spec tunnel_response_cancel(String.t()) :: map()
+  def tunnel_response_cancel(request_id) do
+    %{
+      type: "tunnel_response_cancel",
+      request_id: request_id
+    }
+  end
+
   @doc """
   Builds a ws_close message (Bidirectional).
 
@@ -115,6 +115,27 @@ defmodule Burrow.Queries.RequestQuery do
     end
   end
 
+  @doc """
+  Aggregates a tunnel's requests that started at or after `since`.
+
+  Returns the request count, how many failed with a 5xx status, the bytes
+  sent both ways and the 50th, 95th and 99th percentile durations in
+  milliseconds (`nil` without completed requests).
+  """
+  def tunnel_totals(tunnel_id, since) do
+    Request
+    |> where([r], r.tunnel_id == ^tunnel_id and r.started_at >= ^since)
+    |> select([r], %{
+      count: count(r.id),
+      errors: filter(count(r.id), r.status >= 500),
+      bytes: sum(fragment("coalesce(?, 0) + coalesce(?, 0)", r.request_size, r.response_size)),
+      p50: fragment("percentile_cont(0.5) WITHIN GROUP (ORDER BY ?)", r.duration_ms),
+      p95: fragment("percentile_cont(0.95) WITHIN GROUP (ORDER BY ?)", r.duration_ms),
+      p99: fragment("percentile_cont(0.99) WITHIN GROUP (ORDER BY ?)", r.duration_ms)
+    })
+    |> Repo.one()
+  end
+
   @doc """
   Deletes all requests.
   """
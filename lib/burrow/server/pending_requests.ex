@@ -66,6 +66,20 @@ defmodule Burrow.Server.PendingRequests do
     GenServer.call(__MODULE__, {:complete, request_id, response})
   end
 
+  @doc """
+  Completes a pending request whose body follows as `tunnel_response_chunk`
+  messages, sending the status and headers to the caller.
+
+  ## Returns
+
+  - `{:ok, caller_pid}` - The process to send the body chunks to
+  - `{:error, :not_found}` - Request not found (already completed, cancelled, or timed out)
+  """
+  @spec start_stream(String.t(), map()) :: {:ok, pid()} | {:error, :not_found}
+  def start_stream(request_id, response) do
+    GenServer.call(__MODULE__, {:start_stream, request_id, response})
+  end
+
   @doc """
   Cancels a pending request without sending a response.
   """
@@ -132,23 +146,17 @@ defmodule Burrow.Server.PendingRequests do
 
   @impl true
   def handle_call({:complete, request_id, response}, _from, state) do
-    case Map.pop(state.requests, request_id) do
-      {nil, _} ->
-        {:reply, {:error, :not_found}, state}
-
-      {pending, requests} ->
-        Process.demonitor(pending.caller_ref, [:flush])
-        send(pending.caller_pid, {:tunnel_response, request_id, response})
-
-        by_tunnel =
-          Map.update(
-            state.by_tunnel,
-            pending.tunnel_id,
-            MapSet.new(),
-            &MapSet.delete(&1, request_id)
-          )
+    case deliver(state, request_id, response) do
+      {:ok, _caller_pid, state} -> {:reply, :ok, state}
+      {:error, :not_found} -> {:reply, {:error, :not_found}, state}
+    end
+  end
 
-        {:reply, :ok, %{state | requests: requests, by_tunnel: by_tunnel}}
+  @impl true
+  def handle_call({:start_stream, request_id, response}, _from, state) do
+    case deliver(state, request_id, response) do
+      {:ok, caller_pid, state} -> {:reply, {:ok, caller_pid}, state}
+      {:error, :not_found} -> {:reply, {:error, :not_found}, state}
     end
   end
 
@@ -205,6 +213,27 @@ defmodule Burrow.Server.PendingRequests do
 
   # Private functions
 
+  defp deliver(state, request_id, response) do
+    case Map.pop(state.requests, request_id) do
+      {nil, _} ->
+        {:error, :not_found}
+
+      {pending, requests} ->
+        Process.demonitor(pending.caller_ref, [:flush])
+        send(pending.caller_pid, {:tunnel_response, request_id, response})
+
+        by_tunnel =
+          Map.update(
+            state.by_tunnel,
+            pending.tunnel_id,
+            MapSet.new(),
+            &MapSet.delete(&1, request_id)
+          )
+
+        {:ok, pending.caller_pid, %{state | requests: requests, by_tunnel: by_tunnel}}
+    end
+  end
+
   defp remove_request(state, request_id) do
     case Map.pop(state.requests, request_id) do
       {nil, _} ->
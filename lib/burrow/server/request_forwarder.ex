@@ -30,6 +30,8 @@ defmodule Burrow.Server.RequestForwarder do
   alias Burrow.ULID
 
   @request_timeout_ms 30_000
+  # Longest gap between chunks of a streamed response before giving up
+  @stream_idle_timeout_ms 300_000
   @ws_upgrade_timeout_ms 10_000
   # Maximum request body size (10MB) - prevents memory exhaustion from large uploads
   @max_body_size 10 * 1024 * 1024
@@ -159,7 +161,12 @@ defmodule Burrow.Server.RequestForwarder do
       {:tunnel_response, ^request_id, response} ->
         duration_ms = System.monotonic_time(:millisecond) - start_time
         log_response(request_id, response, duration_ms)
-        build_response(conn, response)
+
+        if is_map(response) and Fields.get(response, :streaming) == true do
+          stream_response(conn, request_id, response, tunnel_info.connection_pid)
+        else
+          build_response(conn, response)
+        end
     after
       @request_timeout_ms ->
         duration_ms = System.monotonic_time(:millisecond) - start_time
@@ -289,29 +296,65 @@ defmodule Burrow.Server.RequestForwarder do
       ErrorPage.render(conn, status, reason: body)
     else
       # Pass through normal responses (including application errors like 404, 500)
-      # Filter out headers that Bandit should calculate
-      skip_headers = ["content-length", "transfer-encoding"]
-
-      conn =
-        Enum.reduce(headers, conn, fn
-          [key, value], acc ->
-            if String.downcase(key) in skip_headers,
-              do: acc,
-              else: put_resp_header(acc, key, value)
-
-          {key, value}, acc ->
-            if String.downcase(key) in skip_headers,
-              do: acc,
-              else: put_resp_header(acc, key, value)
-
-          _, acc ->
-            acc
-        end)
-
-      send_resp(conn, status, body || "")
+      conn
+      |> put_response_headers(headers)
+      |> send_resp(status, body || "")
+    end
+  end
+
+  # Relay a response whose body arrives as tunnel_response_chunk messages,
+  # e.g. Server-Sent Events, until the client sends the last chunk. When
+  # relaying stops early, the tunnel connection is told so it can stop the
+  # client streaming.
+  defp stream_response(conn, request_id, response, connection_pid) do
+    conn
+    |> put_response_headers(Fields.get(response, :headers, []))
+    |> send_chunked(Fields.get(response, :status, 200))
+    |> relay_chunks(request_id, connection_pid)
+  end
+
+  defp relay_chunks(conn, request_id, connection_pid) do
+    receive do
+      {:tunnel_response_chunk, ^request_id, data, is_last} ->
+        case chunk(conn, data) do
+          {:ok, conn} when is_last ->
+            conn
+
+          {:ok, conn} ->
+            relay_chunks(conn, request_id, connection_pid)
+
+          # The browser went away
+          {:error, _reason} ->
+            send(connection_pid, {:cancel_stream, request_id})
+            conn
+        end
+    after
+      @stream_idle_timeout_ms ->
+        send(connection_pid, {:cancel_stream, request_id})
+        conn
     end
   end
 
+  # Filter out headers that Bandit should calculate
+  defp put_response_headers(conn, headers) do
+    skip_headers = ["content-length", "transfer-encoding"]
+
+    Enum.reduce(headers, conn, fn
+      [key, value], acc ->
+        if String.downcase(key) in skip_headers,
+          do: acc,
+          else: put_resp_header(acc, key, value)
+
+      {key, value}, acc ->
+        if String.downcase(key) in skip_headers,
+          do: acc,
+          else: put_resp_header(acc, key, value)
+
+      _, acc ->
+        acc
+    end)
+  end
+
   # Detect if this is a tunnel client error (not an application error)
   defp is_tunnel_error?(body) when is_binary(body) do
     String.starts_with?(body, "Bad Gateway:") or
@@ -21,10 +21,12 @@ defmodule Burrow.Server.TunnelRegistry do
           tunnel_id: String.t(),
           subdomain: String.t(),
           user_id: String.t() | nil,
+          session_id: String.t() | nil,
           connection_pid: pid(),
           stream_ref: reference(),
           local_host: String.t(),
           local_port: pos_integer(),
+          tags: [String.t()],
           registered_at: DateTime.t()
         }
 
@@ -53,10 +55,12 @@ defmodule Burrow.Server.TunnelRegistry do
   - `:tunnel_id` - Unique identifier for the tunnel
   - `:subdomain` - Requested subdomain
   - `:user_id` - User ID (from API token)
+  - `:session_id` - Client session the tunnel may be handed over within (optional)
   - `:connection_pid` - PID of the connection process
   - `:stream_ref` - Reference to the HTTP/2 stream
   - `:local_host` - Client's local target host
   - `:local_port` - Client's local target port
+  - `:tags` - Labels dashboards can filter tunnels by (optional)
 
   ## Returns
 
@@ -76,6 +80,33 @@ defmodule Burrow.Server.TunnelRegistry do
     GenServer.cast(__MODULE__, {:unregister, subdomain})
   end
 
+  @doc """
+  Unregisters a tunnel only if it is still routed to `connection_pid`, so a
+  connection closing after its tunnels were adopted leaves them alone.
+  """
+  @spec unregister(String.t(), pid()) :: :ok
+  def unregister(subdomain, connection_pid) do
+    GenServer.cast(__MODULE__, {:unregister, subdomain, connection_pid})
+  end
+
+  @doc """
+  Routes a tunnel on this node to a new connection of the same client
+  session, e.g. after the client reconnected before the old connection was
+  noticed to be gone. Tunnels of other sessions, such as another machine
+  using the same account, are never handed over.
+
+  ## Returns
+
+  - `{:ok, tunnel_info}` - The tunnel, now owned by `connection_pid`
+  - `{:error, :not_found}` - No such tunnel for this user and session on
+    another connection
+  """
+  @spec adopt(String.t(), String.t(), String.t() | nil, pid()) ::
+          {:ok, tunnel_info()} | {:error, :not_found}
+  def adopt(subdomain, user_id, session_id, connection_pid) do
+    GenServer.call(__MODULE__, {:adopt, subdomain, user_id, session_id, connection_pid})
+  end
+
   @doc """
   Looks up a tunnel by subdomain across the cluster.
 
@@ -215,6 +246,28 @@ defmodule Burrow.Server.TunnelRegistry do
     end
   end
 
+  @impl true
+  def handle_call({:adopt, _subdomain, _user_id, nil, _connection_pid}, _from, state) do
+    {:reply, {:error, :not_found}, state}
+  end
+
+  def handle_call({:adopt, subdomain, user_id, session_id, connection_pid}, _from, state) do
+    case Map.fetch(state.tunnels, subdomain) do
+      {:ok, %{user_id: ^user_id, session_id: ^session_id, connection_pid: old_pid} = info}
+      when old_pid != connection_pid ->
+        :pg.leave(@pg_scope, {:tunnel, subdomain}, old_pid)
+        :ok = :pg.join(@pg_scope, {:tunnel, subdomain}, connection_pid)
+        Process.monitor(connection_pid)
+
+        info = %{info | connection_pid: connection_pid, stream_ref: make_ref()}
+        Logger.info("[TunnelRegistry] Moved tunnel #{subdomain} to a new connection")
+        {:reply, {:ok, info}, %{state | tunnels: Map.put(state.tunnels, subdomain, info)}}
+
+      _ ->
+        {:reply, {:error, :not_found}, state}
+    end
+  end
+
   @impl true
   def handle_call({:lookup_local, subdomain}, _from, state) do
     case Map.fetch(state.tunnels, subdomain) do
@@ -252,6 +305,13 @@ defmodule Burrow.Server.TunnelRegistry do
     {:noreply, remove_tunnel(state, subdomain)}
   end
 
+  def handle_cast({:unregister, subdomain, connection_pid}, state) do
+    case Map.get(state.tunnels, subdomain) do
+      %{connection_pid: ^connection_pid} -> {:noreply, remove_tunnel(state, subdomain)}
+      _ -> {:noreply, state}
+    end
+  end
+
   @impl true
   def handle_info({:DOWN, _ref, :process, pid, _reason}, state) do
     # Remove all tunnels for this connection
@@ -271,10 +331,12 @@ defmodule Burrow.Server.TunnelRegistry do
       tunnel_id: params.tunnel_id,
       subdomain: params.subdomain,
       user_id: params[:user_id],
+      session_id: params[:session_id],
       connection_pid: params.connection_pid,
       stream_ref: params.stream_ref,
       local_host: params.local_host,
       local_port: params.local_port,
+      tags: params[:tags] || [],
       registered_at: DateTime.utc_now()
     }
   end
@@ -47,6 +47,8 @@ defmodule Burrow.Server.TunnelSocket do
   defstruct status: :awaiting_registration,
             tunnels: %{},
             tcp_tunnels: %{},
+            response_streams: %{},
+            registrations: %{},
             user_id: nil,
             last_pong_at: nil
 
@@ -93,6 +95,19 @@ defmodule Burrow.Server.TunnelSocket do
     {:push, {:text, json}, state}
   end
 
+  # The request process relaying a streamed response lost its browser
+  def handle_info({:cancel_stream, request_id}, state) do
+    cancel_stream(state, request_id)
+  end
+
+  # The request process relaying a streamed response went away
+  def handle_info({:DOWN, ref, :process, _pid, _reason}, state) do
+    case Enum.find(state.response_streams, fn {_, {_pid, stream_ref}} -> stream_ref == ref end) do
+      {request_id, _} -> cancel_stream(state, request_id)
+      nil -> {:ok, state}
+    end
+  end
+
   def handle_info(:send_heartbeat, state) do
     now = System.monotonic_time(:millisecond)
     time_since_pong = now - state.last_pong_at
@@ -118,9 +133,10 @@ defmodule Burrow.Server.TunnelSocket do
 
   @impl WebSock
   def terminate(_reason, state) do
-    # Unregister all HTTP tunnels for this connection
+    # Unregister all HTTP tunnels for this connection, unless a newer
+    # connection of the client has taken them over
     for {subdomain, _tunnel} <- state.tunnels do
-      TunnelRegistry.unregister(subdomain)
+      TunnelRegistry.unregister(subdomain, self())
     end
 
     # Stop all TCP listeners for this connection
@@ -132,46 +148,43 @@ defmodule Burrow.Server.TunnelSocket do
       TCPRegistry.unregister_tunnel(tcp_tunnel_id)
     end
 
+    # End any streamed responses still being relayed
+    for {request_id, {caller_pid, _ref}} <- state.response_streams do
+      send(caller_pid, {:tunnel_response_chunk, request_id, "", true})
+    end
+
     :ok
   end
 
   # Message Handlers
 
   defp handle_message(:register_tunnel, message, state) do
-    case process_registration(message, state) do
+    registration_id = registration_id(message)
+
+    # A client retrying a registration it got no answer for gets the tunnel
+    # registered the first time rather than a second one
+    result =
+      case registration_id && Map.fetch(state.registrations, registration_id) do
+        {:ok, tunnel_info} -> {:ok, tunnel_info, state}
+        _ -> process_registration(message, state)
+      end
+
+    case result do
       {:ok, tunnel_info, new_state} ->
         response =
-          Message.tunnel_registered(
-            tunnel_info.tunnel_id,
-            tunnel_info.subdomain,
-            tunnel_info.full_url
-          )
+          tunnel_info.tunnel_id
+          |> Message.tunnel_registered(tunnel_info.subdomain, tunnel_info.full_url)
+          |> with_registration_id(registration_id)
 
+        new_state = remember_registration(new_state, registration_id, tunnel_info)
         {:reply, :ok, {:text, Codec.encode!(response)}, new_state}
 
-      {:error, :invalid_token} ->
-        error = Message.error("invalid_token", "Invalid or missing API token")
-        {:reply, :ok, {:text, Codec.encode!(error)}, state}
-
-      {:error, :expired_token} ->
-        error = Message.error("expired_token", "API token has expired")
-        {:reply, :ok, {:text, Codec.encode!(error)}, state}
-
-      {:error, :subdomain_taken} ->
+      {:error, reason} ->
         error =
-          Message.error(
-            "subdomain_taken",
-            "Requested subdomain is already in use by another user"
-          )
-
-        {:reply, :ok, {:text, Codec.encode!(error)}, state}
+          reason
+          |> registration_error()
+          |> with_registration_id(registration_id)
 
-      {:error, :subdomain_reserved} ->
-        error = Message.error("subdomain_reserved", "This subdomain is reserved by another user")
-        {:reply, :ok, {:text, Codec.encode!(error)}, state}
-
-      {:error, reason} ->
-        error = Message.error("registration_failed", "Registration failed: #{inspect(reason)}")
         {:reply, :ok, {:text, Codec.encode!(error)}, state}
     end
   end
@@ -183,12 +196,50 @@ defmodule Burrow.Server.TunnelSocket do
       status: message.status,
       headers: message.headers,
       body: Map.get(message, :body),
-      body_encoding: Map.get(message, :body_encoding)
+      body_encoding: Map.get(message, :body_encoding),
+      streaming: Map.get(message, :streaming) == true
     }
 
-    case PendingRequests.complete(request_id, response_data) do
-      :ok -> {:ok, state}
-      {:error, :not_found} -> {:ok, state}
+    if response_data.streaming do
+      # The body follows as tunnel_response_chunk messages
+      case PendingRequests.start_stream(request_id, response_data) do
+        {:ok, caller_pid} ->
+          ref = Process.monitor(caller_pid)
+          streams = Map.put(state.response_streams, request_id, {caller_pid, ref})
+          {:ok, %{state | response_streams: streams}}
+
+        {:error, :not_found} ->
+          {:ok, state}
+      end
+    else
+      case PendingRequests.complete(request_id, response_data) do
+        :ok -> {:ok, state}
+        {:error, :not_found} -> {:ok, state}
+      end
+    end
+  end
+
+  defp handle_message(:tunnel_response_chunk, message, state) do
+    request_id = message.request_id
+    is_last = Map.get(message, :is_last) == true
+
+    case Map.fetch(state.response_streams, request_id) do
+      {:ok, {caller_pid, ref}} ->
+        data = Fields.decode_body(Map.get(message, :data), Map.get(message, :data_encoding))
+        send(caller_pid, {:tunnel_response_chunk, request_id, data || "", is_last})
+
+        streams =
+          if is_last do
+            Process.demonitor(ref, [:flush])
+            Map.delete(state.response_streams, request_id)
+          else
+            state.response_streams
+          end
+
+        {:ok, %{state | response_streams: streams}}
+
+      :error ->
+        {:ok, state}
     end
   end
 
@@ -314,6 +365,89 @@ defmodule Burrow.Server.TunnelSocket do
     {:ok, state}
   end
 
+  defp handle_message(:batch, %{messages: messages}, state) when is_list(messages) do
+    # Several client messages sent as one frame; handle them in order and
+    # push any replies together
+    {replies, state} =
+      Enum.reduce(messages, {[], state}, fn message, {replies, state} ->
+        case handle_batched(message, state) do
+          {:reply, :ok, frame, state} -> {[frame | replies], state}
+          {:push, frames, state} -> {Enum.reverse(List.wrap(frames)) ++ replies, state}
+          {:ok, state} -> {replies, state}
+        end
+      end)
+
+    case replies do
+      [] -> {:ok, state}
+      replies -> {:push, Enum.reverse(replies), state}
+    end
+  end
+
+  # Batches hold plain messages only; a nested one is answered with an error
+  defp handle_batched(message, state) do
+    case Message.type(message) do
+      :batch ->
+        error = Message.error("nested_batch", "A batch can't contain another batch")
+        {:reply, :ok, {:text, Codec.encode!(error)}, state}
+
+      type ->
+        handle_message(type, message, state)
+    end
+  end
+
+  defp handle_message(:query_tunnels, message, state) do
+    with :ok <- check_protocol_version(message),
+         {:ok, api_token} <- verify_token(Fields.get(message, :token)) do
+      adopted =
+        adopt_tunnels(
+          api_token.user_id,
+          session_id(message),
+          Fields.get(message, :tunnels) || []
+        )
+
+      tunnels =
+        Enum.reduce(adopted, state.tunnels, fn info, tunnels ->
+          Map.put(tunnels, info.subdomain, %{
+            tunnel_id: info.tunnel_id,
+            subdomain: info.subdomain,
+            full_url: build_url(info.subdomain),
+            local_host: info.local_host,
+            local_port: info.local_port
+          })
+        end)
+
+      listed =
+        Enum.map(adopted, fn info ->
+          %{
+            tunnel_id: info.tunnel_id,
+            subdomain: info.subdomain,
+            full_url: build_url(info.subdomain),
+            local_port: info.local_port
+          }
+        end)
+
+      new_state = %{state | status: :connected, user_id: api_token.user_id, tunnels: tunnels}
+      {:reply, :ok, {:text, Codec.encode!(Message.tunnel_list(listed))}, new_state}
+    else
+      {:error, :protocol_version_too_old} ->
+        error =
+          Message.error(
+            "protocol_version_too_old",
+            "This server requires a newer version of the burrow client"
+          )
+
+        {:reply, :ok, {:text, Codec.encode!(error)}, state}
+
+      {:error, :expired_token} ->
+        error = Message.error("expired_token", "API token has expired")
+        {:reply, :ok, {:text, Codec.encode!(error)}, state}
+
+      {:error, _} ->
+        error = Message.error("invalid_token", "Invalid or missing API token")
+        {:reply, :ok, {:text, Codec.encode!(error)}, state}
+    end
+  end
+
   defp handle_message(:unknown, _message, state) do
     error = Message.error("unknown_message", "Unknown message type")
     {:reply, :ok, {:text, Codec.encode!(error)}, state}
@@ -335,7 +469,8 @@ defmodule Burrow.Server.TunnelSocket do
   defp process_registration(message, state) do
     token = Fields.get(message, :token)
 
-    with {:ok, api_token} <- verify_token(token),
+    with :ok <- check_protocol_version(message),
+         {:ok, api_token} <- verify_token(token),
          {:ok, subdomain} <- assign_subdomain(api_token.user_id, message),
          {:ok, tunnel_id} <- register_tunnel(subdomain, api_token.user_id, message) do
       tunnel_info = %{
@@ -357,6 +492,18 @@ defmodule Burrow.Server.TunnelSocket do
     end
   end
 
+  # Clients from before the protocol version was sent count as version 0
+  defp check_protocol_version(message) do
+    version = Fields.get(message, :protocol_version) || 0
+    minimum = Application.get_env(:burrow, :server, [])[:min_protocol_version] || 0
+
+    if is_integer(version) and version >= minimum do
+      :ok
+    else
+      {:error, :protocol_version_too_old}
+    end
+  end
+
   defp verify_token(nil), do: {:error, :invalid_token}
   defp verify_token(""), do: {:error, :invalid_token}
 
@@ -412,10 +559,12 @@ defmodule Burrow.Server.TunnelSocket do
       tunnel_id: tunnel_id,
       subdomain: subdomain,
       user_id: user_id,
+      session_id: session_id(message),
       connection_pid: self(),
       stream_ref: stream_ref,
       local_host: Fields.get(message, :local_host) || "localhost",
-      local_port: Fields.get(message, :local_port) || 80
+      local_port: Fields.get(message, :local_port) || 80,
+      tags: parse_tags(Fields.get(message, :tags))
     }
 
     case TunnelRegistry.register(params) do
@@ -424,6 +573,91 @@ defmodule Burrow.Server.TunnelSocket do
     end
   end
 
+  # Take over the tunnels on this node that the same client session registered
+  # and that match a query by local port and requested subdomain (the user's
+  # default when none was requested)
+  defp adopt_tunnels(_user_id, nil, _queries), do: []
+
+  defp adopt_tunnels(user_id, session_id, queries) when is_list(queries) do
+    owned = TunnelRegistry.list_by_user(user_id)
+    default = Accounts.generate_default_subdomain(user_id)
+
+    Enum.flat_map(queries, fn query ->
+      port = Fields.get(query, :local_port)
+      wanted = Fields.get(query, :requested_subdomain) || default
+
+      with %{subdomain: subdomain} <-
+             Enum.find(owned, &(&1.local_port == port and &1.subdomain == wanted)),
+           {:ok, info} <- TunnelRegistry.adopt(subdomain, user_id, session_id, self()) do
+        [info]
+      else
+        _ -> []
+      end
+    end)
+  end
+
+  defp adopt_tunnels(_user_id, _session_id, _queries), do: []
+
+  # Forget a streamed response and tell the client to stop sending it
+  defp cancel_stream(state, request_id) do
+    case Map.pop(state.response_streams, request_id) do
+      {{_caller_pid, ref}, streams} ->
+        Process.demonitor(ref, [:flush])
+        cancel = Message.tunnel_response_cancel(request_id)
+        {:push, {:text, Codec.encode!(cancel)}, %{state | response_streams: streams}}
+
+      {nil, _} ->
+        {:ok, state}
+    end
+  end
+
+  defp registration_error(:invalid_token),
+    do: Message.error("invalid_token", "Invalid or missing API token")
+
+  defp registration_error(:expired_token),
+    do: Message.error("expired_token", "API token has expired")
+
+  defp registration_error(:subdomain_taken),
+    do: Message.error("subdomain_taken", "Requested subdomain is already in use by another user")
+
+  defp registration_error(:protocol_version_too_old),
+    do:
+      Message.error(
+        "protocol_version_too_old",
+        "This server requires a newer version of the burrow client"
+      )
+
+  defp registration_error(:subdomain_reserved),
+    do: Message.error("subdomain_reserved", "This subdomain is reserved by another user")
+
+  defp registration_error(reason),
+    do: Message.error("registration_failed", "Registration failed: #{inspect(reason)}")
+
+  defp registration_id(message) do
+    case Fields.get(message, :registration_id) do
+      id when is_binary(id) and id != "" -> id
+      _ -> nil
+    end
+  end
+
+  defp with_registration_id(response, nil), do: response
+  defp with_registration_id(response, id), do: Map.put(response, :registration_id, id)
+
+  defp remember_registration(state, nil, _tunnel_info), do: state
+
+  defp remember_registration(state, id, tunnel_info),
+    do: %{state | registrations: Map.put(state.registrations, id, tunnel_info)}
+
+  defp session_id(message) do
+    case Fields.get(message, :session_id) do
+      id when is_binary(id) and id != "" -> id
+      _ -> nil
+    end
+  end
+
+  defp parse_tags(tags) when is_list(tags), do: Enum.filter(tags, &is_binary/1)
+  defp parse_tags(_), do: []
+
   defp build_url(subdomain) do
     base_domain = Application.get_env(:burrow, :server, [])[:base_domain] || "localhost"
     "https://#{subdomain}.#{base_domain}"
@@ -0,0 +1,24 @@
+defmodule Burrow.Server.Web.AccountController do
+  @moduledoc """
+  API controller for the authenticated account.
+
+  Requires token-based authentication via the ApiAuth plug.
+  """
+
+  use Phoenix.Controller, formats: [:json]
+
+  @doc """
+  Shows the account the API token belongs to.
+
+  GET /api/account
+  """
+  def show(conn, _params) do
+    user = conn.assigns.current_user
+
+    json(conn, %{
+      username: user.username,
+      display_name: user.display_name,
+      created_at: user.inserted_at
+    })
+  end
+end
@@ -0,0 +1,81 @@
+defmodule Burrow.Server.Web.TunnelController do
+  @moduledoc """
+  API controller for the current user's tunnels.
+
+  Requires token-based authentication via the ApiAuth plug.
+  """
+
+  use Phoenix.Controller, formats: [:json]
+
+  alias Burrow.Queries.RequestQuery
+  alias Burrow.Server.TunnelRegistry
+
+  @default_window_seconds 60 * 60
+  @max_window_seconds 7 * 24 * 60 * 60
+
+  @doc """
+  Shows traffic statistics for one of the user's tunnels connected to this
+  node, over the last `since` seconds (default one hour).
+
+  GET /api/tunnels/:id/stats
+  """
+  def stats(conn, %{"id" => tunnel_id} = params) do
+    user = conn.assigns.current_user
+
+    with {:ok, window} <- parse_window(params["since"]),
+         true <- owns_tunnel?(user.id, tunnel_id) do
+      since = DateTime.add(DateTime.utc_now(), -window, :second)
+      totals = RequestQuery.tunnel_totals(tunnel_id, since)
+
+      json(conn, Map.put(summarize(totals, window), :since_seconds, window))
+    else
+      :error ->
+        conn
+        |> put_status(:bad_request)
+        |> json(%{
+          error: %{
+            code: "invalid_since",
+            message: "since must be a number of seconds up to #{@max_window_seconds}"
+          }
+        })
+
+      false ->
+        conn
+        |> put_status(:not_found)
+        |> json(%{error: %{code: "not_found", message: "Tunnel not found"}})
+    end
+  end
+
+  @doc """
+  Turns a tunnel's request totals over `window` seconds into rates.
+  """
+  def summarize(totals, window) do
+    count = totals.count
+
+    %{
+      requests_per_minute: count / (window / 60),
+      latency_ms: %{
+        p50: totals.p50 || 0.0,
+        p95: totals.p95 || 0.0,
+        p99: totals.p99 || 0.0
+      },
+      error_rate: if(count > 0, do: totals.errors / count, else: 0.0),
+      bytes_per_second: (totals.bytes || 0) / window
+    }
+  end
+
+  defp parse_window(nil), do: {:ok, @default_window_seconds}
+
+  defp parse_window(since) do
+    case Integer.parse(since) do
+      {seconds, ""} when seconds > 0 and seconds <= @max_window_seconds -> {:ok, seconds}
+      _ -> :error
+    end
+  end
+
+  defp owns_tunnel?(user_id, tunnel_id) do
+    user_id
+    |> TunnelRegistry.list_by_user()
+    |> Enum.any?(&(&1.tunnel_id == tunnel_id))
+  end
+end
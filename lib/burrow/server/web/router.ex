@@ -66,8 +66,10 @@ defmodule Burrow.Server.Web.Router do
   scope "/api", Burrow.Server.Web do
     pipe_through([:api, :api_auth])
 
+    get("/account", AccountController, :show)
     get("/subdomains", SubdomainController, :index)
     delete("/subdomains/:subdomain", SubdomainController, :delete)
+    get("/tunnels/:id/stats", TunnelController, :stats)
   end
 
   # Oban Web dashboard (protected)
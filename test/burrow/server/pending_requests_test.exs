@@ -45,6 +45,23 @@ defmodule Burrow.Server.PendingRequestsTest do
     end
   end
 
+  describe "start_stream/2" do
+    test "sends the response head to the caller and returns the caller" do
+      :ok = PendingRequests.register("req-1", "tunnel-1", self())
+
+      response = %{status: 200, headers: [], streaming: true}
+      assert {:ok, pid} = PendingRequests.start_stream("req-1", response)
+      assert pid == self()
+
+      assert_receive {:tunnel_response, "req-1", ^response}
+      assert PendingRequests.count() == 0
+    end
+
+    test "returns error for non-existent request" do
+      assert {:error, :not_found} = PendingRequests.start_stream("nonexistent", %{})
+    end
+  end
+
   describe "cancel/1" do
     test "cancels a pending request" do
       :ok = PendingRequests.register("req-1", "tunnel-1", self())
@@ -223,4 +223,60 @@ defmodule Burrow.Server.TunnelRegistryTest do
       assert TunnelRegistry.count() == 5
     end
   end
+
+  describe "adopt/4" do
+    setup do
+      test_pid = self()
+
+      old_connection =
+        spawn(fn ->
+          {:ok, _} =
+            TunnelRegistry.register(%{
+              tunnel_id: "tid-1",
+              subdomain: "kept",
+              user_id: "user-1",
+              session_id: "session-1",
+              connection_pid: self(),
+              stream_ref: make_ref(),
+              local_host: "localhost",
+              local_port: 3000
+            })
+
+          send(test_pid, :registered)
+
+          receive do
+            :exit -> :ok
+          end
+        end)
+
+      assert_receive :registered, 1000
+      on_exit(fn -> Process.exit(old_connection, :kill) end)
+      %{old_connection: old_connection}
+    end
+
+    test "moves a tunnel to a new connection of the same session", %{
+      old_connection: old_connection
+    } do
+      assert {:ok, info} = TunnelRegistry.adopt("kept", "user-1", "session-1", self())
+      assert info.connection_pid == self()
+      assert {:ok, %{connection_pid: pid}} = TunnelRegistry.lookup("kept")
+      assert pid == self()
+
+      # The old connection closing no longer removes the tunnel
+      Process.exit(old_connection, :kill)
+      Process.sleep(50)
+      assert {:ok, _} = TunnelRegistry.lookup("kept")
+    end
+
+    test "leaves another session's tunnel alone", %{old_connection: old_connection} do
+      assert {:error, :not_found} = TunnelRegistry.adopt("kept", "user-1", "session-2", self())
+      assert {:error, :not_found} = TunnelRegistry.adopt("kept", "user-1", nil, self())
+      assert {:error, :not_found} = TunnelRegistry.adopt("kept", "user-2", "session-1", self())
+      assert {:ok, %{connection_pid: ^old_connection}} = TunnelRegistry.lookup("kept")
+    end
+
+    test "returns not_found for an unknown subdomain" do
+      assert {:error, :not_found} = TunnelRegistry.adopt("missing", "user-1", "session-1", self())
+    end
+  end
 end
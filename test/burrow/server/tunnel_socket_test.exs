@@ -130,6 +130,86 @@ defmodule Burrow.Server.TunnelSocketTest do
     end
   end
 
+  describe "handle_in/2 - streamed responses" do
+    test "relays chunks to the caller until the last one" do
+      :ok = Burrow.Server.PendingRequests.register("req-1", "tunnel-1", self())
+      state = start_stream("req-1")
+      assert_receive {:tunnel_response, "req-1", %{streaming: true}}
+
+      {:ok, state} = handle_text(response_chunk("req-1", 0, "hi", false), state)
+      assert_receive {:tunnel_response_chunk, "req-1", "hi", false}
+
+      {:ok, state} = handle_text(response_chunk("req-1", 1, "", true), state)
+      assert_receive {:tunnel_response_chunk, "req-1", "", true}
+      assert state.response_streams == %{}
+    end
+
+    test "tells the client to stop when the browser disconnects" do
+      :ok = Burrow.Server.PendingRequests.register("req-1", "tunnel-1", self())
+      state = start_stream("req-1")
+
+      {:push, {:text, json}, state} = TunnelSocket.handle_info({:cancel_stream, "req-1"}, state)
+
+      cancel = Codec.decode!(json)
+      assert cancel.type == "tunnel_response_cancel"
+      assert cancel.request_id == "req-1"
+      assert state.response_streams == %{}
+
+      # Chunks already on their way are dropped
+      {:ok, _state} = handle_text(response_chunk("req-1", 0, "late", false), state)
+      refute_receive {:tunnel_response_chunk, "req-1", _, _}
+    end
+
+    test "tells the client to stop when the caller exits" do
+      caller =
+        spawn(fn ->
+          receive do
+            :stop -> :ok
+          end
+        end)
+
+      :ok = Burrow.Server.PendingRequests.register("req-1", "tunnel-1", caller)
+      state = start_stream("req-1")
+
+      send(caller, :stop)
+      assert_receive {:DOWN, _ref, :process, ^caller, _reason} = down
+
+      {:push, {:text, json}, state} = TunnelSocket.handle_info(down, state)
+      assert Codec.decode!(json).type == "tunnel_response_cancel"
+      assert state.response_streams == %{}
+    end
+  end
+
+  describe "handle_in/2 - batch" do
+    test "handles each message and pushes the replies together" do
+      {:ok, state} = TunnelSocket.init([])
+      batch = %{type: "batch", messages: [%{type: "heartbeat"}, %{type: "heartbeat"}]}
+
+      {:push, frames, _state} = handle_text(batch, state)
+
+      assert [{:text, first}, {:text, second}] = frames
+      assert Codec.decode!(first).type == "heartbeat"
+      assert Codec.decode!(second).type == "heartbeat"
+    end
+
+    test "rejects a nested batch without dropping the rest" do
+      {:ok, state} = TunnelSocket.init([])
+
+      batch = %{
+        type: "batch",
+        messages: [
+          %{type: "batch", messages: [%{type: "heartbeat"}]},
+          %{type: "heartbeat"}
+        ]
+      }
+
+      {:push, [{:text, error}, {:text, heartbeat}], _state} = handle_text(batch, state)
+
+      assert Codec.decode!(error).code == "nested_batch"
+      assert Codec.decode!(heartbeat).type == "heartbeat"
+    end
+  end
+
   describe "handle_in/2 - heartbeat" do
     test "responds to heartbeat" do
       {:ok, state} = TunnelSocket.init([])
@@ -209,4 +289,162 @@ defmodule Burrow.Server.TunnelSocketTest do
       assert {:error, :not_found} = Burrow.Server.TunnelRegistry.lookup(reg.subdomain)
     end
   end
+
+  describe "handle_in/2 - registration retries" do
+    test "echoes the registration id with an error" do
+      {:ok, state} = TunnelSocket.init([])
+
+      message = register_tunnel("not-a-token", "reg-1", 3000)
+
+      {:reply, :ok, {:text, response_json}, new_state} =
+        TunnelSocket.handle_in({Codec.encode!(message), [opcode: :text]}, state)
+
+      response = Codec.decode!(response_json)
+      assert response.type == "error"
+      assert response.code == "invalid_token"
+      assert response.registration_id == "reg-1"
+      assert new_state.registrations == %{}
+    end
+
+    @tag :database
+    test "answers a repeated registration with the tunnel already registered" do
+      :ok = Ecto.Adapters.SQL.Sandbox.checkout(Burrow.Repo)
+      {:ok, user} = Burrow.Accounts.create_user(%{username: "retrier"})
+      {:ok, _token, token} = Burrow.Accounts.create_api_token(user.id, %{name: "test"})
+
+      {:ok, state} = TunnelSocket.init([])
+      message = Codec.encode!(register_tunnel(token, "reg-1", 3000))
+
+      {:reply, :ok, {:text, first_json}, state} =
+        TunnelSocket.handle_in({message, [opcode: :text]}, state)
+
+      {:reply, :ok, {:text, second_json}, state} =
+        TunnelSocket.handle_in({message, [opcode: :text]}, state)
+
+      first = Codec.decode!(first_json)
+      second = Codec.decode!(second_json)
+      assert first.type == "tunnel_registered"
+      assert first.registration_id == "reg-1"
+      assert second == first
+      assert map_size(state.tunnels) == 1
+      assert length(Burrow.Server.TunnelRegistry.list_by_user(user.id)) == 1
+    end
+  end
+
+  describe "handle_in/2 - query_tunnels" do
+    test "rejects an invalid token without handing over tunnels" do
+      {:ok, state} = TunnelSocket.init([])
+
+      message = query_tunnels("not-a-token", "session-1", [%{local_port: 3000}])
+
+      {:reply, :ok, {:text, response_json}, new_state} =
+        TunnelSocket.handle_in({Codec.encode!(message), [opcode: :text]}, state)
+
+      assert Codec.decode!(response_json).type == "error"
+      assert new_state.tunnels == %{}
+    end
+
+    @tag :database
+    test "hands over only tunnels of the same session" do
+      :ok = Ecto.Adapters.SQL.Sandbox.checkout(Burrow.Repo)
+      {:ok, user} = Burrow.Accounts.create_user(%{username: "adopter"})
+      {:ok, _token, token} = Burrow.Accounts.create_api_token(user.id, %{name: "test"})
+
+      test_pid = self()
+
+      tunnels = [{"mine", "session-1", 3000}, {"theirs", "session-2", 4000}]
+
+      for {subdomain, session_id, port} <- tunnels do
+        spawn(fn ->
+          {:ok, _} =
+            Burrow.Server.TunnelRegistry.register(%{
+              tunnel_id: "tid-#{subdomain}",
+              subdomain: subdomain,
+              user_id: user.id,
+              session_id: session_id,
+              connection_pid: self(),
+              stream_ref: make_ref(),
+              local_host: "localhost",
+              local_port: port
+            })
+
+          send(test_pid, :registered)
+          Process.sleep(:infinity)
+        end)
+
+        assert_receive :registered, 1000
+      end
+
+      {:ok, state} = TunnelSocket.init([])
+
+      message =
+        query_tunnels(token, "session-1", [
+          %{local_port: 3000, requested_subdomain: "mine"},
+          %{local_port: 4000, requested_subdomain: "theirs"}
+        ])
+
+      {:reply, :ok, {:text, response_json}, new_state} =
+        TunnelSocket.handle_in({Codec.encode!(message), [opcode: :text]}, state)
+
+      response = Codec.decode!(response_json)
+      assert response.type == "tunnel_list"
+      assert Enum.map(response.tunnels, & &1.subdomain) == ["mine"]
+      assert Map.keys(new_state.tunnels) == ["mine"]
+      assert {:ok, %{connection_pid: pid}} = Burrow.Server.TunnelRegistry.lookup("theirs")
+      assert pid != self()
+    end
+  end
+
+  defp start_stream(request_id) do
+    {:ok, state} = TunnelSocket.init([])
+
+    head = %{
+      type: "tunnel_response",
+      request_id: request_id,
+      status: 200,
+      headers: [["content-type", "text/event-stream"]],
+      streaming: true
+    }
+
+    {:ok, state} = handle_text(head, state)
+    assert Map.has_key?(state.response_streams, request_id)
+    state
+  end
+
+  defp response_chunk(request_id, sequence, data, is_last) do
+    %{
+      type: "tunnel_response_chunk",
+      request_id: request_id,
+      sequence: sequence,
+      data: Base.encode64(data),
+      data_encoding: "base64",
+      is_last: is_last
+    }
+  end
+
+  defp handle_text(message, state) do
+    TunnelSocket.handle_in({Codec.encode!(message), [opcode: :text]}, state)
+  end
+
+  defp register_tunnel(token, registration_id, local_port) do
+    %{
+      type: "register_tunnel",
+      token: token,
+      session_id: "session-1",
+      registration_id: registration_id,
+      local_host: "localhost",
+      local_port: local_port,
+      protocol_version: 1
+    }
+  end
+
+  defp query_tunnels(token, session_id, tunnels) do
+    %{
+      type: "query_tunnels",
+      token: token,
+      session_id: session_id,
+      protocol_version: 1,
+      tunnels: tunnels
+    }
+  end
 end
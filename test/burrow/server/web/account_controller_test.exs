@@ -0,0 +1,52 @@
+defmodule Burrow.Server.Web.AccountControllerTest do
+  use ExUnit.Case, async: false
+
+  import Plug.Test
+  import Plug.Conn
+
+  alias Burrow.Schemas.User
+  alias Burrow.Server.Dispatcher
+  alias Burrow.Server.Web.AccountController
+
+  setup do
+    start_supervised!({Burrow.Server.TunnelRegistry, name: Burrow.Server.TunnelRegistry})
+    start_supervised!({Burrow.Server.PendingRequests, name: Burrow.Server.PendingRequests})
+    start_supervised!(Burrow.Server.Web.Endpoint)
+
+    Application.put_env(:burrow, :server, base_domain: "burrow.test")
+
+    :ok
+  end
+
+  describe "GET /api/account" do
+    test "shows the account the token belongs to" do
+      user = %User{
+        username: "alice",
+        display_name: "Alice",
+        inserted_at: ~U[2024-01-02 03:04:05Z]
+      }
+
+      conn =
+        conn(:get, "/api/account")
+        |> assign(:current_user, user)
+        |> AccountController.show(%{})
+
+      assert conn.status == 200
+
+      assert Jason.decode!(conn.resp_body) == %{
+               "username" => "alice",
+               "display_name" => "Alice",
+               "created_at" => "2024-01-02T03:04:05Z"
+             }
+    end
+
+    test "rejects requests without a token" do
+      conn = conn(:get, "/api/account")
+      conn = %{conn | host: "burrow.test"}
+      conn = Dispatcher.call(conn, Dispatcher.init([]))
+
+      assert conn.status == 401
+      assert %{"error" => %{"code" => "missing_token"}} = Jason.decode!(conn.resp_body)
+    end
+  end
+end
@@ -0,0 +1,63 @@
+defmodule Burrow.Server.Web.TunnelControllerTest do
+  use ExUnit.Case, async: false
+
+  import Plug.Test
+  import Plug.Conn
+
+  alias Burrow.Schemas.User
+  alias Burrow.Server.Web.TunnelController
+
+  setup do
+    start_supervised!({Burrow.Server.TunnelRegistry, name: Burrow.Server.TunnelRegistry})
+
+    :ok
+  end
+
+  defp stats(tunnel_id, params \\ %{}) do
+    conn(:get, "/api/tunnels/#{tunnel_id}/stats")
+    |> assign(:current_user, %User{id: "user-1", username: "alice"})
+    |> TunnelController.stats(Map.put(params, "id", tunnel_id))
+  end
+
+  describe "GET /api/tunnels/:id/stats" do
+    test "returns 404 for a tunnel the user doesn't have" do
+      conn = stats("t-unknown")
+
+      assert conn.status == 404
+      assert %{"error" => %{"code" => "not_found"}} = Jason.decode!(conn.resp_body)
+    end
+
+    test "rejects an invalid window" do
+      for since <- ["abc", "0", "-5", "1h", "999999999"] do
+        conn = stats("t-unknown", %{"since" => since})
+
+        assert conn.status == 400, since
+        assert %{"error" => %{"code" => "invalid_since"}} = Jason.decode!(conn.resp_body)
+      end
+    end
+  end
+
+  describe "summarize/2" do
+    test "turns totals into rates" do
+      totals = %{count: 120, errors: 6, bytes: 36_000, p50: 12.0, p95: 80.5, p99: 150.0}
+
+      assert TunnelController.summarize(totals, 60 * 60) == %{
+               requests_per_minute: 2.0,
+               latency_ms: %{p50: 12.0, p95: 80.5, p99: 150.0},
+               error_rate: 0.05,
+               bytes_per_second: 10.0
+             }
+    end
+
+    test "reports zeroes without requests" do
+      totals = %{count: 0, errors: 0, bytes: nil, p50: nil, p95: nil, p99: nil}
+
+      assert TunnelController.summarize(totals, 60) == %{
+               requests_per_minute: 0.0,
+               latency_ms: %{p50: 0.0, p95: 0.0, p99: 0.0},
+               error_rate: 0.0,
+               bytes_per_second: 0.0
+             }
+    end
+  end
+end
@@ -1 +1,2 @@
-ExUnit.start()
+# Tests tagged :database need a PostgreSQL database (see Burrow.DataCase)
+ExUnit.start(exclude: if(System.get_env("DATABASE_URL"), do: [], else: [:database]))